@@ -26,6 +26,7 @@
     ProtocolParams,
     ReportedReputationMeasurements,
     Service,
+    ServiceArtifact,
     ServiceId,
     ServiceRevenue,
     TotalServed,
@@ -94,6 +95,7 @@ impl Env<UpdatePerm> {
             .with_table::<(NodeIndex, NodeIndex), Duration>("latencies")
             .with_table::<Epoch, Committee>("committee")
             .with_table::<ServiceId, Service>("service")
+            .with_table::<ServiceId, ServiceArtifact>("service_artifact")
             .with_table::<ProtocolParams, u128>("parameter")
             .with_table::<NodeIndex, Vec<ReportedReputationMeasurements>>("rep_measurements")
             .with_table::<NodeIndex, u8>("rep_scores")
@@ -116,6 +118,7 @@ impl Env<UpdatePerm> {
             .enable_iter("executed_digests")
             .enable_iter("uptime")
             .enable_iter("service_revenue")
+            .enable_iter("service_artifact")
             .enable_iter("cid_to_node")
             .enable_iter("node_to_cid");
 
@@ -273,6 +276,8 @@ pub fn genesis(&mut self, config: &Config) -> bool {
             let mut account_table = ctx.get_table::<EthAddress, AccountInfo>("account");
             let mut client_table = ctx.get_table::<ClientPublicKey, EthAddress>("client_keys");
             let mut service_table = ctx.get_table::<ServiceId, Service>("service");
+            let mut service_artifact_table =
+                ctx.get_table::<ServiceId, ServiceArtifact>("service_artifact");
             let mut param_table = ctx.get_table::<ProtocolParams, u128>("parameter");
             let mut committee_table = ctx.get_table::<Epoch, Committee>("committee");
             let mut commodity_prices_table =
@@ -407,7 +412,10 @@ pub fn genesis(&mut self, config: &Config) -> bool {
                         commodity_type: service.commodity_type,
                         slashing: (),
                     },
-                )
+                );
+                if let Some(artifact) = service.artifact {
+                    service_artifact_table.insert(service.id, artifact);
+                }
             }
 
             for account in genesis.account {
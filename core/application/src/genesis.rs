@@ -11,6 +11,7 @@
     NodePorts,
     NodeServed,
     Participation,
+    ServiceArtifact,
     Staking,
     TotalServed,
 };
@@ -58,6 +59,8 @@ pub struct GenesisService {
     pub id: u32,
     pub owner: EthAddress,
     pub commodity_type: CommodityTypes,
+    #[serde(default)]
+    pub artifact: Option<ServiceArtifact>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -25,6 +25,7 @@
     ProtocolParams,
     ReportedReputationMeasurements,
     Service,
+    ServiceArtifact,
     ServiceId,
     ServiceRevenue,
     TotalServed,
@@ -49,6 +50,7 @@ pub struct QueryRunner {
     pub_key_to_index: ResolvedTableReference<NodePublicKey, NodeIndex>,
     committee_table: ResolvedTableReference<Epoch, Committee>,
     services_table: ResolvedTableReference<ServiceId, Service>,
+    service_artifacts_table: ResolvedTableReference<ServiceId, ServiceArtifact>,
     param_table: ResolvedTableReference<ProtocolParams, u128>,
     current_epoch_served: ResolvedTableReference<NodeIndex, NodeServed>,
     rep_measurements: ResolvedTableReference<NodeIndex, Vec<ReportedReputationMeasurements>>,
@@ -76,6 +78,8 @@ fn new(atomo: Atomo<QueryPerm, AtomoStorage>) -> Self {
             pub_key_to_index: atomo.resolve::<NodePublicKey, NodeIndex>("pub_key_to_index"),
             committee_table: atomo.resolve::<Epoch, Committee>("committee"),
             services_table: atomo.resolve::<ServiceId, Service>("service"),
+            service_artifacts_table: atomo
+                .resolve::<ServiceId, ServiceArtifact>("service_artifact"),
             param_table: atomo.resolve::<ProtocolParams, u128>("parameter"),
             current_epoch_served: atomo.resolve::<NodeIndex, NodeServed>("current_epoch_served"),
             rep_measurements: atomo
@@ -181,6 +185,20 @@ fn get_service_info(&self, id: &ServiceId) -> Option<Service> {
         self.inner.run(|ctx| self.services_table.get(ctx).get(id))
     }
 
+    fn get_service_artifact(&self, id: &ServiceId) -> Option<ServiceArtifact> {
+        self.inner
+            .run(|ctx| self.service_artifacts_table.get(ctx).get(id))
+    }
+
+    #[inline]
+    fn get_service_artifact_table_iter<V>(
+        &self,
+        closure: impl FnOnce(KeyIterator<ServiceId>) -> V,
+    ) -> V {
+        self.inner
+            .run(|ctx| closure(self.service_artifacts_table.get(ctx).keys()))
+    }
+
     fn get_protocol_param(&self, param: &ProtocolParams) -> Option<u128> {
         self.inner.run(|ctx| self.param_table.get(ctx).get(param))
     }
@@ -38,6 +38,7 @@
     ReportedReputationMeasurements,
     ReputationMeasurements,
     Service,
+    ServiceArtifact,
     ServiceId,
     ServiceRevenue,
     Staking,
@@ -117,6 +118,7 @@ pub struct State<B: Backend> {
     pub latencies: B::Ref<(NodeIndex, NodeIndex), Duration>,
     pub committee_info: B::Ref<Epoch, Committee>,
     pub services: B::Ref<ServiceId, Service>,
+    pub service_artifacts: B::Ref<ServiceId, ServiceArtifact>,
     pub parameters: B::Ref<ProtocolParams, u128>,
     pub rep_measurements: B::Ref<NodeIndex, Vec<ReportedReputationMeasurements>>,
     pub rep_scores: B::Ref<NodeIndex, u8>,
@@ -144,6 +146,7 @@ pub fn new(backend: B) -> Self {
             pub_key_to_index: backend.get_table_reference("pub_key_to_index"),
             committee_info: backend.get_table_reference("committee"),
             services: backend.get_table_reference("service"),
+            service_artifacts: backend.get_table_reference("service_artifact"),
             parameters: backend.get_table_reference("parameter"),
             rep_measurements: backend.get_table_reference("rep_measurements"),
             latencies: backend.get_table_reference("latencies"),
@@ -244,6 +247,11 @@ fn execute_fleek_transaction(&self, txn: UpdateRequest) -> TransactionResponse {
                 service_id,
             } => self.add_service(txn.payload.sender, service, service_id),
 
+            UpdateMethod::UpgradeService {
+                service_id,
+                artifact,
+            } => self.upgrade_service(txn.payload.sender, service_id, artifact),
+
             UpdateMethod::RemoveService { service_id } => {
                 self.remove_service(txn.payload.sender, service_id)
             },
@@ -1009,13 +1017,7 @@ fn change_protocol_param(
             Ok(account) => account,
             Err(e) => return e,
         };
-        // TODO(matthias): should be panic here or revert? Since the governance address will be
-        // seeded though genesis, this should never happen.
-        let governance_address = match self.metadata.get(&Metadata::GovernanceAddress) {
-            Some(Value::AccountPublicKey(address)) => address,
-            _ => panic!("Governance address is missing from state."),
-        };
-        if sender != governance_address {
+        if sender != self.governance_address() {
             return TransactionResponse::Revert(ExecutionError::OnlyGovernance);
         }
         self.parameters.set(param, value);
@@ -1074,19 +1076,67 @@ fn get_node_registry(&self) -> BTreeMap<NodeIndex, NodeInfo> {
 
     fn add_service(
         &self,
-        _sender: TransactionSender,
-        _service: Service,
-        _service_id: ServiceId,
+        sender: TransactionSender,
+        service: Service,
+        service_id: ServiceId,
     ) -> TransactionResponse {
-        TransactionResponse::Revert(ExecutionError::Unimplemented)
+        let sender = match self.only_account_owner(sender) {
+            Ok(account) => account,
+            Err(e) => return e,
+        };
+        if sender != self.governance_address() {
+            return TransactionResponse::Revert(ExecutionError::OnlyGovernance);
+        }
+        if self.services.get(&service_id).is_some() {
+            return TransactionResponse::Revert(ExecutionError::InvalidServiceId);
+        }
+        self.services.set(service_id, service);
+        TransactionResponse::Success(ExecutionData::None)
+    }
+
+    fn upgrade_service(
+        &self,
+        sender: TransactionSender,
+        service_id: ServiceId,
+        artifact: ServiceArtifact,
+    ) -> TransactionResponse {
+        let sender = match self.only_account_owner(sender) {
+            Ok(account) => account,
+            Err(e) => return e,
+        };
+        let Some(service) = self.services.get(&service_id) else {
+            return TransactionResponse::Revert(ExecutionError::NonExistingService);
+        };
+        if sender != service.owner && sender != self.governance_address() {
+            return TransactionResponse::Revert(ExecutionError::OnlyServiceOwner);
+        }
+        // Versions only go up, so nodes can tell which of two versions is the latest.
+        let current = self.service_artifacts.get(&service_id);
+        if matches!(current, Some(current) if current.version >= artifact.version) {
+            return TransactionResponse::Revert(ExecutionError::InvalidServiceVersion);
+        }
+        self.service_artifacts.set(service_id, artifact);
+        TransactionResponse::Success(ExecutionData::None)
     }
 
     fn remove_service(
         &self,
-        _sender: TransactionSender,
-        _service_id: ServiceId,
+        sender: TransactionSender,
+        service_id: ServiceId,
     ) -> TransactionResponse {
-        TransactionResponse::Revert(ExecutionError::Unimplemented)
+        let sender = match self.only_account_owner(sender) {
+            Ok(account) => account,
+            Err(e) => return e,
+        };
+        let Some(service) = self.services.get(&service_id) else {
+            return TransactionResponse::Revert(ExecutionError::NonExistingService);
+        };
+        if sender != service.owner && sender != self.governance_address() {
+            return TransactionResponse::Revert(ExecutionError::OnlyServiceOwner);
+        }
+        self.services.remove(&service_id);
+        self.service_artifacts.remove(&service_id);
+        TransactionResponse::Success(ExecutionData::None)
     }
 
     fn slash(
@@ -1772,6 +1822,15 @@ pub fn increment_block_number(&self) -> u64 {
 
     // Useful for transaction that nodes cannot call but an account owner can
     // Does not panic
+    fn governance_address(&self) -> EthAddress {
+        // TODO(matthias): should be panic here or revert? Since the governance address will be
+        // seeded though genesis, this should never happen.
+        match self.metadata.get(&Metadata::GovernanceAddress) {
+            Some(Value::AccountPublicKey(address)) => address,
+            _ => panic!("Governance address is missing from state."),
+        }
+    }
+
     fn only_account_owner(
         &self,
         sender: TransactionSender,
@@ -39,6 +39,8 @@
     ProofOfConsensus,
     ProtocolParams,
     ReputationMeasurements,
+    Service,
+    ServiceArtifact,
     Staking,
     Tokens,
     TotalServed,
@@ -524,11 +526,13 @@ fn test_genesis() -> Genesis {
                 id: 0,
                 owner: EthAddress::from_str("0xDC0A31F9eeb151f82BF1eE6831095284fC215Ee7").unwrap(),
                 commodity_type: CommodityTypes::Bandwidth,
+                artifact: None,
             },
             GenesisService {
                 id: 1,
                 owner: EthAddress::from_str("0x684166BDbf530a256d7c92Fa0a4128669aFd9B9F").unwrap(),
                 commodity_type: CommodityTypes::Compute,
+                artifact: None,
             },
         ],
         account: vec![GenesisAccount {
@@ -1957,6 +1961,85 @@ async fn test_change_protocol_params() {
     assert_eq!(query_runner.get_protocol_param(&param).unwrap(), new_value)
 }
 
+#[tokio::test]
+async fn test_add_upgrade_and_remove_service() {
+    let governance_secret_key = AccountOwnerSecretKey::generate();
+    let owner_secret_key = AccountOwnerSecretKey::generate();
+    let other_secret_key = AccountOwnerSecretKey::generate();
+
+    let mut genesis = test_genesis();
+    genesis.governance_address = governance_secret_key.to_pk().into();
+
+    let (update_socket, query_runner) = init_app_with_genesis(&genesis);
+
+    let service_id = 1069;
+    let service = Service {
+        owner: owner_secret_key.to_pk().into(),
+        commodity_type: CommodityTypes::Compute,
+        slashing: (),
+    };
+
+    // Only the governance can add services.
+    let add = UpdateMethod::AddService {
+        service,
+        service_id,
+    };
+    let update = prepare_update_request_account(add.clone(), &owner_secret_key, 1);
+    expect_tx_revert!(update, &update_socket, ExecutionError::OnlyGovernance);
+    let update = prepare_update_request_account(add.clone(), &governance_secret_key, 1);
+    expect_tx_success!(update, &update_socket);
+    assert_eq!(query_runner.get_service_info(&service_id), Some(service));
+    assert_eq!(query_runner.get_service_artifact(&service_id), None);
+
+    let update = prepare_update_request_account(add, &governance_secret_key, 2);
+    expect_tx_revert!(update, &update_socket, ExecutionError::InvalidServiceId);
+
+    // Only the owner can upgrade the service, and only to a newer version.
+    let artifact = ServiceArtifact {
+        hash: [2; 32],
+        version: 2,
+    };
+    let upgrade = UpdateMethod::UpgradeService {
+        service_id,
+        artifact,
+    };
+    let update = prepare_update_request_account(upgrade.clone(), &other_secret_key, 1);
+    expect_tx_revert!(update, &update_socket, ExecutionError::OnlyServiceOwner);
+    let update = prepare_update_request_account(upgrade.clone(), &owner_secret_key, 2);
+    expect_tx_success!(update, &update_socket);
+    assert_eq!(
+        query_runner.get_service_artifact(&service_id),
+        Some(artifact)
+    );
+    assert_eq!(
+        query_runner.get_service_artifact_table_iter(|iter| iter.collect::<Vec<_>>()),
+        vec![service_id]
+    );
+
+    let update = prepare_update_request_account(upgrade, &owner_secret_key, 3);
+    expect_tx_revert!(
+        update,
+        &update_socket,
+        ExecutionError::InvalidServiceVersion
+    );
+
+    let upgrade = UpdateMethod::UpgradeService {
+        service_id: service_id + 1,
+        artifact,
+    };
+    let update = prepare_update_request_account(upgrade, &owner_secret_key, 4);
+    expect_tx_revert!(update, &update_socket, ExecutionError::NonExistingService);
+
+    // The owner can remove the service.
+    let remove = UpdateMethod::RemoveService { service_id };
+    let update = prepare_update_request_account(remove.clone(), &other_secret_key, 2);
+    expect_tx_revert!(update, &update_socket, ExecutionError::OnlyServiceOwner);
+    let update = prepare_update_request_account(remove, &owner_secret_key, 5);
+    expect_tx_success!(update, &update_socket);
+    assert_eq!(query_runner.get_service_info(&service_id), None);
+    assert_eq!(query_runner.get_service_artifact(&service_id), None);
+}
+
 #[tokio::test]
 async fn test_change_protocol_params_reverts_not_account_key() {
     let committee_size = 4;
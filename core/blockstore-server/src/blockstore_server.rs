@@ -2,7 +2,7 @@
 //!       to simplify and correct the `Socket` usage here to avoid `raw_bounded`.
 
 use std::borrow::Cow;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BinaryHeap, HashMap};
 use std::mem;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -18,25 +18,29 @@
     Blake3Hash,
     CompressionAlgoSet,
     CompressionAlgorithm,
+    FetchPriority,
+    FetchProgress,
     NodeIndex,
     PeerRequestError,
     RejectReason,
     ServerRequest,
 };
-use lightning_interfaces::ServiceScope;
+use lightning_interfaces::{ServerResponse, ServiceScope};
 use lightning_metrics::increment_counter;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{broadcast, mpsc};
-use tokio::task::JoinSet;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::{AbortHandle, JoinSet};
 use tokio::time::timeout;
 use tokio_stream::StreamExt;
 use tracing::error;
 
 use crate::config::Config;
 
-type ServerRequestTask = Task<ServerRequest, broadcast::Receiver<Result<(), PeerRequestError>>>;
+type ServerRequestTask = Task<ServerRequest, ServerResponse>;
 
 const REQUEST_TIMEOUT: Duration = Duration::from_millis(1000);
+/// How often we check for requests that nobody is waiting on anymore.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 pub struct BlockstoreServer<C: Collection> {
     inner: Option<BlockstoreServerInner<C>>,
@@ -125,12 +129,17 @@ pub fn new(
     }
 
     pub async fn start(mut self) {
-        let mut pending_requests: HashMap<
-            PeerRequest,
-            broadcast::Sender<Result<(), PeerRequestError>>,
-        > = HashMap::new();
+        let mut pending_requests: HashMap<PeerRequest, PendingRequest> = HashMap::new();
         let mut tasks = JoinSet::new();
-        let mut queue = VecDeque::new();
+        let mut queue = BinaryHeap::new();
+        let mut next_seq = 0;
+        let mut cancel_interval = tokio::time::interval(CANCEL_CHECK_INTERVAL);
+        let ctx = RequestContext::<C> {
+            blockstore: self.blockstore.clone(),
+            pool_requester: self.pool_requester.clone(),
+            rep_reporter: self.rep_reporter.clone(),
+        };
+        let max_conc_req = self.max_conc_req;
 
         // Hack to force the JoinSet to never return `None`. This simplifies the tokio::select.
         tasks.spawn(futures::future::pending());
@@ -180,48 +189,53 @@ pub async fn start(mut self) {
                 task = self.request_rx.recv() => {
                     if let Some(task) = task {
                         let peer_request = PeerRequest { hash: task.request.hash };
-                        let rx = if let Some(tx) = pending_requests.get(&peer_request) {
+                        let priority = task.request.priority;
+                        let pending = pending_requests.get_mut(&peer_request);
+                        let response = if let Some(pending) = pending {
+                            // If a request for this hash is still queued, it is bumped to the
+                            // higher priority. The old queue entry is skipped once popped.
+                            if pending.abort.is_none() && priority > pending.priority {
+                                pending.priority = priority;
+                                queue.push(QueuedRequest {
+                                    priority,
+                                    seq: next_seq,
+                                    request: peer_request.clone(),
+                                });
+                                next_seq += 1;
+                            }
                             // If a request for this hash is currently pending, subscribe to get
                             // notified about the result.
-                            tx.subscribe()
+                            ServerResponse::new(
+                                pending.result.subscribe(),
+                                pending.progress.subscribe(),
+                            )
                         } else {
                             // If no request for this hash currently exists, create new request.
-                            if tasks.len() < self.max_conc_req {
-                                let blockstore = self.blockstore.clone();
-                                let pool_requester = self.pool_requester.clone();
-                                let peer_request_ = peer_request.clone();
-                                let rep_reporter = self.rep_reporter.clone();
-                                tasks.spawn(async move {
-                                    let res = send_request::<C>(
-                                        task.request.peer,
-                                        peer_request_,
-                                        blockstore,
-                                        pool_requester,
-                                        rep_reporter,
-                                    ).await;
-
-                                    if res.is_ok() {
-                                        increment_counter!(
-                                           "blockstore_server_send_request_ok",
-                                           Some("Counter for the number of successful blockstore requests made")
-                                        );
-                                    } else {
-                                        increment_counter!(
-                                            "blockstore_servier_send_request_err",
-                                            Some("Counter for the number of failed blockstore requests made")
-                                        );
-                                    }
-
-                                    res
-                                });
+                            let (result, rx) = broadcast::channel(1);
+                            let (progress, _) = watch::channel(FetchProgress::default());
+                            let mut pending = PendingRequest {
+                                result,
+                                progress: Arc::new(progress),
+                                priority,
+                                peer: task.request.peer,
+                                abort: None,
+                            };
+                            let response = ServerResponse::new(rx, pending.progress.subscribe());
+                            if tasks.len() < max_conc_req {
+                                let abort = ctx.spawn(&mut tasks, &pending, peer_request.clone());
+                                pending.abort = Some(abort);
                             } else {
-                                queue.push_back(peer_request.clone());
+                                queue.push(QueuedRequest {
+                                    priority,
+                                    seq: next_seq,
+                                    request: peer_request.clone(),
+                                });
+                                next_seq += 1;
                             }
-                            let (tx, rx) = broadcast::channel(1);
-                            pending_requests.insert(peer_request, tx);
-                            rx
+                            pending_requests.insert(peer_request, pending);
+                            response
                         };
-                        task.respond(rx);
+                        task.respond(response);
                     } else {
                         break;
                     }
@@ -229,24 +243,149 @@ pub async fn start(mut self) {
                 Some(res) = tasks.join_next() => {
                     match res {
                         Ok(Ok(peer_request)) => {
-                            if let Some(tx) = pending_requests.remove(&peer_request) {
-                                tx.send(Ok(())).expect("Failed to send response");
+                            if let Some(pending) = pending_requests.remove(&peer_request) {
+                                let _ = pending.result.send(Ok(()));
                             }
                         },
                         Ok(Err(error_res)) => {
                             error!("Failed to fetch data from peer: {:?}", error_res.error);
-                            if let Some(tx) = pending_requests.remove(&error_res.request) {
-                                tx.send(Err(error_res.error)).expect("Failed to send response");
+                            if let Some(pending) = pending_requests.remove(&error_res.request) {
+                                let _ = pending.result.send(Err(error_res.error));
                             }
                         },
+                        // The request was aborted because every requester lost interest.
+                        Err(e) if e.is_cancelled() => {},
                         Err(e) => error!("Failed to join task: {e:?}"),
                     }
+                    ctx.spawn_queued(&mut tasks, &mut queue, &mut pending_requests, max_conc_req);
+                }
+                _ = cancel_interval.tick() => {
+                    // Requests that nobody is waiting on anymore are dropped from the queue, or
+                    // aborted if they are already running.
+                    pending_requests.retain(|_, pending| {
+                        if pending.result.receiver_count() > 0 {
+                            return true;
+                        }
+                        if let Some(abort) = &pending.abort {
+                            abort.abort();
+                        }
+                        false
+                    });
+                    ctx.spawn_queued(&mut tasks, &mut queue, &mut pending_requests, max_conc_req);
                 }
             }
         }
     }
 }
 
+/// A request for a peer that was submitted to the socket and has not finished yet.
+struct PendingRequest {
+    result: broadcast::Sender<Result<(), PeerRequestError>>,
+    progress: Arc<watch::Sender<FetchProgress>>,
+    priority: FetchPriority,
+    peer: NodeIndex,
+    /// Set once the request is running.
+    abort: Option<AbortHandle>,
+}
+
+/// An entry in the queue of requests waiting for a free slot. Entries are popped by priority,
+/// and in the order they were queued for equal priorities.
+#[derive(PartialEq, Eq)]
+pub(crate) struct QueuedRequest {
+    pub(crate) priority: FetchPriority,
+    pub(crate) seq: u64,
+    pub(crate) request: PeerRequest,
+}
+
+impl Ord for QueuedRequest {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for QueuedRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Everything needed to spawn a request for a peer.
+#[allow(clippy::type_complexity)]
+struct RequestContext<C: Collection> {
+    blockstore: C::BlockstoreInterface,
+    pool_requester: c!(C::PoolInterface::Requester),
+    rep_reporter: c!(C::ReputationAggregatorInterface::ReputationReporter),
+}
+
+impl<C: Collection> RequestContext<C> {
+    fn spawn(
+        &self,
+        tasks: &mut JoinSet<Result<PeerRequest, ErrorResponse>>,
+        pending: &PendingRequest,
+        peer_request: PeerRequest,
+    ) -> AbortHandle {
+        let blockstore = self.blockstore.clone();
+        let pool_requester = self.pool_requester.clone();
+        let rep_reporter = self.rep_reporter.clone();
+        let progress = pending.progress.clone();
+        let peer = pending.peer;
+        tasks.spawn(async move {
+            let res = send_request::<C>(
+                peer,
+                peer_request,
+                blockstore,
+                pool_requester,
+                rep_reporter,
+                progress,
+            )
+            .await;
+
+            if res.is_ok() {
+                increment_counter!(
+                    "blockstore_server_send_request_ok",
+                    Some("Counter for the number of successful blockstore requests made")
+                );
+            } else {
+                increment_counter!(
+                    "blockstore_servier_send_request_err",
+                    Some("Counter for the number of failed blockstore requests made")
+                );
+            }
+
+            res
+        })
+    }
+
+    /// Spawns queued requests until we are at capacity again.
+    fn spawn_queued(
+        &self,
+        tasks: &mut JoinSet<Result<PeerRequest, ErrorResponse>>,
+        queue: &mut BinaryHeap<QueuedRequest>,
+        pending_requests: &mut HashMap<PeerRequest, PendingRequest>,
+        max_conc_req: usize,
+    ) {
+        while tasks.len() < max_conc_req {
+            let Some(queued) = queue.pop() else {
+                break;
+            };
+            let Some(pending) = pending_requests.get_mut(&queued.request) else {
+                continue;
+            };
+            // Skip the entries of requests that are running already or were bumped.
+            if pending.abort.is_some() || pending.priority != queued.priority {
+                continue;
+            }
+            if pending.result.receiver_count() == 0 {
+                pending_requests.remove(&queued.request);
+                continue;
+            }
+            pending.abort = Some(self.spawn(tasks, pending, queued.request));
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 enum Message {
     Request {
@@ -406,6 +545,7 @@ async fn send_request<C: Collection>(
     blockstore: C::BlockstoreInterface,
     pool_requester: c!(C::PoolInterface::Requester),
     rep_reporter: c!(C::ReputationAggregatorInterface::ReputationReporter),
+    progress: Arc<watch::Sender<FetchProgress>>,
 ) -> Result<PeerRequest, ErrorResponse> {
     match timeout(
         REQUEST_TIMEOUT,
@@ -437,9 +577,15 @@ async fn send_request<C: Collection>(
                         };
                         match frame {
                             Frame::Proof(proof) => putter.feed_proof(&proof).unwrap(),
-                            Frame::Chunk(chunk) => putter
-                                .write(&chunk, CompressionAlgorithm::Uncompressed)
-                                .unwrap(),
+                            Frame::Chunk(chunk) => {
+                                putter
+                                    .write(&chunk, CompressionAlgorithm::Uncompressed)
+                                    .unwrap();
+                                progress.send_modify(|progress| {
+                                    progress.bytes += chunk.len() as u64;
+                                    progress.blocks += 1;
+                                });
+                            },
                             Frame::Eos => {
                                 // TODO: Handle premature end of stream errors instead of
                                 // unwrapping here, since we there could be an upstream blockstore
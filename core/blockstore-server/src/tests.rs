@@ -1,9 +1,10 @@
 use std::borrow::Cow;
-use std::collections::VecDeque;
+use std::collections::{BinaryHeap, VecDeque};
 use std::path::PathBuf;
 use std::time::Duration;
 
 use blake3_tree::ProofBuf;
+use bytes::Bytes;
 use fleek_crypto::{AccountOwnerSecretKey, NodePublicKey, SecretKey};
 use lightning_application::app::Application;
 use lightning_application::config::{Config as AppConfig, Mode, StorageConfig};
@@ -15,6 +16,8 @@
 use lightning_interfaces::types::{
     CompressionAlgoSet,
     CompressionAlgorithm,
+    FetchPriority,
+    FetchProgress,
     NodePorts,
     ServerRequest,
 };
@@ -27,7 +30,7 @@
 use lightning_topology::Topology;
 
 use super::BlockstoreServer;
-use crate::blockstore_server::Frame;
+use crate::blockstore_server::{Frame, PeerRequest, QueuedRequest};
 use crate::config::Config;
 
 partial!(TestBinding {
@@ -250,6 +253,7 @@ async fn test_send_and_receive() {
         .run(ServerRequest {
             hash,
             peer: node_index1,
+            priority: FetchPriority::Normal,
         })
         .await
         .expect("Failed to send request");
@@ -257,6 +261,13 @@ async fn test_send_and_receive() {
         Ok(()) => {
             let recv_content = peers[1].blockstore().read_all_to_vec(&hash).await.unwrap();
             assert_eq!(recv_content, content);
+            assert_eq!(
+                *res.progress().borrow(),
+                FetchProgress {
+                    bytes: content.len() as u64,
+                    blocks: 4,
+                }
+            );
         },
         Err(e) => panic!("Failed to receive content: {e:?}"),
     }
@@ -271,3 +282,37 @@ async fn test_send_and_receive() {
         std::fs::remove_dir_all(path).unwrap();
     }
 }
+
+#[test]
+fn test_queue_order() {
+    let request = PeerRequest::try_from(Bytes::from(vec![0; 32])).unwrap();
+    let mut queue = BinaryHeap::new();
+    for (seq, priority) in [
+        FetchPriority::Normal,
+        FetchPriority::Background,
+        FetchPriority::Interactive,
+        FetchPriority::Normal,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        queue.push(QueuedRequest {
+            priority,
+            seq: seq as u64,
+            request: request.clone(),
+        });
+    }
+
+    let popped = std::iter::from_fn(|| queue.pop())
+        .map(|queued| (queued.priority, queued.seq))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        popped,
+        vec![
+            (FetchPriority::Interactive, 2),
+            (FetchPriority::Normal, 0),
+            (FetchPriority::Normal, 3),
+            (FetchPriority::Background, 1),
+        ]
+    );
+}
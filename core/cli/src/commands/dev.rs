@@ -112,7 +112,11 @@ async fn fetch<C: Collection<ConfigProviderInterface = TomlConfigProvider<C>>>(
 
     tracing::info!("Downloading {hash_string} from peer {peer}");
     let mut result = socket
-        .run(lightning_interfaces::types::ServerRequest { hash, peer })
+        .run(lightning_interfaces::types::ServerRequest {
+            hash,
+            peer,
+            priority: lightning_interfaces::types::FetchPriority::Interactive,
+        })
         .await
         .expect("Failed to send task.");
 
@@ -1,3 +1,4 @@
+use std::future::Future;
 use std::marker::PhantomData;
 
 use affair::{AsyncWorkerUnordered, Executor, TokioSpawn};
@@ -5,20 +6,26 @@
 use lightning_interfaces::prelude::*;
 use lightning_interfaces::types::{
     Blake3Hash,
+    CompressionAlgoSet,
+    FetchPriority,
+    FetchProgress,
     FetcherRequest,
     FetcherResponse,
     ImmutablePointer,
     ServerRequest,
 };
-use lightning_interfaces::{BlockstoreServerSocket, FetcherSocket};
+use lightning_interfaces::{BlockstoreServerSocket, FetchHandle, FetchTracker, FetcherSocket};
 use lightning_metrics::increment_counter;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 
 use crate::config::Config;
 use crate::origin::{OriginFetcher, OriginRequest};
 
 pub(crate) type Uri = Vec<u8>;
 
+/// The size of every block of a content except the last one.
+const BLOCK_SIZE: u64 = 256 << 10;
+
 pub struct Fetcher<C: Collection> {
     socket: FetcherSocket,
     _collection: PhantomData<C>,
@@ -77,28 +84,93 @@ struct FetcherWorker<C: Collection> {
     resolver: C::ResolverInterface,
 }
 
+impl<C: Collection> Clone for FetcherWorker<C> {
+    fn clone(&self) -> Self {
+        Self {
+            origin_tx: self.origin_tx.clone(),
+            blockstore: self.blockstore.clone(),
+            blockstore_server_socket: self.blockstore_server_socket.clone(),
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
 impl<C: Collection> FetcherWorker<C> {
+    /// Runs the request until it completes or is cancelled, and responds to its handle.
+    async fn run(self, req: FetcherRequest, tracker: FetchTracker) {
+        let response = match req {
+            FetcherRequest::Put { pointer, priority } => {
+                let res = tokio::select! {
+                    res = self.put(pointer, priority, &tracker) => res,
+                    _ = tracker.cancelled() => Err(cancelled_error()),
+                };
+                if res.is_err() {
+                    increment_counter!(
+                        "fetcher_put_request_failed",
+                        Some("Counter for failed put requests for origin content")
+                    );
+                } else {
+                    increment_counter!(
+                        "fetcher_put_request_succeed",
+                        Some("Counter for successful put requests for origin content")
+                    );
+                }
+                FetcherResponse::Put(res)
+            },
+            FetcherRequest::Fetch { hash, priority } => {
+                let res = tokio::select! {
+                    res = self.fetch(hash, priority, &tracker) => res,
+                    _ = tracker.cancelled() => Err(cancelled_error()),
+                };
+                if res.is_err() {
+                    increment_counter!(
+                        "fetcher_fetch_request_failed",
+                        Some("Counter for failed fetch requests for native content")
+                    );
+                } else {
+                    increment_counter!(
+                        "fetcher_fetch_request_succeed",
+                        Some("Counter for successful fetch requests for native content")
+                    );
+                }
+                FetcherResponse::Fetch(res)
+            },
+        };
+        tracker.respond(response);
+    }
+
     /// Fetches the data from the corresponding origin, puts it in the blockstore,
     /// and stores the mapping using the resolver. If pulling a origin fails, it will not ,
     /// the data will not be fetched from origin again.
     #[inline(always)]
-    async fn put(&self, pointer: ImmutablePointer) -> anyhow::Result<[u8; 32]> {
+    async fn put(
+        &self,
+        pointer: ImmutablePointer,
+        priority: FetchPriority,
+        tracker: &FetchTracker,
+    ) -> anyhow::Result<[u8; 32]> {
         if let Some(hash) = self.resolver.get_blake3_hash(pointer.clone()).await {
             // If we know about a mapping, forward the call to fetch which
             // will attempt to pull from multiple sources.
-            return self.fetch(hash).await.map(|_| hash);
+            return self.fetch(hash, priority, tracker).await.map(|_| hash);
         }
 
         // Otherwise, try to fetch directly from the origin
-        self.fetch_origin(pointer).await
+        self.fetch_origin(pointer, priority, tracker).await
     }
 
     #[inline(always)]
-    async fn fetch_origin(&self, pointer: ImmutablePointer) -> Result<[u8; 32]> {
+    async fn fetch_origin(
+        &self,
+        pointer: ImmutablePointer,
+        priority: FetchPriority,
+        tracker: &FetchTracker,
+    ) -> Result<[u8; 32]> {
         let (response_tx, response_rx) = oneshot::channel();
         self.origin_tx
             .send(OriginRequest {
                 pointer,
+                priority,
                 response: response_tx,
             })
             .await
@@ -107,11 +179,14 @@ impl<C: Collection> FetcherWorker<C> {
         let mut res = response_rx.await?;
         let res = res.recv().await?.context("failed to fetch from origin");
 
-        if res.is_ok() {
+        if let Ok(hash) = &res {
             increment_counter!(
                 "fetcher_from_origin",
                 Some("Counter for content that was fetched from an origin")
             );
+            // Origins do not report their progress, so we only report once the content is in
+            // the blockstore.
+            self.report_stored(hash, tracker).await;
         } else {
             increment_counter!(
                 "fetcher_from_origin_failed",
@@ -126,12 +201,18 @@ impl<C: Collection> FetcherWorker<C> {
     /// then iterate through the provider records, requesting from the provider,
     /// then falling back to the record's immutable pointer.
     #[inline(always)]
-    async fn fetch(&self, hash: Blake3Hash) -> Result<()> {
+    async fn fetch(
+        &self,
+        hash: Blake3Hash,
+        priority: FetchPriority,
+        tracker: &FetchTracker,
+    ) -> Result<()> {
         if self.blockstore.get_tree(&hash).await.is_some() {
             increment_counter!(
                 "fetcher_from_cache",
                 Some("Counter for content that was already cached locally")
             );
+            self.report_stored(&hash, tracker).await;
             return Ok(());
         } else if let Some(pointers) = self.resolver.get_origins(hash) {
             for res_pointer in pointers {
@@ -142,6 +223,7 @@ async fn fetch(&self, hash: Blake3Hash) -> Result<()> {
                         "fetcher_from_cache",
                         Some("Counter for content that was already cached locally")
                     );
+                    self.report_stored(&hash, tracker).await;
                     return Ok(());
                 }
 
@@ -152,11 +234,12 @@ async fn fetch(&self, hash: Blake3Hash) -> Result<()> {
                     .run(ServerRequest {
                         hash,
                         peer: res_pointer.originator,
+                        priority,
                     })
                     .await
                     .expect("Failed to send request to blockstore server");
-                let res = res
-                    .recv()
+                let progress = res.progress();
+                let res = report_until(progress, tracker, res.recv())
                     .await
                     .expect("Failed to receive response from blockstore server");
 
@@ -175,13 +258,66 @@ async fn fetch(&self, hash: Blake3Hash) -> Result<()> {
 
                 // If not, attempt to pull from the origin. This strikes a balance between trying
                 // to fetch from a bunch of peers vs going to the origin right away.
-                if self.fetch_origin(res_pointer.pointer).await.is_ok() {
+                if self
+                    .fetch_origin(res_pointer.pointer, priority, tracker)
+                    .await
+                    .is_ok()
+                {
                     return Ok(());
                 }
             }
         }
         Err(anyhow!("Failed to resolve hash"))
     }
+
+    /// Reports the size of content that is already in the blockstore as its progress.
+    async fn report_stored(&self, hash: &Blake3Hash, tracker: &FetchTracker) {
+        let Some(tree) = self.blockstore.get_tree(hash).await else {
+            return;
+        };
+        let blocks = tree.len();
+        // Every block but the last one is full, so we only need to look at the last one.
+        let bytes = match blocks.checked_sub(1) {
+            Some(last) => {
+                let compression = CompressionAlgoSet::default();
+                let last_len = self
+                    .blockstore
+                    .get(last as u32, &tree[last], compression)
+                    .await
+                    .map(|chunk| chunk.content.len() as u64)
+                    .unwrap_or(0);
+                last as u64 * BLOCK_SIZE + last_len
+            },
+            None => 0,
+        };
+        tracker.report(FetchProgress {
+            bytes,
+            blocks: blocks as u64,
+        });
+    }
+}
+
+fn cancelled_error() -> anyhow::Error {
+    increment_counter!(
+        "fetcher_request_cancelled",
+        Some("Counter for requests that were cancelled before completing")
+    );
+    anyhow!("the request was cancelled")
+}
+
+/// Forwards the progress from `progress` to the tracker until `fut` resolves.
+async fn report_until<F: Future>(
+    mut progress: watch::Receiver<FetchProgress>,
+    tracker: &FetchTracker,
+    fut: F,
+) -> F::Output {
+    tokio::pin!(fut);
+    loop {
+        tokio::select! {
+            out = &mut fut => return out,
+            Ok(()) = progress.changed() => tracker.report(*progress.borrow_and_update()),
+        }
+    }
 }
 
 impl<C: Collection> ConfigConsumer for Fetcher<C> {
@@ -197,40 +333,11 @@ fn build_graph() -> fdi::DependencyGraph {
 
 impl<C: Collection> AsyncWorkerUnordered for FetcherWorker<C> {
     type Request = FetcherRequest;
-    type Response = FetcherResponse;
+    type Response = FetchHandle;
 
     async fn handle(&self, req: Self::Request) -> Self::Response {
-        match req {
-            FetcherRequest::Put { pointer } => {
-                let res = self.put(pointer).await;
-                if res.is_err() {
-                    increment_counter!(
-                        "fetcher_put_request_failed",
-                        Some("Counter for failed put requests for origin content")
-                    );
-                } else {
-                    increment_counter!(
-                        "fetcher_put_request_succeed",
-                        Some("Counter for successful put requests for origin content")
-                    );
-                }
-                FetcherResponse::Put(res)
-            },
-            FetcherRequest::Fetch { hash } => {
-                let res = self.fetch(hash).await;
-                if res.is_err() {
-                    increment_counter!(
-                        "fetcher_fetch_request_failed",
-                        Some("Counter for failed fetch requests for native content")
-                    );
-                } else {
-                    increment_counter!(
-                        "fetcher_fetch_request_succeed",
-                        Some("Counter for successful fetch requests for native content")
-                    );
-                }
-                FetcherResponse::Fetch(res)
-            },
-        }
+        let (handle, tracker) = FetchHandle::new();
+        tokio::spawn(self.clone().run(req, tracker));
+        handle
     }
 }
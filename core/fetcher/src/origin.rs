@@ -1,17 +1,22 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BinaryHeap, HashMap};
+use std::time::Duration;
 
 use lightning_interfaces::prelude::*;
-use lightning_interfaces::types::{Blake3Hash, ImmutablePointer, OriginProvider};
+use lightning_interfaces::types::{Blake3Hash, FetchPriority, ImmutablePointer};
 use lightning_interfaces::OriginProviderSocket;
 use tokio::sync::{broadcast, mpsc, oneshot};
-use tokio::task::JoinSet;
+use tokio::task::{AbortHandle, JoinSet};
 use tracing::error;
 
 use crate::fetcher::Uri;
 
+/// How often we check for requests that nobody is waiting on anymore.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 pub struct OriginFetcher<C: Collection> {
     tasks: JoinSet<Result<SuccessResponse, ErrorResponse>>,
-    queue: VecDeque<ImmutablePointer>,
+    queue: BinaryHeap<QueuedRequest>,
+    next_seq: u64,
     rx: mpsc::Receiver<OriginRequest>,
     origin_socket: OriginProviderSocket,
     resolver: C::ResolverInterface,
@@ -27,7 +32,8 @@ pub fn new(
     ) -> Self {
         Self {
             tasks: JoinSet::new(),
-            queue: VecDeque::new(),
+            queue: BinaryHeap::new(),
+            next_seq: 0,
             rx,
             origin_socket,
             resolver,
@@ -36,29 +42,44 @@ pub fn new(
     }
 
     pub async fn start(mut self) {
-        let mut pending_requests: HashMap<Uri, broadcast::Sender<Result<Blake3Hash, OriginError>>> =
-            HashMap::new();
+        let mut pending_requests: HashMap<Uri, PendingRequest> = HashMap::new();
+        let mut cancel_interval = tokio::time::interval(CANCEL_CHECK_INTERVAL);
         loop {
             tokio::select! {
                 request = self.rx.recv() => {
                     if let Some(request) = request {
                         let uri = request.pointer.uri.clone();
-                        let rx = if let Some(tx) = pending_requests.get(&uri) {
+                        let rx = if let Some(pending) = pending_requests.get_mut(&uri) {
+                            // If the request for this uri is still queued, it is bumped to the
+                            // higher priority. The old queue entry is skipped once popped.
+                            if pending.abort.is_none() && request.priority > pending.priority {
+                                pending.priority = request.priority;
+                                self.enqueue(uri, request.priority);
+                            }
                             // If a request for this uri is currently pending, subscribe to get
                             // notified about the result.
-                            tx.subscribe()
+                            pending.tx.subscribe()
                         } else {
                             // If no request for this uri currently exists, create new request.
-                            if self.tasks.len() < self.capacity {
-                                self.spawn(request.pointer).await;
-                            } else {
-                                self.queue.push_back(request.pointer);
-                            }
                             let (tx, rx) = broadcast::channel(1);
-                            pending_requests.insert(uri, tx);
+                            let abort = if self.tasks.len() < self.capacity {
+                                Some(self.spawn(request.pointer.clone()))
+                            } else {
+                                self.enqueue(uri.clone(), request.priority);
+                                None
+                            };
+                            pending_requests.insert(uri, PendingRequest {
+                                tx,
+                                pointer: request.pointer,
+                                priority: request.priority,
+                                abort,
+                            });
                             rx
                         };
-                        request.response.send(rx).expect("Failed to send response");
+                        // The requester might have been cancelled in the meantime.
+                        let _ = request.response.send(rx);
+                    } else {
+                        break;
                     }
                 }
                 Some(res) = self.tasks.join_next() => {
@@ -66,56 +87,125 @@ pub async fn start(mut self) {
                         Ok(Ok(SuccessResponse { pointer, hash })) => {
                             let uri = pointer.uri.clone();
                             self.resolver.publish(hash, &[pointer]).await;
-                            if let Some(tx) = pending_requests.remove(&uri) {
-                                tx.send(Ok(hash)).expect("Failed to send hash");
+                            if let Some(pending) = pending_requests.remove(&uri) {
+                                let _ = pending.tx.send(Ok(hash));
                             }
                         }
                         Ok(Err(e)) => {
                             match e {
                                 ErrorResponse::OriginSocketError => error!("Failed to get response from socket"),
                                 ErrorResponse::OriginFetchError(uri) => {
-                                    if let Some(tx) = pending_requests.remove(&uri) {
-                                        tx.send(Err(OriginError)).expect("Failed to send response");
+                                    if let Some(pending) = pending_requests.remove(&uri) {
+                                        let _ = pending.tx.send(Err(OriginError));
                                     }
                                     error!("Failed to fetch data from origin");
                                 },
                             }
                         },
+                        // The request was aborted because every requester lost interest.
+                        Err(e) if e.is_cancelled() => {},
                         Err(e) => error!("Failed to join task: {e:?}"),
                     }
-                    if self.tasks.len() < self.capacity {
-                        if let Some(pointer) = self.queue.pop_front() {
-                            self.spawn(pointer).await;
-                        }
-                    }
+                    self.spawn_queued(&mut pending_requests);
                 }
-                else => {
-                    break;
+                _ = cancel_interval.tick() => {
+                    // Requests that nobody is waiting on anymore are dropped from the queue, or
+                    // aborted if they are already running.
+                    pending_requests.retain(|_, pending| {
+                        if pending.tx.receiver_count() > 0 {
+                            return true;
+                        }
+                        if let Some(abort) = &pending.abort {
+                            abort.abort();
+                        }
+                        false
+                    });
+                    self.spawn_queued(&mut pending_requests);
                 }
             }
         }
     }
 
-    async fn spawn(&mut self, pointer: ImmutablePointer) {
+    fn enqueue(&mut self, uri: Uri, priority: FetchPriority) {
+        self.queue.push(QueuedRequest {
+            priority,
+            seq: self.next_seq,
+            uri,
+        });
+        self.next_seq += 1;
+    }
+
+    /// Spawns queued requests until we are at capacity again.
+    fn spawn_queued(&mut self, pending_requests: &mut HashMap<Uri, PendingRequest>) {
+        while self.tasks.len() < self.capacity {
+            let Some(queued) = self.queue.pop() else {
+                break;
+            };
+            let Some(pending) = pending_requests.get_mut(&queued.uri) else {
+                continue;
+            };
+            // Skip the entries of requests that are running already or were bumped.
+            if pending.abort.is_some() || pending.priority != queued.priority {
+                continue;
+            }
+            if pending.tx.receiver_count() == 0 {
+                pending_requests.remove(&queued.uri);
+                continue;
+            }
+            pending.abort = Some(self.spawn(pending.pointer.clone()));
+        }
+    }
+
+    fn spawn(&mut self, pointer: ImmutablePointer) -> AbortHandle {
         let origin_socket = self.origin_socket.clone();
         self.tasks.spawn(async move {
-            match &pointer.origin {
-                OriginProvider::IPFS => match origin_socket.run(pointer.clone()).await {
-                    Ok(Ok(hash)) => Ok(SuccessResponse { pointer, hash }),
-                    Ok(Err(_)) => Err(ErrorResponse::OriginFetchError(pointer.uri)),
-                    Err(_) => Err(ErrorResponse::OriginSocketError),
-                },
-                _ => unreachable!(),
+            match origin_socket.run(pointer.clone()).await {
+                Ok(Ok(hash)) => Ok(SuccessResponse { pointer, hash }),
+                Ok(Err(_)) => Err(ErrorResponse::OriginFetchError(pointer.uri)),
+                Err(_) => Err(ErrorResponse::OriginSocketError),
             }
-        });
+        })
     }
 }
 
 pub struct OriginRequest {
     pub pointer: ImmutablePointer,
+    pub priority: FetchPriority,
     pub response: oneshot::Sender<broadcast::Receiver<Result<Blake3Hash, OriginError>>>,
 }
 
+/// A request that was submitted to the origin fetcher and has not finished yet.
+struct PendingRequest {
+    tx: broadcast::Sender<Result<Blake3Hash, OriginError>>,
+    pointer: ImmutablePointer,
+    priority: FetchPriority,
+    /// Set once the request is running.
+    abort: Option<AbortHandle>,
+}
+
+/// An entry in the queue of requests waiting for a free slot. Entries are popped by priority,
+/// and in the order they were queued for equal priorities.
+#[derive(PartialEq, Eq)]
+struct QueuedRequest {
+    priority: FetchPriority,
+    seq: u64,
+    uri: Uri,
+}
+
+impl Ord for QueuedRequest {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for QueuedRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 struct SuccessResponse {
     pointer: ImmutablePointer,
     hash: Blake3Hash,
@@ -13,6 +13,7 @@
 use lightning_indexer::Indexer;
 use lightning_interfaces::prelude::*;
 use lightning_interfaces::types::{
+    FetchPriority,
     FetcherRequest,
     FetcherResponse,
     ImmutablePointer,
@@ -144,6 +145,7 @@ async fn get_fetchers(
                                     ),
                                 }],
                                 gateway_timeout: Duration::from_millis(5000),
+                                ..Default::default()
                             },
                             ..Default::default()
                         })
@@ -175,7 +177,14 @@ async fn test_simple_origin_fetch() {
     peers[0].start().await;
 
     let req_fut = async move {
-        let response = socket.run(FetcherRequest::Put { pointer }).await.unwrap();
+        let handle = socket
+            .run(FetcherRequest::Put {
+                pointer,
+                priority: FetchPriority::Normal,
+            })
+            .await
+            .unwrap();
+        let response = handle.response().await.unwrap();
         let hash = match response {
             FetcherResponse::Put(Ok(hash)) => hash,
             FetcherResponse::Put(Err(e)) => panic!("Failed to put cid: {e:?}"),
@@ -227,7 +236,14 @@ async fn test_fetch_from_peer() {
     // Put some data onto peer1.
     let (tx, rx) = oneshot::channel();
     let put_fut = async move {
-        let response = socket1.run(FetcherRequest::Put { pointer }).await.unwrap();
+        let handle = socket1
+            .run(FetcherRequest::Put {
+                pointer,
+                priority: FetchPriority::Normal,
+            })
+            .await
+            .unwrap();
+        let response = handle.response().await.unwrap();
         let hash = match response {
             FetcherResponse::Put(Ok(hash)) => hash,
             FetcherResponse::Put(Err(e)) => panic!("Failed to put cid: {e:?}"),
@@ -249,12 +265,20 @@ async fn test_fetch_from_peer() {
     // Send a fetch request to peer2.
     // We don't start the corresponding dummy ipfs gateway to ensure that peer2 can only fetch the
     // content from peer1.
-    let response = socket2.run(FetcherRequest::Fetch { hash }).await.unwrap();
-    match response {
+    let handle = socket2
+        .run(FetcherRequest::Fetch {
+            hash,
+            priority: FetchPriority::Interactive,
+        })
+        .await
+        .unwrap();
+    let progress_rx = handle.progress_receiver();
+    match handle.response().await.unwrap() {
         FetcherResponse::Fetch(Ok(())) => {
             let content1 = blockstore1.read_all_to_vec(&hash).await.unwrap();
             let content2 = blockstore2.read_all_to_vec(&hash).await.unwrap();
             assert_eq!(content1, content2);
+            assert_eq!(progress_rx.borrow().bytes, content2.len() as u64);
         },
         FetcherResponse::Fetch(Err(e)) => panic!("Failed to fetch cid: {e:?}"),
         _ => panic!("Unexpected response"),
@@ -267,3 +291,38 @@ async fn test_fetch_from_peer() {
         std::fs::remove_dir_all(&path).unwrap();
     }
 }
+
+#[tokio::test]
+async fn test_cancel_fetch() {
+    let (mut peers, path) = get_fetchers("lightning-test-cancel-fetch", 30601, 40601, 1).await;
+    let mut peer = peers.pop().unwrap();
+    let socket = peer.provider.get::<Fetcher<TestBinding>>().get_socket();
+    peer.start().await;
+
+    let req_cid =
+        Cid::try_from("bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi").unwrap();
+    let pointer = ImmutablePointer {
+        origin: OriginProvider::IPFS,
+        uri: req_cid.to_bytes(),
+    };
+
+    // The gateway is never started, so the request can not succeed.
+    let handle = socket
+        .run(FetcherRequest::Put {
+            pointer,
+            priority: FetchPriority::Background,
+        })
+        .await
+        .unwrap();
+    handle.cancel();
+    match handle.response().await.unwrap() {
+        FetcherResponse::Put(Err(e)) => assert!(e.to_string().contains("cancelled")),
+        response => panic!("Unexpected response: {response:?}"),
+    }
+
+    peer.shutdown().await;
+
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
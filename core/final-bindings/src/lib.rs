@@ -15,6 +15,7 @@
 use lightning_pinger::Pinger;
 use lightning_pool::PoolProvider;
 use lightning_rep_collector::ReputationAggregator;
+use lightning_replicator::Replicator;
 use lightning_resolver::resolver::Resolver;
 use lightning_rpc::Rpc;
 use lightning_service_executor::shim::ServiceExecutor;
@@ -48,6 +49,7 @@
     PoolInterface = PoolProvider<Self>;
     PingerInterface = Pinger<Self>;
     IndexerInterface = Indexer<Self>;
+    ReplicatorInterface = Replicator<Self>;
     DeliveryAcknowledgmentAggregatorInterface = lightning_interfaces::_hacks::Blanket;
 });
 
@@ -75,5 +77,6 @@
     PoolInterface = PoolProvider<Self>;
     PingerInterface = Pinger<Self>;
     IndexerInterface = Indexer<Self>;
+    ReplicatorInterface = Replicator<Self>;
     DeliveryAcknowledgmentAggregatorInterface = lightning_interfaces::_hacks::Blanket;
 });
@@ -71,8 +71,8 @@ async fn perform_handshake(tx: &Sender<Bytes>, rx: &mut Receiver<Bytes>) {
         schema::HandshakeRequestFrame::Handshake {
             retry: None,
             service: 1001,
-            pk: ClientPublicKey([1; 96]),
-            pop: ClientSignature([2; 48]),
+            pk: ClientPublicKey([0; 96]),
+            pop: ClientSignature([0; 48]),
         }
         .encode(),
     )
@@ -120,8 +120,8 @@ fn run_clients(n: usize) -> Vec<JoinHandle<()>> {
                         schema::HandshakeRequestFrame::Handshake {
                             retry: None,
                             service: 1001,
-                            pk: ClientPublicKey([1; 96]),
-                            pop: ClientSignature([2; 48]),
+                            pk: ClientPublicKey([0; 96]),
+                            pop: ClientSignature([0; 48]),
                         }
                         .encode(),
                     )
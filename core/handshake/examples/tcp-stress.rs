@@ -221,8 +221,8 @@ macro_rules! data {
         .send_handshake(HandshakeRequestFrame::Handshake {
             retry: None,
             service: 1001,
-            pk: ClientPublicKey([1; 96]),
-            pop: ClientSignature([2; 48]),
+            pk: ClientPublicKey([0; 96]),
+            pop: ClientSignature([0; 48]),
         })
         .await?;
     data!(line, "handshake_sent", timer);
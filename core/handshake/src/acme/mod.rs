@@ -0,0 +1,466 @@
+//! Certificate management with ACME, for the https listener and the WebTransport transport.
+
+mod resolver;
+
+use std::fs::{File, OpenOptions, Permissions};
+use std::io::{BufReader, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Context, Result};
+use axum::extract::Path as UrlPath;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Extension, Router};
+use dashmap::DashMap;
+use instant_acme::{
+    Account,
+    AccountCredentials,
+    AuthorizationStatus,
+    ChallengeType,
+    HttpClient,
+    Identifier,
+    NewAccount,
+    NewOrder,
+    OrderStatus,
+};
+use lightning_interfaces::ShutdownWaiter;
+use lightning_metrics::increment_counter;
+use rcgen::{CertificateParams, DistinguishedName};
+pub use resolver::{CertResolver, CertificateWatch, TlsCertificate};
+use tracing::{error, info, warn};
+
+use crate::config::{AcmeChallenge, AcmeConfig};
+
+/// How long to wait before trying again after failing to get a certificate. ACME servers rate
+/// limit failed attempts, so this should not be too short.
+const RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+/// Number of times the order is polled while the ACME server validates the challenges.
+const ORDER_POLL_ATTEMPTS: usize = 10;
+
+const ACCOUNT_FILE: &str = "account.json";
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// Obtains and renews the certificate of the node. The certificate is served through the
+/// [`CertResolver`], which picks up renewed certificates without restarting the listeners.
+pub struct Acme {
+    config: AcmeConfig,
+    resolver: Arc<CertResolver>,
+    /// Key authorizations of the pending `http-01` challenges, by token.
+    http_challenges: Arc<DashMap<String, String>>,
+}
+
+impl Acme {
+    /// Create the certificate manager, loading the stored certificate if there is one.
+    pub fn new(config: AcmeConfig) -> Self {
+        let current = match load_certificate(&config.storage) {
+            Ok(cert) => cert,
+            Err(e) => {
+                warn!("failed to load the stored certificate: {e:?}");
+                None
+            },
+        };
+
+        Self {
+            config,
+            resolver: Arc::new(CertResolver::new(current)),
+            http_challenges: Default::default(),
+        }
+    }
+
+    pub fn resolver(&self) -> Arc<CertResolver> {
+        self.resolver.clone()
+    }
+
+    /// The routes serving the `http-01` challenges, which have to be reachable on port 80 of
+    /// the domains.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route(
+                "/.well-known/acme-challenge/:token",
+                get(
+                    |UrlPath(token): UrlPath<String>,
+                     Extension(challenges): Extension<Arc<DashMap<String, String>>>| async move {
+                        challenges
+                            .get(&token)
+                            .map(|key_authorization| key_authorization.clone())
+                            .ok_or(StatusCode::NOT_FOUND)
+                    },
+                ),
+            )
+            .layer(Extension(self.http_challenges.clone()))
+    }
+
+    /// Renew the certificate whenever it is about to expire, until shutdown.
+    pub async fn run(self, waiter: ShutdownWaiter) {
+        loop {
+            let delay = if self.needs_certificate() {
+                match self.order_certificate().await {
+                    Ok(cert) => {
+                        info!("obtained certificate for {:?}", self.config.domains);
+                        increment_counter!(
+                            "handshake_acme_certificates",
+                            Some("Counter for number of certificates obtained with ACME")
+                        );
+                        self.resolver.set_certificate(cert);
+                        self.config.check_interval
+                    },
+                    Err(e) => {
+                        error!("failed to obtain certificate: {e:?}");
+                        RETRY_DELAY.min(self.config.check_interval)
+                    },
+                }
+            } else {
+                self.config.check_interval
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {},
+                _ = waiter.wait_for_shutdown() => break,
+            }
+        }
+    }
+
+    fn needs_certificate(&self) -> bool {
+        let Some(cert) = self.resolver.current() else {
+            return true;
+        };
+        if !cert.covers(&self.config.domains) {
+            return true;
+        }
+        match cert.not_after() {
+            Ok(not_after) => not_after <= SystemTime::now() + self.config.renew_before,
+            Err(_) => true,
+        }
+    }
+
+    async fn order_certificate(&self) -> Result<TlsCertificate> {
+        if self.config.domains.is_empty() {
+            bail!("no domains configured");
+        }
+
+        let account = self.account().await?;
+        let identifiers = self
+            .config
+            .domains
+            .iter()
+            .map(|domain| Identifier::Dns(domain.clone()))
+            .collect::<Vec<_>>();
+        let mut order = account
+            .new_order(&NewOrder {
+                identifiers: &identifiers,
+            })
+            .await?;
+
+        // Set up the challenges of every pending authorization.
+        let challenge_type = match self.config.challenge {
+            AcmeChallenge::Http01 => ChallengeType::Http01,
+            AcmeChallenge::TlsAlpn01 => ChallengeType::TlsAlpn01,
+        };
+        let mut pending = Vec::new();
+        for authorization in order.authorizations().await? {
+            match authorization.status {
+                AuthorizationStatus::Pending => {},
+                AuthorizationStatus::Valid => continue,
+                status => bail!("unexpected authorization status: {status:?}"),
+            }
+
+            let Identifier::Dns(domain) = authorization.identifier;
+            let challenge = authorization
+                .challenges
+                .into_iter()
+                .find(|challenge| challenge.r#type == challenge_type)
+                .with_context(|| format!("no {challenge_type:?} challenge for {domain}"))?;
+
+            let key_authorization = order.key_authorization(&challenge);
+            match self.config.challenge {
+                AcmeChallenge::Http01 => {
+                    self.http_challenges.insert(
+                        challenge.token.clone(),
+                        key_authorization.as_str().to_string(),
+                    );
+                },
+                AcmeChallenge::TlsAlpn01 => {
+                    self.resolver
+                        .add_challenge(&domain, key_authorization.digest().as_ref())?;
+                },
+            }
+            pending.push((domain, challenge));
+        }
+
+        let result = self.complete_challenges(&mut order, &pending).await;
+        for (domain, challenge) in &pending {
+            self.http_challenges.remove(&challenge.token);
+            self.resolver.remove_challenge(domain);
+        }
+        result?;
+
+        // The order is ready, request the certificate for a new key.
+        let mut params = CertificateParams::new(self.config.domains.clone());
+        params.distinguished_name = DistinguishedName::new();
+        let key = rcgen::Certificate::from_params(params)?;
+        order.finalize(&key.serialize_request_der()?).await?;
+
+        let chain_pem = loop {
+            match order.certificate().await? {
+                Some(chain) => break chain,
+                None => tokio::time::sleep(Duration::from_secs(1)).await,
+            }
+        };
+
+        let key_pem = key.serialize_private_key_pem();
+        let cert = parse_certificate(chain_pem.as_bytes(), key_pem.as_bytes())?;
+        store_certificate(&self.config.storage, &chain_pem, &key_pem)?;
+        Ok(cert)
+    }
+
+    /// Tell the ACME server the challenges are ready and wait until it validated them.
+    async fn complete_challenges(
+        &self,
+        order: &mut instant_acme::Order,
+        pending: &[(String, instant_acme::Challenge)],
+    ) -> Result<()> {
+        for (_, challenge) in pending {
+            order.set_challenge_ready(&challenge.url).await?;
+        }
+
+        let mut delay = Duration::from_millis(250);
+        for _ in 0..ORDER_POLL_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            let state = order.refresh().await?;
+            match state.status {
+                OrderStatus::Ready | OrderStatus::Valid => return Ok(()),
+                OrderStatus::Invalid => bail!("the order is invalid, the challenges failed"),
+                _ => delay *= 2,
+            }
+        }
+
+        Err(anyhow!(
+            "timed out waiting for the challenges to be validated"
+        ))
+    }
+
+    /// Load the stored account or create a new one.
+    async fn account(&self) -> Result<Account> {
+        let path = self.config.storage.join(ACCOUNT_FILE);
+        if path.exists() {
+            let credentials: AccountCredentials =
+                serde_json::from_reader(BufReader::new(File::open(&path)?))?;
+            let account = match self.http_client()? {
+                Some(http) => Account::from_credentials_and_http(credentials, http).await?,
+                None => Account::from_credentials(credentials).await?,
+            };
+            return Ok(account);
+        }
+
+        let contact = self
+            .config
+            .contact
+            .iter()
+            .map(|email| format!("mailto:{email}"))
+            .collect::<Vec<_>>();
+        let contact = contact.iter().map(String::as_str).collect::<Vec<_>>();
+        let new_account = NewAccount {
+            contact: &contact,
+            terms_of_service_agreed: true,
+            only_return_existing: false,
+        };
+        let (account, credentials) = match self.http_client()? {
+            Some(http) => {
+                Account::create_with_http(&new_account, &self.config.directory_url, None, http)
+                    .await?
+            },
+            None => Account::create(&new_account, &self.config.directory_url, None).await?,
+        };
+
+        std::fs::create_dir_all(&*self.config.storage)?;
+        write_secret(&path, &serde_json::to_vec(&credentials)?)?;
+        Ok(account)
+    }
+
+    /// An http client trusting the configured root certificate of the ACME server, if any.
+    fn http_client(&self) -> Result<Option<Box<dyn HttpClient>>> {
+        let Some(ca) = &self.config.directory_ca else {
+            return Ok(None);
+        };
+
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in read_pem_certs(ca)? {
+            roots.add(&rustls::Certificate(cert))?;
+        }
+        let tls = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(tls)
+            .https_only()
+            .enable_http1()
+            .build();
+        Ok(Some(Box::new(hyper::Client::builder().build(connector))))
+    }
+}
+
+fn read_pem_certs(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(rustls_pemfile::certs(&mut reader)?)
+}
+
+fn parse_certificate(mut chain_pem: &[u8], mut key_pem: &[u8]) -> Result<TlsCertificate> {
+    let chain = rustls_pemfile::certs(&mut chain_pem)?;
+    let key = rustls_pemfile::pkcs8_private_keys(&mut key_pem)?
+        .pop()
+        .context("missing private key")?;
+    TlsCertificate::new(chain, key)
+}
+
+fn load_certificate(storage: &Path) -> Result<Option<TlsCertificate>> {
+    let (cert_path, key_path) = certificate_paths(storage);
+    if !cert_path.exists() || !key_path.exists() {
+        return Ok(None);
+    }
+    let chain_pem = std::fs::read(cert_path)?;
+    let key_pem = std::fs::read(key_path)?;
+    parse_certificate(&chain_pem, &key_pem).map(Some)
+}
+
+fn store_certificate(storage: &Path, chain_pem: &str, key_pem: &str) -> Result<()> {
+    std::fs::create_dir_all(storage)?;
+    let (cert_path, key_path) = certificate_paths(storage);
+    std::fs::write(cert_path, chain_pem)?;
+    write_secret(&key_path, key_pem.as_bytes())?;
+    Ok(())
+}
+
+/// Write a file only the owner can read, for the account credentials and the private key.
+fn write_secret(path: &Path, data: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // The mode only applies to new files.
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn certificate_paths(storage: &Path) -> (PathBuf, PathBuf) {
+    (storage.join(CERT_FILE), storage.join(KEY_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn self_signed(domains: Vec<String>) -> (String, String) {
+        let cert = rcgen::generate_simple_self_signed(domains).unwrap();
+        (
+            cert.serialize_pem().unwrap(),
+            cert.serialize_private_key_pem(),
+        )
+    }
+
+    fn config(storage: &Path) -> AcmeConfig {
+        AcmeConfig {
+            domains: vec!["node.example.com".to_string()],
+            storage: storage.to_path_buf().try_into().unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn load_stored_certificate() {
+        let storage = std::env::temp_dir().join("lightning-handshake-acme-load");
+        let _ = std::fs::remove_dir_all(&storage);
+
+        // Nothing stored yet, so a certificate is needed.
+        let acme = Acme::new(config(&storage));
+        assert!(acme.resolver().current().is_none());
+        assert!(acme.needs_certificate());
+
+        let (chain, key) = self_signed(vec!["node.example.com".to_string()]);
+        store_certificate(&storage, &chain, &key).unwrap();
+        let (_, key_path) = certificate_paths(&storage);
+        let mode = std::fs::metadata(key_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // The stored certificate is loaded, and is valid for years.
+        let acme = Acme::new(config(&storage));
+        let cert = acme
+            .resolver()
+            .current()
+            .expect("certificate should be loaded");
+        assert!(cert.covers(&["node.example.com".to_string()]));
+        assert!(!acme.needs_certificate());
+
+        // A certificate for other domains has to be replaced.
+        let mut config = config(&storage);
+        config.domains.push("other.example.com".to_string());
+        assert!(Acme::new(config).needs_certificate());
+
+        std::fs::remove_dir_all(&storage).unwrap();
+    }
+
+    #[test]
+    fn renew_before_expiry() {
+        let storage = std::env::temp_dir().join("lightning-handshake-acme-renew");
+        let _ = std::fs::remove_dir_all(&storage);
+
+        let (chain, key) = self_signed(vec!["node.example.com".to_string()]);
+        store_certificate(&storage, &chain, &key).unwrap();
+
+        // rcgen certificates are valid until 4096, so renewing a thousand years early is due.
+        let mut config = config(&storage);
+        config.renew_before = Duration::from_secs(3000 * 365 * 24 * 60 * 60);
+        assert!(Acme::new(config).needs_certificate());
+
+        std::fs::remove_dir_all(&storage).unwrap();
+    }
+
+    /// Runs against a local Pebble server started with `PEBBLE_VA_ALWAYS_VALID=1`, for example:
+    ///
+    /// ```sh
+    /// docker run -p 14000:14000 -e PEBBLE_VA_ALWAYS_VALID=1 letsencrypt/pebble
+    /// PEBBLE_CA=/path/to/pebble.minica.pem cargo test -p lightning-handshake pebble -- --ignored
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires a local Pebble ACME server"]
+    async fn pebble() {
+        let storage = std::env::temp_dir().join("lightning-handshake-acme-pebble");
+        let _ = std::fs::remove_dir_all(&storage);
+
+        let mut config = config(&storage);
+        config.directory_url = std::env::var("PEBBLE_DIRECTORY")
+            .unwrap_or_else(|_| "https://localhost:14000/dir".to_string());
+        config.directory_ca = Some(
+            std::env::var("PEBBLE_CA")
+                .expect("PEBBLE_CA is not set")
+                .into(),
+        );
+
+        let acme = Acme::new(config.clone());
+        let cert = acme
+            .order_certificate()
+            .await
+            .expect("failed to obtain certificate");
+        assert!(cert.covers(&config.domains));
+
+        // The account and certificate are stored, and picked up on restart.
+        assert!(storage.join(ACCOUNT_FILE).exists());
+        let acme = Acme::new(config);
+        assert!(!acme.needs_certificate());
+
+        // Renewing reuses the stored account.
+        acme.order_certificate()
+            .await
+            .expect("failed to renew certificate");
+
+        std::fs::remove_dir_all(&storage).unwrap();
+    }
+}
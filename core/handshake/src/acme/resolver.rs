@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use rcgen::{CertificateParams, CustomExtension, PKCS_ECDSA_P256_SHA256};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use tokio::sync::watch;
+use x509_parser::extensions::GeneralName;
+
+/// The ALPN protocol of the `tls-alpn-01` challenge.
+pub const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/// A certificate chain and its private key.
+pub struct TlsCertificate {
+    /// The certificate chain in DER format, starting with the leaf certificate.
+    pub chain: Vec<Vec<u8>>,
+    /// The PKCS#8 private key in DER format.
+    pub key: Vec<u8>,
+    certified: Arc<CertifiedKey>,
+}
+
+impl TlsCertificate {
+    pub fn new(chain: Vec<Vec<u8>>, key: Vec<u8>) -> Result<Self> {
+        if chain.is_empty() {
+            return Err(anyhow!("empty certificate chain"));
+        }
+        let signing_key = rustls::sign::any_supported_type(&rustls::PrivateKey(key.clone()))
+            .map_err(|_| anyhow!("unsupported private key"))?;
+        let certs = chain.iter().cloned().map(rustls::Certificate).collect();
+        Ok(Self {
+            chain,
+            key,
+            certified: Arc::new(CertifiedKey::new(certs, signing_key)),
+        })
+    }
+
+    /// Returns the time the leaf certificate expires.
+    pub fn not_after(&self) -> Result<SystemTime> {
+        let (_, cert) = x509_parser::parse_x509_certificate(&self.chain[0])?;
+        let secs = cert.validity().not_after.timestamp();
+        Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+    }
+
+    /// Returns true if the leaf certificate is valid for all of the domains.
+    pub fn covers(&self, domains: &[String]) -> bool {
+        let Ok((_, cert)) = x509_parser::parse_x509_certificate(&self.chain[0]) else {
+            return false;
+        };
+        let Ok(Some(san)) = cert.subject_alternative_name() else {
+            return false;
+        };
+        domains.iter().all(|domain| {
+            san.value
+                .general_names
+                .iter()
+                .any(|name| matches!(name, GeneralName::DNSName(name) if name == domain))
+        })
+    }
+}
+
+/// Receives the current certificate, and is notified when it is renewed.
+pub type CertificateWatch = watch::Receiver<Option<Arc<TlsCertificate>>>;
+
+/// Resolves the certificate of the https listener, which can be replaced at any time. During a
+/// `tls-alpn-01` challenge, the challenge certificate is served to the ACME server instead.
+pub struct CertResolver {
+    current: watch::Sender<Option<Arc<TlsCertificate>>>,
+    challenges: DashMap<String, Arc<CertifiedKey>>,
+}
+
+impl CertResolver {
+    pub fn new(current: Option<TlsCertificate>) -> Self {
+        let (current, _) = watch::channel(current.map(Arc::new));
+        Self {
+            current,
+            challenges: DashMap::new(),
+        }
+    }
+
+    pub fn current(&self) -> Option<Arc<TlsCertificate>> {
+        self.current.borrow().clone()
+    }
+
+    pub fn set_certificate(&self, cert: TlsCertificate) {
+        self.current.send_replace(Some(Arc::new(cert)));
+    }
+
+    /// Subscribe to changes of the certificate.
+    pub fn subscribe(&self) -> CertificateWatch {
+        self.current.subscribe()
+    }
+
+    /// Serve a `tls-alpn-01` challenge certificate for the domain, carrying the digest of the key
+    /// authorization.
+    pub fn add_challenge(&self, domain: &str, digest: &[u8]) -> Result<()> {
+        let mut params = CertificateParams::new(vec![domain.to_string()]);
+        params.alg = &PKCS_ECDSA_P256_SHA256;
+        params.custom_extensions = vec![CustomExtension::new_acme_identifier(digest)];
+        let cert = rcgen::Certificate::from_params(params)?;
+
+        let key =
+            rustls::sign::any_supported_type(&rustls::PrivateKey(cert.serialize_private_key_der()))
+                .map_err(|_| anyhow!("unsupported private key"))?;
+        let certified = CertifiedKey::new(vec![rustls::Certificate(cert.serialize_der()?)], key);
+        self.challenges
+            .insert(domain.to_string(), Arc::new(certified));
+        Ok(())
+    }
+
+    pub fn remove_challenge(&self, domain: &str) {
+        self.challenges.remove(domain);
+    }
+
+    /// Create the TLS configuration of the https listener.
+    pub fn server_config(self: &Arc<Self>) -> rustls::ServerConfig {
+        let mut config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec(), ACME_TLS_ALPN.to_vec()];
+        config
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let is_challenge = client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|p| p == ACME_TLS_ALPN));
+        if is_challenge {
+            let domain = client_hello.server_name()?;
+            return self.challenges.get(domain).map(|key| key.clone());
+        }
+
+        self.current
+            .borrow()
+            .as_ref()
+            .map(|cert| cert.certified.clone())
+    }
+}
@@ -0,0 +1,85 @@
+use std::net::SocketAddr;
+
+use affair::AsyncWorkerUnordered;
+use anyhow::Context as _;
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use lightning_interfaces::schema::handshake::TerminationReason;
+use lightning_interfaces::types::HandshakeConnection;
+use lightning_interfaces::{
+    ExecutorProviderInterface,
+    HandshakeAdminRequest,
+    HandshakeAdminResponse,
+    ShutdownWaiter,
+};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+
+use crate::handshake::Context;
+
+/// Handles the requests of the admin socket of the handshake.
+pub struct AdminWorker<P: ExecutorProviderInterface>(pub Context<P>);
+
+impl<P: ExecutorProviderInterface> AsyncWorkerUnordered for AdminWorker<P> {
+    type Request = HandshakeAdminRequest;
+    type Response = HandshakeAdminResponse;
+
+    async fn handle(&self, req: Self::Request) -> Self::Response {
+        match req {
+            HandshakeAdminRequest::Connections => {
+                HandshakeAdminResponse::Connections(self.0.connections())
+            },
+            HandshakeAdminRequest::Terminate {
+                connection_id,
+                reason,
+            } => HandshakeAdminResponse::Terminated(
+                self.0.terminate_connection(connection_id, reason),
+            ),
+        }
+    }
+}
+
+/// Run the debug http server of the connection table.
+pub async fn spawn_admin_server<P: ExecutorProviderInterface>(
+    addr: SocketAddr,
+    ctx: Context<P>,
+    shutdown: ShutdownWaiter,
+) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/connections", get(connections::<P>))
+        .route("/connections/:id/terminate", post(terminate::<P>))
+        .layer(Extension(ctx));
+    let shutdown = async move {
+        shutdown.wait_for_shutdown().await;
+    };
+    let listener = TcpListener::bind(&addr).await?;
+    axum::serve::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown)
+        .await
+        .context("failed to run admin http server")
+}
+
+async fn connections<P: ExecutorProviderInterface>(
+    Extension(ctx): Extension<Context<P>>,
+) -> Json<Vec<HandshakeConnection>> {
+    Json(ctx.connections())
+}
+
+#[derive(Deserialize)]
+struct TerminateRequest {
+    reason: TerminationReason,
+}
+
+async fn terminate<P: ExecutorProviderInterface>(
+    Extension(ctx): Extension<Context<P>>,
+    Path(id): Path<u64>,
+    Json(request): Json<TerminateRequest>,
+) -> StatusCode {
+    if ctx.terminate_connection(id, request.reason) {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
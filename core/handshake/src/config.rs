@@ -1,6 +1,8 @@
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
+use std::time::Duration;
 
+use resolved_pathbuf::ResolvedPathBuf;
 use serde::{Deserialize, Serialize};
 
 use crate::transports;
@@ -12,6 +14,11 @@ pub struct HandshakeConfig {
     pub transports: Vec<TransportConfig>,
     pub http_address: SocketAddr,
     pub https: Option<HttpsConfig>,
+    pub resume: ResumeConfig,
+    pub rate_limit: RateLimitConfig,
+    /// Address of a debug http server listing the connections and terminating them, disabled
+    /// by default.
+    pub admin_http_address: Option<SocketAddr>,
 }
 
 impl Default for HandshakeConfig {
@@ -22,13 +29,90 @@ fn default() -> Self {
                 TransportConfig::WebTransport(Default::default()),
                 TransportConfig::Tcp(Default::default()),
                 TransportConfig::Http(Default::default()),
+                TransportConfig::WebSocket(Default::default()),
             ],
             http_address: ([0, 0, 0, 0], 4220).into(),
             https: None,
+            resume: Default::default(),
+            rate_limit: Default::default(),
+            admin_http_address: None,
         }
     }
 }
 
+/// Resumption of primary connections whose transport was lost.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct ResumeConfig {
+    /// How long a connection is kept alive without any transport, waiting for the client to
+    /// resume it with a retry handshake.
+    pub timeout: Duration,
+    /// Maximum number of bytes of service payloads kept per connection, to replay the ones the
+    /// client did not receive after it resumes. Set to zero to disable replaying.
+    pub buffer_size: usize,
+}
+
+impl Default for ResumeConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            buffer_size: 1 << 20,
+        }
+    }
+}
+
+/// Rate limits of the requests made by clients. Every new connection and every service payload
+/// sent by a client counts as a request, and is counted against both the IP address and the
+/// public key of the client.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// The limit of every IP address, for services without their own limits.
+    pub per_ip: Option<RateLimit>,
+    /// The limit of every client public key, for services without their own limits.
+    pub per_client: Option<RateLimit>,
+    /// Limits of specific services, replacing the ones above.
+    pub services: Vec<ServiceRateLimit>,
+    /// IP addresses that are never limited.
+    pub allowlist: Vec<IpAddr>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            per_ip: Some(RateLimit {
+                rate: 200.0,
+                burst: 400,
+            }),
+            per_client: Some(RateLimit {
+                rate: 100.0,
+                burst: 200,
+            }),
+            services: Vec::new(),
+            allowlist: Vec::new(),
+        }
+    }
+}
+
+/// The limits of a service. A missing limit means the service is not limited by it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServiceRateLimit {
+    pub service: u32,
+    #[serde(default)]
+    pub per_ip: Option<RateLimit>,
+    #[serde(default)]
+    pub per_client: Option<RateLimit>,
+}
+
+/// A token bucket refilled with `rate` requests per second, holding at most `burst` requests.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub rate: f64,
+    pub burst: u32,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(tag = "type")]
 pub enum TransportConfig {
@@ -37,11 +121,69 @@ pub enum TransportConfig {
     WebRTC(transports::webrtc::WebRtcConfig),
     WebTransport(transports::webtransport::WebTransportConfig),
     Http(transports::http::Config),
+    WebSocket(transports::websocket::WebSocketConfig),
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct HttpsConfig {
-    pub cert: PathBuf,
-    pub key: PathBuf,
+    /// The certificate chain in PEM format. Not needed when certificates are managed with ACME.
+    #[serde(default)]
+    pub cert: Option<PathBuf>,
+    /// The private key in PEM format. Not needed when certificates are managed with ACME.
+    #[serde(default)]
+    pub key: Option<PathBuf>,
     pub address: SocketAddr,
+    /// Obtain and renew the certificate automatically from an ACME server. The certificate is
+    /// also used by the WebTransport transport.
+    #[serde(default)]
+    pub acme: Option<AcmeConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AcmeConfig {
+    /// The domains of the node. The certificate is issued for all of them.
+    pub domains: Vec<String>,
+    /// Contact email addresses for the ACME account.
+    pub contact: Vec<String>,
+    /// The directory url of the ACME server.
+    pub directory_url: String,
+    /// A root certificate in PEM format to trust for the ACME server, when it is not signed by
+    /// a public authority, like a local Pebble server.
+    pub directory_ca: Option<PathBuf>,
+    /// The challenge used to prove control of the domains. With `http-01` the ACME server
+    /// connects to port 80 of the domains, which has to be forwarded to the http address. With
+    /// `tls-alpn-01` it connects to port 443, which has to be forwarded to the https address.
+    pub challenge: AcmeChallenge,
+    /// Directory where the account and the certificate are stored.
+    pub storage: ResolvedPathBuf,
+    /// How long before it expires the certificate is renewed.
+    pub renew_before: Duration,
+    /// How often the certificate is checked for renewal.
+    pub check_interval: Duration,
+}
+
+impl Default for AcmeConfig {
+    fn default() -> Self {
+        Self {
+            domains: Vec::new(),
+            contact: Vec::new(),
+            directory_url: "https://acme-v02.api.letsencrypt.org/directory".to_string(),
+            directory_ca: None,
+            challenge: AcmeChallenge::Http01,
+            storage: "~/.lightning/keystore/acme"
+                .try_into()
+                .expect("Failed to resolve path."),
+            renew_before: Duration::from_secs(30 * 24 * 60 * 60),
+            check_interval: Duration::from_secs(12 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AcmeChallenge {
+    #[serde(rename = "http-01")]
+    Http01,
+    #[serde(rename = "tls-alpn-01")]
+    TlsAlpn01,
 }
@@ -1,22 +1,34 @@
-use std::sync::atomic::AtomicU64;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use affair::{Executor, TokioSpawn};
 use async_channel::{bounded, Sender};
 use axum::{Extension, Router};
 use axum_server::Handle;
 use dashmap::DashMap;
-use fleek_crypto::NodePublicKey;
+use fleek_crypto::{ClientPublicKey, NodePublicKey, PublicKey};
 use fn_sdk::header::{write_header, ConnectionHeader};
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use lightning_interfaces::prelude::*;
-use lightning_interfaces::schema::handshake::{HandshakeRequestFrame, TerminationReason};
+use lightning_interfaces::schema::handshake::{
+    proof_of_possession_message,
+    HandshakeRequestFrame,
+    TerminationReason,
+    ANONYMOUS_CLIENT,
+};
+use lightning_interfaces::types::HandshakeConnection;
+use lightning_interfaces::HandshakeAdminSocket;
 use rand::RngCore;
 use tracing::warn;
 use triomphe::Arc;
 
-use crate::config::HandshakeConfig;
+use crate::acme::{Acme, CertificateWatch};
+use crate::admin::{spawn_admin_server, AdminWorker};
+use crate::config::{HandshakeConfig, ResumeConfig};
 use crate::http::{self, spawn_http_server, spawn_https_server};
+use crate::limiter::RateLimiter;
 use crate::proxy::{Proxy, State};
 use crate::transports::{
     spawn_transport_by_config,
@@ -29,6 +41,7 @@ pub struct Handshake<C: Collection> {
     status: Option<Run<C>>,
     config: HandshakeConfig,
     pk: NodePublicKey,
+    admin_socket: HandshakeAdminSocket,
 }
 
 struct Run<C: Collection> {
@@ -36,9 +49,14 @@ struct Run<C: Collection> {
     // The axum_server Server API (TLS server) does not have a `with_graceful_shutdown`
     // similarly to axum Server. The only way to shut it down gracefully is via its Handle API.
     handle: Handle,
+    acme: Option<Acme>,
 }
 
-impl<C: Collection> HandshakeInterface<C> for Handshake<C> {}
+impl<C: Collection> HandshakeInterface<C> for Handshake<C> {
+    fn get_admin_socket(&self) -> HandshakeAdminSocket {
+        self.admin_socket.clone()
+    }
+}
 
 impl<C: Collection> Handshake<C> {
     pub fn new(
@@ -50,13 +68,22 @@ pub fn new(
         let config = config.get::<Self>();
         let provider = service_executor.get_provider();
         let pk = keystore.get_ed25519_pk();
-        let ctx = Context::new(provider, waiter);
+        let mut ctx = Context::new(provider, waiter, &config);
         let handle = Handle::new();
 
+        let acme = config
+            .https
+            .as_ref()
+            .and_then(|https| https.acme.clone())
+            .map(Acme::new);
+        ctx.certificates = acme.as_ref().map(|acme| acme.resolver().subscribe());
+        let admin_socket = TokioSpawn::spawn_async_unordered(AdminWorker(ctx.clone()));
+
         Self {
-            status: Some(Run::<C> { ctx, handle }),
+            status: Some(Run::<C> { ctx, handle, acme }),
             config,
             pk,
+            admin_socket,
         }
     }
 
@@ -66,8 +93,20 @@ async fn start(
     ) {
         let run = this.status.take().expect("restart not implemented.");
 
+        tokio::spawn(run.ctx.clone().prune_rate_limiter());
+
+        if let Some(addr) = this.config.admin_http_address {
+            let ctx = run.ctx.clone();
+            let waiter = waiter.clone();
+            tokio::spawn(async move {
+                if let Err(e) = spawn_admin_server(addr, ctx, waiter).await {
+                    warn!("failed to run the handshake admin server: {e}");
+                }
+            });
+        }
+
         // Spawn transports in parallel for accepting incoming handshakes.
-        let routers = this
+        let mut routers = this
             .config
             .transports
             .iter()
@@ -79,6 +118,13 @@ async fn start(
             .collect::<Vec<_>>()
             .await;
 
+        // Start renewing the certificate, and serve the challenges for it.
+        let resolver = run.acme.as_ref().map(|acme| acme.resolver());
+        if let Some(acme) = run.acme {
+            routers.push(acme.router());
+            tokio::spawn(acme.run(waiter.clone()));
+        }
+
         // If we have routers to use, start the http server
         if !routers.is_empty() {
             let mut router = Router::new();
@@ -93,7 +139,9 @@ async fn start(
             if let Some(https) = this.config.https.clone() {
                 let https_router = router.clone();
                 let handle = run.handle.clone();
-                tokio::spawn(async move { spawn_https_server(https_router, https, handle).await });
+                tokio::spawn(async move {
+                    spawn_https_server(https_router, https, resolver, handle).await
+                });
             }
 
             // Start HTTP server.
@@ -124,12 +172,19 @@ pub struct TokenState {
     pub timeout: Option<u128>,
 }
 
+/// How often idle rate limiter buckets are removed.
+const RATE_LIMIT_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Shared context given to the transport listener tasks and the connection proxies.
 #[derive(Clone)]
 pub struct Context<P: ExecutorProviderInterface> {
     /// Service unix socket provider
     provider: P,
     pub(crate) shutdown: ShutdownWaiter,
+    pub(crate) resume: ResumeConfig,
+    /// The certificate managed with ACME, if enabled.
+    pub(crate) certificates: Option<CertificateWatch>,
+    limiter: Arc<RateLimiter>,
     connection_counter: Arc<AtomicU64>,
     connections: Arc<DashMap<u64, ConnectionEntry>>,
 }
@@ -142,13 +197,36 @@ struct ConnectionEntry {
     access_token: [u8; 48],
     /// The timeout for the access token.
     timeout: u128,
+    /// The client that made the connection and the service it is connected to, which must match
+    /// when the connection is resumed.
+    pk: ClientPublicKey,
+    service: u32,
+    /// The address the connection was made from, if the transport knows it.
+    ip: Option<IpAddr>,
+    /// The transport of the most recent primary or secondary connection.
+    transport: &'static str,
+    stats: Arc<ConnectionStats>,
+    /// Tells the proxy to terminate the connection.
+    terminate: Sender<TerminationReason>,
+}
+
+/// Counters of a connection, updated by its proxy.
+#[derive(Default)]
+pub struct ConnectionStats {
+    /// The number of bytes of service payloads received from the client.
+    pub bytes_in: AtomicU64,
+    /// The number of bytes of service payloads sent by the service.
+    pub bytes_out: AtomicU64,
 }
 
 impl<P: ExecutorProviderInterface> Context<P> {
-    pub fn new(provider: P, waiter: ShutdownWaiter) -> Self {
+    pub fn new(provider: P, waiter: ShutdownWaiter, config: &HandshakeConfig) -> Self {
         Self {
             provider,
             shutdown: waiter,
+            resume: config.resume,
+            certificates: None,
+            limiter: Arc::new(RateLimiter::new(&config.rate_limit)),
             connection_counter: AtomicU64::new(0).into(),
             connections: DashMap::new().into(),
         }
@@ -168,11 +246,29 @@ pub async fn handle_new_connection<S: TransportSender, R: TransportReceiver>(
                 retry: None,
                 service,
                 pk,
-                ..
+                pop,
             } => {
-                // TODO: Verify proof of possession
+                // Anonymous clients have no key to prove, and the limiter tells them apart by
+                // their IP address only.
+                if pk != ANONYMOUS_CLIENT && !pk.verify(&pop, &proof_of_possession_message(&pk)) {
+                    sender.terminate(TerminationReason::InvalidHandshake);
+                    return;
+                }
+
                 // TODO: Send handshake response
 
+                let ip = receiver.remote_ip();
+                if !self.limiter.check(service, ip, &pk) {
+                    sender.terminate(TerminationReason::ResourcesUnavailable);
+                    return;
+                }
+
+                if !self.provider.is_available(service) {
+                    sender.terminate(TerminationReason::ServiceTerminated);
+                    warn!("service {service} is unavailable");
+                    return;
+                }
+
                 // Attempt to connect to the service, getting the unix socket.
                 let Some(mut socket) = self.provider.connect(service).await else {
                     sender.terminate(TerminationReason::InvalidService);
@@ -191,11 +287,12 @@ pub async fn handle_new_connection<S: TransportSender, R: TransportReceiver>(
                     return;
                 }
 
-                let connection_id = self
-                    .connection_counter
-                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let connection_id = self.connection_counter.fetch_add(1, Ordering::Relaxed);
 
                 let (tx, rx) = bounded(1);
+                let (terminate_tx, terminate_rx) = bounded(1);
+                let stats = Arc::new(ConnectionStats::default());
+                let pair: TransportPair = (sender, receiver).into();
 
                 // TODO: look into potentially more secure and audit-friendly
                 //       implementations of randomness.
@@ -211,18 +308,31 @@ pub async fn handle_new_connection<S: TransportSender, R: TransportReceiver>(
                         connection_sender: tx,
                         access_token,
                         timeout: 0,
+                        pk,
+                        service,
+                        ip,
+                        transport: pair.name(),
+                        stats: stats.clone(),
+                        terminate: terminate_tx,
                     },
                 );
 
-                Proxy::new(connection_id, socket, rx, self.clone()).spawn(Some(
-                    State::OnlyPrimaryConnection((sender, receiver).into()),
-                ));
+                Proxy::new(
+                    connection_id,
+                    service,
+                    socket,
+                    rx,
+                    terminate_rx,
+                    stats,
+                    self.clone(),
+                )
+                .spawn(Some(State::OnlyPrimaryConnection(pair)));
             },
             // Join request to an existing connection
             HandshakeRequestFrame::JoinRequest { access_token } => {
                 let connection_id = u64::from_be_bytes(*arrayref::array_ref![access_token, 0, 8]);
 
-                let Some(connection) = self.connections.get(&connection_id) else {
+                let Some(mut connection) = self.connections.get_mut(&connection_id) else {
                     sender.terminate(TerminationReason::InvalidToken);
                     return;
                 };
@@ -232,6 +342,14 @@ pub async fn handle_new_connection<S: TransportSender, R: TransportReceiver>(
                     return;
                 }
 
+                if !self
+                    .limiter
+                    .check(connection.service, receiver.remote_ip(), &connection.pk)
+                {
+                    sender.terminate(TerminationReason::ResourcesUnavailable);
+                    return;
+                }
+
                 if connection.timeout
                     < SystemTime::now()
                         .duration_since(UNIX_EPOCH)
@@ -242,25 +360,44 @@ pub async fn handle_new_connection<S: TransportSender, R: TransportReceiver>(
                     return;
                 }
 
-                connection
-                    .connection_sender
-                    .send((false, (sender, receiver).into()))
-                    .await
-                    .ok();
+                let pair: TransportPair = (sender, receiver).into();
+                connection.transport = pair.name();
+                let connection_sender = connection.connection_sender.clone();
+                drop(connection);
+                connection_sender.send((false, pair)).await.ok();
             },
+            // Resume an existing connection after its transport was lost
             HandshakeRequestFrame::Handshake {
-                retry: Some(id), ..
+                retry: Some(id),
+                service,
+                pk,
+                pop,
             } => {
-                let Some(connection) = self.connections.get(&id) else {
+                let Some(mut connection) = self.connections.get_mut(&id) else {
                     sender.terminate(TerminationReason::InvalidToken);
                     return;
                 };
 
-                connection
-                    .connection_sender
-                    .send((true, (sender, receiver).into()))
-                    .await
-                    .ok();
+                // The proof of possession of a resume is the signature of the access token by
+                // the client, which only the client that made the connection can provide.
+                if connection.pk != pk
+                    || connection.service != service
+                    || !pk.verify(&pop, &connection.access_token)
+                {
+                    sender.terminate(TerminationReason::InvalidHandshake);
+                    return;
+                }
+
+                if !self.limiter.check(service, receiver.remote_ip(), &pk) {
+                    sender.terminate(TerminationReason::ResourcesUnavailable);
+                    return;
+                }
+
+                let pair: TransportPair = (sender, receiver).into();
+                connection.transport = pair.name();
+                let connection_sender = connection.connection_sender.clone();
+                drop(connection);
+                connection_sender.send((true, pair)).await.ok();
             },
         }
     }
@@ -276,12 +413,67 @@ pub async fn handle_new_connection<S: TransportSender, R: TransportReceiver>(
             .expect("Failed to get current time.")
             .as_millis();
 
-        let new_timeout = now + (ttl * 60) as u128;
+        // The ttl is in seconds.
+        let new_timeout = now + ttl as u128 * 1000;
         connection.timeout = connection.timeout.max(new_timeout);
-        let ttl = ((connection.timeout - now) / 60) as u64;
+        let ttl = ((connection.timeout - now) / 1000) as u64;
         (connection.access_token, ttl)
     }
 
+    /// Returns true if the client of the connection is allowed to send another request.
+    pub fn allow_request(&self, connection_id: u64) -> bool {
+        let Some(connection) = self.connections.get(&connection_id) else {
+            return false;
+        };
+        self.limiter
+            .check(connection.service, connection.ip, &connection.pk)
+    }
+
+    /// Periodically remove the idle buckets of the rate limiter until shutdown.
+    pub(crate) async fn prune_rate_limiter(self) {
+        let mut interval = tokio::time::interval(RATE_LIMIT_PRUNE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => self.limiter.prune(),
+                _ = self.shutdown.wait_for_shutdown() => break,
+            }
+        }
+    }
+
+    /// Returns the ongoing connections, ordered by id.
+    pub fn connections(&self) -> Vec<HandshakeConnection> {
+        let mut connections = self
+            .connections
+            .iter()
+            .map(|entry| {
+                let connection = entry.value();
+                HandshakeConnection {
+                    id: *entry.key(),
+                    client: connection.pk,
+                    ip: connection.ip,
+                    transport: connection.transport.to_string(),
+                    service: connection.service,
+                    bytes_in: connection.stats.bytes_in.load(Ordering::Relaxed),
+                    bytes_out: connection.stats.bytes_out.load(Ordering::Relaxed),
+                    token_expiry: (connection.timeout > 0).then_some(connection.timeout as u64),
+                }
+            })
+            .collect::<Vec<_>>();
+        connections.sort_by_key(|connection| connection.id);
+        connections
+    }
+
+    /// Terminate a connection, sending the reason to the client. Returns false if there is no
+    /// such connection.
+    pub fn terminate_connection(&self, connection_id: u64, reason: TerminationReason) -> bool {
+        let Some(connection) = self.connections.get(&connection_id) else {
+            return false;
+        };
+        // If the channel is full the connection is already being terminated.
+        connection.terminate.try_send(reason).ok();
+        true
+    }
+
     pub fn cleanup_connection(&self, connection_id: u64) {
         self.connections.remove(&connection_id);
     }
@@ -1,6 +1,7 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use axum::body::Body;
 use axum::http::{HeaderName, HeaderValue, Response};
 use axum::Router;
@@ -11,6 +12,7 @@
 use tower_http::cors::CorsLayer;
 use tower_http::set_header::SetResponseHeaderLayer;
 
+use crate::acme::CertResolver;
 use crate::config::HttpsConfig;
 
 pub const FLEEK_NODE_HEADER: &str = "x-fleek-node";
@@ -40,15 +42,19 @@ pub async fn spawn_http_server(
 pub async fn spawn_https_server(
     router: Router,
     https_config: HttpsConfig,
+    resolver: Option<Arc<CertResolver>>,
     handle: Handle,
 ) -> anyhow::Result<()> {
     let app = router
         .layer(CorsLayer::permissive())
         .into_make_service_with_connect_info::<SocketAddr>();
 
-    let config =
-        RustlsConfig::from_pem_file(https_config.cert.as_path(), https_config.key.as_path())
-            .await?;
+    // Certificates managed with ACME are served through the resolver, which picks up renewals.
+    let config = match (resolver, &https_config.cert, &https_config.key) {
+        (Some(resolver), _, _) => RustlsConfig::from_config(Arc::new(resolver.server_config())),
+        (None, Some(cert), Some(key)) => RustlsConfig::from_pem_file(cert, key).await?,
+        _ => bail!("https requires either a certificate and key or acme to be configured"),
+    };
     axum_server::bind_rustls(https_config.address, config)
         .handle(handle)
         .serve(app)
@@ -1,6 +1,9 @@
 #![allow(dead_code)]
 
+mod acme;
+mod admin;
 mod http;
+mod limiter;
 mod proxy;
 
 pub mod config;
@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use fleek_crypto::ClientPublicKey;
+use lightning_interfaces::schema::handshake::ANONYMOUS_CLIENT;
+use lightning_metrics::increment_counter;
+
+use crate::config::{RateLimit, RateLimitConfig};
+
+/// Token bucket rate limiter of the requests made by clients, keyed by IP address and by client
+/// public key. Every service has its own buckets.
+pub struct RateLimiter {
+    enabled: bool,
+    per_ip: Option<RateLimit>,
+    per_client: Option<RateLimit>,
+    services: HashMap<u32, (Option<RateLimit>, Option<RateLimit>)>,
+    allowlist: HashSet<IpAddr>,
+    ips: DashMap<(IpAddr, u32), Bucket>,
+    clients: DashMap<(ClientPublicKey, u32), Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            per_ip: config.per_ip,
+            per_client: config.per_client,
+            services: config
+                .services
+                .iter()
+                .map(|limit| (limit.service, (limit.per_ip, limit.per_client)))
+                .collect(),
+            allowlist: config.allowlist.iter().copied().collect(),
+            ips: DashMap::new(),
+            clients: DashMap::new(),
+        }
+    }
+
+    /// Returns true if a request of the client to the service is allowed, taking a token from
+    /// its buckets. The key of the client must have been verified, since a client could
+    /// otherwise drain the bucket of another one. The anonymous client is only limited by its IP
+    /// address.
+    pub fn check(&self, service: u32, ip: Option<IpAddr>, pk: &ClientPublicKey) -> bool {
+        self.check_at(service, ip, pk, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        service: u32,
+        ip: Option<IpAddr>,
+        pk: &ClientPublicKey,
+        now: Instant,
+    ) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        let (per_ip, per_client) = self
+            .services
+            .get(&service)
+            .copied()
+            .unwrap_or((self.per_ip, self.per_client));
+
+        if let (Some(limit), Some(ip)) = (per_ip, ip) {
+            if !self.allowlist.contains(&ip) && !take(&self.ips, (ip, service), limit, now) {
+                increment_counter!(
+                    "handshake_rate_limited",
+                    Some("Counter for requests rejected by the handshake rate limiter"),
+                    "key" => "ip"
+                );
+                return false;
+            }
+        }
+
+        if let Some(limit) = per_client {
+            if *pk != ANONYMOUS_CLIENT && !take(&self.clients, (*pk, service), limit, now) {
+                increment_counter!(
+                    "handshake_rate_limited",
+                    Some("Counter for requests rejected by the handshake rate limiter"),
+                    "key" => "client"
+                );
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Remove the buckets that have been idle long enough to be full again, since they behave
+    /// the same as new ones.
+    pub fn prune(&self) {
+        self.prune_at(Instant::now())
+    }
+
+    fn prune_at(&self, now: Instant) {
+        // The slowest limit to refill bounds the time any bucket takes to be full again.
+        let Some(refill) = self
+            .services
+            .values()
+            .flat_map(|(per_ip, per_client)| [*per_ip, *per_client])
+            .chain([self.per_ip, self.per_client])
+            .flatten()
+            .map(|limit| {
+                // A bucket too slow to refill in any representable time is never pruned.
+                Duration::try_from_secs_f64(limit.burst as f64 / limit.rate.max(f64::EPSILON))
+                    .unwrap_or(Duration::MAX)
+            })
+            .max()
+        else {
+            return;
+        };
+
+        let is_active = |bucket: &Bucket| now.saturating_duration_since(bucket.updated) < refill;
+        self.ips.retain(|_, bucket| is_active(bucket));
+        self.clients.retain(|_, bucket| is_active(bucket));
+    }
+}
+
+/// Take a token from the bucket of the key, creating a full bucket if there is none.
+fn take<K: Eq + Hash>(
+    buckets: &DashMap<K, Bucket>,
+    key: K,
+    limit: RateLimit,
+    now: Instant,
+) -> bool {
+    let mut bucket = buckets.entry(key).or_insert_with(|| Bucket {
+        tokens: limit.burst as f64,
+        updated: now,
+    });
+
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * limit.rate).min(limit.burst as f64);
+    bucket.updated = now;
+
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceRateLimit;
+
+    const LIMIT: RateLimit = RateLimit {
+        rate: 1.0,
+        burst: 2,
+    };
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: true,
+            per_ip: Some(LIMIT),
+            per_client: Some(LIMIT),
+            services: Vec::new(),
+            allowlist: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_refills_over_time() {
+        let limiter = RateLimiter::new(&config());
+        let ip = Some([127, 0, 0, 1].into());
+        let now = Instant::now();
+
+        assert!(limiter.check_at(0, ip, &ANONYMOUS_CLIENT, now));
+        assert!(limiter.check_at(0, ip, &ANONYMOUS_CLIENT, now));
+        assert!(!limiter.check_at(0, ip, &ANONYMOUS_CLIENT, now));
+
+        // Other services and addresses have their own buckets.
+        assert!(limiter.check_at(1, ip, &ANONYMOUS_CLIENT, now));
+        assert!(limiter.check_at(0, Some([127, 0, 0, 2].into()), &ANONYMOUS_CLIENT, now));
+
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check_at(0, ip, &ANONYMOUS_CLIENT, later));
+        assert!(!limiter.check_at(0, ip, &ANONYMOUS_CLIENT, later));
+    }
+
+    #[test]
+    fn test_client_limit() {
+        let limiter = RateLimiter::new(&config());
+        let pk = ClientPublicKey([1; 96]);
+        let now = Instant::now();
+
+        // The client is limited across addresses.
+        assert!(limiter.check_at(0, Some([127, 0, 0, 1].into()), &pk, now));
+        assert!(limiter.check_at(0, Some([127, 0, 0, 2].into()), &pk, now));
+        assert!(!limiter.check_at(0, Some([127, 0, 0, 3].into()), &pk, now));
+
+        // Anonymous clients are only limited by address.
+        assert!(limiter.check_at(0, None, &ANONYMOUS_CLIENT, now));
+        assert!(limiter.check_at(0, None, &ANONYMOUS_CLIENT, now));
+        assert!(limiter.check_at(0, None, &ANONYMOUS_CLIENT, now));
+    }
+
+    #[test]
+    fn test_allowlist_and_service_limits() {
+        let ip = [127, 0, 0, 1].into();
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            services: vec![ServiceRateLimit {
+                service: 1,
+                per_ip: None,
+                per_client: None,
+            }],
+            allowlist: vec![ip],
+            ..config()
+        });
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            assert!(limiter.check_at(0, Some(ip), &ANONYMOUS_CLIENT, now));
+            assert!(limiter.check_at(1, Some([127, 0, 0, 2].into()), &ANONYMOUS_CLIENT, now));
+        }
+        assert!(limiter.check_at(0, Some([127, 0, 0, 2].into()), &ANONYMOUS_CLIENT, now));
+        assert!(limiter.check_at(0, Some([127, 0, 0, 2].into()), &ANONYMOUS_CLIENT, now));
+        assert!(!limiter.check_at(0, Some([127, 0, 0, 2].into()), &ANONYMOUS_CLIENT, now));
+    }
+
+    #[test]
+    fn test_prune_idle_buckets() {
+        let limiter = RateLimiter::new(&config());
+        let now = Instant::now();
+        assert!(limiter.check_at(0, Some([127, 0, 0, 1].into()), &ANONYMOUS_CLIENT, now));
+
+        limiter.prune_at(now + Duration::from_secs(1));
+        assert_eq!(limiter.ips.len(), 1);
+        limiter.prune_at(now + Duration::from_secs(2));
+        assert!(limiter.ips.is_empty());
+    }
+
+    #[test]
+    fn test_prune_never_refilled_buckets() {
+        for rate in [0.0, -1.0, 1e-300] {
+            let limit = RateLimit {
+                rate,
+                burst: u32::MAX,
+            };
+            let limiter = RateLimiter::new(&RateLimitConfig {
+                per_ip: Some(limit),
+                ..config()
+            });
+            let now = Instant::now();
+            assert!(limiter.check_at(0, Some([127, 0, 0, 1].into()), &ANONYMOUS_CLIENT, now));
+
+            limiter.prune_at(now + Duration::from_secs(3600));
+            assert_eq!(limiter.ips.len(), 1);
+        }
+    }
+}
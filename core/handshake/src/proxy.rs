@@ -1,15 +1,18 @@
 use std::collections::VecDeque;
-use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
 
 use arrayref::array_ref;
 use async_channel::Receiver;
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use lightning_interfaces::schema::handshake::{ResponseFrame, TerminationReason};
 use lightning_interfaces::ExecutorProviderInterface;
+use lightning_metrics::histogram;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
+use triomphe::Arc;
 
-use crate::handshake::Context;
+use crate::handshake::{ConnectionStats, Context};
 use crate::schema::RequestFrame;
 use crate::transports::{match_transport, TransportPair, TransportReceiver, TransportSender};
 
@@ -17,12 +20,18 @@ pub struct Proxy<P: ExecutorProviderInterface> {
     context: Context<P>,
     /// The id for this connection.
     connection_id: u64,
+    /// The service of the connection, as the label of its metrics.
+    service: String,
     /// The unix socket connection to the service made specifically for this ongoing connection.
     socket: UnixStream,
     /// The buffer using which we read bytes from the unix socket.
     buffer: BytesMut,
     //// A socket used to make us aware of new connection requests or refresh by the client.
     connection_rx: Receiver<(IsPrimary, TransportPair)>,
+    /// Receives the reason when the connection is terminated by the node.
+    terminate_rx: Receiver<TerminationReason>,
+    /// The counters of this connection shown in the connection table.
+    stats: Arc<ConnectionStats>,
     /// The size of the current service payload we're writing from the service socket to the client
     /// transport.
     current_write: usize,
@@ -39,6 +48,26 @@ pub struct Proxy<P: ExecutorProviderInterface> {
     /// payload through the transport. And randomly inserting in some other frame in the middle of
     /// an active length delimited message before reaching the promised length breaks many things.
     queued_primary_response: VecDeque<ResponseFrame>,
+    /// The chunks of a service payload received from the client so far. The service only gets
+    /// complete payloads, so they are buffered until the final payload frame arrives.
+    pending_payload: BytesMut,
+    /// The most recent complete service payloads, kept to replay the ones the client missed
+    /// when it resumes the connection. `replay_start` is the number of the first one.
+    replay: VecDeque<Bytes>,
+    replay_start: u64,
+    replay_size: usize,
+    /// The part of the current service payload read so far, if it fits in the replay buffer.
+    replay_current: Option<BytesMut>,
+    /// Set when a primary connection is resumed, until the client tells us how many payloads it
+    /// has received. Service payloads are only kept for replaying until then.
+    resuming: bool,
+    /// Set until the first service payload, which is paid for by the rate limit token taken
+    /// when the connection was made. An http connection carries a single request, and is only
+    /// charged once for it.
+    prepaid: bool,
+    /// When the service payloads still waiting for a response were sent to the service. The
+    /// next payload of the service is taken as the response to the oldest one.
+    requests: VecDeque<Instant>,
 }
 
 pub type IsPrimary = bool;
@@ -51,10 +80,21 @@ pub enum State {
     Terminated,
 }
 
+/// Maximum number of payloads kept for replaying, on top of the size limit.
+const MAX_REPLAY_PAYLOADS: usize = 1024;
+
+/// Maximum number of requests waiting for a response whose latency is reported. Services that
+/// don't respond to every request would grow the queue forever otherwise.
+const MAX_PENDING_REQUESTS: usize = 1024;
+
 enum HandleRequestResult {
     Ok,
+    /// The client resumed the connection and received `count` payloads.
+    Replay {
+        count: u64,
+    },
     DropTransport,
-    TerminateConnection,
+    TerminateConnection(TerminationReason),
 }
 
 impl<P: ExecutorProviderInterface> Proxy<P> {
@@ -62,20 +102,34 @@ impl<P: ExecutorProviderInterface> Proxy<P> {
     #[inline(always)]
     pub fn new(
         connection_id: u64,
+        service: u32,
         socket: UnixStream,
         connection_rx: Receiver<(IsPrimary, TransportPair)>,
+        terminate_rx: Receiver<TerminationReason>,
+        stats: Arc<ConnectionStats>,
         context: Context<P>,
     ) -> Self {
         Self {
             context,
             connection_id,
+            service: service.to_string(),
             socket,
             buffer: Default::default(),
             connection_rx,
+            terminate_rx,
+            stats,
             current_write: 0,
             discard_bytes: false,
             is_primary_the_current_sender: false,
             queued_primary_response: VecDeque::new(),
+            pending_payload: BytesMut::new(),
+            replay: VecDeque::new(),
+            replay_start: 0,
+            replay_size: 0,
+            replay_current: None,
+            resuming: false,
+            prepaid: true,
+            requests: VecDeque::new(),
         }
     }
 
@@ -123,9 +177,16 @@ pub async fn run(mut self, start: Option<State>) {
 
     #[inline]
     async fn run_with_no_connection(&mut self) -> State {
-        match tokio::time::timeout(Duration::from_secs(10), self.connection_rx.recv()).await {
+        let timeout = self.context.resume.timeout;
+        let res = tokio::select! {
+            res = tokio::time::timeout(timeout, self.connection_rx.recv()) => res,
+            // There is no transport to tell the client why.
+            Ok(_) = self.terminate_rx.recv() => return State::Terminated,
+        };
+        match res {
             Ok(Ok((is_primary, pair))) => {
                 if is_primary {
+                    self.resuming = true;
                     State::OnlyPrimaryConnection(pair)
                 } else {
                     State::OnlySecondaryConnection(pair)
@@ -179,8 +240,19 @@ async fn run_with_one_connection<S: TransportSender, R: TransportReceiver>(
                             self.maybe_flush_primary_queue(true, &mut sender);
                         },
                         Some(HandleRequestResult::Ok) => {},
-                        Some(HandleRequestResult::TerminateConnection) => {
-                            break 'outer TerminationReason::InternalError;
+                        Some(HandleRequestResult::Replay { count }) => {
+                            if !self.can_replay(count, true) {
+                                break 'outer TerminationReason::ResourcesUnavailable;
+                            }
+                            if !self.replay(true, &mut sender).await {
+                                self.discard_bytes = true;
+                                self.queued_primary_response.clear();
+                                self.pending_payload.clear();
+                                return State::NoConnection;
+                            }
+                        },
+                        Some(HandleRequestResult::TerminateConnection(reason)) => {
+                            break 'outer reason;
                         },
                         Some(HandleRequestResult::DropTransport) | None => {
                             // We're possibly switching connection. If there are any pending bytes from
@@ -188,10 +260,12 @@ async fn run_with_one_connection<S: TransportSender, R: TransportReceiver>(
                             // send the next payload to the new connection.
                             self.discard_bytes = true;
                             self.queued_primary_response.clear();
+                            self.pending_payload.clear();
                             return State::NoConnection;
                         }
                     }
                 },
+                Ok(reason) = self.terminate_rx.recv() => break 'outer reason,
                 res = self.connection_rx.recv() => match res {
                     Ok((is_new_primary, pair)) => return match (is_primary, is_new_primary) {
                         // Drop the previous connection if a new attempt is being made to reuse
@@ -201,6 +275,7 @@ async fn run_with_one_connection<S: TransportSender, R: TransportReceiver>(
                             sender.terminate(TerminationReason::ConnectionInUse);
                             self.discard_bytes = true;
                             self.queued_primary_response.clear();
+                            self.resuming = true;
                             State::OnlyPrimaryConnection(pair)
                         },
                         (false, false) => {
@@ -218,6 +293,7 @@ async fn run_with_one_connection<S: TransportSender, R: TransportReceiver>(
                         },
                         (false, true) => {
                             self.is_primary_the_current_sender = false;
+                            self.resuming = true;
                             State::PrimaryAndSecondary(pair,(sender, receiver).into())
                         },
                     },
@@ -245,9 +321,11 @@ async fn run_with_one_connection<S: TransportSender, R: TransportReceiver>(
                                 }
 
                                 // When we are here it means we're reading a new message so any
-                                // assumption about the previous payload has to be reset.
+                                // assumption about the previous payload has to be reset. A
+                                // resumed primary only gets new payloads after the ones it missed,
+                                // so until then they are only kept for replaying.
                                 self.is_primary_the_current_sender = false;
-                                self.discard_bytes = false;
+                                self.discard_bytes = is_primary && self.resuming;
 
                                 // This might be a window to flush some pending responses to the
                                 // primary.
@@ -257,14 +335,18 @@ async fn run_with_one_connection<S: TransportSender, R: TransportReceiver>(
 
                                 let bytes = self.buffer.split_to(4);
                                 let len = u32::from_be_bytes(*array_ref![bytes, 0, 4]) as usize;
-                                sender.start_write(len);
+                                if !self.discard_bytes {
+                                    sender.start_write(len);
+                                }
                                 self.current_write = len;
+                                self.start_payload(len);
                                 continue 'inner; // to handle `len` == 0.
                             }
 
                             let take = self.current_write.min(self.buffer.len());
                             let bytes = self.buffer.split_to(take);
                             self.current_write -= take;
+                            self.record_payload(&bytes);
 
                             if self.discard_bytes {
                                 // Just ignore these bytes and move on.
@@ -276,6 +358,7 @@ async fn run_with_one_connection<S: TransportSender, R: TransportReceiver>(
                                 self.queued_primary_response.clear();
                                 return State::NoConnection;
                             }
+                            sender.ready().await;
                         }
                     }
                 }
@@ -311,8 +394,22 @@ pub async fn run_with_primary_and_secondary<
                         Some(HandleRequestResult::Ok) => {
                             self.maybe_flush_primary_queue(false, &mut p_sender);
                         },
-                        Some(HandleRequestResult::TerminateConnection) => {
-                            break 'outer TerminationReason::InternalError;
+                        Some(HandleRequestResult::Replay { count }) => {
+                            // The secondary is the active writer, so the current payload keeps
+                            // going its way and only complete payloads are replayed.
+                            if !self.can_replay(count, false) {
+                                break 'outer TerminationReason::ResourcesUnavailable;
+                            }
+                            if !self.replay(false, &mut p_sender).await {
+                                self.queued_primary_response.clear();
+                                self.pending_payload.clear();
+                                let pair = (s_sender, s_receiver).into();
+                                return State::OnlySecondaryConnection(pair);
+                            }
+                            self.maybe_flush_primary_queue(false, &mut p_sender);
+                        },
+                        Some(HandleRequestResult::TerminateConnection(reason)) => {
+                            break 'outer reason;
                         },
                         Some(HandleRequestResult::DropTransport) | None => {
                             // We lost connection with primary. So if we're currently writing to it
@@ -321,34 +418,40 @@ pub async fn run_with_primary_and_secondary<
                                 self.discard_bytes = true;
                             }
                             self.queued_primary_response.clear();
+                            self.pending_payload.clear();
                             return State::OnlySecondaryConnection((s_sender, s_receiver).into());
                         }
                     }
                 },
                 res = s_receiver.recv() => {
                     match async_map(res, |r| self.handle_incoming(false, r)).await {
-                        Some(HandleRequestResult::Ok) => {},
-                        Some(HandleRequestResult::TerminateConnection) => {
-                            break 'outer TerminationReason::InternalError;
+                        // Only a primary connection can be resumed.
+                        Some(HandleRequestResult::Ok | HandleRequestResult::Replay { .. }) => {},
+                        Some(HandleRequestResult::TerminateConnection(reason)) => {
+                            break 'outer reason;
                         },
                         Some(HandleRequestResult::DropTransport) | None => {
                          if !self.is_primary_the_current_sender {
                                 self.discard_bytes = true;
                             }
+                            self.pending_payload.clear();
                             return State::OnlyPrimaryConnection((p_sender, p_receiver).into());
                         }
                     }
                 },
+                Ok(reason) = self.terminate_rx.recv() => break 'outer reason,
                 res = self.connection_rx.recv() => match res {
                     Ok((is_primary, pair)) => {
                         return match (is_primary, self.is_primary_the_current_sender) {
                             (true, true) => {
                                 self.discard_bytes = true;
                                 self.queued_primary_response.clear();
+                                self.resuming = true;
                                 State::PrimaryAndSecondary(pair, (s_sender, s_receiver).into())
                             },
                             (true, false) => {
                                 self.queued_primary_response.clear();
+                                self.resuming = true;
                                 State::PrimaryAndSecondary(pair, (s_sender, s_receiver).into())
                             },
                             (false, true) => {
@@ -396,12 +499,14 @@ pub async fn run_with_primary_and_secondary<
                                 let len = u32::from_be_bytes(*array_ref![bytes, 0, 4]) as usize;
                                 s_sender.start_write(len);
                                 self.current_write = len;
+                                self.start_payload(len);
                                 continue 'inner; // to handle `len` == 0.
                             }
 
                             let take = self.current_write.min(self.buffer.len());
                             let bytes = self.buffer.split_to(take);
                             self.current_write -= take;
+                            self.record_payload(&bytes);
 
                             if self.discard_bytes {
                                 // Just ignore these bytes and move on.
@@ -410,6 +515,7 @@ pub async fn run_with_primary_and_secondary<
 
                             return if self.is_primary_the_current_sender {
                                 if p_sender.write(bytes.freeze()).is_ok() {
+                                    p_sender.ready().await;
                                     continue 'inner;
                                 }
                                 self.discard_bytes = true;
@@ -417,6 +523,7 @@ pub async fn run_with_primary_and_secondary<
                                 State::OnlySecondaryConnection((s_sender, s_receiver).into())
                             } else {
                                 if s_sender.write(bytes.freeze()).is_ok() {
+                                    s_sender.ready().await;
                                     continue 'inner;
                                 }
                                 self.discard_bytes = true;
@@ -439,12 +546,50 @@ async fn handle_incoming(
         request: RequestFrame,
     ) -> HandleRequestResult {
         match request {
-            RequestFrame::ServicePayload { bytes } => {
+            RequestFrame::ServicePayloadChunk { bytes } => {
+                self.stats
+                    .bytes_in
+                    .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                if self.pending_payload.len() + bytes.len() > u32::MAX as usize {
+                    return HandleRequestResult::TerminateConnection(
+                        TerminationReason::InternalError,
+                    );
+                }
+                self.pending_payload.extend_from_slice(&bytes);
+                HandleRequestResult::Ok
+            },
+            RequestFrame::ServicePayload { mut bytes } => {
+                self.stats
+                    .bytes_in
+                    .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                if !std::mem::take(&mut self.prepaid)
+                    && !self.context.allow_request(self.connection_id)
+                {
+                    return HandleRequestResult::TerminateConnection(
+                        TerminationReason::ResourcesUnavailable,
+                    );
+                }
+                if !self.pending_payload.is_empty() {
+                    if self.pending_payload.len() + bytes.len() > u32::MAX as usize {
+                        return HandleRequestResult::TerminateConnection(
+                            TerminationReason::InternalError,
+                        );
+                    }
+                    self.pending_payload.extend_from_slice(&bytes);
+                    bytes = self.pending_payload.split().freeze();
+                }
                 if self.socket.write_u32(bytes.len() as u32).await.is_err() {
-                    return HandleRequestResult::TerminateConnection;
+                    return HandleRequestResult::TerminateConnection(
+                        TerminationReason::InternalError,
+                    );
                 }
                 if self.socket.write_all(&bytes).await.is_err() {
-                    return HandleRequestResult::TerminateConnection;
+                    return HandleRequestResult::TerminateConnection(
+                        TerminationReason::InternalError,
+                    );
+                }
+                if self.requests.len() < MAX_PENDING_REQUESTS {
+                    self.requests.push_back(Instant::now());
                 }
                 HandleRequestResult::Ok
             },
@@ -469,10 +614,123 @@ async fn handle_incoming(
                 // todo: not supported/expected at the moment.
                 HandleRequestResult::DropTransport
             },
+            RequestFrame::Received { count } => {
+                // The payloads the client has received don't have to be kept anymore.
+                while self.replay_start < count {
+                    let Some(payload) = self.replay.pop_front() else {
+                        break;
+                    };
+                    self.replay_size -= payload.len();
+                    self.replay_start += 1;
+                }
+
+                if is_primary && self.resuming {
+                    self.resuming = false;
+                    HandleRequestResult::Replay { count }
+                } else {
+                    HandleRequestResult::Ok
+                }
+            },
             _ => unreachable!(),
         }
     }
 
+    /// Called when the header of a new service payload is read from the socket.
+    #[inline(always)]
+    fn start_payload(&mut self, len: usize) {
+        let size = self.context.resume.buffer_size;
+        self.replay_current = (size > 0 && len <= size).then(|| BytesMut::with_capacity(len));
+        if len == 0 {
+            self.finish_payload();
+        }
+    }
+
+    /// Called with every part of the current service payload read from the socket.
+    #[inline(always)]
+    fn record_payload(&mut self, bytes: &[u8]) {
+        self.stats
+            .bytes_out
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        if let Some(current) = &mut self.replay_current {
+            current.extend_from_slice(bytes);
+        }
+        if self.current_write == 0 {
+            self.finish_payload();
+        }
+    }
+
+    fn finish_payload(&mut self) {
+        if let Some(start) = self.requests.pop_front() {
+            histogram!(
+                "service_request_duration",
+                Some("Time it took a service to respond to the requests of clients, in seconds"),
+                start.elapsed().as_secs_f64(),
+                "service" => self.service.as_str()
+            );
+        }
+
+        let Some(payload) = self.replay_current.take() else {
+            // The payload was too large to keep. Replaying the ones before it would leave a gap,
+            // so they are dropped as well.
+            self.replay_start += self.replay.len() as u64 + 1;
+            self.replay.clear();
+            self.replay_size = 0;
+            return;
+        };
+
+        self.replay_size += payload.len();
+        self.replay.push_back(payload.freeze());
+        while self.replay_size > self.context.resume.buffer_size
+            || self.replay.len() > MAX_REPLAY_PAYLOADS
+        {
+            let Some(payload) = self.replay.pop_front() else {
+                break;
+            };
+            self.replay_size -= payload.len();
+            self.replay_start += 1;
+        }
+    }
+
+    /// Returns true if every payload after the first `count` ones can still be replayed.
+    #[inline(always)]
+    fn can_replay(&self, count: u64, include_current: bool) -> bool {
+        let current_lost =
+            include_current && self.current_write > 0 && self.replay_current.is_none();
+        count >= self.replay_start && !current_lost
+    }
+
+    /// Send the kept payloads to a resumed primary connection. With `include_current`, the part
+    /// of the current payload read so far is sent as well, and the rest of it follows as it is
+    /// read from the socket. Returns false if the transport failed.
+    async fn replay<S: TransportSender>(&mut self, include_current: bool, sender: &mut S) -> bool {
+        for payload in &self.replay {
+            sender.start_write(payload.len());
+            if payload.is_empty() {
+                continue;
+            }
+            if sender.write(payload.clone()).is_err() {
+                return false;
+            }
+            sender.ready().await;
+        }
+
+        if include_current && self.current_write > 0 {
+            let Some(current) = &self.replay_current else {
+                return false;
+            };
+            sender.start_write(current.len() + self.current_write);
+            if !current.is_empty() {
+                if sender.write(Bytes::copy_from_slice(current)).is_err() {
+                    return false;
+                }
+                sender.ready().await;
+            }
+            self.discard_bytes = false;
+        }
+
+        true
+    }
+
     /// Makes sure the buffer has a proper allocated capacity based on the expected number of bytes.
     #[inline(always)]
     fn grow_buffer(&mut self) {
@@ -532,11 +790,12 @@ mod tests {
     use std::time::Duration;
 
     use anyhow::Result;
-    use fleek_crypto::{ClientPublicKey, ClientSignature};
+    use fleek_crypto::{ClientPublicKey, ClientSecretKey, ClientSignature, SecretKey};
     use fn_sdk::header::read_header;
     use futures::{SinkExt, StreamExt};
     use lightning_interfaces::prelude::*;
     use lightning_interfaces::schema::handshake::{
+        proof_of_possession_message,
         HandshakeRequestFrame,
         RequestFrame,
         ResponseFrame,
@@ -592,7 +851,7 @@ async fn connect(&self, service_id: ServiceId) -> Option<UnixStream> {
 
     async fn start_mock_node<P: ExecutorProviderInterface>(id: u16) -> Result<ShutdownController> {
         let shutdown = ShutdownController::default();
-        let context = Context::new(MockServiceProvider, shutdown.waiter());
+        let context = Context::new(MockServiceProvider, shutdown.waiter(), &Default::default());
         let (transport, _) =
             MockTransport::bind::<P>(shutdown.waiter(), MockTransportConfig { port: id }).await?;
         transport.spawn_listener_task(context);
@@ -638,6 +897,211 @@ async fn primary_connection() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn chunked_request_payload() -> Result<()> {
+        // start and connect to the mock node
+        let mut shutdown = start_mock_node::<MockServiceProvider>(4).await?;
+        let (tx, rx) = dial_mock(4).await.expect("failed to dial");
+
+        // send handshake req
+        tx.send(
+            HandshakeRequestFrame::Handshake {
+                retry: None,
+                service: ECHO_SERVICE,
+                pk: ClientPublicKey([0; 96]),
+                pop: ClientSignature([0; 48]),
+            }
+            .encode(),
+        )
+        .await?;
+
+        // send the payload in chunks, the service should receive it as a single message
+        let (first, rest) = TEST_PAYLOAD.split_at(100);
+        let (second, last) = rest.split_at(200);
+        for bytes in [first, second] {
+            tx.send(
+                RequestFrame::ServicePayloadChunk {
+                    bytes: bytes.to_vec().into(),
+                }
+                .encode(),
+            )
+            .await?;
+        }
+        tx.send(
+            RequestFrame::ServicePayload {
+                bytes: last.to_vec().into(),
+            }
+            .encode(),
+        )
+        .await?;
+
+        match ResponseFrame::decode(&rx.recv().await?)? {
+            ResponseFrame::ServicePayload { bytes } => assert_eq!(&bytes, TEST_PAYLOAD),
+            f => panic!("expected payload, got {f:?}"),
+        }
+
+        shutdown.shutdown().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resume_connection() -> Result<()> {
+        // start and connect to the mock node
+        let mut shutdown = start_mock_node::<MockServiceProvider>(5).await?;
+        let (tx, rx) = dial_mock(5).await.expect("failed to dial");
+
+        let sk = ClientSecretKey::generate();
+        tx.send(
+            HandshakeRequestFrame::Handshake {
+                retry: None,
+                service: ECHO_SERVICE,
+                pk: sk.to_pk(),
+                pop: sk.sign(&proof_of_possession_message(&sk.to_pk())),
+            }
+            .encode(),
+        )
+        .await?;
+
+        // the connection id is the start of the access token, which is signed to resume it
+        tx.send(RequestFrame::AccessToken { ttl: 1 }.encode())
+            .await?;
+        let access_token = match ResponseFrame::decode(&rx.recv().await?)? {
+            ResponseFrame::AccessToken { access_token, .. } => *access_token,
+            f => panic!("expected access token, got {f:?}"),
+        };
+        let connection_id = u64::from_be_bytes(*arrayref::array_ref![access_token, 0, 8]);
+
+        // send a payload and lose the connection before the response is read
+        tx.send(
+            RequestFrame::ServicePayload {
+                bytes: TEST_PAYLOAD.into(),
+            }
+            .encode(),
+        )
+        .await?;
+        drop((tx, rx));
+
+        // resuming with a different client, or without a valid signature of the access token,
+        // is rejected
+        let other = ClientSecretKey::generate();
+        for (pk, pop) in [
+            (other.to_pk(), other.sign(&access_token)),
+            (sk.to_pk(), ClientSignature([0; 48])),
+            (sk.to_pk(), sk.sign(&connection_id.to_be_bytes())),
+            (sk.to_pk(), other.sign(&access_token)),
+        ] {
+            let (tx, rx) = dial_mock(5).await.expect("failed to dial");
+            tx.send(
+                HandshakeRequestFrame::Handshake {
+                    retry: Some(connection_id),
+                    service: ECHO_SERVICE,
+                    pk,
+                    pop,
+                }
+                .encode(),
+            )
+            .await?;
+            assert_eq!(
+                ResponseFrame::decode(&rx.recv().await?)?,
+                ResponseFrame::Termination {
+                    reason: TerminationReason::InvalidHandshake
+                }
+            );
+        }
+
+        // resume the connection, the lost response should be replayed
+        let (tx, rx) = dial_mock(5).await.expect("failed to dial");
+        tx.send(
+            HandshakeRequestFrame::Handshake {
+                retry: Some(connection_id),
+                service: ECHO_SERVICE,
+                pk: sk.to_pk(),
+                pop: sk.sign(&access_token),
+            }
+            .encode(),
+        )
+        .await?;
+        tx.send(RequestFrame::Received { count: 0 }.encode())
+            .await?;
+
+        let bytes = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("payload should be replayed within 1 second")?;
+        match ResponseFrame::decode(&bytes)? {
+            ResponseFrame::ServicePayload { bytes } => assert_eq!(&bytes, TEST_PAYLOAD),
+            f => panic!("expected payload, got {f:?}"),
+        }
+
+        // the resumed connection keeps working
+        tx.send(
+            RequestFrame::ServicePayload {
+                bytes: TEST_PAYLOAD.into(),
+            }
+            .encode(),
+        )
+        .await?;
+        match ResponseFrame::decode(&rx.recv().await?)? {
+            ResponseFrame::ServicePayload { bytes } => assert_eq!(&bytes, TEST_PAYLOAD),
+            f => panic!("expected payload, got {f:?}"),
+        }
+
+        shutdown.shutdown().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_proof_of_possession() -> Result<()> {
+        // start the mock node
+        let mut shutdown = start_mock_node::<MockServiceProvider>(7).await?;
+
+        // a client must sign its own key, and the anonymous key needs no signature
+        let sk = ClientSecretKey::generate();
+        let other = ClientSecretKey::generate();
+        let message = proof_of_possession_message(&sk.to_pk());
+        for (pk, pop, valid) in [
+            (sk.to_pk(), ClientSignature([0; 48]), false),
+            (sk.to_pk(), sk.sign(&sk.to_pk().0), false),
+            (sk.to_pk(), other.sign(&message), false),
+            (ClientPublicKey([1; 96]), ClientSignature([0; 48]), false),
+            (sk.to_pk(), sk.sign(&message), true),
+            (ClientPublicKey([0; 96]), ClientSignature([0; 48]), true),
+        ] {
+            let (tx, rx) = dial_mock(7).await.expect("failed to dial");
+            tx.send(
+                HandshakeRequestFrame::Handshake {
+                    retry: None,
+                    service: ECHO_SERVICE,
+                    pk,
+                    pop,
+                }
+                .encode(),
+            )
+            .await?;
+            if valid {
+                tx.send(
+                    RequestFrame::ServicePayload {
+                        bytes: TEST_PAYLOAD.into(),
+                    }
+                    .encode(),
+                )
+                .await?;
+            }
+
+            match ResponseFrame::decode(&rx.recv().await?)? {
+                ResponseFrame::ServicePayload { bytes } if valid => {
+                    assert_eq!(&bytes, TEST_PAYLOAD)
+                },
+                ResponseFrame::Termination {
+                    reason: TerminationReason::InvalidHandshake,
+                } if !valid => {},
+                f => panic!("unexpected frame for a valid={valid} handshake: {f:?}"),
+            }
+        }
+
+        shutdown.shutdown().await;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn join_secondary_connection() -> Result<()> {
         // start and connect to the mock node
@@ -825,4 +1289,67 @@ async fn extend_token() -> Result<()> {
         shutdown.shutdown().await;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn terminate_connection() -> Result<()> {
+        let mut shutdown = ShutdownController::default();
+        let context = Context::new(MockServiceProvider, shutdown.waiter(), &Default::default());
+        let (transport, _) = MockTransport::bind::<MockServiceProvider>(
+            shutdown.waiter(),
+            MockTransportConfig { port: 6 },
+        )
+        .await?;
+        transport.spawn_listener_task(context.clone());
+        let (tx, rx) = dial_mock(6).await.expect("failed to dial");
+
+        tx.send(
+            HandshakeRequestFrame::Handshake {
+                retry: None,
+                service: ECHO_SERVICE,
+                pk: ClientPublicKey([0; 96]),
+                pop: ClientSignature([0; 48]),
+            }
+            .encode(),
+        )
+        .await?;
+        tx.send(
+            RequestFrame::ServicePayload {
+                bytes: TEST_PAYLOAD.into(),
+            }
+            .encode(),
+        )
+        .await?;
+        match ResponseFrame::decode(&rx.recv().await?)? {
+            ResponseFrame::ServicePayload { bytes } => assert_eq!(&bytes, TEST_PAYLOAD),
+            f => panic!("expected payload, got {f:?}"),
+        }
+
+        // the connection is in the table along with the bytes sent both ways
+        let connections = context.connections();
+        assert_eq!(connections.len(), 1);
+        let connection = &connections[0];
+        assert_eq!(connection.transport, "mock");
+        assert_eq!(connection.service, ECHO_SERVICE);
+        assert_eq!(connection.bytes_in, TEST_PAYLOAD.len() as u64);
+        assert_eq!(connection.bytes_out, TEST_PAYLOAD.len() as u64);
+        assert_eq!(connection.token_expiry, None);
+
+        assert!(
+            context.terminate_connection(connection.id, TerminationReason::ResourcesUnavailable)
+        );
+        match ResponseFrame::decode(&timeout(Duration::from_secs(1), rx.recv()).await??)? {
+            ResponseFrame::Termination { reason } => {
+                assert_eq!(reason, TerminationReason::ResourcesUnavailable)
+            },
+            f => panic!("expected termination, got {f:?}"),
+        }
+
+        // the connection is removed once the proxy is done
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(context.connections().is_empty());
+        assert!(!context.terminate_connection(connection.id, TerminationReason::Shutdown));
+
+        shutdown.shutdown().await;
+        Ok(())
+    }
 }
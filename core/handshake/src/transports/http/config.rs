@@ -1,4 +1,33 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Default, Deserialize, Serialize)]
-pub struct Config {}
+use super::routes::Route;
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Maximum size of a request body. Larger requests are rejected with `413 Payload Too Large`.
+    pub max_request_body_size: usize,
+    /// The services that can be reached over http, by `/services/<id>/` or by a route. Requests
+    /// for the other services are rejected with `404 Not Found`.
+    pub services: Vec<u32>,
+    /// Custom host names routed to services. Requests to the routes of the node itself, like
+    /// `/services/<id>/`, are not affected.
+    #[serde(rename = "route")]
+    pub routes: Vec<Route>,
+    /// The services whose first payload is the head of the response, with its status and
+    /// headers as JSON. All the payloads of the other services are part of the body.
+    pub head_services: Vec<u32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_request_body_size: 64 << 20,
+            // The fetcher and the JS service.
+            services: vec![0, 1],
+            routes: Vec::new(),
+            // The fetcher and the JS service.
+            head_services: vec![0, 1],
+        }
+    }
+}
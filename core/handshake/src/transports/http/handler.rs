@@ -1,36 +1,65 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::net::SocketAddr;
 use std::str::FromStr;
 
+use async_channel::Sender;
 use axum::body::Body;
-use axum::extract::{OriginalUri, Path, Query};
+use axum::extract::{ConnectInfo, OriginalUri, Path, Query};
+use axum::http::header::{CONTENT_LENGTH, HOST};
 use axum::http::{HeaderMap, Method, StatusCode, Uri};
 use axum::response::{IntoResponse, Response};
 use axum::Extension;
 use bytes::Bytes;
-use fleek_crypto::{ClientPublicKey, ClientSignature};
-use fn_sdk::header::{HttpMethod, HttpOverrides, TransportDetail};
-use lightning_interfaces::schema::handshake::{HandshakeRequestFrame, RequestFrame};
+use fleek_crypto::ClientSignature;
+use fn_sdk::header::{HttpMethod, TransportDetail};
+use futures::StreamExt;
+use lightning_interfaces::schema::handshake::{
+    HandshakeRequestFrame,
+    RequestFrame,
+    TerminationReason,
+    ANONYMOUS_CLIENT,
+};
 use lightning_interfaces::ExecutorProviderInterface;
 use lightning_metrics::increment_counter;
 use tokio::sync::oneshot;
+use tracing::warn;
 use url::Url;
 
 use crate::handshake::Context;
-use crate::transports::http::{HttpReceiver, HttpSender, Service};
+use crate::transports::http::{routes, Config, HttpReceiver, HttpSender};
 
 pub async fn handler<P: ExecutorProviderInterface>(
     method: Method,
     headers: HeaderMap,
     OriginalUri(uri): OriginalUri,
-    Path((service_id, path)): Path<(String, String)>,
+    path: Option<Path<(String, String)>>,
     Query(params): Query<HashMap<String, String>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     Extension(provider): Extension<Context<P>>,
-    payload: Bytes,
+    Extension(config): Extension<Config>,
+    body: Body,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let service_id = u32::from_str(&service_id)
-        .map_err(|_| (StatusCode::NOT_FOUND, "route not found".to_string()))
-        .and_then(Service::try_from)?;
+    // Requests to `/services/<id>/` name the service, others are routed by their host name.
+    let (service_id, url) = match path {
+        Some(Path((service_id, path))) => {
+            let service_id = u32::from_str(&service_id)
+                .map_err(|_| (StatusCode::NOT_FOUND, "route not found".to_string()))?;
+            (service_id, extract_url(&path, uri))
+        },
+        None => {
+            let host = headers
+                .get(HOST)
+                .and_then(|host| host.to_str().ok())
+                .or(uri.host())
+                .unwrap_or_default();
+            routes::resolve(&config.routes, host, uri.path(), uri.query())
+                .ok_or_else(|| (StatusCode::NOT_FOUND, "route not found".to_string()))?
+        },
+    };
+    if !config.services.contains(&service_id) {
+        return Err((StatusCode::NOT_FOUND, "service not found".to_string()));
+    }
 
     let method = match method {
         Method::GET => HttpMethod::GET,
@@ -42,29 +71,33 @@ pub async fn handler<P: ExecutorProviderInterface>(
         _ => return Err((StatusCode::NOT_FOUND, "invalid method".to_string())),
     };
 
-    let body_frame = RequestFrame::ServicePayload { bytes: payload };
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok())
+        .and_then(|len| usize::from_str(len).ok());
+    if content_length.is_some_and(|len| len > config.max_request_body_size) {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "request body too large".to_string(),
+        ));
+    }
 
     let handshake_frame = HandshakeRequestFrame::Handshake {
-        service: service_id as u32,
-        pk: ClientPublicKey([0; 96]),
+        service: service_id,
+        pk: ANONYMOUS_CLIENT,
         pop: ClientSignature([0; 48]),
         retry: None,
     };
 
     let (frame_tx, frame_rx) = async_channel::bounded(8);
-    // Todo: Fix synchronization between data transfer and connection proxy.
-    // Notes: Reducing the size of this queue produces enough backpressure to slow down the
-    // transfer of data and if the socket is dropped before all the data is transferred,
-    // the proxy drops the connection and the client receives incomplete data.
-    let (body_tx, body_rx) = async_channel::bounded(2_000_000);
-    let (termination_tx, termination_rx) = oneshot::channel();
-
-    let sender = HttpSender::new(service_id, frame_tx, body_tx, termination_tx);
+    let (head_tx, head_rx) = oneshot::channel();
+    let (body_tx, body_rx) = HttpSender::body_channel();
+
     let receiver = HttpReceiver::new(
         frame_rx,
         TransportDetail::HttpRequest {
             method,
-            url: extract_url(&path, uri),
+            url,
             header: headers
                 .into_iter()
                 .filter_map(|(name, val)| {
@@ -80,14 +113,23 @@ pub async fn handler<P: ExecutorProviderInterface>(
                 })
                 .collect(),
         },
+        connect_info.map(|ConnectInfo(addr)| addr.ip()),
     );
 
-    sender.frame_tx.try_send(Some(body_frame)).map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "unexpected error".to_string(),
-        )
-    })?;
+    // The request body is streamed to the service while the response is being handled, the
+    // bounded frame channel slows the client down if the service can't keep up.
+    tokio::spawn(stream_request_body(
+        body,
+        frame_tx.clone(),
+        config.max_request_body_size,
+    ));
+
+    let sender = HttpSender::new(
+        frame_tx,
+        head_tx,
+        body_tx,
+        config.head_services.contains(&service_id),
+    );
 
     increment_counter!(
         "handshake_http_sessions",
@@ -98,47 +140,95 @@ pub async fn handler<P: ExecutorProviderInterface>(
         .handle_new_connection(handshake_frame, sender, receiver)
         .await;
 
+    // Wait for the head of the response, so the status and headers can be set before the body
+    // is streamed. If the connection is terminated before that, we can still respond with an
+    // error status.
+    let overrides = match head_rx.await {
+        Ok(Ok(overrides)) => overrides,
+        Ok(Err(TerminationReason::ResourcesUnavailable)) => {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                "too many requests".to_string(),
+            ));
+        },
+        Ok(Err(TerminationReason::InvalidService)) => {
+            return Err((StatusCode::NOT_FOUND, "service not found".to_string()));
+        },
+        Ok(Err(TerminationReason::InternalError)) => {
+            return Err((
+                StatusCode::BAD_GATEWAY,
+                "invalid response head from the service".to_string(),
+            ));
+        },
+        Ok(Err(reason)) => return Err(bad_request(format!("handshake failed: {reason:?}"))),
+        Err(_) => return Err(bad_request("Connection closed before headers were sent")),
+    };
+
     let mut response_builder = Response::builder();
 
     if let Some(content_type) = params.get("mime") {
         response_builder = response_builder.header("Content-Type", content_type);
     }
 
-    // If the service is the javascript service. Await the first response as the possible header
-    // overrides and over ride the response headers
-    if matches!(service_id, Service::Js | Service::Fetcher) {
-        let header_bytes = body_rx
-            .recv()
-            .await
-            .map_err(|_| bad_request("Connection closed before headers were sent"))
-            .and_then(|res| {
-                res.map_err(|e| bad_request(format!("Unable to get headers from service: {e}")))
-            })?;
-        let header_overrides =
-            serde_json::from_slice::<HttpOverrides>(&header_bytes).unwrap_or_default();
-
-        if let Some(headers) = header_overrides.headers {
-            for header in headers {
-                for header_value in header.1 {
-                    response_builder = response_builder.header(header.0.clone(), header_value);
-                }
+    if let Some(headers) = overrides.headers {
+        for header in headers {
+            for header_value in header.1 {
+                response_builder = response_builder.header(header.0.clone(), header_value);
             }
         }
-        if let Some(status) = header_overrides.status {
-            response_builder = response_builder.status(status);
-        }
+    }
+    if let Some(status) = overrides.status {
+        response_builder = response_builder.status(status);
     }
 
-    let body = Body::from_stream(body_rx);
+    // The body has no known length, so it is sent with chunked transfer encoding.
+    response_builder
+        .body(Body::from_stream(body_rx))
+        .map_err(|_| bad_request("invalid type value"))
+}
 
-    // If there is an error while streaming, the status header has already been sent,
-    // this is a hacky way of returning an error status before beginning streaming the body.
-    match termination_rx.await {
-        Ok(reason) => Err(bad_request(format!("handshake failed: {reason:?}"))),
-        Err(_) => response_builder
-            .body(body)
-            .map_err(|_| bad_request("invalid type value")),
+/// Forward the request body to the connection as it arrives. Every chunk but the last is sent
+/// as a [`RequestFrame::ServicePayloadChunk`], so the service receives the whole body as a
+/// single payload. The connection is closed if the body fails or exceeds `limit`.
+async fn stream_request_body(body: Body, frame_tx: Sender<Option<RequestFrame>>, limit: usize) {
+    let mut stream = body.into_data_stream();
+    let mut size = 0;
+    let mut last = Bytes::new();
+
+    loop {
+        match stream.next().await {
+            Some(Ok(chunk)) => {
+                if chunk.is_empty() {
+                    continue;
+                }
+                size += chunk.len();
+                if size > limit {
+                    warn!("request body exceeds the limit of {limit} bytes");
+                    let _ = frame_tx.send(None).await;
+                    return;
+                }
+                let previous = std::mem::replace(&mut last, chunk);
+                if !previous.is_empty()
+                    && frame_tx
+                        .send(Some(RequestFrame::ServicePayloadChunk { bytes: previous }))
+                        .await
+                        .is_err()
+                {
+                    return;
+                }
+            },
+            Some(Err(e)) => {
+                warn!("failed to read request body: {e}");
+                let _ = frame_tx.send(None).await;
+                return;
+            },
+            None => break,
+        }
     }
+
+    let _ = frame_tx
+        .send(Some(RequestFrame::ServicePayload { bytes: last }))
+        .await;
 }
 
 #[inline(always)]
@@ -1,14 +1,18 @@
 mod config;
 mod handler;
+mod routes;
 
-use async_channel::{Receiver, Sender};
+use std::collections::VecDeque;
+use std::net::IpAddr;
+
+use anyhow::anyhow;
+use async_channel::{Receiver, Sender, TrySendError};
 use async_trait::async_trait;
-use axum::http::StatusCode;
 use axum::routing::any;
-use axum::Router;
+use axum::{Extension, Router};
 use bytes::{Bytes, BytesMut};
 pub use config::Config;
-use fn_sdk::header::TransportDetail;
+use fn_sdk::header::{HttpOverrides, TransportDetail};
 use lightning_interfaces::prelude::*;
 use lightning_interfaces::schema::handshake::{
     HandshakeRequestFrame,
@@ -17,6 +21,7 @@
     ResponseFrame,
     TerminationReason,
 };
+pub use routes::Route;
 use tokio::sync::oneshot;
 use tracing::warn;
 
@@ -32,9 +37,15 @@ impl Transport for HttpTransport {
 
     async fn bind<P: ExecutorProviderInterface>(
         _: ShutdownWaiter,
-        _: Self::Config,
+        config: Self::Config,
     ) -> anyhow::Result<(Self, Option<Router>)> {
-        let router = Router::new().route("/services/:service/*path", any(handler::handler::<P>));
+        let mut router =
+            Router::new().route("/services/:service/*path", any(handler::handler::<P>));
+        // Requests that don't match any other route may be made to a custom host name.
+        if !config.routes.is_empty() {
+            router = router.fallback(handler::handler::<P>);
+        }
+        let router = router.layer(Extension(config));
         Ok((Self {}, Some(router)))
     }
 
@@ -43,116 +54,132 @@ async fn accept(&mut self) -> Option<(HandshakeRequestFrame, Self::Sender, Self:
     }
 }
 
-#[derive(Clone, Copy)]
-#[repr(u32)]
-pub enum Service {
-    Fetcher = 0,
-    Js = 1,
-}
-
-impl TryFrom<u32> for Service {
-    type Error = (StatusCode, String);
+/// The head of the http response, which is the first payload sent by the services configured to
+/// send one.
+pub type ResponseHead = Result<HttpOverrides, TerminationReason>;
 
-    fn try_from(value: u32) -> Result<Self, Self::Error> {
-        match value {
-            0 => Ok(Service::Fetcher),
-            1 => Ok(Service::Js),
-            _ => Err((StatusCode::NOT_FOUND, "route not found".to_string())),
-        }
-    }
-}
-
-impl Service {
-    pub fn supports_http_overrides(&self) -> bool {
-        matches!(self, Service::Js)
-    }
-}
+/// Number of body chunks buffered before the proxy has to wait for the client to catch up.
+const BODY_BUFFER: usize = 32;
 
 pub struct HttpSender {
-    service: Service,
-    frame_tx: Sender<Option<RequestFrame>>,
+    /// Keeps the request frame channel open for as long as the connection is alive.
+    _frame_tx: Sender<Option<RequestFrame>>,
+    head_tx: Option<oneshot::Sender<ResponseHead>>,
+    /// Whether the first payload of the service is the head of the response.
+    expects_head: bool,
+    head_buffer: BytesMut,
     body_tx: Sender<anyhow::Result<Bytes>>,
+    /// Body chunks that did not fit in the channel yet, flushed by [`TransportSender::ready`].
+    pending: VecDeque<anyhow::Result<Bytes>>,
     current_write: usize,
-    termination_tx: Option<oneshot::Sender<TerminationReason>>,
-    header_buffer: Option<BytesMut>,
 }
 
 impl HttpSender {
     pub fn new(
-        service: Service,
         frame_tx: Sender<Option<RequestFrame>>,
+        head_tx: oneshot::Sender<ResponseHead>,
         body_tx: Sender<anyhow::Result<Bytes>>,
-        termination_tx: oneshot::Sender<TerminationReason>,
+        expects_head: bool,
     ) -> Self {
         Self {
-            service,
-            frame_tx,
+            _frame_tx: frame_tx,
+            head_tx: Some(head_tx),
+            expects_head,
+            head_buffer: BytesMut::new(),
             body_tx,
+            pending: VecDeque::new(),
             current_write: 0,
-            termination_tx: Some(termination_tx),
-            header_buffer: Some(BytesMut::new()),
         }
     }
 
+    /// Create the channel the response body is streamed through.
+    pub fn body_channel() -> (
+        Sender<anyhow::Result<Bytes>>,
+        Receiver<anyhow::Result<Bytes>>,
+    ) {
+        async_channel::bounded(BODY_BUFFER)
+    }
+
     #[inline(always)]
-    fn inner_send(&mut self, bytes: Bytes) {
-        if let Err(e) = self.body_tx.try_send(Ok(bytes)) {
-            warn!("payload dropped, failed to send to write loop: {e}");
+    fn send_head(&mut self, head: ResponseHead) {
+        if let Some(head_tx) = self.head_tx.take() {
+            // The handler is gone if this fails, which is noticed on the next body write.
+            let _ = head_tx.send(head);
         }
     }
 
-    fn send_with_http_override(&mut self, bytes: Bytes) {
-        if let Some(header_buffer) = self.header_buffer.as_mut() {
-            header_buffer.extend(bytes);
-            if self.current_write == 0 {
-                // always Some due to previous check
-                let payload = self.header_buffer.take().unwrap_or_default();
-
-                if let Err(e) = self.body_tx.try_send(Ok(payload.into())) {
-                    warn!("payload dropped, failed to send to write loop: {e}");
-                }
-            }
-        } else if let Err(e) = self.body_tx.try_send(Ok(bytes)) {
-            warn!("payload dropped, failed to send to write loop: {e}");
+    /// End the response. If nothing was sent yet we can still respond with an error status,
+    /// otherwise the body is aborted so the client knows the response is incomplete.
+    fn end(&mut self, reason: TerminationReason) {
+        if self.head_tx.is_some() {
+            self.send_head(Err(reason));
+        } else if reason != TerminationReason::ServiceTerminated {
+            let _ = self.send_body(Err(anyhow!("connection terminated: {reason:?}")));
         }
     }
 
+    /// Queue a chunk of the body. Only fails if the client is gone.
     #[inline(always)]
-    fn close(&mut self) {
-        if self.frame_tx.try_send(None).is_err() {
-            warn!("failed to send close signal to receiver");
+    fn send_body(&mut self, chunk: anyhow::Result<Bytes>) -> anyhow::Result<()> {
+        if !self.pending.is_empty() {
+            self.pending.push_back(chunk);
+            return Ok(());
+        }
+
+        match self.body_tx.try_send(chunk) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(chunk)) => {
+                self.pending.push_back(chunk);
+                Ok(())
+            },
+            Err(TrySendError::Closed(_)) => Err(anyhow!("http client closed the connection")),
         }
     }
 }
 
+#[async_trait]
 impl TransportSender for HttpSender {
     fn send_handshake_response(&mut self, _: HandshakeResponse) {
-        unimplemented!()
+        // Http clients don't take part in the handshake.
     }
 
-    fn send(&mut self, _: ResponseFrame) {
-        unimplemented!()
+    fn send(&mut self, frame: ResponseFrame) {
+        match frame {
+            ResponseFrame::ServicePayload { bytes }
+            | ResponseFrame::ServicePayloadChunk { bytes } => {
+                if let Err(e) = self.send_body(Ok(bytes)) {
+                    warn!("payload dropped: {e}");
+                }
+            },
+            ResponseFrame::Termination { reason } => self.end(reason),
+            // Access tokens can't be used by http clients, since a request can't be joined by
+            // another connection.
+            _ => {},
+        }
     }
 
     fn terminate(mut self, reason: TerminationReason) {
-        if let Some(reason_sender) = self.termination_tx.take() {
-            let _ = reason_sender.send(reason);
+        self.end(reason);
+
+        if !self.pending.is_empty() {
+            // Finish flushing the body in the background, the connection is done with us.
+            tokio::spawn(async move { self.ready().await });
         }
     }
 
     fn start_write(&mut self, len: usize) {
-        // if the header buffer is gone it means we sent the headers already and are ready to stream
-        // the body
-        if self.header_buffer.is_none() || !self.service.supports_http_overrides() {
-            self.termination_tx.take();
-        }
-
         debug_assert!(
             self.current_write == 0,
             "data should be written completely before calling start_write again"
         );
 
         self.current_write = len;
+
+        if self.head_tx.is_some() && (len == 0 || !self.expects_head) {
+            // An empty head, or a service that doesn't send one, responds with the default status
+            // and headers.
+            self.send_head(Ok(HttpOverrides::default()));
+        }
     }
 
     fn write(&mut self, buf: Bytes) -> anyhow::Result<usize> {
@@ -163,26 +190,55 @@ fn write(&mut self, buf: Bytes) -> anyhow::Result<usize> {
 
         self.current_write -= len;
 
-        if self.service.supports_http_overrides() {
-            self.send_with_http_override(buf);
-        } else {
-            self.inner_send(buf);
+        if self.head_tx.is_none() {
+            self.send_body(Ok(buf))?;
+            return Ok(len);
+        }
+
+        // The first payload of the service is the head of the response, which sets the status
+        // and headers before the body is streamed.
+        self.head_buffer.extend_from_slice(&buf);
+        if self.current_write == 0 {
+            match serde_json::from_slice(&self.head_buffer.split()) {
+                Ok(head) => self.send_head(Ok(head)),
+                Err(e) => {
+                    // Don't respond with a body the service didn't mean to send.
+                    self.send_head(Err(TerminationReason::InternalError));
+                    return Err(anyhow!("invalid response head: {e}"));
+                },
+            }
         }
 
         Ok(len)
     }
+
+    async fn ready(&mut self) {
+        while let Some(chunk) = self.pending.pop_front() {
+            if self.body_tx.send(chunk).await.is_err() {
+                // The client is gone, the next write will fail.
+                self.pending.clear();
+                return;
+            }
+        }
+    }
 }
 
 pub struct HttpReceiver {
     inner: Receiver<Option<RequestFrame>>,
     detail: Option<TransportDetail>,
+    ip: Option<IpAddr>,
 }
 
 impl HttpReceiver {
-    pub fn new(inner: Receiver<Option<RequestFrame>>, detail: TransportDetail) -> Self {
+    pub fn new(
+        inner: Receiver<Option<RequestFrame>>,
+        detail: TransportDetail,
+        ip: Option<IpAddr>,
+    ) -> Self {
         Self {
             inner,
             detail: Some(detail),
+            ip,
         }
     }
 }
@@ -195,7 +251,104 @@ fn detail(&mut self) -> TransportDetail {
             .expect("HTTP Transport detail already taken.")
     }
 
+    fn remote_ip(&self) -> Option<IpAddr> {
+        self.ip
+    }
+
     async fn recv(&mut self) -> Option<RequestFrame> {
         self.inner.recv().await.ok().flatten()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn head_then_streamed_body() -> anyhow::Result<()> {
+        let (frame_tx, _frame_rx) = async_channel::bounded(8);
+        let (head_tx, head_rx) = oneshot::channel();
+        let (body_tx, body_rx) = HttpSender::body_channel();
+        let mut sender = HttpSender::new(frame_tx, head_tx, body_tx, true);
+
+        // Write the head in two parts
+        let head = serde_json::to_vec(&HttpOverrides {
+            status: Some(201),
+            headers: None,
+        })?;
+        let (first, second) = head.split_at(4);
+        sender.start_write(head.len());
+        sender.write(first.to_vec().into())?;
+        sender.write(second.to_vec().into())?;
+        let overrides = head_rx.await?.expect("head should be sent");
+        assert_eq!(overrides.status, Some(201));
+
+        // Write more chunks than fit in the channel, which should all be delivered in order
+        let (reader, writer) = tokio::join!(body_rx.collect::<Vec<_>>(), async move {
+            for i in 0..BODY_BUFFER * 2 {
+                sender.start_write(1);
+                sender.write(vec![i as u8].into())?;
+                sender.ready().await;
+            }
+            sender.terminate(TerminationReason::ServiceTerminated);
+            anyhow::Ok(())
+        });
+        writer?;
+        let body = reader
+            .into_iter()
+            .map(|chunk| chunk.map(|bytes| bytes[0]))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(body, (0..BODY_BUFFER as u8 * 2).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn terminate_before_head() {
+        let (frame_tx, _frame_rx) = async_channel::bounded(8);
+        let (head_tx, head_rx) = oneshot::channel();
+        let (body_tx, _body_rx) = HttpSender::body_channel();
+        let sender = HttpSender::new(frame_tx, head_tx, body_tx, true);
+
+        sender.terminate(TerminationReason::InvalidService);
+        assert_eq!(
+            head_rx.await.unwrap().unwrap_err(),
+            TerminationReason::InvalidService
+        );
+    }
+
+    #[tokio::test]
+    async fn body_without_head() -> anyhow::Result<()> {
+        let (frame_tx, _frame_rx) = async_channel::bounded(8);
+        let (head_tx, head_rx) = oneshot::channel();
+        let (body_tx, body_rx) = HttpSender::body_channel();
+        let mut sender = HttpSender::new(frame_tx, head_tx, body_tx, false);
+
+        // The first payload is part of the body, even if it looks like a head.
+        sender.start_write(2);
+        sender.write(b"{}".to_vec().into())?;
+        let overrides = head_rx.await?.expect("head should be sent");
+        assert_eq!(overrides.status, None);
+        assert_eq!(body_rx.recv().await??.as_ref(), b"{}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_head() {
+        let (frame_tx, _frame_rx) = async_channel::bounded(8);
+        let (head_tx, head_rx) = oneshot::channel();
+        let (body_tx, _body_rx) = HttpSender::body_channel();
+        let mut sender = HttpSender::new(frame_tx, head_tx, body_tx, true);
+
+        // The payload is not a head, so the request fails instead of losing it.
+        sender.start_write(5);
+        assert!(sender.write(b"hello".to_vec().into()).is_err());
+        assert_eq!(
+            head_rx.await.unwrap().unwrap_err(),
+            TerminationReason::InternalError
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Routes the requests made to a custom host name to a service, so sites can be served on their
+/// own domain instead of under `/services/<id>/`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Route {
+    /// The host name, matched against the `Host` header of the request.
+    pub host: String,
+    /// Only requests with a path under this prefix are routed, the prefix itself is removed from
+    /// the path. When several routes of a host match, the longest prefix wins.
+    #[serde(default)]
+    pub prefix: String,
+    /// The service handling the requests, which must be one of the services reachable over http.
+    pub service: u32,
+    /// The path the request path is appended to before it is given to the service, for example
+    /// `/blake3/<hash>` to serve a site with the JS service.
+    #[serde(default)]
+    pub root: String,
+}
+
+/// Find the route of a request, returning the service and the url given to the service.
+pub fn resolve(
+    routes: &[Route],
+    host: &str,
+    path: &str,
+    query: Option<&str>,
+) -> Option<(u32, Url)> {
+    // The port is not part of the host name.
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+
+    let (route, rest) = routes
+        .iter()
+        .filter(|route| route.host.eq_ignore_ascii_case(host))
+        .filter_map(|route| Some((route, strip_prefix(path, &route.prefix)?)))
+        .max_by_key(|(route, _)| route.prefix.trim_end_matches('/').len())?;
+
+    let mut url = Url::parse("http://fleek/").unwrap();
+    url.set_path(&format!("{}/{}", route.root.trim_end_matches('/'), rest));
+    url.set_query(query);
+    Some((route.service, url))
+}
+
+/// Strip the prefix from the path if it is one of its ancestors, returning the rest of the path
+/// without the leading slash.
+fn strip_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(prefix.trim_end_matches('/'))?;
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix('/')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(host: &str, prefix: &str, service: u32, root: &str) -> Route {
+        Route {
+            host: host.to_string(),
+            prefix: prefix.to_string(),
+            service,
+            root: root.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_host() {
+        let routes = vec![
+            route("example.com", "", 1, "/blake3/abcd"),
+            route("fetch.example.com", "", 0, ""),
+        ];
+
+        let (service, url) =
+            resolve(&routes, "Example.com:8080", "/a/b.html", Some("x=1")).unwrap();
+        assert_eq!(service, 1);
+        assert_eq!(url.as_str(), "http://fleek/blake3/abcd/a/b.html?x=1");
+
+        let (service, url) = resolve(&routes, "example.com", "/", None).unwrap();
+        assert_eq!(service, 1);
+        assert_eq!(url.path(), "/blake3/abcd/");
+
+        let (service, url) = resolve(&routes, "fetch.example.com", "/ipfs/cid", None).unwrap();
+        assert_eq!(service, 0);
+        assert_eq!(url.path(), "/ipfs/cid");
+
+        assert!(resolve(&routes, "other.com", "/", None).is_none());
+    }
+
+    #[test]
+    fn test_resolve_longest_prefix() {
+        let routes = vec![
+            route("example.com", "/", 1, "/blake3/site"),
+            route("example.com", "/api/", 1, "/blake3/api"),
+        ];
+
+        let (_, url) = resolve(&routes, "example.com", "/api/users", None).unwrap();
+        assert_eq!(url.path(), "/blake3/api/users");
+
+        let (_, url) = resolve(&routes, "example.com", "/api", None).unwrap();
+        assert_eq!(url.path(), "/blake3/api/");
+
+        // The prefix only matches whole segments.
+        let (_, url) = resolve(&routes, "example.com", "/apis", None).unwrap();
+        assert_eq!(url.path(), "/blake3/site/apis");
+    }
+}
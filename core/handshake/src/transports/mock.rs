@@ -144,7 +144,9 @@ fn write(&mut self, buf: Bytes) -> anyhow::Result<usize> {
         if self.buffer.len() >= self.current_write {
             let bytes = self.buffer.split_to(self.current_write).into();
             self.current_write = 0;
-            self.send(schema::ResponseFrame::ServicePayload { bytes });
+            self.tx
+                .try_send(schema::ResponseFrame::ServicePayload { bytes }.encode())
+                .context("mock connection closed")?;
         }
         Ok(len)
     }
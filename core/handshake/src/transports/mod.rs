@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use async_trait::async_trait;
 use axum::Router;
 use bytes::{BufMut, Bytes, BytesMut};
@@ -9,6 +11,7 @@
 use self::mock::{MockTransportReceiver, MockTransportSender};
 use self::tcp::{TcpReceiver, TcpSender};
 use self::webrtc::{WebRtcReceiver, WebRtcSender};
+use self::websocket::{WebSocketReceiver, WebSocketSender};
 use self::webtransport::{WebTransportReceiver, WebTransportSender};
 use crate::config::TransportConfig;
 use crate::handshake::Context;
@@ -19,16 +22,26 @@
 pub mod mock;
 pub mod tcp;
 pub mod webrtc;
+pub mod websocket;
 pub mod webtransport;
 
 macro_rules! transport_pairs {
-    {$($name:ident($sender:tt, $receiver:tt),)*} => {
+    {$($name:ident($sender:tt, $receiver:tt) => $label:literal,)*} => {
         /// Static enum of transport pairs for handling secondary connections
         #[non_exhaustive]
         pub enum TransportPair {
             $($name($sender, $receiver),)*
         }
 
+        impl TransportPair {
+            /// The name of the transport.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(TransportPair::$name(..) => $label,)*
+                }
+            }
+        }
+
         $(
         impl From<($sender, $receiver)> for TransportPair {
             fn from(value: ($sender, $receiver)) -> Self {
@@ -50,12 +63,12 @@ macro_rules! match_transport {
 }
 
 transport_pairs! {
-    Mock(MockTransportSender, MockTransportReceiver),
-    Tcp(TcpSender, TcpReceiver),
-    WebRtc(WebRtcSender, WebRtcReceiver),
-    WebTransport(WebTransportSender, WebTransportReceiver),
-    Http(HttpSender, HttpReceiver),
-
+    Mock(MockTransportSender, MockTransportReceiver) => "mock",
+    Tcp(TcpSender, TcpReceiver) => "tcp",
+    WebRtc(WebRtcSender, WebRtcReceiver) => "webrtc",
+    WebTransport(WebTransportSender, WebTransportReceiver) => "webtransport",
+    Http(HttpSender, HttpReceiver) => "http",
+    WebSocket(WebSocketSender, WebSocketReceiver) => "websocket",
 }
 
 #[async_trait]
@@ -99,6 +112,7 @@ fn spawn_listener_task(mut self, ctx: Context<impl ExecutorProviderInterface>)
 
 // TODO: Explore being able to make this async while also avoiding dynamic dispatch after support
 //       for secondary connections are added
+#[async_trait]
 pub trait TransportSender: Sized + Send + Sync + 'static {
     /// Send the initial handshake response to the client.
     fn send_handshake_response(&mut self, response: schema::HandshakeResponse);
@@ -117,6 +131,10 @@ fn terminate(mut self, reason: schema::TerminationReason) {
     /// Write some bytes as service payloads. Must ALWAYS be called after
     /// [`TransportSender::start_write`].
     fn write(&mut self, buf: Bytes) -> anyhow::Result<usize>;
+
+    /// Wait until the transport is ready to accept more bytes. This is called after every write,
+    /// so transports with a bounded buffer can apply backpressure instead of dropping data.
+    async fn ready(&mut self) {}
 }
 
 #[async_trait]
@@ -127,6 +145,12 @@ fn detail(&mut self) -> TransportDetail {
         TransportDetail::Other
     }
 
+    /// Returns the IP address of the client, if the transport knows it. Used to rate limit the
+    /// requests of the client.
+    fn remote_ip(&self) -> Option<IpAddr> {
+        None
+    }
+
     /// Receive a frame from the connection. Returns `None` when the connection
     /// is closed.
     async fn recv(&mut self) -> Option<schema::RequestFrame>;
@@ -157,8 +181,17 @@ pub async fn spawn_transport_by_config<P: ExecutorProviderInterface>(
             Ok(router)
         },
         TransportConfig::WebTransport(config) => {
+            let (transport, router) = webtransport::WebTransport::bind_with_certificates(
+                shutdown.clone(),
+                config,
+                ctx.certificates.clone(),
+            )?;
+            transport.spawn_listener_task(ctx);
+            Ok(router)
+        },
+        TransportConfig::WebSocket(config) => {
             let (transport, router) =
-                webtransport::WebTransport::bind::<P>(shutdown.clone(), config).await?;
+                websocket::WebSocketTransport::bind::<P>(shutdown.clone(), config).await?;
             transport.spawn_listener_task(ctx);
             Ok(router)
         },
@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
 use anyhow::Result;
 use arrayref::array_ref;
@@ -232,6 +232,10 @@ pub fn new(reader: OwnedReadHalf) -> Self {
 
 #[async_trait]
 impl TransportReceiver for TcpReceiver {
+    fn remote_ip(&self) -> Option<IpAddr> {
+        self.reader.peer_addr().ok().map(|addr| addr.ip())
+    }
+
     /// Cancel Safety:
     /// This method is cancel safe, but could potentially allocate multiple times for the delimiter
     /// if canceled.
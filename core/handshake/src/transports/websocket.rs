@@ -0,0 +1,357 @@
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, State};
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use bytes::Bytes;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use lightning_interfaces::prelude::*;
+use lightning_metrics::increment_counter;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{trace, warn};
+use triomphe::Arc;
+
+use super::{Transport, TransportReceiver, TransportSender};
+use crate::schema;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    /// Maximum size of a single message received from a client.
+    pub max_message_size: usize,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: 16 << 20,
+        }
+    }
+}
+
+/// An accepted connection along with its handshake request.
+type Connection = (
+    schema::HandshakeRequestFrame,
+    WebSocketSender,
+    WebSocketReceiver,
+);
+
+/// A WebSocket transport. Connections are upgraded from the `/ws` route of the http server, and
+/// every frame is sent as a single binary message.
+pub struct WebSocketTransport {
+    rx: mpsc::Receiver<Connection>,
+}
+
+struct WebSocketState {
+    config: WebSocketConfig,
+    conn_tx: mpsc::Sender<Connection>,
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    type Config = WebSocketConfig;
+    type Sender = WebSocketSender;
+    type Receiver = WebSocketReceiver;
+
+    async fn bind<P: ExecutorProviderInterface>(
+        _: ShutdownWaiter,
+        config: Self::Config,
+    ) -> Result<(Self, Option<Router>)> {
+        // bounded channel to provide some back pressure for incoming connections
+        let (conn_tx, rx) = mpsc::channel(256);
+
+        let router = Router::new()
+            .route("/ws", get(handler))
+            .with_state(Arc::new(WebSocketState { config, conn_tx }));
+
+        Ok((Self { rx }, Some(router)))
+    }
+
+    #[inline(always)]
+    async fn accept(
+        &mut self,
+    ) -> Option<(schema::HandshakeRequestFrame, Self::Sender, Self::Receiver)> {
+        let res = self.rx.recv().await?;
+
+        increment_counter!(
+            "handshake_websocket_sessions",
+            Some("Counter for number of handshake sessions accepted over websocket")
+        );
+
+        Some(res)
+    }
+}
+
+async fn handler(
+    ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    State(state): State<Arc<WebSocketState>>,
+) -> Response {
+    let ip = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    ws.max_message_size(state.config.max_message_size)
+        .on_upgrade(move |socket| handle_socket(socket, ip, state))
+}
+
+async fn handle_socket(mut socket: WebSocket, ip: Option<IpAddr>, state: Arc<WebSocketState>) {
+    // The first message of the connection must be the handshake request.
+    let frame = loop {
+        match socket.recv().await {
+            Some(Ok(Message::Binary(bytes))) => match schema::HandshakeRequestFrame::decode(&bytes)
+            {
+                Ok(frame) => break frame,
+                Err(_) => {
+                    trace!("dropping connection, invalid handshake request");
+                    return;
+                },
+            },
+            Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+            _ => return,
+        }
+    };
+
+    let (sink, stream) = socket.split();
+
+    // Send the frame and the new connection over the channel
+    state
+        .conn_tx
+        .send((
+            frame,
+            WebSocketSender::spawn(sink),
+            WebSocketReceiver { stream, ip },
+        ))
+        .await
+        .ok();
+}
+
+/// Driver loop to write outgoing frames to the socket, one message per frame.
+async fn spawn_write_driver(
+    mut sink: SplitSink<WebSocket, Message>,
+    rx: async_channel::Receiver<Bytes>,
+) {
+    while let Ok(bytes) = rx.recv().await {
+        if let Err(e) = sink.send(Message::Binary(bytes.to_vec())).await {
+            warn!("Dropping payload, failed to write to websocket: {e}");
+            return;
+        };
+    }
+
+    // The sender was dropped, so the connection is done.
+    sink.close().await.ok();
+}
+
+pub struct WebSocketSender {
+    sender: async_channel::Sender<Bytes>,
+    current_write: usize,
+}
+
+impl WebSocketSender {
+    /// Create the [`WebSocketSender`], additionally spawning a task to handle writing messages to
+    /// the socket.
+    #[inline(always)]
+    fn spawn(sink: SplitSink<WebSocket, Message>) -> Self {
+        let (sender, receiver) = async_channel::unbounded();
+        tokio::spawn(spawn_write_driver(sink, receiver));
+
+        Self {
+            sender,
+            current_write: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn send_inner(&mut self, bytes: Bytes) {
+        if let Err(e) = self.sender.try_send(bytes) {
+            warn!("payload dropped, failed to send to write loop: {e}");
+        }
+    }
+}
+
+impl TransportSender for WebSocketSender {
+    fn send_handshake_response(&mut self, response: schema::HandshakeResponse) {
+        self.send_inner(response.encode());
+    }
+
+    fn send(&mut self, frame: schema::ResponseFrame) {
+        debug_assert!(
+            !matches!(
+                frame,
+                schema::ResponseFrame::ServicePayload { .. }
+                    | schema::ResponseFrame::ServicePayloadChunk { .. }
+            ),
+            "payloads should only be sent via start_write and write"
+        );
+
+        self.send_inner(frame.encode());
+    }
+
+    fn start_write(&mut self, len: usize) {
+        debug_assert!(
+            self.current_write == 0,
+            "data should be written completely before calling start_write again"
+        );
+
+        self.current_write = len;
+
+        if len == 0 {
+            // There won't be any call to write, so send the empty payload right away.
+            self.send_inner(
+                schema::ResponseFrame::ServicePayload {
+                    bytes: Bytes::new(),
+                }
+                .encode(),
+            );
+        }
+    }
+
+    fn write(&mut self, buf: Bytes) -> anyhow::Result<usize> {
+        let len = buf.len();
+        debug_assert!(self.current_write != 0);
+        debug_assert!(self.current_write >= len);
+
+        self.current_write -= len;
+
+        // Messages can be arbitrarily large, so every write is sent as a single frame. Only the
+        // last one completes the payload.
+        let frame = if self.current_write > 0 {
+            schema::ResponseFrame::ServicePayloadChunk { bytes: buf }
+        } else {
+            schema::ResponseFrame::ServicePayload { bytes: buf }
+        };
+        self.send_inner(frame.encode());
+
+        Ok(len)
+    }
+}
+
+pub struct WebSocketReceiver {
+    stream: SplitStream<WebSocket>,
+    ip: Option<IpAddr>,
+}
+
+#[async_trait]
+impl TransportReceiver for WebSocketReceiver {
+    fn remote_ip(&self) -> Option<IpAddr> {
+        self.ip
+    }
+
+    /// Cancel Safety:
+    /// This method is cancel safe.
+    async fn recv(&mut self) -> Option<schema::RequestFrame> {
+        loop {
+            match self.stream.next().await? {
+                Ok(Message::Binary(bytes)) => match schema::RequestFrame::decode(&bytes) {
+                    Ok(frame) => return Some(frame),
+                    Err(_) => {
+                        warn!("invalid frame from client, dropping payload");
+                        continue;
+                    },
+                },
+                Ok(Message::Close(_)) | Err(_) => return None,
+                // Pings are answered by axum, and text messages are not part of the protocol.
+                Ok(_) => continue,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fleek_crypto::{ClientPublicKey, ClientSignature, NodePublicKey, NodeSignature};
+    use lightning_interfaces::ShutdownController;
+    use lightning_service_executor::shim::Provider;
+    use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+    use super::*;
+    use crate::schema::{HandshakeRequestFrame, HandshakeResponse, RequestFrame, ResponseFrame};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn handshake() -> Result<()> {
+        // Bind the transport and serve its router
+        let notifier = ShutdownController::default();
+        let (mut transport, router) =
+            WebSocketTransport::bind::<Provider>(notifier.waiter(), Default::default()).await?;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:20101").await?;
+        tokio::spawn(async move { axum::serve(listener, router.unwrap()).await });
+
+        // Connect a dummy client
+        let (mut client, _) = tokio_tungstenite::connect_async("ws://127.0.0.1:20101/ws")
+            .await
+            .expect("should connect");
+
+        const REQ_FRAME: HandshakeRequestFrame = HandshakeRequestFrame::Handshake {
+            retry: None,
+            service: 0,
+            pk: ClientPublicKey([1; 96]),
+            pop: ClientSignature([2; 48]),
+        };
+
+        const RES_FRAME: HandshakeResponse = HandshakeResponse {
+            pk: NodePublicKey([3; 32]),
+            pop: NodeSignature([4; 64]),
+        };
+
+        // Write the handshake frame
+        client
+            .send(ClientMessage::Binary(REQ_FRAME.encode().to_vec()))
+            .await?;
+
+        // Accept the connection from the transport, which should read the handshake request frame
+        let (frame, mut sender, mut receiver) = transport
+            .accept()
+            .await
+            .expect("failed to receive connection");
+        assert_eq!(REQ_FRAME, frame, "received incorrect request frame");
+
+        // Send the response frame and a payload written in two parts
+        sender.send_handshake_response(RES_FRAME);
+        sender.start_write(6);
+        sender.write(Bytes::from_static(b"foo"))?;
+        sender.write(Bytes::from_static(b"bar"))?;
+
+        let Some(ClientMessage::Binary(bytes)) = client.next().await.transpose()? else {
+            panic!("expected handshake response");
+        };
+        assert_eq!(HandshakeResponse::decode(&bytes)?, RES_FRAME);
+
+        let Some(ClientMessage::Binary(bytes)) = client.next().await.transpose()? else {
+            panic!("expected payload chunk");
+        };
+        assert_eq!(
+            ResponseFrame::decode(&bytes)?,
+            ResponseFrame::ServicePayloadChunk {
+                bytes: Bytes::from_static(b"foo")
+            }
+        );
+
+        let Some(ClientMessage::Binary(bytes)) = client.next().await.transpose()? else {
+            panic!("expected payload");
+        };
+        assert_eq!(
+            ResponseFrame::decode(&bytes)?,
+            ResponseFrame::ServicePayload {
+                bytes: Bytes::from_static(b"bar")
+            }
+        );
+
+        // Send a request frame from the client
+        let request = RequestFrame::ServicePayload {
+            bytes: Bytes::from_static(b"baz"),
+        };
+        client
+            .send(ClientMessage::Binary(request.encode().to_vec()))
+            .await?;
+        assert_eq!(receiver.recv().await, Some(request));
+
+        // Closing the client ends the connection
+        client.close(None).await?;
+        assert_eq!(receiver.recv().await, None);
+
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
@@ -13,6 +14,7 @@
 use wtransport::endpoint::IncomingSession;
 use wtransport::{Endpoint, RecvStream, SendStream};
 
+use crate::acme::CertificateWatch;
 use crate::schema::HandshakeRequestFrame;
 use crate::transports::webtransport::{self, WebTransportConfig};
 
@@ -23,13 +25,15 @@
 /// The execution context of the WebTransport server.
 pub struct Context {
     pub endpoint: Endpoint<Server>,
-    pub accept_tx: Sender<(HandshakeRequestFrame, (SendStream, FramedStreamRx))>,
+    pub accept_tx: Sender<(HandshakeRequestFrame, IpAddr, (SendStream, FramedStreamRx))>,
     pub published_cert_hash: Arc<RwLock<Vec<u8>>>,
     pub transport_config: WebTransportConfig,
     pub shutdown: ShutdownWaiter,
+    /// The certificate managed with ACME, which replaces the self-signed one when present.
+    pub certificates: Option<CertificateWatch>,
 }
 
-pub async fn main_loop(ctx: Context) {
+pub async fn main_loop(mut ctx: Context) {
     // Timer used to manage the self-signed certificate.
     let mut timer = tokio::time::interval(Duration::from_secs(CERTIFICATE_RENEWAL_PERIOD));
     // The first tick completes immediately.
@@ -45,7 +49,34 @@ pub async fn main_loop(ctx: Context) {
                     }
                 });
             }
+            _ = certificate_changed(&mut ctx.certificates) => {
+                let Some(cert) = ctx.certificates.as_ref().and_then(|rx| rx.borrow().clone()) else {
+                    continue;
+                };
+                match webtransport::create_cert_hash_and_server_config_with_certificate(
+                    &cert,
+                    ctx.transport_config.clone()
+                ) {
+                    Ok((cert_hash, server_config)) => {
+                        match ctx.endpoint.reload_config(server_config, false) {
+                            Ok(_) => {
+                                *ctx.published_cert_hash.write().unwrap() = cert_hash;
+                            }
+                            Err(e) => {
+                                error!("failed to reload server configuration: {e:?}");
+                            }
+                        }
+                    },
+                    Err(e) => {
+                        error!("failed to reload server configuration: {e:?}");
+                    }
+                }
+            }
             _ = timer.tick() => {
+                // The certificate managed with ACME is renewed separately.
+                if has_certificate(&ctx.certificates) {
+                    continue;
+                }
                 match webtransport::create_cert_hash_and_server_config(
                     NodeSecretKey::generate(),
                     ctx.transport_config.clone()
@@ -73,9 +104,27 @@ pub async fn main_loop(ctx: Context) {
     }
 }
 
+fn has_certificate(certificates: &Option<CertificateWatch>) -> bool {
+    certificates
+        .as_ref()
+        .is_some_and(|rx| rx.borrow().is_some())
+}
+
+/// Resolves when the certificate changes, and never if there is no certificate to watch.
+async fn certificate_changed(certificates: &mut Option<CertificateWatch>) {
+    let Some(rx) = certificates else {
+        return std::future::pending().await;
+    };
+    if rx.changed().await.is_err() {
+        // The certificates are no longer managed, keep serving the last one.
+        *certificates = None;
+        std::future::pending().await
+    }
+}
+
 pub async fn handle_incoming_session(
     incoming: IncomingSession,
-    accept_tx: Sender<(HandshakeRequestFrame, (SendStream, FramedStreamRx))>,
+    accept_tx: Sender<(HandshakeRequestFrame, IpAddr, (SendStream, FramedStreamRx))>,
 ) -> Result<()> {
     let session_request = incoming.await?;
     // Todo: validate authority and scheme.
@@ -90,6 +139,7 @@ pub async fn handle_incoming_session(
     // the WebTransport server MAY accept the session by replying with a 2xx series status code,
     // as defined in Section 15.3 of [HTTP].
     let connection = session_request.accept().await?;
+    let ip = connection.remote_address().ip();
     loop {
         let (stream_tx, stream_rx) = connection.accept_bi().await?;
         let mut reader = FramedRead::new(stream_rx, LengthDelimitedCodec::new());
@@ -106,7 +156,7 @@ pub async fn handle_incoming_session(
                     let accept_tx_clone = accept_tx.clone();
                     tokio::spawn(async move {
                         if accept_tx_clone
-                            .send((frame, (stream_tx, reader)))
+                            .send((frame, ip, (stream_tx, reader)))
                             .await
                             .is_err()
                         {
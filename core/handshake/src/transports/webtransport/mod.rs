@@ -2,6 +2,7 @@
 mod config;
 mod connection;
 
+use std::net::IpAddr;
 use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
@@ -18,6 +19,7 @@
 use wtransport::{Endpoint, SendStream, ServerConfig};
 
 use super::delimit_frame;
+use crate::acme::{CertificateWatch, TlsCertificate};
 use crate::schema::{
     HandshakeRequestFrame,
     HandshakeResponse,
@@ -29,23 +31,26 @@
 use crate::transports::{Transport, TransportReceiver, TransportSender};
 
 pub struct WebTransport {
-    conn_rx: Receiver<(HandshakeRequestFrame, (SendStream, FramedStreamRx))>,
+    conn_rx: Receiver<(HandshakeRequestFrame, IpAddr, (SendStream, FramedStreamRx))>,
 }
 
-#[async_trait]
-impl Transport for WebTransport {
-    type Config = WebTransportConfig;
-    type Sender = WebTransportSender;
-    type Receiver = WebTransportReceiver;
-
-    async fn bind<P: ExecutorProviderInterface>(
+impl WebTransport {
+    /// Bind the transport, serving the certificate managed with ACME when there is one instead
+    /// of a self-signed certificate.
+    pub fn bind_with_certificates(
         shutdown: ShutdownWaiter,
-        config: Self::Config,
+        config: WebTransportConfig,
+        certificates: Option<CertificateWatch>,
     ) -> anyhow::Result<(Self, Option<Router>)> {
         info!("Binding WebTransport on {}", config.address);
 
-        let (cert_hash, server_config) =
-            create_cert_hash_and_server_config(NodeSecretKey::generate(), config.clone())?;
+        let current = certificates.as_ref().and_then(|rx| rx.borrow().clone());
+        let (cert_hash, server_config) = match current {
+            Some(cert) => {
+                create_cert_hash_and_server_config_with_certificate(&cert, config.clone())?
+            },
+            None => create_cert_hash_and_server_config(NodeSecretKey::generate(), config.clone())?,
+        };
 
         let shared_cert_hash = Arc::new(RwLock::new(cert_hash));
         let router = Router::new()
@@ -69,6 +74,7 @@ async fn bind<P: ExecutorProviderInterface>(
             published_cert_hash: shared_cert_hash,
             transport_config: config,
             shutdown,
+            certificates,
         };
         tokio::spawn(connection::main_loop(ctx));
 
@@ -79,9 +85,23 @@ async fn bind<P: ExecutorProviderInterface>(
             Some(router),
         ))
     }
+}
+
+#[async_trait]
+impl Transport for WebTransport {
+    type Config = WebTransportConfig;
+    type Sender = WebTransportSender;
+    type Receiver = WebTransportReceiver;
+
+    async fn bind<P: ExecutorProviderInterface>(
+        shutdown: ShutdownWaiter,
+        config: Self::Config,
+    ) -> anyhow::Result<(Self, Option<Router>)> {
+        Self::bind_with_certificates(shutdown, config, None)
+    }
 
     async fn accept(&mut self) -> Option<(HandshakeRequestFrame, Self::Sender, Self::Receiver)> {
-        let (frame, (frame_writer, frame_reader)) = self.conn_rx.recv().await?;
+        let (frame, ip, (frame_writer, frame_reader)) = self.conn_rx.recv().await?;
         let (data_tx, data_rx) = async_channel::unbounded();
         tokio::spawn(connection::sender_loop(data_rx, frame_writer));
 
@@ -96,7 +116,10 @@ async fn accept(&mut self) -> Option<(HandshakeRequestFrame, Self::Sender, Self:
                 tx: data_tx,
                 current_write: 0,
             },
-            WebTransportReceiver { rx: frame_reader },
+            WebTransportReceiver {
+                rx: frame_reader,
+                ip,
+            },
         ))
     }
 }
@@ -162,10 +185,15 @@ fn write(&mut self, buf: Bytes) -> anyhow::Result<usize> {
 
 pub struct WebTransportReceiver {
     rx: FramedStreamRx,
+    ip: IpAddr,
 }
 
 #[async_trait]
 impl TransportReceiver for WebTransportReceiver {
+    fn remote_ip(&self) -> Option<IpAddr> {
+        Some(self.ip)
+    }
+
     async fn recv(&mut self) -> Option<RequestFrame> {
         let data = match self.rx.next().await? {
             Ok(data) => data,
@@ -207,3 +235,24 @@ pub fn create_cert_hash_and_server_config(
             .build(),
     ))
 }
+
+pub fn create_cert_hash_and_server_config_with_certificate(
+    cert: &TlsCertificate,
+    config: WebTransportConfig,
+) -> anyhow::Result<(Vec<u8>, ServerConfig)> {
+    let cert_hash = ring::digest::digest(&ring::digest::SHA256, &cert.chain[0])
+        .as_ref()
+        .to_vec();
+
+    Ok((
+        cert_hash,
+        ServerConfig::builder()
+            .with_bind_address(config.address)
+            .with_certificate(
+                Certificate::new(cert.chain.clone(), cert.key.clone())
+                    .map_err(|e| anyhow::anyhow!("invalid certificate: {e:?}"))?,
+            )
+            .keep_alive_interval(config.keep_alive)
+            .build(),
+    ))
+}
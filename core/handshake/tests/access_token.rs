@@ -0,0 +1,122 @@
+//! Access tokens and secondary connections with `cdk_rust` against a mock node.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use cdk_rust::Builder;
+use common::{spawn_echo, start_mock_node, MockDialer};
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::ServiceId;
+use tokio::net::UnixStream;
+
+mod common;
+
+const ECHO_SERVICE: u32 = 1001;
+
+#[derive(Clone)]
+struct EchoService;
+
+impl ExecutorProviderInterface for EchoService {
+    async fn connect(&self, service_id: ServiceId) -> Option<UnixStream> {
+        if service_id != ECHO_SERVICE {
+            return None;
+        }
+        let (left, right) = UnixStream::pair().ok()?;
+        spawn_echo(left);
+        Some(right)
+    }
+}
+
+fn connector(
+    port: u16,
+) -> cdk_rust::Connector<cdk_rust::PrimaryConnection<MockDialer>, MockDialer> {
+    Builder::primary([0; 32], ECHO_SERVICE)
+        .transport(MockDialer { port })
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn issue_access_token() -> Result<()> {
+    let mut shutdown = start_mock_node(200, EchoService).await?;
+    let mut connection = connector(200).connect().await?;
+
+    // Secondary connections need a token.
+    assert!(connection.access_token().is_none());
+    assert!(
+        connection
+            .secondary_connector(MockDialer { port: 200 })
+            .is_err()
+    );
+
+    let (ttl, token) = connection.request_access_token(60).await?;
+    assert_eq!(ttl, 60);
+    let access_token = connection.access_token().expect("token should be stored");
+    assert_eq!(access_token.token(), *token);
+    assert!(!access_token.is_expired());
+
+    // Extending the token moves its expiry forward.
+    connection.extend_access_token(120).await?;
+    assert!(connection.access_token().unwrap().expires_at() > access_token.expires_at());
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn refresh_access_token() -> Result<()> {
+    let mut shutdown = start_mock_node(201, EchoService).await?;
+    let mut connection = connector(201).connect().await?;
+    connection.request_access_token(1).await?;
+
+    // A ttl within the margin would refresh the token all the time.
+    assert!(
+        connection
+            .refresh_access_token(1, Duration::from_secs(1))
+            .is_err()
+    );
+
+    // The token expires within the margin, so it is refreshed by the next payload.
+    connection.refresh_access_token(3, Duration::from_secs(2))?;
+    let expires_at = connection.access_token().unwrap().expires_at();
+    connection.send(b"hello".to_vec().into()).await?;
+    assert_eq!(connection.recv().await.unwrap()?.as_ref(), b"hello");
+    assert!(connection.access_token().unwrap().expires_at() > expires_at);
+
+    // The refreshed token is still accepted by the node after the first ttl.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let mut secondary = connection
+        .secondary_connector(MockDialer { port: 201 })?
+        .connect()
+        .await?;
+    secondary.send(b"world".to_vec().into()).await?;
+    assert_eq!(secondary.recv().await.unwrap()?.as_ref(), b"world");
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn attach_secondary_connection() -> Result<()> {
+    let mut shutdown = start_mock_node(202, EchoService).await?;
+    let mut connection = connector(202).connect().await?;
+    connection.request_access_token(1).await?;
+
+    let connector = connection.secondary_connector(MockDialer { port: 202 })?;
+    let mut secondary = connector.connect().await?;
+    assert_eq!(
+        secondary.access_token().map(|token| token.token()),
+        connection.access_token().map(|token| token.token())
+    );
+    for _ in 0..10 {
+        secondary.send(b"hello".to_vec().into()).await?;
+        assert_eq!(secondary.recv().await.unwrap()?.as_ref(), b"hello");
+    }
+
+    // Once the token expired, the connector refuses to join.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(connector.connect().await.is_err());
+
+    shutdown.shutdown().await;
+    Ok(())
+}
@@ -0,0 +1,76 @@
+//! The client side of the mock transport for `cdk_rust`, and a mock node listening on it.
+
+// Every test crate uses a different part of this module.
+#![allow(dead_code)]
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use cdk_rust::transport::{Transport as CdkTransport, TransportReceiver, TransportSender};
+use fn_sdk::header::read_header;
+use futures::{SinkExt, StreamExt};
+use lightning_handshake::handshake::Context;
+use lightning_handshake::transports::mock::{dial_mock, MockTransport, MockTransportConfig};
+use lightning_handshake::transports::Transport;
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::ShutdownController;
+use tokio::net::UnixStream;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+pub struct MockDialer {
+    pub port: u16,
+}
+
+pub struct MockSender(async_channel::Sender<Bytes>);
+
+pub struct MockReceiver(async_channel::Receiver<Bytes>);
+
+#[async_trait]
+impl CdkTransport for MockDialer {
+    type Sender = MockSender;
+    type Receiver = MockReceiver;
+
+    async fn connect(&self) -> Result<(Self::Sender, Self::Receiver)> {
+        let (tx, rx) = dial_mock(self.port).await?;
+        Ok((MockSender(tx), MockReceiver(rx)))
+    }
+}
+
+#[async_trait]
+impl TransportSender for MockSender {
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        self.0.send(Bytes::copy_from_slice(data)).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransportReceiver for MockReceiver {
+    async fn recv(&mut self) -> Option<Bytes> {
+        self.0.recv().await.ok()
+    }
+}
+
+pub async fn start_mock_node<P: ExecutorProviderInterface>(
+    port: u16,
+    provider: P,
+) -> Result<ShutdownController> {
+    let shutdown = ShutdownController::default();
+    let context = Context::new(provider, shutdown.waiter(), &Default::default());
+    let (transport, _) =
+        MockTransport::bind::<P>(shutdown.waiter(), MockTransportConfig { port }).await?;
+    transport.spawn_listener_task(context);
+    Ok(shutdown)
+}
+
+/// Serve a service connection by sending every payload back.
+pub fn spawn_echo(mut stream: UnixStream) {
+    tokio::spawn(async move {
+        read_header(&mut stream).await?;
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+        while let Some(Ok(bytes)) = framed.next().await {
+            framed.send(bytes.into()).await?;
+        }
+        anyhow::Ok(())
+    });
+}
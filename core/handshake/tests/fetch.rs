@@ -0,0 +1,197 @@
+//! Verified downloads with `cdk_rust::fetch` against a mock node serving the fetcher protocol.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use blake3_tree::blake3::tree::HashTreeBuilder;
+use blake3_tree::ProofBuf;
+use bytes::{Buf, Bytes};
+use cdk_rust::fetch::{Fetch, BLOCK_SIZE};
+use cdk_rust::Builder;
+use common::{start_mock_node, MockDialer};
+use fn_sdk::header::read_header;
+use futures::{SinkExt, StreamExt};
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::ServiceId;
+use tokio::io::AsyncReadExt;
+use tokio::net::UnixStream;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+mod common;
+
+const FETCHER_SERVICE: u32 = 0;
+
+/// Content of 5 blocks and a bit.
+fn content() -> Vec<u8> {
+    (0..5 * BLOCK_SIZE as usize + 1000)
+        .map(|i| (i % 251) as u8)
+        .collect()
+}
+
+/// How the mock fetcher misbehaves.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Fault {
+    None,
+    /// Close the first connection after sending two blocks.
+    Disconnect,
+    /// Flip a bit of the third block.
+    Corrupt,
+}
+
+#[derive(Clone)]
+struct MockFetcher {
+    content: Arc<Vec<u8>>,
+    fault: Fault,
+    /// Set once the first connection has been closed.
+    disconnected: Arc<AtomicBool>,
+}
+
+impl MockFetcher {
+    fn new(fault: Fault) -> Self {
+        Self {
+            content: Arc::new(content()),
+            fault,
+            disconnected: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn serve(self, mut stream: UnixStream) -> Result<()> {
+        read_header(&mut stream).await?;
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+        let mut tree_builder = HashTreeBuilder::new();
+        tree_builder.update(&self.content);
+        let output = tree_builder.finalize();
+        let num_blocks = (output.tree.len() + 1) / 2;
+
+        while let Some(Ok(mut payload)) = framed.next().await {
+            assert_eq!(payload.get_u8(), 0x80);
+            let start = payload.get_u32() as usize;
+            let end = (payload.get_u32() as usize).min(num_blocks);
+            assert_eq!(payload.as_ref(), output.hash.as_bytes());
+
+            let mut header = (num_blocks as u32).to_be_bytes().to_vec();
+            header.extend_from_slice(output.hash.as_bytes());
+            framed.send(header.into()).await?;
+
+            for block in start..end {
+                if self.fault == Fault::Disconnect
+                    && block == start + 2
+                    && !self.disconnected.swap(true, Ordering::Relaxed)
+                {
+                    return Ok(());
+                }
+
+                let proof = if block == start {
+                    ProofBuf::new(&output.tree, block)
+                } else {
+                    ProofBuf::resume(&output.tree, block)
+                };
+                framed
+                    .send(Bytes::copy_from_slice(proof.as_slice()))
+                    .await?;
+
+                let offset = block * BLOCK_SIZE as usize;
+                let mut data = self.content
+                    [offset..(offset + BLOCK_SIZE as usize).min(self.content.len())]
+                    .to_vec();
+                if self.fault == Fault::Corrupt && block == 2 {
+                    data[0] ^= 1;
+                }
+                framed.send(data.into()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ExecutorProviderInterface for MockFetcher {
+    async fn connect(&self, service_id: ServiceId) -> Option<UnixStream> {
+        if service_id != FETCHER_SERVICE {
+            return None;
+        }
+        let (left, right) = UnixStream::pair().ok()?;
+        tokio::spawn(self.clone().serve(left));
+        Some(right)
+    }
+}
+
+fn fetch() -> Fetch {
+    let mut tree_builder = HashTreeBuilder::new();
+    tree_builder.update(&content());
+    Fetch::blake3(*tree_builder.finalize().hash.as_bytes())
+}
+
+fn connector(
+    port: u16,
+) -> cdk_rust::Connector<cdk_rust::PrimaryConnection<MockDialer>, MockDialer> {
+    Builder::primary([0; 32], FETCHER_SERVICE)
+        .transport(MockDialer { port })
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn fetch_verified_content() -> Result<()> {
+    let mut shutdown = start_mock_node(100, MockFetcher::new(Fault::None)).await?;
+
+    let mut reader = fetch().start(connector(100)).await?;
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).await?;
+    assert!(data == content());
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_byte_range() -> Result<()> {
+    let mut shutdown = start_mock_node(101, MockFetcher::new(Fault::None)).await?;
+    let content = content();
+
+    for range in [
+        1000..2000,
+        BLOCK_SIZE - 10..3 * BLOCK_SIZE + 10,
+        4 * BLOCK_SIZE..content.len() as u64,
+    ] {
+        let mut reader = fetch().range(range.clone()).start(connector(101)).await?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        assert!(data == content[range.start as usize..range.end as usize]);
+    }
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_resumes_after_disconnect() -> Result<()> {
+    let provider = MockFetcher::new(Fault::Disconnect);
+    let mut shutdown = start_mock_node(102, provider.clone()).await?;
+
+    let mut reader = fetch().start(connector(102)).await?;
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).await?;
+    assert!(provider.disconnected.load(Ordering::Relaxed));
+    assert!(data == content());
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_rejects_invalid_block() -> Result<()> {
+    let mut shutdown = start_mock_node(103, MockFetcher::new(Fault::Corrupt)).await?;
+
+    let mut reader = fetch().start(connector(103)).await?;
+    let mut data = Vec::new();
+    let err = reader.read_to_end(&mut data).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    // Only the blocks before the invalid one are read.
+    assert_eq!(data.len(), 2 * BLOCK_SIZE as usize);
+
+    shutdown.shutdown().await;
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! The `cdk_rust` transports against the transports of a node served over http.
+
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use axum::{Extension, Router};
+use bytes::Bytes;
+use cdk_rust::transport::http::HttpTransport;
+use cdk_rust::transport::tcp::TcpTransport;
+use cdk_rust::transport::webrtc::WebRtcTransport;
+use cdk_rust::transport::{select, AnyTransport, Transport as CdkTransport};
+use cdk_rust::Builder;
+use common::spawn_echo;
+use fn_sdk::header::read_header;
+use futures::{SinkExt, StreamExt};
+use lightning_handshake::config::{HandshakeConfig, RateLimit, RateLimitConfig, TransportConfig};
+use lightning_handshake::handshake::Context;
+use lightning_handshake::transports::http::Config as HttpConfig;
+use lightning_handshake::transports::spawn_transport_by_config;
+use lightning_handshake::transports::webrtc::WebRtcConfig;
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::ServiceId;
+use lightning_interfaces::ShutdownController;
+use tokio::net::{TcpListener, UnixStream};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+mod common;
+
+/// Sends every payload back.
+const ECHO_SERVICE: u32 = 1001;
+/// Responds to an http request with its body.
+const HTTP_ECHO_SERVICE: u32 = 1002;
+
+#[derive(Clone)]
+struct Services;
+
+impl ExecutorProviderInterface for Services {
+    async fn connect(&self, service_id: ServiceId) -> Option<UnixStream> {
+        let (left, right) = UnixStream::pair().ok()?;
+        match service_id {
+            ECHO_SERVICE => spawn_echo(left),
+            HTTP_ECHO_SERVICE => {
+                tokio::spawn(http_echo(left));
+            },
+            _ => return None,
+        }
+        Some(right)
+    }
+}
+
+async fn http_echo(mut stream: UnixStream) -> Result<()> {
+    read_header(&mut stream).await?;
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let Some(body) = framed.next().await.transpose()? else {
+        return Ok(());
+    };
+    // An empty head responds with the default status and headers.
+    framed.send(Bytes::new()).await?;
+    framed.send(body.freeze()).await?;
+    Ok(())
+}
+
+/// Start a node with the transports, returning the address of its http server.
+async fn start_node(transports: Vec<TransportConfig>) -> Result<(ShutdownController, SocketAddr)> {
+    start_node_with_config(transports, &Default::default()).await
+}
+
+/// Start a node with the transports and the handshake config, returning the address of its
+/// http server.
+async fn start_node_with_config(
+    transports: Vec<TransportConfig>,
+    config: &HandshakeConfig,
+) -> Result<(ShutdownController, SocketAddr)> {
+    let shutdown = ShutdownController::default();
+    let ctx = Context::new(Services, shutdown.waiter(), config);
+
+    let mut router = Router::new();
+    for config in transports {
+        if let Some(child) =
+            spawn_transport_by_config(shutdown.waiter(), ctx.clone(), config).await?
+        {
+            router = router.merge(child);
+        }
+    }
+    let app = router
+        .layer(Extension(ctx))
+        .into_make_service_with_connect_info::<SocketAddr>();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let signal = shutdown.waiter().wait_for_shutdown_owned();
+    tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(signal)
+            .await
+    });
+
+    Ok((shutdown, addr))
+}
+
+/// The http transport, with the services of the tests.
+fn http_config() -> TransportConfig {
+    TransportConfig::Http(HttpConfig {
+        services: vec![HTTP_ECHO_SERVICE, 404],
+        head_services: vec![HTTP_ECHO_SERVICE],
+        ..Default::default()
+    })
+}
+
+/// An address nothing listens on.
+async fn unused_addr() -> Result<SocketAddr> {
+    Ok(TcpListener::bind("127.0.0.1:0").await?.local_addr()?)
+}
+
+#[tokio::test]
+async fn http_request() -> Result<()> {
+    let (mut shutdown, addr) = start_node(vec![http_config()]).await?;
+
+    let transport = HttpTransport::new(format!("http://{addr}"));
+    let mut connection = Builder::primary([0; 32], HTTP_ECHO_SERVICE)
+        .transport(transport)
+        .build()?
+        .connect()
+        .await?;
+    connection.send(b"hello".to_vec().into()).await?;
+    assert_eq!(connection.recv().await.unwrap()?.as_ref(), b"hello");
+    // The service is done after a single request.
+    assert!(connection.recv().await.is_none());
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn http_error_status() -> Result<()> {
+    let (mut shutdown, addr) = start_node(vec![http_config()]).await?;
+
+    // The node responds with 404 to requests for unknown services.
+    let transport = HttpTransport::new(format!("http://{addr}"));
+    let mut connection = Builder::primary([0; 32], 404)
+        .transport(transport)
+        .build()?
+        .connect()
+        .await?;
+    connection.send(b"hello".to_vec().into()).await?;
+    let err = connection.recv().await.unwrap().unwrap_err();
+    assert!(err.to_string().contains("InvalidService"), "{err}");
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn http_service_not_allowed() -> Result<()> {
+    let (mut shutdown, addr) = start_node(vec![http_config()]).await?;
+
+    // The echo service runs on the node, but it can't be reached over http.
+    let transport = HttpTransport::new(format!("http://{addr}"));
+    let mut connection = Builder::primary([0; 32], ECHO_SERVICE)
+        .transport(transport)
+        .build()?
+        .connect()
+        .await?;
+    connection.send(b"hello".to_vec().into()).await?;
+    let err = connection.recv().await.unwrap().unwrap_err();
+    assert!(err.to_string().contains("InvalidService"), "{err}");
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn http_rate_limit() -> Result<()> {
+    let config = HandshakeConfig {
+        rate_limit: RateLimitConfig {
+            per_ip: Some(RateLimit {
+                rate: 0.0,
+                burst: 2,
+            }),
+            per_client: None,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut shutdown, addr) = start_node_with_config(vec![http_config()], &config).await?;
+
+    // Every http request takes a single token of the bucket of the address.
+    for allowed in [true, true, false] {
+        let transport = HttpTransport::new(format!("http://{addr}"));
+        let mut connection = Builder::primary([0; 32], HTTP_ECHO_SERVICE)
+            .transport(transport)
+            .build()?
+            .connect()
+            .await?;
+        connection.send(b"hello".to_vec().into()).await?;
+        match connection.recv().await.unwrap() {
+            Ok(bytes) if allowed => assert_eq!(bytes.as_ref(), b"hello"),
+            Err(err) if !allowed => {
+                assert!(err.to_string().contains("ResourcesUnavailable"), "{err}")
+            },
+            res => panic!("unexpected response with allowed={allowed}: {res:?}"),
+        }
+    }
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn http_unreachable() -> Result<()> {
+    let transport = HttpTransport::new(format!("http://{}", unused_addr().await?));
+    assert!(transport.connect().await.is_err());
+    Ok(())
+}
+
+/// The node queries a public STUN server when binding the WebRTC transport.
+#[tokio::test]
+#[ignore = "requires network access"]
+async fn webrtc_connection() -> Result<()> {
+    let (mut shutdown, addr) = start_node(vec![TransportConfig::WebRTC(WebRtcConfig {
+        address: ([127, 0, 0, 1], 0).into(),
+    })])
+    .await?;
+
+    let transport = WebRtcTransport::new(format!("http://{addr}/sdp"));
+    let mut connection = Builder::primary([0; 32], ECHO_SERVICE)
+        .transport(transport)
+        .build()?
+        .connect()
+        .await?;
+    for _ in 0..10 {
+        connection.send(b"hello".to_vec().into()).await?;
+        assert_eq!(connection.recv().await.unwrap()?.as_ref(), b"hello");
+    }
+
+    shutdown.shutdown().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn select_available_transport() -> Result<()> {
+    let (mut shutdown, addr) = start_node(vec![http_config()]).await?;
+
+    // Tcp is preferred, but the node doesn't listen for it.
+    let transport = select([
+        AnyTransport::Http(HttpTransport::new(format!("http://{addr}"))),
+        AnyTransport::Tcp(TcpTransport::new(unused_addr().await?)),
+    ])
+    .await?;
+    assert_eq!(transport.name(), "http");
+
+    let unreachable = format!("http://{}", unused_addr().await?);
+    assert!(
+        select([AnyTransport::Http(HttpTransport::new(unreachable))])
+            .await
+            .is_err()
+    );
+
+    shutdown.shutdown().await;
+    Ok(())
+}
@@ -41,6 +41,7 @@
     ProtocolParams,
     ReportedReputationMeasurements,
     Service,
+    ServiceArtifact,
     ServiceId,
     TotalServed,
     TransactionResponse,
@@ -117,6 +118,7 @@ fn register_tables<B: StorageBackendConstructor, S: SerdeBackend>(
             .with_table::<(NodeIndex, NodeIndex), Duration>("latencies")
             .with_table::<Epoch, Committee>("committee")
             .with_table::<ServiceId, Service>("service")
+            .with_table::<ServiceId, ServiceArtifact>("service_artifact")
             .with_table::<ProtocolParams, u128>("parameter")
             .with_table::<NodeIndex, Vec<ReportedReputationMeasurements>>("rep_measurements")
             .with_table::<NodeIndex, u8>("rep_scores")
@@ -168,6 +170,18 @@ fn get_committe_info<V>(
     /// Returns the service information for a given [`ServiceId`]
     fn get_service_info(&self, id: &ServiceId) -> Option<Service>;
 
+    /// Query Service Artifacts Table
+    /// Returns the artifact of a service, if one was published for it
+    fn get_service_artifact(&self, id: &ServiceId) -> Option<ServiceArtifact>;
+
+    /// Query Service Artifacts Table
+    /// Returns the output of the closure given an iterator over the ids of the services with an
+    /// artifact
+    fn get_service_artifact_table_iter<V>(
+        &self,
+        closure: impl FnOnce(KeyIterator<ServiceId>) -> V,
+    ) -> V;
+
     /// Query Params Table
     /// Returns the passed in protocol parameter
     fn get_protocol_param(&self, param: &ProtocolParams) -> Option<u128>;
@@ -1,14 +1,13 @@
 use affair::Socket;
 use anyhow::Result;
 use fdi::BuildGraph;
-use lightning_types::{PeerRequestError, ServerRequest};
-use tokio::sync::broadcast;
+use lightning_types::{FetchProgress, PeerRequestError, ServerRequest};
+use tokio::sync::{broadcast, watch};
 
 use crate::collection::Collection;
 use crate::ConfigConsumer;
 
-pub type BlockstoreServerSocket =
-    Socket<ServerRequest, broadcast::Receiver<Result<(), PeerRequestError>>>;
+pub type BlockstoreServerSocket = Socket<ServerRequest, ServerResponse>;
 
 #[interfaces_proc::blank]
 pub trait BlockstoreServerInterface<C: Collection>:
@@ -17,3 +16,31 @@ pub trait BlockstoreServerInterface<C: Collection>:
     #[socket]
     fn get_socket(&self) -> BlockstoreServerSocket;
 }
+
+/// The response to a [`ServerRequest`]. Requests for the same content are deduplicated, so
+/// every response to such requests follows the same download.
+pub struct ServerResponse {
+    result: broadcast::Receiver<Result<(), PeerRequestError>>,
+    progress: watch::Receiver<FetchProgress>,
+}
+
+impl ServerResponse {
+    pub fn new(
+        result: broadcast::Receiver<Result<(), PeerRequestError>>,
+        progress: watch::Receiver<FetchProgress>,
+    ) -> Self {
+        Self { result, progress }
+    }
+
+    /// Waits for the download to finish.
+    pub async fn recv(
+        &mut self,
+    ) -> Result<Result<(), PeerRequestError>, broadcast::error::RecvError> {
+        self.result.recv().await
+    }
+
+    /// Returns a receiver for the progress of the download.
+    pub fn progress(&self) -> watch::Receiver<FetchProgress> {
+        self.progress.clone()
+    }
+}
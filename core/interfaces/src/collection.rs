@@ -35,6 +35,7 @@
     PoolInterface,
     PingerInterface,
     IndexerInterface,
+    ReplicatorInterface,
 ]);
 
 /// The Fleek Network node.
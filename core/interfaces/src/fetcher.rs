@@ -1,10 +1,15 @@
 use affair::Socket;
+use anyhow::Context;
 use fdi::BuildGraph;
-use lightning_types::{FetcherRequest, FetcherResponse};
+use lightning_types::{FetchProgress, FetcherRequest, FetcherResponse};
+use tokio::sync::{oneshot, watch};
+use tokio_util::sync::{CancellationToken, DropGuard};
 
 use crate::collection::Collection;
 
-pub type FetcherSocket = Socket<FetcherRequest, FetcherResponse>;
+/// A socket for submitting requests to the fetcher. The fetcher responds right away with a
+/// handle to the request, the actual response has to be awaited on the handle.
+pub type FetcherSocket = Socket<FetcherRequest, FetchHandle>;
 
 #[interfaces_proc::blank]
 pub trait FetcherInterface<C: Collection>: BuildGraph + Sized + Send + Sync {
@@ -12,3 +17,96 @@ pub trait FetcherInterface<C: Collection>: BuildGraph + Sized + Send + Sync {
     #[socket]
     fn get_socket(&self) -> FetcherSocket;
 }
+
+/// A handle to a request that was submitted to the fetcher.
+///
+/// Dropping the handle does not cancel the request, use [`FetchHandle::cancel`] for that.
+pub struct FetchHandle {
+    progress: watch::Receiver<FetchProgress>,
+    response: oneshot::Receiver<FetcherResponse>,
+    cancel: CancellationToken,
+}
+
+/// The fetcher side of a [`FetchHandle`], used to report on the request.
+pub struct FetchTracker {
+    progress: watch::Sender<FetchProgress>,
+    response: oneshot::Sender<FetcherResponse>,
+    cancel: CancellationToken,
+}
+
+impl FetchHandle {
+    /// Creates a new handle along with the tracker that reports to it.
+    pub fn new() -> (Self, FetchTracker) {
+        let (progress_tx, progress_rx) = watch::channel(FetchProgress::default());
+        let (response_tx, response_rx) = oneshot::channel();
+        let cancel = CancellationToken::new();
+        let handle = Self {
+            progress: progress_rx,
+            response: response_rx,
+            cancel: cancel.clone(),
+        };
+        let tracker = FetchTracker {
+            progress: progress_tx,
+            response: response_tx,
+            cancel,
+        };
+        (handle, tracker)
+    }
+
+    /// Returns the latest progress of the request.
+    pub fn progress(&self) -> FetchProgress {
+        *self.progress.borrow()
+    }
+
+    /// Waits for the progress of the request to change. Returns `None` once the request has
+    /// finished.
+    pub async fn progress_changed(&mut self) -> Option<FetchProgress> {
+        self.progress.changed().await.ok()?;
+        Some(*self.progress.borrow_and_update())
+    }
+
+    /// Returns a receiver for the progress of the request, which outlives the handle.
+    pub fn progress_receiver(&self) -> watch::Receiver<FetchProgress> {
+        self.progress.clone()
+    }
+
+    /// Cancels the request. The response will be an error unless the request finished before
+    /// the cancellation was noticed.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Returns a guard cancelling the request once dropped, which outlives the handle. Cancelling
+    /// a request that already finished has no effect.
+    pub fn cancel_on_drop(&self) -> DropGuard {
+        self.cancel.clone().drop_guard()
+    }
+
+    /// Waits for the response to the request.
+    pub async fn response(self) -> anyhow::Result<FetcherResponse> {
+        self.response
+            .await
+            .context("the fetcher dropped the request")
+    }
+}
+
+impl FetchTracker {
+    /// Reports the progress of the request.
+    pub fn report(&self, progress: FetchProgress) {
+        self.progress.send_replace(progress);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Resolves once the request is cancelled.
+    pub async fn cancelled(&self) {
+        self.cancel.cancelled().await
+    }
+
+    /// Sends the response to the handle, if it is still around.
+    pub fn respond(self, response: FetcherResponse) {
+        let _ = self.response.send(response);
+    }
+}
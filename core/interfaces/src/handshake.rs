@@ -1,6 +1,34 @@
+use affair::Socket;
 use fdi::BuildGraph;
+use lightning_schema::handshake::TerminationReason;
+use lightning_types::HandshakeConnection;
 
 use crate::collection::Collection;
 
+/// A socket for inspecting and terminating the connections of the handshake.
+pub type HandshakeAdminSocket = Socket<HandshakeAdminRequest, HandshakeAdminResponse>;
+
 #[interfaces_proc::blank]
-pub trait HandshakeInterface<C: Collection>: BuildGraph + Sized + Send + Sync {}
+pub trait HandshakeInterface<C: Collection>: BuildGraph + Sized + Send + Sync {
+    /// Returns a socket that can be used to inspect and terminate the connections.
+    #[socket]
+    fn get_admin_socket(&self) -> HandshakeAdminSocket;
+}
+
+#[derive(Clone, Debug)]
+pub enum HandshakeAdminRequest {
+    /// List the ongoing connections.
+    Connections,
+    /// Terminate a connection, sending the reason to the client.
+    Terminate {
+        connection_id: u64,
+        reason: TerminationReason,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub enum HandshakeAdminResponse {
+    Connections(Vec<HandshakeConnection>),
+    /// Whether the connection existed.
+    Terminated(bool),
+}
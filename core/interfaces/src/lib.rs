@@ -17,6 +17,7 @@
 mod origin;
 mod pinger;
 mod pool;
+mod replicator;
 mod reputation;
 mod resolver;
 mod rpc;
@@ -44,6 +45,7 @@
 pub use origin::*;
 pub use pinger::*;
 pub use pool::*;
+pub use replicator::*;
 pub use reputation::*;
 pub use resolver::*;
 pub use rpc::*;
@@ -93,6 +93,7 @@ macro_rules! partial {
             PoolInterface,
             PingerInterface,
             IndexerInterface,
+            ReplicatorInterface,
         }, { $($name),*});
     };
     (@gen_body { $($name:ident = $ty:ty;)* }) => {
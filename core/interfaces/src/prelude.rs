@@ -63,6 +63,7 @@
     PingerInterface,
     PoolInterface,
     PubSub,
+    ReplicatorInterface,
     ReputationAggregatorInterface,
     ReputationQueryInteface,
     ReputationReporterInterface,
@@ -0,0 +1,8 @@
+use fdi::BuildGraph;
+
+use crate::collection::Collection;
+
+/// The replicator keeps the content in the content registry available, by fetching content
+/// that is provided by fewer nodes than the replication policy asks for.
+#[interfaces_proc::blank]
+pub trait ReplicatorInterface<C: Collection>: BuildGraph + Sized + Send + Sync {}
@@ -30,6 +30,12 @@ pub trait ServiceExecutorInterface<C: Collection>: BuildGraph + Sized + Send + S
 
 #[interfaces_proc::blank]
 pub trait ExecutorProviderInterface: Clone + Send + Sync + 'static {
+    /// Returns false if the service is known to be unavailable, e.g. it's crash looping, in
+    /// which case connections to it should be refused right away.
+    fn is_available(&self, _service_id: ServiceId) -> bool {
+        true
+    }
+
     /// Make a connection to the provided service.
     async fn connect(&self, service_id: ServiceId) -> Option<UnixStream>;
 }
@@ -0,0 +1,89 @@
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use prometheus::core::Collector;
+use prometheus::{register_int_gauge_vec, IntGaugeVec};
+pub use stdext::function_name;
+use tracing::error;
+
+use crate::labels::Labels;
+
+static GAUGES: Lazy<DashMap<String, IntGaugeVec>> = Lazy::new(DashMap::new);
+
+pub trait Gauge {
+    fn set(
+        family: &str,
+        description: Option<&str>,
+        labels: &[&str],
+        label_values: &[&str],
+        value: i64,
+    );
+}
+
+impl Gauge for Labels {
+    fn set(
+        family: &str,
+        description: Option<&str>,
+        labels: &[&str],
+        label_values: &[&str],
+        value: i64,
+    ) {
+        let existing_labels: Option<Vec<_>> = GAUGES.get(family).and_then(|existing_gauge| {
+            let families = existing_gauge.clone().collect();
+            families
+                .first()
+                .and_then(|f| f.get_metric().first())
+                .map(|metric| {
+                    metric
+                        .get_label()
+                        .iter()
+                        .map(|l| l.get_name().to_owned())
+                        .collect()
+                })
+        });
+        if let Some(existing_labels) = &existing_labels {
+            let mut sorted_existing_labels = existing_labels.clone();
+            let mut sorted_new_labels: Vec<_> = labels.to_vec();
+            sorted_existing_labels.sort();
+            sorted_new_labels.sort();
+
+            if sorted_existing_labels != sorted_new_labels {
+                error!(
+                    "Mismatched labels for family '{}'. Existing labels: {:?}, New labels: {:?}",
+                    family, existing_labels, labels
+                );
+                return;
+            }
+        };
+        let gauge = GAUGES.entry(family.to_string()).or_insert_with(|| {
+            register_int_gauge_vec!(family, description.unwrap_or_default(), labels).unwrap()
+        });
+
+        gauge.with_label_values(label_values).set(value);
+    }
+}
+
+/// Set the value of a gauge. Since the name of the calling function is one of the labels, a
+/// gauge should always be set from the same function.
+#[macro_export]
+macro_rules! set_gauge {
+    ($family:expr, $description:expr, $value:expr $(, $($label:expr => $label_value:expr),*)?) => {
+        {
+            let function =
+                $crate::labels::Labels::extract_fn_name($crate::gauge::function_name!());
+            let default_labels = $crate::labels::Labels::new(function, module_path!());
+            let default_labels = default_labels.to_vec();
+
+            let additional_labels = vec![$($($label),*)?];
+            let additional_values = vec![$($($label_value),*)?];
+
+            let all_labels: Vec<_> = default_labels
+                .iter().map(|a| a.0).chain(additional_labels).collect();
+            let all_values: Vec<_> = default_labels
+                .iter().map(|a| a.1).chain(additional_values).collect();
+
+            <$crate::labels::Labels as $crate::gauge::Gauge>::set(
+                $family, $description, &all_labels, &all_values, $value
+            );
+        }
+    };
+}
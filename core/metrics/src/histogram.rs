@@ -71,6 +71,22 @@ fn observe(
 
 #[macro_export]
 macro_rules! histogram {
+    ($family:expr, $description:expr, $value:expr $(, $label:expr => $label_value:expr)+ ) => {
+        {
+            let function =
+                $crate::labels::Labels::extract_fn_name($crate::histogram::function_name!());
+            let default_labels = $crate::labels::Labels::new(function, module_path!());
+            let default_labels = default_labels.to_vec();
+
+            let all_labels: Vec<_> = default_labels
+                .iter().map(|a| a.0).chain([$($label),+]).collect();
+            let all_values: Vec<_> = default_labels
+                .iter().map(|a| a.1).chain([$($label_value),+]).collect();
+            <$crate::labels::Labels as $crate::histogram::Histogram>::observe(
+                $family, $description, &all_labels, &all_values, $value, None
+            );
+        }
+    };
     ($family:expr, $description:expr, $value:expr, $($bucket:expr),+ ) => {
         {
             let buckets = vec![$($bucket),+];
@@ -1,6 +1,12 @@
 use autometrics::settings::AutometricsSettingsBuilder;
 
-use crate::{histogram, increment_counter, DEFAULT_HISTOGRAM_BUCKETS, METRICS_SERVICE_NAME};
+use crate::{
+    histogram,
+    increment_counter,
+    set_gauge,
+    DEFAULT_HISTOGRAM_BUCKETS,
+    METRICS_SERVICE_NAME,
+};
 
 fn init() {
     let _ = AutometricsSettingsBuilder::default()
@@ -76,3 +82,70 @@ fn test_histogram_macro() {
         }
     }
 }
+
+#[test]
+fn test_histogram_macro_with_labels() {
+    init();
+    histogram!(
+        "Test_Labeled_Histogram",
+        Some("A custom histogram"),
+        0.3,
+        "extra_label" => "1"
+    );
+    histogram!(
+        "Test_Labeled_Histogram",
+        Some("A custom histogram"),
+        0.6,
+        "extra_label" => "1"
+    );
+    histogram!(
+        "Test_Labeled_Histogram",
+        Some("A custom histogram"),
+        0.6,
+        "extra_label" => "2"
+    );
+
+    let metric_families = prometheus::gather();
+    let metric_family = metric_families
+        .iter()
+        .find(|mf| mf.get_name() == "Test_Labeled_Histogram")
+        .expect("histogram not found");
+
+    let metrics = metric_family.get_metric();
+    assert_eq!(metrics.len(), 2);
+    let count = |value: &str| {
+        metrics
+            .iter()
+            .find(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|l| l.get_name() == "extra_label" && l.get_value() == value)
+            })
+            .map(|metric| metric.get_histogram().get_sample_count())
+    };
+    assert_eq!(count("1"), Some(2));
+    assert_eq!(count("2"), Some(1));
+}
+
+#[test]
+fn test_gauge_macro() {
+    init();
+    set_gauge!("Test_Custom_Gauge", Some("A custom gauge"), 5, "extra_label" => "1");
+    set_gauge!("Test_Custom_Gauge", Some("A custom gauge"), 3, "extra_label" => "1");
+    set_gauge!("Test_Custom_Gauge", Some("A custom gauge"), -2, "extra_label" => "2");
+
+    let metric_families = prometheus::gather();
+    let metric_family = metric_families
+        .iter()
+        .find(|mf| mf.get_name() == "Test_Custom_Gauge")
+        .expect("gauge not found");
+
+    let mut values = metric_family
+        .get_metric()
+        .iter()
+        .map(|metric| metric.get_gauge().get_value())
+        .collect::<Vec<_>>();
+    values.sort_by(f64::total_cmp);
+    assert_eq!(values, vec![-2.0, 3.0]);
+}
@@ -4,4 +4,5 @@
 pub struct Config {
     pub http: lightning_origin_http::Config,
     pub ipfs: lightning_origin_ipfs::Config,
+    pub s3: lightning_origin_s3::Config,
 }
@@ -3,12 +3,14 @@
 use lightning_interfaces::Collection;
 use lightning_origin_http::HttpOrigin;
 use lightning_origin_ipfs::IPFSOrigin;
+use lightning_origin_s3::S3Origin;
 
 use crate::Config;
 
 pub struct Demuxer<C: Collection> {
     http: HttpOrigin<C>,
     ipfs: IPFSOrigin<C>,
+    s3: S3Origin<C>,
 }
 
 impl<C: Collection> AsyncWorkerUnordered for Demuxer<C> {
@@ -19,6 +21,7 @@ async fn handle(&self, req: Self::Request) -> Self::Response {
         match &req.origin {
             OriginProvider::HTTP => self.http.fetch(&req.uri).await,
             OriginProvider::IPFS => self.ipfs.fetch(&req.uri).await,
+            OriginProvider::S3 => self.s3.fetch(&req.uri).await,
             _ => Err(anyhow::anyhow!("unknown origin type")),
         }
     }
@@ -28,7 +31,8 @@ impl<C: Collection> Demuxer<C> {
     pub fn new(config: Config, blockstore: C::BlockstoreInterface) -> anyhow::Result<Self> {
         Ok(Self {
             http: HttpOrigin::<C>::new(config.http, blockstore.clone())?,
-            ipfs: IPFSOrigin::<C>::new(config.ipfs, blockstore)?,
+            ipfs: IPFSOrigin::<C>::new(config.ipfs, blockstore.clone())?,
+            s3: S3Origin::<C>::new(config.s3, blockstore)?,
         })
     }
 }
@@ -17,7 +17,7 @@
 use lightning_test_utils::keys::EphemeralKeystore;
 use lightning_test_utils::server;
 
-use crate::OriginDemuxer;
+use crate::{Config, OriginDemuxer};
 
 partial!(TestBinding {
     ConfigProviderInterface = JsonConfigProvider;
@@ -143,6 +143,13 @@ async fn create_app_state(test_name: String) -> AppState {
                         probability_txn_lost: 0.0,
                         transactions_to_lose: HashSet::new(),
                         new_block_interval: Duration::from_secs(5),
+                    })
+                    .with::<OriginDemuxer<TestBinding>>(Config {
+                        http: lightning_origin_http::Config {
+                            allow_private_ips: true,
+                            ..Default::default()
+                        },
+                        ..Default::default()
                     }),
             )
             .with(keystore),
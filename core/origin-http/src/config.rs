@@ -1,4 +1,47 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Deserialize, Serialize)]
-pub struct Config {}
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Timeout for establishing a connection with the remote host.
+    pub connect_timeout: Duration,
+    /// Maximum amount of time we wait for the next chunk of the body before giving up.
+    pub read_timeout: Duration,
+    /// Timeout for the entire request, including streaming the body.
+    pub request_timeout: Duration,
+    /// Maximum size in bytes of the content we are willing to fetch.
+    pub max_size: u64,
+    /// URL schemes that are allowed to be fetched.
+    pub allowed_schemes: Vec<String>,
+    /// If not empty, only these hosts can be fetched from.
+    pub allowed_hosts: Vec<String>,
+    /// Hosts that can never be fetched from.
+    pub denied_hosts: Vec<String>,
+    /// Whether requests to loopback, private, link-local and other non-global addresses are
+    /// allowed. This should only be enabled for testing.
+    pub allow_private_ips: bool,
+    /// Headers that are sent along with every request.
+    pub headers: HashMap<String, String>,
+    /// Maximum number of redirects that will be followed. Zero disables redirects.
+    pub max_redirects: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(300),
+            max_size: 1024 * 1024 * 1024,
+            allowed_schemes: vec!["http".to_string(), "https".to_string()],
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+            allow_private_ips: false,
+            headers: HashMap::new(),
+            max_redirects: 5,
+        }
+    }
+}
@@ -1,18 +1,28 @@
 mod config;
+mod policy;
 #[cfg(test)]
 mod tests;
 
+use std::sync::Arc;
 use std::time::Duration;
 
+use anyhow::{anyhow, bail, Context};
 use fast_sri::IntegrityMetadata;
 use lightning_interfaces::prelude::*;
 use lightning_interfaces::types::{Blake3Hash, CompressionAlgorithm};
-use reqwest::{Client, Url};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{redirect, Client, Url};
+use tokio::time::timeout;
 
 pub use crate::config::Config;
+use crate::policy::{PolicyResolver, UrlPolicy};
 
 pub struct HttpOrigin<C: Collection> {
     client: Client,
+    policy: Arc<UrlPolicy>,
+    read_timeout: Duration,
+    request_timeout: Duration,
+    max_size: u64,
     blockstore: C::BlockstoreInterface,
 }
 
@@ -20,38 +30,110 @@ impl<C: Collection> Clone for HttpOrigin<C> {
     fn clone(&self) -> Self {
         Self {
             client: self.client.clone(),
+            policy: self.policy.clone(),
+            read_timeout: self.read_timeout,
+            request_timeout: self.request_timeout,
+            max_size: self.max_size,
             blockstore: self.blockstore.clone(),
         }
     }
 }
 
 impl<C: Collection> HttpOrigin<C> {
-    pub fn new(_: Config, blockstore: C::BlockstoreInterface) -> anyhow::Result<Self> {
-        let client = Client::new();
-        Ok(Self { client, blockstore })
+    pub fn new(config: Config, blockstore: C::BlockstoreInterface) -> anyhow::Result<Self> {
+        let policy = Arc::new(UrlPolicy::new(&config));
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            headers.insert(
+                HeaderName::try_from(name.as_str())?,
+                HeaderValue::try_from(value.as_str())?,
+            );
+        }
+
+        // Every hop of a redirect chain has to pass the same policy as the original url.
+        let max_redirects = config.max_redirects;
+        let redirect_policy = policy.clone();
+        let redirect = redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > max_redirects {
+                attempt.error("too many redirects")
+            } else if let Err(e) = redirect_policy.check(attempt.url()) {
+                attempt.error(e.to_string())
+            } else {
+                attempt.follow()
+            }
+        });
+
+        let client = Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .default_headers(headers)
+            .redirect(redirect)
+            .dns_resolver(Arc::new(PolicyResolver::new(policy.clone())))
+            .build()?;
+
+        Ok(Self {
+            client,
+            policy,
+            read_timeout: config.read_timeout,
+            request_timeout: config.request_timeout,
+            max_size: config.max_size,
+            blockstore,
+        })
     }
 
     pub async fn fetch(&self, uri: &[u8]) -> anyhow::Result<Blake3Hash> {
         let (url, sri) = get_url_and_sri(uri)?;
-        let resp = self
-            .client
-            .get(url)
-            .timeout(Duration::from_millis(500))
-            .send()
-            .await?;
-        let mut data: Vec<u8> = resp.bytes().await?.into();
-
-        // We verify before inserting any blocks
-        if let Some(integrity_metadata) = sri {
-            let (is_valid, verified_data) = integrity_metadata.verify(data);
-            if !is_valid {
-                anyhow::bail!("sri failed: invalid digest");
-            }
-            data = verified_data;
+        self.policy.check(&url)?;
+        timeout(self.request_timeout, self.stream_into_blockstore(url, sri))
+            .await
+            .map_err(|_| anyhow!("request timed out"))?
+    }
+
+    async fn stream_into_blockstore(
+        &self,
+        url: Url,
+        sri: Option<IntegrityMetadata>,
+    ) -> anyhow::Result<Blake3Hash> {
+        let mut resp = self.client.get(url).send().await?;
+        if !resp.status().is_success() {
+            bail!("request failed with status {}", resp.status());
+        }
+        if resp.content_length().unwrap_or(0) > self.max_size {
+            bail!(
+                "content exceeds the maximum size of {} bytes",
+                self.max_size
+            );
         }
 
+        let mut verifier = sri.map(IntegrityMetadata::into_streaming_verifier);
         let mut putter = self.blockstore.put(None);
-        putter.write(data.as_ref(), CompressionAlgorithm::Uncompressed)?;
+        let mut size = 0;
+
+        while let Some(chunk) = timeout(self.read_timeout, resp.chunk())
+            .await
+            .context("timed out waiting for data")??
+        {
+            size += chunk.len() as u64;
+            if size > self.max_size {
+                bail!(
+                    "content exceeds the maximum size of {} bytes",
+                    self.max_size
+                );
+            }
+            if let Some(verifier) = verifier.as_mut() {
+                verifier.update(&chunk);
+            }
+            putter.write(chunk.as_ref(), CompressionAlgorithm::Uncompressed)?;
+        }
+
+        // The blocks might already be on disk at this point, but the content only becomes
+        // reachable once the putter is finalized, so we must verify before that.
+        if let Some(verifier) = verifier {
+            if !verifier.verify() {
+                bail!("sri failed: invalid digest");
+            }
+        }
+
         putter.finalize().await.map_err(Into::into)
     }
 }
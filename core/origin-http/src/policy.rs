@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::Url;
+
+use crate::Config;
+
+/// Decides which URLs the origin is allowed to fetch from.
+pub(crate) struct UrlPolicy {
+    allowed_schemes: HashSet<String>,
+    allowed_hosts: HashSet<String>,
+    denied_hosts: HashSet<String>,
+    allow_private_ips: bool,
+}
+
+impl UrlPolicy {
+    pub fn new(config: &Config) -> Self {
+        let lowercase = |items: &[String]| items.iter().map(|s| s.to_lowercase()).collect();
+        Self {
+            allowed_schemes: lowercase(&config.allowed_schemes),
+            allowed_hosts: lowercase(&config.allowed_hosts),
+            denied_hosts: lowercase(&config.denied_hosts),
+            allow_private_ips: config.allow_private_ips,
+        }
+    }
+
+    /// Checks the URL against the policy.
+    ///
+    /// Hosts given by name are only checked against the allow and deny lists, the addresses
+    /// they resolve to are checked by the [`PolicyResolver`].
+    pub fn check(&self, url: &Url) -> anyhow::Result<()> {
+        if !self.allowed_schemes.contains(url.scheme()) {
+            bail!("scheme not allowed: {}", url.scheme());
+        }
+
+        let host = url.host_str().context("url has no host")?.to_lowercase();
+        if self.denied_hosts.contains(&host) {
+            bail!("host not allowed: {host}");
+        }
+        if !self.allowed_hosts.is_empty() && !self.allowed_hosts.contains(&host) {
+            bail!("host not allowed: {host}");
+        }
+
+        let ip = match url.host() {
+            Some(url::Host::Ipv4(ip)) => Some(IpAddr::V4(ip)),
+            Some(url::Host::Ipv6(ip)) => Some(IpAddr::V6(ip)),
+            _ => None,
+        };
+        if let Some(ip) = ip {
+            if !self.is_ip_allowed(&ip) {
+                bail!("address not allowed: {ip}");
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_ip_allowed(&self, ip: &IpAddr) -> bool {
+        self.allow_private_ips || is_global(ip)
+    }
+}
+
+/// A DNS resolver that drops every address that is not allowed by the [`UrlPolicy`].
+///
+/// Filtering at resolution time, instead of before sending the request, guarantees that the
+/// address we connect to is the one we checked, including for hosts we got redirected to.
+pub(crate) struct PolicyResolver {
+    policy: Arc<UrlPolicy>,
+}
+
+impl PolicyResolver {
+    pub fn new(policy: Arc<UrlPolicy>) -> Self {
+        Self { policy }
+    }
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve(self.policy.clone(), name))
+    }
+}
+
+async fn resolve(
+    policy: Arc<UrlPolicy>,
+    name: Name,
+) -> Result<Addrs, Box<dyn Error + Send + Sync>> {
+    let addrs = tokio::net::lookup_host((name.as_str(), 0))
+        .await?
+        .filter(|addr| policy.is_ip_allowed(&addr.ip()))
+        .collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(format!("no allowed address for host {}", name.as_str()).into());
+    }
+    Ok(Box::new(addrs.into_iter()))
+}
+
+/// Returns true if the address is globally routable.
+pub(crate) fn is_global(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_global_v4(ip),
+        IpAddr::V6(ip) => is_global_v6(ip),
+    }
+}
+
+fn is_global_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8 "this network".
+        || a == 0
+        // 100.64.0.0/10 shared address space.
+        || (a == 100 && (b & 0b1100_0000) == 0b0100_0000)
+        // 192.0.0.0/24 protocol assignments.
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        // 198.18.0.0/15 benchmarking.
+        || (a == 198 && (b & 0xfe) == 18)
+        // 240.0.0.0/4 reserved.
+        || a >= 240)
+}
+
+fn is_global_v6(ip: &Ipv6Addr) -> bool {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_global_v4(&ip);
+    }
+    let segments = ip.segments();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7 unique local.
+        || (segments[0] & 0xfe00) == 0xfc00
+        // fe80::/10 link local.
+        || (segments[0] & 0xffc0) == 0xfe80
+        // 2001:db8::/32 documentation.
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+}
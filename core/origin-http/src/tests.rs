@@ -17,7 +17,8 @@
 use lightning_test_utils::keys::EphemeralKeystore;
 use lightning_test_utils::server;
 
-use crate::{get_url_and_sri, HttpOrigin};
+use crate::policy::is_global;
+use crate::{get_url_and_sri, Config, HttpOrigin};
 
 partial!(TestBinding {
     ConfigProviderInterface = JsonConfigProvider;
@@ -152,6 +153,13 @@ async fn create_app_state(test_name: String) -> AppState {
     }
 }
 
+fn test_config() -> Config {
+    Config {
+        allow_private_ips: true,
+        ..Default::default()
+    }
+}
+
 #[tokio::test]
 async fn test_http_origin() {
     // Todo: let's use a different type of content.
@@ -161,8 +169,7 @@ async fn test_http_origin() {
     let url = "http://127.0.0.1:30233/bar/index.ts".to_string();
     // Given: an origin.
     let mut state = create_app_state("test_http_origin".to_string()).await;
-    let origin =
-        HttpOrigin::<TestBinding>::new(Default::default(), state.blockstore().clone()).unwrap();
+    let origin = HttpOrigin::<TestBinding>::new(test_config(), state.blockstore().clone()).unwrap();
 
     // When: we fetch some content using the origin.
     let test_fut = async move {
@@ -189,8 +196,7 @@ async fn test_http_origin_with_integrity_check() {
     let url = "http://127.0.0.1:30400/bar/index.ts#integrity=sha256-61z/GbpXJljbPypnYd2389IVCTbzU/taXTCVOUR67is=".to_string();
     // Given: an origin.
     let mut state = create_app_state("test_http_origin_with_integrity_check".to_string()).await;
-    let origin =
-        HttpOrigin::<TestBinding>::new(Default::default(), state.blockstore().clone()).unwrap();
+    let origin = HttpOrigin::<TestBinding>::new(test_config(), state.blockstore().clone()).unwrap();
 
     // When: we fetch some content using the origin.
     let test_fut = async move {
@@ -215,8 +221,7 @@ async fn test_http_origin_with_integrity_check_invalid_hash() {
     // Given: an origin.
     let mut state =
         create_app_state("test_http_origin_with_integrity_check_invalid_hash".to_string()).await;
-    let origin =
-        HttpOrigin::<TestBinding>::new(Default::default(), state.blockstore().clone()).unwrap();
+    let origin = HttpOrigin::<TestBinding>::new(test_config(), state.blockstore().clone()).unwrap();
 
     // When: we fetch some content using the origin.
     let test_fut = async move {
@@ -241,6 +246,106 @@ async fn test_http_origin_with_integrity_check_invalid_hash() {
     }
 }
 
+#[tokio::test]
+async fn test_http_origin_denies_private_ips() {
+    // Given: an identifier for a resource on a private address.
+    let url = "http://127.0.0.1:30402/bar/index.ts".to_string();
+    // Given: an origin with the default config.
+    let mut state = create_app_state("test_http_origin_denies_private_ips".to_string()).await;
+    let origin =
+        HttpOrigin::<TestBinding>::new(Default::default(), state.blockstore().clone()).unwrap();
+
+    let test_fut = async move {
+        // When: we fetch the content.
+        let err = origin.fetch(url.as_bytes()).await.unwrap_err();
+        // Then: the request is rejected.
+        assert_eq!(err.to_string(), "address not allowed: 127.0.0.1");
+
+        state.node.shutdown().await;
+    };
+
+    tokio::select! {
+        biased;
+        _ = server::spawn_server(30402) => {}
+        _ = test_fut => {}
+    }
+}
+
+#[tokio::test]
+async fn test_http_origin_max_size() {
+    // Given: an identifier for some resource.
+    let url = "http://127.0.0.1:30403/bar/index.ts".to_string();
+    // Given: an origin that only accepts content smaller than the resource.
+    let mut state = create_app_state("test_http_origin_max_size".to_string()).await;
+    let config = Config {
+        max_size: 16,
+        ..test_config()
+    };
+    let origin = HttpOrigin::<TestBinding>::new(config, state.blockstore().clone()).unwrap();
+
+    let test_fut = async move {
+        // When: we fetch the content.
+        let err = origin.fetch(url.as_bytes()).await.unwrap_err();
+        // Then: the request fails.
+        assert_eq!(
+            err.to_string(),
+            "content exceeds the maximum size of 16 bytes"
+        );
+
+        state.node.shutdown().await;
+    };
+
+    tokio::select! {
+        biased;
+        _ = server::spawn_server(30403) => {}
+        _ = test_fut => {}
+    }
+}
+
+#[tokio::test]
+async fn test_http_origin_disallowed_scheme_and_host() {
+    let mut state =
+        create_app_state("test_http_origin_disallowed_scheme_and_host".to_string()).await;
+    let config = Config {
+        allowed_hosts: vec!["lightning.com".to_string()],
+        ..test_config()
+    };
+    let origin = HttpOrigin::<TestBinding>::new(config, state.blockstore().clone()).unwrap();
+
+    let err = origin.fetch(b"ftp://lightning.com/foo").await.unwrap_err();
+    assert_eq!(err.to_string(), "scheme not allowed: ftp");
+
+    let err = origin.fetch(b"https://example.com/foo").await.unwrap_err();
+    assert_eq!(err.to_string(), "host not allowed: example.com");
+
+    state.node.shutdown().await;
+}
+
+#[test]
+fn test_is_global() {
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(
+            !is_global(&ip.parse().unwrap()),
+            "{ip} should not be global"
+        );
+    }
+    for ip in ["1.1.1.1", "8.8.8.8", "2606:4700:4700::1111"] {
+        assert!(is_global(&ip.parse().unwrap()), "{ip} should be global");
+    }
+}
+
 #[test]
 fn test_url_and_integrity_hash() {
     let (_, integrity) =
@@ -2,19 +2,30 @@
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub gateways: Vec<Gateway>,
     pub gateway_timeout: Duration,
+    /// Files larger than this are fetched in ranges of roughly this many bytes, using the
+    /// `entity-bytes` parameter of the trustless gateway spec. Zero disables ranged requests.
+    pub range_size: u64,
+    /// Maximum number of ranges of a single file that are fetched in parallel. The ranges are
+    /// spread across the available gateways.
+    pub max_parallel_ranges: usize,
+    /// Number of consecutive failures after which a gateway is temporarily banned.
+    pub ban_threshold: u32,
+    /// How long a failing gateway is banned for.
+    pub ban_duration: Duration,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum Protocol {
     Http,
     Https,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Gateway {
     pub protocol: Protocol,
     pub authority: String,
@@ -38,6 +49,10 @@ fn default() -> Self {
                 },
             ],
             gateway_timeout: Duration::from_millis(5000),
+            range_size: 4 * 1024 * 1024,
+            max_parallel_ranges: 4,
+            ban_threshold: 3,
+            ban_duration: Duration::from_secs(60),
         }
     }
 }
@@ -0,0 +1,183 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lightning_metrics::{histogram, increment_counter};
+
+/// Weight of a new latency sample in the moving average.
+const LATENCY_ALPHA: f64 = 0.2;
+
+/// Keeps track of how well each gateway has been performing, so that requests go to the
+/// fastest healthy gateways first and gateways that keep failing are left alone for a while.
+pub struct GatewayHealth {
+    authorities: Vec<String>,
+    state: Mutex<Vec<Health>>,
+    ban_threshold: u32,
+    ban_duration: Duration,
+}
+
+#[derive(Default)]
+struct Health {
+    /// Moving average of the time it took the gateway to respond.
+    latency: Option<Duration>,
+    /// Number of failures since the last successful request.
+    failures: u32,
+    banned_until: Option<Instant>,
+}
+
+impl GatewayHealth {
+    pub fn new(authorities: Vec<String>, ban_threshold: u32, ban_duration: Duration) -> Self {
+        let state = authorities.iter().map(|_| Health::default()).collect();
+        Self {
+            authorities,
+            state: Mutex::new(state),
+            ban_threshold,
+            ban_duration,
+        }
+    }
+
+    /// Returns the indices of the gateways in the order they should be tried in.
+    pub fn ranked(&self) -> Vec<usize> {
+        self.ranked_at(Instant::now())
+    }
+
+    fn ranked_at(&self, now: Instant) -> Vec<usize> {
+        let state = self.state.lock().unwrap();
+        let is_banned = |health: &Health| health.banned_until.is_some_and(|until| until > now);
+
+        let mut available = (0..state.len())
+            .filter(|i| !is_banned(&state[*i]))
+            .collect::<Vec<_>>();
+        if available.is_empty() {
+            // Every gateway is banned, so rather than failing right away we try the ones whose
+            // ban is about to expire first.
+            let mut banned = (0..state.len()).collect::<Vec<_>>();
+            banned.sort_by_key(|i| state[*i].banned_until);
+            return banned;
+        }
+
+        // Gateways we don't know anything about yet keep their configured order and are tried
+        // first, so they get a chance to be measured. The sort is stable.
+        available.sort_by_key(|i| state[*i].score());
+        available
+    }
+
+    pub fn record_success(&self, index: usize, latency: Duration) {
+        {
+            let mut state = self.state.lock().unwrap();
+            let health = &mut state[index];
+            health.latency = Some(match health.latency {
+                Some(avg) => avg.mul_f64(1.0 - LATENCY_ALPHA) + latency.mul_f64(LATENCY_ALPHA),
+                None => latency,
+            });
+            health.failures = 0;
+            health.banned_until = None;
+        }
+
+        increment_counter!(
+            "ipfs_gateway_request_succeeded",
+            Some("Counter for successful requests to IPFS gateways"),
+            "gateway" => self.authorities[index].as_str()
+        );
+        histogram!(
+            "ipfs_gateway_latency",
+            Some("Time it took IPFS gateways to respond, in seconds"),
+            latency.as_secs_f64()
+        );
+    }
+
+    pub fn record_failure(&self, index: usize) {
+        let banned = {
+            let mut state = self.state.lock().unwrap();
+            let health = &mut state[index];
+            health.failures += 1;
+            // A gateway that fails again right after its ban expired is banned again right away.
+            if health.failures >= self.ban_threshold {
+                health.banned_until = Some(Instant::now() + self.ban_duration);
+                true
+            } else {
+                false
+            }
+        };
+
+        increment_counter!(
+            "ipfs_gateway_request_failed",
+            Some("Counter for failed requests to IPFS gateways"),
+            "gateway" => self.authorities[index].as_str()
+        );
+        if banned {
+            increment_counter!(
+                "ipfs_gateway_banned",
+                Some("Counter for IPFS gateways being banned after repeated failures"),
+                "gateway" => self.authorities[index].as_str()
+            );
+        }
+    }
+}
+
+impl Health {
+    /// Lower is better. Failures that did not lead to a ban yet still push the gateway back.
+    fn score(&self) -> Duration {
+        self.latency.unwrap_or_default() * (1 + self.failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(ban_threshold: u32, ban_duration: Duration) -> GatewayHealth {
+        let authorities = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        GatewayHealth::new(authorities, ban_threshold, ban_duration)
+    }
+
+    #[test]
+    fn test_unmeasured_keep_config_order() {
+        let health = health(3, Duration::from_secs(60));
+        assert_eq!(health.ranked(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_orders_by_latency() {
+        let health = health(3, Duration::from_secs(60));
+        health.record_success(0, Duration::from_millis(300));
+        health.record_success(1, Duration::from_millis(100));
+        health.record_success(2, Duration::from_millis(200));
+        assert_eq!(health.ranked(), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_failures_push_back() {
+        let health = health(3, Duration::from_secs(60));
+        health.record_success(0, Duration::from_millis(100));
+        health.record_success(1, Duration::from_millis(150));
+        health.record_success(2, Duration::from_millis(250));
+        health.record_failure(0);
+        assert_eq!(health.ranked(), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_bans_after_consecutive_failures() {
+        let health = health(2, Duration::from_secs(60));
+        health.record_failure(1);
+        assert_eq!(health.ranked(), vec![0, 1, 2]);
+        health.record_failure(1);
+        assert_eq!(health.ranked(), vec![0, 2]);
+
+        // The ban is lifted once it expires.
+        let later = Instant::now() + Duration::from_secs(61);
+        assert_eq!(health.ranked_at(later), vec![0, 1, 2]);
+
+        // A success clears the ban right away.
+        health.record_success(1, Duration::ZERO);
+        assert_eq!(health.ranked(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_all_banned() {
+        let health = health(1, Duration::from_secs(60));
+        health.record_failure(2);
+        health.record_failure(0);
+        health.record_failure(1);
+        assert_eq!(health.ranked(), vec![2, 0, 1]);
+    }
+}
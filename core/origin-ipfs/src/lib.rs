@@ -2,7 +2,9 @@
 pub mod config;
 mod decoder;
 mod error;
+mod health;
 mod origin_ipfs;
+mod range;
 #[cfg(test)]
 mod tests;
 
@@ -1,12 +1,12 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
 use cid::multihash::{Code, MultihashDigest};
 use cid::Cid;
-use fleek_ipld::unixfs::Data;
-use futures::TryStreamExt;
+use fleek_ipld::unixfs::{Data, DataType};
+use futures::{StreamExt, TryStreamExt};
 use hyper::client::{self, HttpConnector};
 use hyper::{Body, Client, Request, Response, Uri};
 use hyper_rustls::{ConfigBuilderExt, HttpsConnector};
@@ -20,12 +20,17 @@
 use crate::car_reader::{hyper_error, CarReader};
 use crate::config::Gateway;
 use crate::error::Error;
+use crate::health::GatewayHealth;
+use crate::range::{plan_ranges, LinkRange};
 use crate::{decoder, Config};
 
 pub struct IPFSOrigin<C: Collection> {
     client: Arc<Client<HttpsConnector<HttpConnector>, Body>>,
     gateways: Arc<Vec<Gateway>>,
     gateway_timeout: Duration,
+    range_size: u64,
+    max_parallel_ranges: usize,
+    health: Arc<GatewayHealth>,
     blockstore: C::BlockstoreInterface,
 }
 
@@ -36,6 +41,9 @@ fn clone(&self) -> Self {
             gateways: self.gateways.clone(),
             blockstore: self.blockstore.clone(),
             gateway_timeout: self.gateway_timeout,
+            range_size: self.range_size,
+            max_parallel_ranges: self.max_parallel_ranges,
+            health: self.health.clone(),
         }
     }
 }
@@ -58,11 +66,24 @@ pub fn new(config: Config, blockstore: C::BlockstoreInterface) -> Result<Self> {
         // Build the hyper client from the HTTPS connector.
         let client: Client<_, hyper::Body> = client::Client::builder().build(https);
 
+        let health = GatewayHealth::new(
+            config
+                .gateways
+                .iter()
+                .map(|gateway| gateway.authority.clone())
+                .collect(),
+            config.ban_threshold,
+            config.ban_duration,
+        );
+
         Ok(IPFSOrigin {
             client: Arc::new(client),
             gateways: Arc::new(config.gateways),
             blockstore,
             gateway_timeout: config.gateway_timeout,
+            range_size: config.range_size,
+            max_parallel_ranges: config.max_parallel_ranges,
+            health: Arc::new(health),
         })
     }
 
@@ -153,22 +174,35 @@ pub async fn stream_car_into_blockstore(
             .map_err(|e| Error::Blockstore(format!("{e}")))
     }
 
+    /// Fetches the content of the uri, which is either the bytes of a CID, or a `<cid>/<path>`
+    /// string for the content at the path inside the directory of the CID.
     pub async fn fetch(&self, uri: &[u8]) -> Result<Blake3Hash> {
-        let requested_cid = Cid::try_from(uri).with_context(|| "Failed to parse uri into cid")?;
-        for gateway in self.gateways.iter() {
-            let url = Uri::builder()
-                .scheme(gateway.protocol.as_str())
-                .authority(gateway.authority.as_str())
-                .path_and_query(format!("/ipfs/{requested_cid}"))
-                .build()?;
-
-            let req = Request::builder()
-                .uri(url)
-                .header("Accept", "application/vnd.ipld.car;version=1")
-                .header("Connection", "keep-alive")
-                .body(Body::default())?;
-
-            match self.fetch_from_gateway(req, gateway).await {
+        let order = self.health.ranked();
+        let requested_cid = match parse_uri(uri)? {
+            (cid, None) => cid,
+            (cid, Some(path)) => self.resolve_path(cid, path, &order).await?,
+        };
+
+        if self.range_size > 0 {
+            match self.fetch_ranged(&requested_cid, &order).await {
+                Ok(Some(hash)) => return Ok(hash),
+                Ok(None) => {},
+                Err(Error::Blockstore(info)) => {
+                    error!("{info:?}. Stopping request.");
+                    return Err(anyhow!("Request for CID {requested_cid} failed: {info:?}"));
+                },
+                Err(e) => {
+                    info!(
+                        "Ranged request for CID {requested_cid} failed: {e}. Fetching the entire CAR instead."
+                    );
+                },
+            }
+        }
+
+        for index in order {
+            let req = car_request(&self.gateways[index], &format!("/ipfs/{requested_cid}"))?;
+
+            match self.fetch_from_gateway(req, index).await {
                 Ok(hash) => return Ok(hash),
                 Err(e) => match e {
                     Error::Blockstore(info) => {
@@ -190,46 +224,284 @@ pub async fn fetch(&self, uri: &[u8]) -> Result<Blake3Hash> {
         Err(anyhow!("Failed to fetch data from gateways."))
     }
 
-    async fn fetch_from_gateway(
+    /// Fetches the content in parallel ranges spread across the gateways. Returns `None` if the
+    /// content is too small to be worth it, or if its DAG has a layout we can't fetch in ranges,
+    /// in which case the entire CAR file should be requested instead.
+    async fn fetch_ranged(&self, cid: &Cid, order: &[usize]) -> Result<Option<Blake3Hash>, Error> {
+        let comp = CompressionAlgorithm::Uncompressed;
+        let root = self.fetch_root(cid, order).await?;
+
+        match cid.codec() {
+            0x55 => {
+                // The root block of raw content is the entire content.
+                let mut blockstore_putter = self.blockstore.put(None);
+                if let Err(e) = blockstore_putter.write(&root, comp) {
+                    return Err(Error::Blockstore(format!("{e}")));
+                }
+                return blockstore_putter
+                    .finalize()
+                    .await
+                    .map(Some)
+                    .map_err(|e| Error::Blockstore(format!("{e}")));
+            },
+            0x70 => {},
+            _ => return Ok(None),
+        }
+
+        let node = PbNode::from_bytes(root.into()).map_err(|e| Error::CarReader(format!("{e}")))?;
+        let Some(unixfs) = node
+            .data
+            .as_ref()
+            .and_then(|data| Data::try_from(data.as_ref()).ok())
+        else {
+            return Ok(None);
+        };
+
+        // We only fetch flat DAGs with raw leaves in ranges. For anything else, the blocks
+        // returned for a range can't be mapped back to the links of the root.
+        if unixfs.blocksizes.len() != node.links.len()
+            || node.links.iter().any(|link| link.cid.codec() != 0x55)
+        {
+            return Ok(None);
+        }
+        let size = unixfs.Data.len() as u64 + unixfs.blocksizes.iter().sum::<u64>();
+        if size <= self.range_size {
+            return Ok(None);
+        }
+
+        let links = node.links.iter().map(|link| link.cid).collect::<Vec<_>>();
+        let ranges = plan_ranges(
+            unixfs.Data.len() as u64,
+            &unixfs.blocksizes,
+            self.range_size,
+        );
+
+        let mut blockstore_putter = self.blockstore.put(None);
+        if !unixfs.Data.is_empty() {
+            if let Err(e) = blockstore_putter.write(&unixfs.Data, comp) {
+                return Err(Error::Blockstore(format!("{e}")));
+            }
+        }
+
+        // Every range starts at a different gateway, so that the ranges are spread across them.
+        // The blocks are written in order, while up to `max_parallel_ranges` ranges are fetched
+        // ahead.
+        let mut fetches = futures::stream::iter(ranges.iter().enumerate())
+            .map(|(i, range)| {
+                let mut order = order.to_vec();
+                order.rotate_left(i % order.len());
+                self.fetch_range(cid, range, &links[range.links.clone()], order)
+            })
+            .buffered(self.max_parallel_ranges.max(1));
+        while let Some(blocks) = fetches.try_next().await? {
+            for block in blocks {
+                if let Err(e) = blockstore_putter.write(&block, comp) {
+                    return Err(Error::Blockstore(format!("{e}")));
+                }
+            }
+        }
+
+        blockstore_putter
+            .finalize()
+            .await
+            .map(Some)
+            .map_err(|e| Error::Blockstore(format!("{e}")))
+    }
+
+    /// Returns the CID of the content at the path inside the directory, fetching one verified
+    /// directory block at a time. Sharded directories are not supported.
+    async fn resolve_path(&self, root: Cid, path: &str, order: &[usize]) -> Result<Cid> {
+        let mut cid = root;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let directory = cid;
+            if directory.codec() != 0x70 {
+                return Err(anyhow!("{directory} is not a directory"));
+            }
+            let block = self
+                .fetch_root(&directory, order)
+                .await
+                .map_err(|e| anyhow!("Failed to fetch directory {directory}: {e}"))?;
+            let node = PbNode::from_bytes(block.into())?;
+            let unixfs = node
+                .data
+                .as_ref()
+                .and_then(|data| Data::try_from(data.as_ref()).ok());
+            if !matches!(unixfs, Some(unixfs) if unixfs.Type == DataType::Directory) {
+                return Err(anyhow!("{directory} is not a directory"));
+            }
+            cid = node
+                .links
+                .iter()
+                .find(|link| link.name.as_deref() == Some(name))
+                .map(|link| link.cid)
+                .with_context(|| format!("{name} not found in directory {directory}"))?;
+        }
+        Ok(cid)
+    }
+
+    /// Fetches only the root block of the DAG.
+    async fn fetch_root(&self, cid: &Cid, order: &[usize]) -> Result<Vec<u8>, Error> {
+        let path = format!("/ipfs/{cid}?format=car&dag-scope=block");
+        let mut last_error = Error::Request("No gateways configured".into());
+        for &index in order {
+            match self.fetch_blocks(index, &path, &[*cid]).await {
+                Ok(mut blocks) => return Ok(blocks.remove(0)),
+                Err(e) => {
+                    info!("Failed to fetch root block of {cid}: {e}. Moving to next gateway.");
+                    last_error = e;
+                },
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Fetches the leaves covering the given range, trying the gateways in the given order.
+    async fn fetch_range(
         &self,
-        request: Request<Body>,
-        gateway: &Gateway,
-    ) -> Result<Blake3Hash, Error> {
-        match timeout(self.gateway_timeout, self.client.request(request)).await {
-            Ok(Ok(res)) => {
-                match res.status().as_u16() {
-                    200..=299 => {
-                        // The gateway responded succesfully
-                        self.stream_car_into_blockstore(res.into_body()).await
-                    },
-                    300..=399 => {
-                        info!(
-                            "Gateway {} returned redirect error code, trying one redirect",
-                            gateway.authority
-                        );
-                        // This is the redirect code we should try to redirect one time to the
-                        // proper location
-                        self.handle_redirect(res).await
-                    },
-                    _ => {
-                        // This is either informational(100-199), error(300-399, server
-                        // error(400-499) so lets try another gateway
-                        // todo(dalton): We should look into what could cause informational
-                        // 100-199 and see if there is anything we can do here
-                        info!(
-                            "Gateway {} response was not successful, moving on to the next gateway",
-                            gateway.authority
-                        );
-
-                        Err(Error::Redirect("Response was not successfull".into()))
-                    },
+        cid: &Cid,
+        range: &LinkRange,
+        links: &[Cid],
+        order: Vec<usize>,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let path = format!(
+            "/ipfs/{cid}?format=car&dag-scope=entity&entity-bytes={}",
+            range.entity_bytes()
+        );
+        let mut last_error = Error::Request("No gateways configured".into());
+        for index in order {
+            match self.fetch_blocks(index, &path, links).await {
+                Ok(blocks) => return Ok(blocks),
+                Err(e) => {
+                    info!(
+                        "Failed to fetch range {} of {cid}: {e}. Moving to next gateway.",
+                        range.entity_bytes()
+                    );
+                    last_error = e;
+                },
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Requests a CAR file from a gateway and returns the verified blocks for the wanted CIDs,
+    /// in the order they were asked for. Any other block in the CAR file is ignored.
+    async fn fetch_blocks(
+        &self,
+        index: usize,
+        path_and_query: &str,
+        wanted: &[Cid],
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let result = async {
+            let request = car_request(&self.gateways[index], path_and_query)?;
+            let (response, latency) = self.send(request).await?;
+            if !response.status().is_success() {
+                return Err(Error::Request(format!(
+                    "Gateway responded with {}",
+                    response.status()
+                )));
+            }
+
+            let reader = StreamReader::new(response.into_body().map_err(hyper_error));
+            let mut car_reader = CarReader::new(reader).await?;
+            let wanted_set = wanted.iter().collect::<HashSet<_>>();
+            let mut blocks = HashMap::new();
+            while blocks.len() < wanted_set.len() {
+                let Some((cid, data)) = car_reader.next_block().await? else {
+                    break;
+                };
+                if wanted_set.contains(&cid) && !blocks.contains_key(&cid) {
+                    verify_data(&cid, &data)?;
+                    blocks.insert(cid, data);
                 }
+            }
+
+            let blocks = wanted
+                .iter()
+                .map(|cid| {
+                    blocks
+                        .get(cid)
+                        .cloned()
+                        .ok_or_else(|| Error::CarReader(format!("Block {cid} is missing")))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((blocks, latency))
+        }
+        .await;
+
+        match result {
+            Ok((blocks, latency)) => {
+                self.health.record_success(index, latency);
+                Ok(blocks)
+            },
+            Err(e) => {
+                self.health.record_failure(index);
+                Err(e)
             },
+        }
+    }
+
+    /// Sends the request and waits for the response headers. Returns the response along with
+    /// the time it took the gateway to respond.
+    async fn send(&self, request: Request<Body>) -> Result<(Response<Body>, Duration), Error> {
+        let start = Instant::now();
+        match timeout(self.gateway_timeout, self.client.request(request)).await {
+            Ok(Ok(res)) => Ok((res, start.elapsed())),
             Ok(Err(e)) => Err(Error::Request(format!("Request failed: {e}"))),
             Err(_) => Err(Error::Request("Request timed out".into())),
         }
     }
 
+    async fn fetch_from_gateway(
+        &self,
+        request: Request<Body>,
+        index: usize,
+    ) -> Result<Blake3Hash, Error> {
+        let gateway = &self.gateways[index];
+        let (res, latency) = match self.send(request).await {
+            Ok(res) => res,
+            Err(e) => {
+                self.health.record_failure(index);
+                return Err(e);
+            },
+        };
+
+        let result = match res.status().as_u16() {
+            200..=299 => {
+                // The gateway responded succesfully
+                self.stream_car_into_blockstore(res.into_body()).await
+            },
+            300..=399 => {
+                info!(
+                    "Gateway {} returned redirect error code, trying one redirect",
+                    gateway.authority
+                );
+                // This is the redirect code we should try to redirect one time to the
+                // proper location
+                self.handle_redirect(res).await
+            },
+            _ => {
+                // This is either informational(100-199), error(300-399, server
+                // error(400-499) so lets try another gateway
+                // todo(dalton): We should look into what could cause informational
+                // 100-199 and see if there is anything we can do here
+                info!(
+                    "Gateway {} response was not successful, moving on to the next gateway",
+                    gateway.authority
+                );
+
+                Err(Error::Redirect("Response was not successfull".into()))
+            },
+        };
+
+        match &result {
+            Ok(_) => self.health.record_success(index, latency),
+            // Failing to write to our own blockstore is not the gateway's fault.
+            Err(Error::Blockstore(_)) => {},
+            Err(_) => self.health.record_failure(index),
+        }
+        result
+    }
+
     async fn handle_redirect(&self, response: Response<Body>) -> Result<Blake3Hash, Error> {
         let headers = response.headers();
         let location_header = headers
@@ -262,6 +534,33 @@ async fn handle_redirect(&self, response: Response<Body>) -> Result<Blake3Hash,
     }
 }
 
+/// Parses a uri into the CID and the path inside it, if any.
+pub(crate) fn parse_uri(uri: &[u8]) -> Result<(Cid, Option<&str>)> {
+    if let Ok(cid) = Cid::try_from(uri) {
+        return Ok((cid, None));
+    }
+    let uri = std::str::from_utf8(uri).context("Failed to parse uri into cid")?;
+    let (cid, path) = uri.split_once('/').unwrap_or((uri, ""));
+    let cid = Cid::try_from(cid).context("Failed to parse uri into cid")?;
+    Ok((cid, Some(path).filter(|path| !path.is_empty())))
+}
+
+fn car_request(gateway: &Gateway, path_and_query: &str) -> Result<Request<Body>, Error> {
+    let url = Uri::builder()
+        .scheme(gateway.protocol.as_str())
+        .authority(gateway.authority.as_str())
+        .path_and_query(path_and_query)
+        .build()
+        .map_err(|e| Error::Request(format!("Failed to build uri: {e}")))?;
+
+    Request::builder()
+        .uri(url)
+        .header("Accept", "application/vnd.ipld.car;version=1")
+        .header("Connection", "keep-alive")
+        .body(Body::default())
+        .map_err(|e| Error::Request(format!("Failed to build request: {e}")))
+}
+
 fn verify_data(cid: &Cid, data: &[u8]) -> Result<(), Error> {
     let valid = match Code::try_from(cid.hash().code()) {
         Ok(hasher) => &hasher.digest(data) == cid.hash(),
@@ -0,0 +1,103 @@
+use std::ops::Range;
+
+/// A contiguous run of links of the root node, together with the bytes of the file they cover.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LinkRange {
+    /// The indices of the links in the root node.
+    pub links: Range<usize>,
+    /// The first byte covered by the links.
+    pub first: u64,
+    /// The last byte covered by the links (inclusive, as used by `entity-bytes`).
+    pub last: u64,
+}
+
+impl LinkRange {
+    /// The value of the `entity-bytes` query parameter for this range.
+    pub fn entity_bytes(&self) -> String {
+        format!("{}:{}", self.first, self.last)
+    }
+}
+
+/// Splits the links of a root node into ranges of at least `range_size` bytes each, except for
+/// the last one. `offset` is the number of bytes stored in the root node itself, which come
+/// before the data of the links.
+pub fn plan_ranges(offset: u64, blocksizes: &[u64], range_size: u64) -> Vec<LinkRange> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut first = offset;
+    let mut size = 0;
+    for (i, block_size) in blocksizes.iter().enumerate() {
+        size += block_size;
+        if size >= range_size || i == blocksizes.len() - 1 {
+            if size > 0 {
+                ranges.push(LinkRange {
+                    links: start..i + 1,
+                    first,
+                    last: first + size - 1,
+                });
+            }
+            start = i + 1;
+            first += size;
+            size = 0;
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_ranges() {
+        let ranges = plan_ranges(0, &[10, 10, 10, 10, 5], 20);
+        assert_eq!(
+            ranges,
+            vec![
+                LinkRange {
+                    links: 0..2,
+                    first: 0,
+                    last: 19
+                },
+                LinkRange {
+                    links: 2..4,
+                    first: 20,
+                    last: 39
+                },
+                LinkRange {
+                    links: 4..5,
+                    first: 40,
+                    last: 44
+                },
+            ]
+        );
+        assert_eq!(ranges[1].entity_bytes(), "20:39");
+    }
+
+    #[test]
+    fn test_plan_ranges_with_root_data() {
+        let ranges = plan_ranges(7, &[10, 10], 15);
+        assert_eq!(
+            ranges,
+            vec![LinkRange {
+                links: 0..2,
+                first: 7,
+                last: 26
+            }]
+        );
+    }
+
+    #[test]
+    fn test_plan_ranges_large_blocks() {
+        let ranges = plan_ranges(0, &[30, 30], 20);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].links, 0..1);
+        assert_eq!(ranges[1].links, 1..2);
+        assert_eq!(ranges[1].first, 30);
+    }
+
+    #[test]
+    fn test_plan_ranges_empty() {
+        assert!(plan_ranges(0, &[], 20).is_empty());
+    }
+}
@@ -19,6 +19,7 @@
 use lightning_test_utils::server::spawn_server;
 
 use crate::config::{Config, Gateway, Protocol};
+use crate::origin_ipfs::parse_uri;
 use crate::IPFSOrigin;
 
 partial!(TestBinding {
@@ -314,3 +315,125 @@ async fn test_origin_bbb_dag_pb_and_raw() {
 
     }
 }
+
+#[tokio::test]
+async fn test_origin_ranged_with_failing_gateway() {
+    let req_cid =
+        Cid::try_from("bafybeieb3754ppknuruchkb5pxdizi5rzz42kldrps4qvjmouomyt3xkte").unwrap();
+    let target_bytes = std::fs::read(
+        "../test-utils/files/bafybeieb3754ppknuruchkb5pxdizi5rzz42kldrps4qvjmouomyt3xkte.js",
+    )
+    .unwrap();
+
+    let mut state = create_app_state("test-origin-ranged-with-failing-gateway".to_string()).await;
+
+    let req_fut = async move {
+        // Nothing is listening on the first gateway, so every range has to fall back to the
+        // second one.
+        let config = Config {
+            gateways: vec![
+                Gateway {
+                    protocol: Protocol::Http,
+                    authority: "127.0.0.1:30204".to_string(),
+                },
+                Gateway {
+                    protocol: Protocol::Http,
+                    authority: "127.0.0.1:30203".to_string(),
+                },
+            ],
+            range_size: 512 * 1024,
+            ban_threshold: 1,
+            ..Default::default()
+        };
+        let ipfs_origin =
+            IPFSOrigin::<TestBinding>::new(config, state.blockstore().clone()).unwrap();
+
+        let hash = ipfs_origin
+            .fetch(req_cid.to_bytes().as_slice())
+            .await
+            .unwrap();
+
+        let bytes = state.blockstore().read_all_to_vec(&hash).await.unwrap();
+        assert_eq!(bytes, target_bytes);
+
+        state.node.shutdown().await;
+    };
+
+    tokio::select! {
+        biased;
+        Err(e) = spawn_server(30203) => {
+            panic!("{e}");
+        }
+        _ = req_fut => {}
+    }
+}
+
+#[tokio::test]
+async fn test_origin_directory_path() {
+    let dir_cid = "bafybeidsn76pyalucpd7zbn6fpzlmdnq2fjcw5wddcimdg3hamwi3ldfym";
+    let target_bytes = std::fs::read(
+        "../test-utils/files/bafkreihiruy5ng7d5v26c6g4gwhtastyencrefjkruqe33vwrnbyhvr74u.txt",
+    )
+    .unwrap();
+
+    let mut state = create_app_state("test-origin-directory-path".to_string()).await;
+
+    let req_fut = async move {
+        let config = Config {
+            gateways: vec![Gateway {
+                protocol: Protocol::Http,
+                authority: "127.0.0.1:30205".to_string(),
+            }],
+            ..Default::default()
+        };
+        let ipfs_origin =
+            IPFSOrigin::<TestBinding>::new(config, state.blockstore().clone()).unwrap();
+
+        // The directory links to the raw content as `hello.txt`.
+        let hash = ipfs_origin
+            .fetch(format!("{dir_cid}/hello.txt").as_bytes())
+            .await
+            .unwrap();
+        let bytes = state.blockstore().read_all_to_vec(&hash).await.unwrap();
+        assert_eq!(bytes, target_bytes);
+
+        assert!(
+            ipfs_origin
+                .fetch(format!("{dir_cid}/missing.txt").as_bytes())
+                .await
+                .is_err()
+        );
+        assert!(
+            ipfs_origin
+                .fetch(format!("{dir_cid}/hello.txt/nested").as_bytes())
+                .await
+                .is_err()
+        );
+
+        state.node.shutdown().await;
+    };
+
+    tokio::select! {
+        biased;
+        Err(e) = spawn_server(30205) => {
+            panic!("{e}");
+        }
+        _ = req_fut => {}
+    }
+}
+
+#[test]
+fn test_parse_uri() {
+    let cid = Cid::try_from("bafkreihiruy5ng7d5v26c6g4gwhtastyencrefjkruqe33vwrnbyhvr74u").unwrap();
+    assert_eq!(parse_uri(&cid.to_bytes()).unwrap(), (cid, None));
+    assert_eq!(parse_uri(cid.to_string().as_bytes()).unwrap(), (cid, None));
+    assert_eq!(
+        parse_uri(format!("{cid}/a/b.js").as_bytes()).unwrap(),
+        (cid, Some("a/b.js"))
+    );
+    assert_eq!(
+        parse_uri(format!("{cid}/").as_bytes()).unwrap(),
+        (cid, None)
+    );
+    assert!(parse_uri(b"not-a-cid/b.js").is_err());
+}
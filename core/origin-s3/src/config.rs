@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// The endpoint used for every bucket that has no entry in `buckets`.
+    pub default: BucketConfig,
+    /// Per-bucket endpoint and credentials, keyed by bucket name.
+    pub buckets: HashMap<String, BucketConfig>,
+    /// Timeout for establishing a connection with the endpoint.
+    pub connect_timeout: Duration,
+    /// Maximum amount of time we wait for the next chunk of the object before giving up.
+    pub read_timeout: Duration,
+    /// Timeout for the entire request, including streaming the object.
+    pub request_timeout: Duration,
+    /// Maximum size in bytes of an object we are willing to fetch.
+    pub max_size: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            default: BucketConfig::default(),
+            buckets: HashMap::new(),
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(300),
+            max_size: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BucketConfig {
+    /// The S3-compatible endpoint, e.g. `https://s3.us-east-1.amazonaws.com`,
+    /// `https://<account>.r2.cloudflarestorage.com` or the address of a MinIO server.
+    pub endpoint: String,
+    /// The region used for signing requests. R2 uses `auto`.
+    pub region: String,
+    /// Address the bucket as `<endpoint>/<bucket>` instead of `<bucket>.<endpoint>`. Required
+    /// by most MinIO deployments.
+    pub path_style: bool,
+    /// The credentials used to sign requests. Requests are sent unsigned if they are missing,
+    /// which only works for public buckets.
+    pub credentials: Option<Credentials>,
+}
+
+impl Default for BucketConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "https://s3.us-east-1.amazonaws.com".to_string(),
+            region: "us-east-1".to_string(),
+            path_style: false,
+            credentials: None,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Only needed for temporary credentials.
+    pub session_token: Option<String>,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
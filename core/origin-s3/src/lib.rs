@@ -0,0 +1,189 @@
+mod config;
+mod pointer;
+mod sigv4;
+#[cfg(test)]
+mod tests;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context};
+use chrono::Utc;
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::{Blake3Hash, CompressionAlgorithm};
+use reqwest::header::{AUTHORIZATION, ETAG, IF_MATCH};
+use reqwest::{Client, Response, Url};
+use tokio::time::timeout;
+
+pub use crate::config::{BucketConfig, Config, Credentials};
+pub use crate::pointer::S3Pointer;
+use crate::sigv4::{CanonicalRequest, AMZ_DATE_FORMAT, EMPTY_PAYLOAD_SHA256};
+
+pub struct S3Origin<C: Collection> {
+    client: Client,
+    config: Arc<Config>,
+    blockstore: C::BlockstoreInterface,
+}
+
+impl<C: Collection> Clone for S3Origin<C> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            config: self.config.clone(),
+            blockstore: self.blockstore.clone(),
+        }
+    }
+}
+
+impl<C: Collection> S3Origin<C> {
+    pub fn new(config: Config, blockstore: C::BlockstoreInterface) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .connect_timeout(config.connect_timeout)
+            // Following a redirect would invalidate the signature.
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        Ok(Self {
+            client,
+            config: Arc::new(config),
+            blockstore,
+        })
+    }
+
+    pub async fn fetch(&self, uri: &[u8]) -> anyhow::Result<Blake3Hash> {
+        let pointer = S3Pointer::parse(uri)?;
+        timeout(
+            self.config.request_timeout,
+            self.stream_into_blockstore(&pointer),
+        )
+        .await
+        .map_err(|_| anyhow!("request timed out"))?
+    }
+
+    async fn stream_into_blockstore(&self, pointer: &S3Pointer) -> anyhow::Result<Blake3Hash> {
+        let mut resp = self.get_object(pointer).await?;
+        if !resp.status().is_success() {
+            bail!("request failed with status {}", resp.status());
+        }
+        if let Some(etag) = &pointer.etag {
+            let actual = resp
+                .headers()
+                .get(ETAG)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim_matches('"'));
+            if actual != Some(etag.as_str()) {
+                bail!("object etag does not match: expected {etag}, got {actual:?}");
+            }
+        }
+        if resp.content_length().unwrap_or(0) > self.config.max_size {
+            bail!(
+                "object exceeds the maximum size of {} bytes",
+                self.config.max_size
+            );
+        }
+
+        let mut putter = self.blockstore.put(None);
+        let mut size = 0;
+        while let Some(chunk) = timeout(self.config.read_timeout, resp.chunk())
+            .await
+            .context("timed out waiting for data")??
+        {
+            size += chunk.len() as u64;
+            if size > self.config.max_size {
+                bail!(
+                    "object exceeds the maximum size of {} bytes",
+                    self.config.max_size
+                );
+            }
+            putter.write(chunk.as_ref(), CompressionAlgorithm::Uncompressed)?;
+        }
+
+        putter.finalize().await.map_err(Into::into)
+    }
+
+    async fn get_object(&self, pointer: &S3Pointer) -> anyhow::Result<Response> {
+        let bucket = self
+            .config
+            .buckets
+            .get(&pointer.bucket)
+            .unwrap_or(&self.config.default);
+        let endpoint = Url::parse(&bucket.endpoint)?;
+        let endpoint_host = endpoint.host_str().context("endpoint has no host")?;
+        let endpoint_host = match endpoint.port() {
+            Some(port) => format!("{endpoint_host}:{port}"),
+            None => endpoint_host.to_string(),
+        };
+
+        let key = sigv4::uri_encode(&pointer.key, false);
+        let (host, path) = if bucket.path_style {
+            (endpoint_host, format!("/{}/{key}", pointer.bucket))
+        } else {
+            (
+                format!("{}.{endpoint_host}", pointer.bucket),
+                format!("/{key}"),
+            )
+        };
+        let query = pointer
+            .version_id
+            .iter()
+            .map(|version_id| ("versionId".to_string(), version_id.clone()))
+            .collect::<Vec<_>>();
+        let encoded_query = query
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    sigv4::uri_encode(name, true),
+                    sigv4::uri_encode(value, true)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut url = format!("{}://{host}{path}", endpoint.scheme());
+        if !encoded_query.is_empty() {
+            url = format!("{url}?{encoded_query}");
+        }
+
+        let timestamp = Utc::now();
+        let mut headers = BTreeMap::from([
+            ("host".to_string(), host),
+            (
+                "x-amz-content-sha256".to_string(),
+                EMPTY_PAYLOAD_SHA256.to_string(),
+            ),
+            (
+                "x-amz-date".to_string(),
+                timestamp.format(AMZ_DATE_FORMAT).to_string(),
+            ),
+        ]);
+
+        let mut request = self.client.get(Url::parse(&url)?);
+        if let Some(credentials) = &bucket.credentials {
+            if let Some(token) = &credentials.session_token {
+                headers.insert("x-amz-security-token".to_string(), token.clone());
+            }
+            let authorization = sigv4::authorization(
+                credentials,
+                &bucket.region,
+                &timestamp,
+                &CanonicalRequest {
+                    method: "GET",
+                    path: &path,
+                    query: &query,
+                    headers: &headers,
+                    payload_hash: EMPTY_PAYLOAD_SHA256,
+                },
+            );
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        if let Some(etag) = &pointer.etag {
+            request = request.header(IF_MATCH, format!("\"{etag}\""));
+        }
+        // The host header is set by the client from the url.
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+
+        request.send().await.map_err(Into::into)
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{bail, Context};
+
+const S3_SCHEME: &str = "s3://";
+
+/// A pointer to an object in a bucket, encoded as `s3://<bucket>/<key>`.
+///
+/// Since objects in a bucket can be overwritten, the pointer can be pinned to a specific
+/// version of the object with a `versionId` query parameter, or to a specific content with an
+/// `etag` query parameter, e.g. `s3://bucket/path/to/key?versionId=3HL4kqtJl`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct S3Pointer {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+    pub etag: Option<String>,
+}
+
+impl S3Pointer {
+    pub fn parse(uri: &[u8]) -> anyhow::Result<Self> {
+        let uri = std::str::from_utf8(uri)?;
+        let rest = uri
+            .strip_prefix(S3_SCHEME)
+            .with_context(|| format!("invalid s3 uri: {uri}"))?;
+
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (bucket, key) = path
+            .split_once('/')
+            .with_context(|| format!("missing object key: {uri}"))?;
+        if bucket.is_empty() || key.is_empty() {
+            bail!("missing bucket or object key: {uri}");
+        }
+        if !is_valid_bucket_name(bucket) {
+            bail!("invalid bucket name: {uri}");
+        }
+
+        let mut version_id = None;
+        let mut etag = None;
+        for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match name.as_ref() {
+                "versionId" => version_id = Some(value.into_owned()),
+                "etag" => etag = Some(value.trim_matches('"').to_string()),
+                _ => bail!("unknown query parameter in s3 uri: {name}"),
+            }
+        }
+
+        Ok(Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            version_id,
+            etag,
+        })
+    }
+}
+
+/// Returns true if the name follows the S3 bucket naming rules. The bucket is part of the host
+/// of the request in the virtual-hosted style, so any other name could point it to another host.
+fn is_valid_bucket_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    (3..=63).contains(&bytes.len())
+        && bytes
+            .iter()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'.' | b'-'))
+        && bytes[0].is_ascii_alphanumeric()
+        && bytes[bytes.len() - 1].is_ascii_alphanumeric()
+        && !name.contains("..")
+        && name.parse::<std::net::Ipv4Addr>().is_err()
+}
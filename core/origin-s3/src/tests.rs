@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use axum::extract::{Path, Query};
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::routing::get;
+use axum::Router;
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use fleek_crypto::{AccountOwnerSecretKey, ConsensusSecretKey, NodeSecretKey, SecretKey};
+use lightning_application::app::Application;
+use lightning_application::config::{Config as AppConfig, Mode, StorageConfig};
+use lightning_application::genesis::{Genesis, GenesisNode};
+use lightning_blockstore::blockstore::Blockstore;
+use lightning_blockstore::config::Config as BlockstoreConfig;
+use lightning_indexer::Indexer;
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::NodePorts;
+use lightning_signer::Signer;
+use lightning_test_utils::consensus::{Config as ConsensusConfig, MockConsensus, MockForwarder};
+use lightning_test_utils::json_config::JsonConfigProvider;
+use lightning_test_utils::keys::EphemeralKeystore;
+
+use crate::sigv4::{self, CanonicalRequest, AMZ_DATE_FORMAT};
+use crate::{BucketConfig, Config, Credentials, S3Origin, S3Pointer};
+
+partial!(TestBinding {
+    ConfigProviderInterface = JsonConfigProvider;
+    ApplicationInterface = Application<Self>;
+    BlockstoreInterface = Blockstore<Self>;
+    KeystoreInterface = EphemeralKeystore<Self>;
+    SignerInterface = Signer<Self>;
+    ForwarderInterface = MockForwarder<Self>;
+    ConsensusInterface = MockConsensus<Self>;
+    IndexerInterface = Indexer<Self>;
+});
+
+struct AppState {
+    node: Node<TestBinding>,
+    temp_dir_path: PathBuf,
+}
+
+impl AppState {
+    fn blockstore(&self) -> fdi::Ref<Blockstore<TestBinding>> {
+        self.node.provider.get()
+    }
+}
+
+impl Drop for AppState {
+    fn drop(&mut self) {
+        if self.temp_dir_path.exists() {
+            std::fs::remove_dir_all(self.temp_dir_path.as_path()).unwrap();
+        }
+    }
+}
+
+// Todo: This is the same one used in blockstore, indexer and possbily others
+// so it might be useful to create a test factory.
+async fn create_app_state(test_name: String) -> AppState {
+    let keystore = EphemeralKeystore::<TestBinding>::default();
+    let (consensus_secret_key, node_secret_key) =
+        (keystore.get_bls_sk(), keystore.get_ed25519_sk());
+    let node_public_key = node_secret_key.to_pk();
+    let consensus_public_key = consensus_secret_key.to_pk();
+    let owner_secret_key = AccountOwnerSecretKey::generate();
+    let owner_public_key = owner_secret_key.to_pk();
+
+    let peer_owner_public_key = AccountOwnerSecretKey::generate();
+    let peer_secret_key = NodeSecretKey::generate();
+    let peer_public_key = peer_secret_key.to_pk();
+    let peer_consensus_secret_key = ConsensusSecretKey::generate();
+    let peer_consensus_public_key = peer_consensus_secret_key.to_pk();
+
+    let mut genesis = Genesis::load().unwrap();
+
+    genesis.node_info.push(GenesisNode::new(
+        owner_public_key.into(),
+        node_public_key,
+        "127.0.0.1".parse().unwrap(),
+        consensus_public_key,
+        "127.0.0.1".parse().unwrap(),
+        node_public_key,
+        NodePorts {
+            primary: 48000,
+            worker: 48101,
+            mempool: 48102,
+            rpc: 48103,
+            pool: 48104,
+            pinger: 48106,
+            handshake: Default::default(),
+        },
+        None,
+        true,
+    ));
+
+    genesis.node_info.push(GenesisNode::new(
+        peer_owner_public_key.to_pk().into(),
+        peer_public_key,
+        "127.0.0.1".parse().unwrap(),
+        peer_consensus_public_key,
+        "127.0.0.1".parse().unwrap(),
+        peer_public_key,
+        NodePorts {
+            primary: 38000,
+            worker: 38101,
+            mempool: 38102,
+            rpc: 38103,
+            pool: 38104,
+            pinger: 38106,
+            handshake: Default::default(),
+        },
+        None,
+        true,
+    ));
+
+    let epoch_start = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    genesis.epoch_start = epoch_start;
+    genesis.epoch_time = 4000; // millis
+
+    let path = std::env::temp_dir().join(test_name);
+
+    let node = Node::<TestBinding>::init_with_provider(
+        fdi::Provider::default()
+            .with(
+                JsonConfigProvider::default()
+                    .with::<Blockstore<TestBinding>>(BlockstoreConfig {
+                        root: path.clone().try_into().unwrap(),
+                    })
+                    .with::<Application<TestBinding>>(AppConfig {
+                        genesis: Some(genesis),
+                        mode: Mode::Test,
+                        testnet: false,
+                        storage: StorageConfig::InMemory,
+                        db_path: None,
+                        db_options: None,
+                    })
+                    .with::<MockConsensus<TestBinding>>(ConsensusConfig {
+                        min_ordering_time: 0,
+                        max_ordering_time: 1,
+                        probability_txn_lost: 0.0,
+                        transactions_to_lose: HashSet::new(),
+                        new_block_interval: Duration::from_secs(5),
+                    }),
+            )
+            .with(keystore),
+    )
+    .expect("failed to initialize node");
+
+    node.start().await;
+
+    AppState {
+        node,
+        temp_dir_path: path,
+    }
+}
+
+const ACCESS_KEY_ID: &str = "test-access-key";
+const SECRET_ACCESS_KEY: &str = "test-secret-key";
+const REGION: &str = "us-east-1";
+const BUCKET: &str = "bucket";
+const KEY: &str = "bar/index.ts";
+const VERSION_ID: &str = "v1";
+const ETAG: &str = "9b2cf535f27731c974343645a3985328";
+
+fn test_config(port: u16) -> Config {
+    Config {
+        default: BucketConfig {
+            endpoint: format!("http://127.0.0.1:{port}"),
+            region: REGION.to_string(),
+            path_style: true,
+            credentials: Some(Credentials {
+                access_key_id: ACCESS_KEY_ID.to_string(),
+                secret_access_key: SECRET_ACCESS_KEY.to_string(),
+                session_token: None,
+            }),
+        },
+        ..Default::default()
+    }
+}
+
+/// A mock of a path-style S3 endpoint serving a single versioned object, which rejects
+/// requests that are not correctly signed.
+async fn spawn_mock_s3(port: u16) -> anyhow::Result<()> {
+    let router = Router::new().route("/:bucket/*key", get(get_object));
+    axum::Server::bind(&format!("0.0.0.0:{port}").parse().unwrap())
+        .serve(router.into_make_service())
+        .await
+        .map_err(|e| e.into())
+}
+
+async fn get_object(
+    Path((bucket, key)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<(HeaderMap, Vec<u8>), StatusCode> {
+    if !is_signature_valid(&uri, &headers) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if bucket != BUCKET || key.trim_start_matches('/') != KEY {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if query.get("versionId").is_some_and(|v| v != VERSION_ID) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if let Some(etag) = headers.get("if-match") {
+        if etag.to_str().unwrap().trim_matches('"') != ETAG {
+            return Err(StatusCode::PRECONDITION_FAILED);
+        }
+    }
+
+    let file = std::fs::read("../test-utils/files/index.ts").unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("ETag", format!("\"{ETAG}\"").parse().unwrap());
+    Ok((headers, file))
+}
+
+fn is_signature_valid(uri: &Uri, headers: &HeaderMap) -> bool {
+    let Some(authorization) = headers.get("authorization").and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let Some(signed_headers) = authorization
+        .split(", ")
+        .find_map(|part| part.strip_prefix("SignedHeaders="))
+    else {
+        return false;
+    };
+    let signed_headers = signed_headers
+        .split(';')
+        .filter_map(|name| {
+            let value = headers.get(name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect::<BTreeMap<_, _>>();
+    let Some(timestamp) = signed_headers
+        .get("x-amz-date")
+        .and_then(|date| NaiveDateTime::parse_from_str(date, AMZ_DATE_FORMAT).ok())
+    else {
+        return false;
+    };
+    let query = url::form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+        .into_owned()
+        .collect::<Vec<_>>();
+
+    let expected = sigv4::authorization(
+        &Credentials {
+            access_key_id: ACCESS_KEY_ID.to_string(),
+            secret_access_key: SECRET_ACCESS_KEY.to_string(),
+            session_token: None,
+        },
+        REGION,
+        &Utc.from_utc_datetime(&timestamp),
+        &CanonicalRequest {
+            method: "GET",
+            path: uri.path(),
+            query: &query,
+            headers: &signed_headers,
+            payload_hash: headers
+                .get("x-amz-content-sha256")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default(),
+        },
+    );
+    authorization == expected
+}
+
+#[tokio::test]
+async fn test_s3_origin() {
+    // Given: Some content that will be returned by the bucket.
+    let file: Vec<u8> = std::fs::read("../test-utils/files/index.ts").unwrap();
+    // Given: an origin.
+    let mut state = create_app_state("test_s3_origin".to_string()).await;
+    let origin =
+        S3Origin::<TestBinding>::new(test_config(30500), state.blockstore().clone()).unwrap();
+
+    let test_fut = async move {
+        // When: we fetch the object, pinned or not to a version or etag.
+        for uri in [
+            format!("s3://{BUCKET}/{KEY}"),
+            format!("s3://{BUCKET}/{KEY}?versionId={VERSION_ID}"),
+            format!("s3://{BUCKET}/{KEY}?etag={ETAG}"),
+        ] {
+            let hash = origin.fetch(uri.as_bytes()).await.unwrap();
+            let bytes = state.blockstore().read_all_to_vec(&hash).await.unwrap();
+            // Then: we get the expected content.
+            assert_eq!(file, bytes);
+        }
+
+        state.node.shutdown().await;
+    };
+
+    tokio::select! {
+        biased;
+        _ = spawn_mock_s3(30500) => {}
+        _ = test_fut => {}
+    }
+}
+
+#[tokio::test]
+async fn test_s3_origin_pinned_object_changed() {
+    // Given: an origin.
+    let mut state = create_app_state("test_s3_origin_pinned_object_changed".to_string()).await;
+    let origin =
+        S3Origin::<TestBinding>::new(test_config(30501), state.blockstore().clone()).unwrap();
+
+    let test_fut = async move {
+        // When: we fetch a version or etag that does not exist anymore.
+        let uri = format!("s3://{BUCKET}/{KEY}?versionId=v2");
+        let err = origin.fetch(uri.as_bytes()).await.unwrap_err();
+        // Then: the fetch fails.
+        assert_eq!(err.to_string(), "request failed with status 404 Not Found");
+
+        let uri = format!("s3://{BUCKET}/{KEY}?etag=foo");
+        let err = origin.fetch(uri.as_bytes()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "request failed with status 412 Precondition Failed"
+        );
+
+        state.node.shutdown().await;
+    };
+
+    tokio::select! {
+        biased;
+        _ = spawn_mock_s3(30501) => {}
+        _ = test_fut => {}
+    }
+}
+
+#[tokio::test]
+async fn test_s3_origin_invalid_credentials() {
+    // Given: an origin with the wrong secret.
+    let mut state = create_app_state("test_s3_origin_invalid_credentials".to_string()).await;
+    let mut config = test_config(30502);
+    config
+        .default
+        .credentials
+        .as_mut()
+        .unwrap()
+        .secret_access_key = "wrong".to_string();
+    let origin = S3Origin::<TestBinding>::new(config, state.blockstore().clone()).unwrap();
+
+    let test_fut = async move {
+        // When: we fetch the object.
+        let err = origin
+            .fetch(format!("s3://{BUCKET}/{KEY}").as_bytes())
+            .await
+            .unwrap_err();
+        // Then: the request is rejected.
+        assert_eq!(err.to_string(), "request failed with status 403 Forbidden");
+
+        state.node.shutdown().await;
+    };
+
+    tokio::select! {
+        biased;
+        _ = spawn_mock_s3(30502) => {}
+        _ = test_fut => {}
+    }
+}
+
+#[test]
+fn test_parse_pointer() {
+    assert_eq!(
+        S3Pointer::parse(b"s3://bucket/path/to/key").unwrap(),
+        S3Pointer {
+            bucket: "bucket".to_string(),
+            key: "path/to/key".to_string(),
+            version_id: None,
+            etag: None,
+        }
+    );
+    assert_eq!(
+        S3Pointer::parse(b"s3://bucket/key?versionId=3HL4kqtJl&etag=%22abc%22").unwrap(),
+        S3Pointer {
+            bucket: "bucket".to_string(),
+            key: "key".to_string(),
+            version_id: Some("3HL4kqtJl".to_string()),
+            etag: Some("abc".to_string()),
+        }
+    );
+    assert!(S3Pointer::parse(b"https://bucket/key").is_err());
+    assert!(S3Pointer::parse(b"s3://bucket").is_err());
+    assert!(S3Pointer::parse(b"s3://bucket/").is_err());
+    assert!(S3Pointer::parse(b"s3://bucket/key?foo=bar").is_err());
+    // Bucket names that are not valid S3 names are rejected, since they end up in the host.
+    assert!(S3Pointer::parse(b"s3://attacker.com#/x").is_err());
+    assert!(S3Pointer::parse(b"s3://evil.com:1234/x").is_err());
+    assert!(S3Pointer::parse(b"s3://user@host/x").is_err());
+    assert!(S3Pointer::parse(b"s3://Bucket/x").is_err());
+    assert!(S3Pointer::parse(b"s3://ab/x").is_err());
+    assert!(S3Pointer::parse(b"s3://-bucket/x").is_err());
+    assert!(S3Pointer::parse(b"s3://bucket./x").is_err());
+    assert!(S3Pointer::parse(b"s3://my..bucket/x").is_err());
+    assert!(S3Pointer::parse(b"s3://192.168.1.1/x").is_err());
+    assert!(S3Pointer::parse(&[b"s3://".as_slice(), &[b'a'; 64], b"/x"].concat()).is_err());
+    assert!(S3Pointer::parse(b"s3://my-bucket.v2/x").is_ok());
+}
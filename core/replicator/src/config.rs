@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The number of nodes that should provide every content in the content registry.
+    pub replication_factor: usize,
+    /// Content that is provided by at least this many nodes is considered hot. Since nodes
+    /// only fetch content on demand, the number of providers reflects how popular the content
+    /// is.
+    pub hot_threshold: usize,
+    /// The number of nodes that should provide hot content.
+    pub hot_replication_factor: usize,
+    /// How often we look for under-replicated content.
+    pub interval: Duration,
+    /// Maximum number of content we fetch at the same time.
+    pub max_concurrent_fetches: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            replication_factor: 3,
+            hot_threshold: 5,
+            hot_replication_factor: 8,
+            interval: Duration::from_secs(60),
+            max_concurrent_fetches: 4,
+        }
+    }
+}
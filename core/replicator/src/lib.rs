@@ -0,0 +1,6 @@
+pub mod config;
+mod policy;
+pub mod replicator;
+
+pub use config::Config;
+pub use replicator::Replicator;
@@ -0,0 +1,137 @@
+//! The replication policy. Every node evaluates the same policy against the same application
+//! state, so the nodes agree on which of them should fetch a content without talking to each
+//! other.
+
+use std::collections::BTreeSet;
+
+use lightning_interfaces::types::{Blake3Hash, NodeIndex};
+
+use crate::config::Config;
+
+/// Returns the number of nodes that should provide a content with the given number of providers.
+pub(crate) fn target_replicas(providers: usize, config: &Config) -> usize {
+    if providers >= config.hot_threshold {
+        config.hot_replication_factor.max(config.replication_factor)
+    } else {
+        config.replication_factor
+    }
+}
+
+/// Returns true if `node` is one of the nodes that should fetch the content to bring it up to
+/// its target replication.
+///
+/// The nodes that are not providing the content yet are ranked using rendezvous hashing, and
+/// only as many of them as there are missing replicas are picked. This spreads the content
+/// evenly across the nodes and avoids fetching more copies than needed.
+pub(crate) fn should_replicate(
+    cid: &Blake3Hash,
+    node: NodeIndex,
+    nodes: &[NodeIndex],
+    providers: &BTreeSet<NodeIndex>,
+    config: &Config,
+) -> bool {
+    if providers.contains(&node) {
+        return false;
+    }
+    let missing = target_replicas(providers.len(), config).saturating_sub(providers.len());
+    if missing == 0 {
+        return false;
+    }
+
+    let mut candidates = nodes
+        .iter()
+        .filter(|index| !providers.contains(index))
+        .map(|index| (score(cid, *index), *index))
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+    candidates
+        .iter()
+        .take(missing)
+        .any(|(_, index)| *index == node)
+}
+
+/// Orders the providers of a content by their rendezvous score, so that the nodes fetching the
+/// same content do not all ask the same provider.
+pub(crate) fn order_providers(cid: &Blake3Hash, providers: &BTreeSet<NodeIndex>) -> Vec<NodeIndex> {
+    let mut providers = providers
+        .iter()
+        .map(|index| (score(cid, *index), *index))
+        .collect::<Vec<_>>();
+    providers.sort_unstable();
+    providers.into_iter().map(|(_, index)| index).collect()
+}
+
+fn score(cid: &Blake3Hash, node: NodeIndex) -> [u8; 32] {
+    let mut hasher = fleek_blake3::Hasher::new();
+    hasher.update(cid);
+    hasher.update(&node.to_le_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            replication_factor: 3,
+            hot_threshold: 5,
+            hot_replication_factor: 8,
+            ..Default::default()
+        }
+    }
+
+    fn replicators(
+        cid: &Blake3Hash,
+        nodes: &[NodeIndex],
+        providers: &BTreeSet<NodeIndex>,
+    ) -> Vec<NodeIndex> {
+        nodes
+            .iter()
+            .copied()
+            .filter(|node| should_replicate(cid, *node, nodes, providers, &config()))
+            .collect()
+    }
+
+    #[test]
+    fn test_target_replicas() {
+        assert_eq!(target_replicas(0, &config()), 3);
+        assert_eq!(target_replicas(4, &config()), 3);
+        assert_eq!(target_replicas(5, &config()), 8);
+    }
+
+    #[test]
+    fn test_fetches_missing_replicas_only() {
+        let nodes = (0..20).collect::<Vec<NodeIndex>>();
+        let cid = [7; 32];
+
+        let providers = BTreeSet::from([3]);
+        let picked = replicators(&cid, &nodes, &providers);
+        assert_eq!(picked.len(), 2);
+        assert!(!picked.contains(&3));
+
+        let providers = BTreeSet::from([1, 2, 3]);
+        assert!(replicators(&cid, &nodes, &providers).is_empty());
+
+        // Hot content gets extra copies.
+        let providers = BTreeSet::from([1, 2, 3, 4, 5]);
+        assert_eq!(replicators(&cid, &nodes, &providers).len(), 3);
+    }
+
+    #[test]
+    fn test_spreads_content_across_nodes() {
+        let nodes = (0..20).collect::<Vec<NodeIndex>>();
+        let providers = BTreeSet::from([0]);
+        let picked = (0..32u8)
+            .flat_map(|i| replicators(&[i; 32], &nodes, &providers))
+            .collect::<BTreeSet<_>>();
+        assert!(picked.len() > 10);
+    }
+
+    #[test]
+    fn test_not_enough_nodes() {
+        let nodes = vec![0, 1];
+        let providers = BTreeSet::from([0]);
+        assert_eq!(replicators(&[1; 32], &nodes, &providers), vec![1]);
+    }
+}
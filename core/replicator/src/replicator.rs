@@ -0,0 +1,257 @@
+use std::collections::{BTreeSet, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+
+use fleek_crypto::NodePublicKey;
+use futures_util::FutureExt;
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::{Blake3Hash, FetchPriority, NodeIndex, ServerRequest};
+use lightning_metrics::increment_counter;
+use lightning_utils::application::QueryRunnerExt;
+use tokio::task::JoinSet;
+use tracing::{debug, error};
+
+use crate::config::Config;
+use crate::policy;
+
+pub struct Replicator<C: Collection> {
+    inner: Option<ReplicatorInner<C>>,
+}
+
+impl<C: Collection> Replicator<C> {
+    pub fn new(
+        config_provider: &C::ConfigProviderInterface,
+        keystore: &C::KeystoreInterface,
+        blockstore_server: &C::BlockstoreServerInterface,
+        fdi::Cloned(blockstore): fdi::Cloned<C::BlockstoreInterface>,
+        fdi::Cloned(indexer): fdi::Cloned<C::IndexerInterface>,
+        fdi::Cloned(query_runner): fdi::Cloned<c!(C::ApplicationInterface::SyncExecutor)>,
+    ) -> anyhow::Result<Self> {
+        let inner = ReplicatorInner::<C> {
+            config: config_provider.get::<Self>(),
+            node_pk: keystore.get_ed25519_pk(),
+            blockstore,
+            blockstore_server_socket: blockstore_server.get_socket(),
+            indexer,
+            query_runner,
+        };
+        Ok(Self { inner: Some(inner) })
+    }
+
+    pub async fn start(
+        mut this: fdi::RefMut<Self>,
+        fdi::Cloned(waiter): fdi::Cloned<ShutdownWaiter>,
+    ) {
+        let inner = this.inner.take().expect("Replicator already started");
+        drop(this);
+        waiter.run_until_shutdown(inner.run()).await;
+    }
+}
+
+impl<C: Collection> ReplicatorInterface<C> for Replicator<C> {}
+
+impl<C: Collection> BuildGraph for Replicator<C> {
+    fn build_graph() -> fdi::DependencyGraph {
+        fdi::DependencyGraph::new().with(Self::new.on("start", Self::start.spawn()))
+    }
+}
+
+impl<C: Collection> ConfigConsumer for Replicator<C> {
+    const KEY: &'static str = "replicator";
+
+    type Config = Config;
+}
+
+struct ReplicatorInner<C: Collection> {
+    config: Config,
+    node_pk: NodePublicKey,
+    blockstore: C::BlockstoreInterface,
+    blockstore_server_socket: BlockstoreServerSocket,
+    indexer: C::IndexerInterface,
+    query_runner: c!(C::ApplicationInterface::SyncExecutor),
+}
+
+impl<C: Collection> ReplicatorInner<C> {
+    async fn run(self) {
+        let mut interval = tokio::time::interval(self.config.interval);
+
+        // We can only provide content once we have a node index.
+        let node_index = loop {
+            interval.tick().await;
+            if let Some(index) = self.query_runner.pubkey_to_index(&self.node_pk) {
+                break index;
+            }
+        };
+
+        let mut replications = Replications::default();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    self.replicate(node_index, &mut replications).await;
+                }
+                Some((_, fetched)) = replications.join_next() => {
+                    if fetched {
+                        increment_counter!(
+                            "replicator_fetch_succeeded",
+                            Some("Counter for content that was replicated to this node")
+                        );
+                    } else {
+                        increment_counter!(
+                            "replicator_fetch_failed",
+                            Some("Counter for failed attempts to replicate content")
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Looks for content that this node should replicate, and starts fetching it as long as we
+    /// are below the concurrency limit. The rest is picked up on the next round.
+    async fn replicate(&self, node_index: NodeIndex, replications: &mut Replications) {
+        let mut nodes = self
+            .query_runner
+            .get_node_registry(None)
+            .into_iter()
+            .map(|node| node.index)
+            .collect::<Vec<_>>();
+        nodes.sort_unstable();
+        let content = nodes
+            .iter()
+            .filter_map(|index| self.query_runner.get_content_registry(index))
+            .flatten()
+            .collect::<BTreeSet<_>>();
+
+        for cid in content {
+            if replications.len() >= self.config.max_concurrent_fetches {
+                break;
+            }
+            if replications.is_pending(&cid) {
+                continue;
+            }
+
+            // Providers that are no longer valid nodes don't count as a replica.
+            let providers = self
+                .query_runner
+                .get_cid_providers(&cid)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|index| nodes.binary_search(index).is_ok())
+                .collect::<BTreeSet<_>>();
+            if !policy::should_replicate(&cid, node_index, &nodes, &providers, &self.config) {
+                continue;
+            }
+
+            if self.blockstore.get_tree(&cid).await.is_some() {
+                // We have the content but it is not in our registry, for example because it was
+                // removed from it. Content that we fetch is registered by the blockstore.
+                self.indexer.register(cid).await;
+                continue;
+            }
+
+            debug!("Replicating content {cid:?}");
+            let socket = self.blockstore_server_socket.clone();
+            let providers = policy::order_providers(&cid, &providers);
+            replications.spawn(cid, fetch_from_providers(socket, cid, providers));
+        }
+    }
+}
+
+/// The content being replicated, with a task fetching each of them.
+#[derive(Default)]
+struct Replications {
+    tasks: JoinSet<(Blake3Hash, bool)>,
+    pending: HashSet<Blake3Hash>,
+}
+
+impl Replications {
+    fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    fn is_pending(&self, cid: &Blake3Hash) -> bool {
+        self.pending.contains(cid)
+    }
+
+    /// Starts fetching the content. A fetch that panics counts as failed, so that the content is
+    /// retried on a later round.
+    fn spawn(&mut self, cid: Blake3Hash, fetch: impl Future<Output = bool> + Send + 'static) {
+        self.pending.insert(cid);
+        self.tasks.spawn(async move {
+            let fetched = AssertUnwindSafe(fetch)
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| {
+                    error!("Replicating content {cid:?} panicked");
+                    false
+                });
+            (cid, fetched)
+        });
+    }
+
+    /// Waits for the next fetch to finish, and returns its content and whether it was fetched.
+    /// Returns `None` if no content is being replicated.
+    async fn join_next(&mut self) -> Option<(Blake3Hash, bool)> {
+        loop {
+            match self.tasks.join_next().await? {
+                Ok((cid, fetched)) => {
+                    self.pending.remove(&cid);
+                    return Some((cid, fetched));
+                },
+                Err(e) => error!("Failed to join task: {e:?}"),
+            }
+        }
+    }
+}
+
+/// Tries to fetch the content from the providers one after another. Returns true on success.
+async fn fetch_from_providers(
+    socket: BlockstoreServerSocket,
+    cid: Blake3Hash,
+    providers: Vec<NodeIndex>,
+) -> bool {
+    for peer in providers {
+        let Ok(mut res) = socket
+            .run(ServerRequest {
+                hash: cid,
+                peer,
+                priority: FetchPriority::Background,
+            })
+            .await
+        else {
+            return false;
+        };
+        if let Ok(Ok(())) = res.recv().await {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replications_retry() {
+        let mut replications = Replications::default();
+        let cid = [1; 32];
+
+        replications.spawn(cid, async { false });
+        assert!(replications.is_pending(&cid));
+        assert_eq!(replications.join_next().await, Some((cid, false)));
+        assert!(!replications.is_pending(&cid));
+
+        // A panicking fetch fails without leaving the content pending.
+        replications.spawn(cid, async { panic!("the fetch panicked") });
+        assert_eq!(replications.join_next().await, Some((cid, false)));
+        assert!(!replications.is_pending(&cid));
+
+        // So the content is replicated on the next round.
+        replications.spawn(cid, async { true });
+        assert_eq!(replications.len(), 1);
+        assert_eq!(replications.join_next().await, Some((cid, true)));
+        assert!(!replications.is_pending(&cid));
+        assert_eq!(replications.join_next().await, None);
+    }
+}
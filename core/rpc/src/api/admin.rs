@@ -1,9 +1,21 @@
 use jsonrpsee::core::RpcResult;
 use jsonrpsee::proc_macros::rpc;
-use lightning_interfaces::types::Blake3Hash;
+use lightning_interfaces::schema::handshake::TerminationReason;
+use lightning_interfaces::types::{Blake3Hash, HandshakeConnection};
 
 #[rpc(client, server, namespace = "admin")]
 pub trait AdminApi {
     #[method(name = "store")]
     async fn store(&self, path: String) -> RpcResult<Blake3Hash>;
+
+    #[method(name = "handshake_connections")]
+    async fn handshake_connections(&self) -> RpcResult<Vec<HandshakeConnection>>;
+
+    /// Returns false if there is no such connection.
+    #[method(name = "handshake_terminate")]
+    async fn handshake_terminate(
+        &self,
+        connection_id: u64,
+        reason: TerminationReason,
+    ) -> RpcResult<bool>;
 }
@@ -7,7 +7,7 @@
 use jsonrpsee::{Methods, RpcModule};
 use lightning_interfaces::prelude::*;
 use lightning_interfaces::types::Event;
-use lightning_interfaces::{FetcherSocket, MempoolSocket};
+use lightning_interfaces::{FetcherSocket, HandshakeAdminSocket, MempoolSocket};
 use reqwest::StatusCode;
 use tower::Service;
 
@@ -33,6 +33,7 @@ pub(crate) struct Data<C: Collection> {
     pub query_runner: c!(C::ApplicationInterface::SyncExecutor),
     pub mempool_socket: MempoolSocket,
     pub fetcher_socket: FetcherSocket,
+    pub handshake_socket: HandshakeAdminSocket,
     pub _blockstore: C::BlockstoreInterface,
     pub node_public_key: NodePublicKey,
     pub consensus_public_key: ConsensusPublicKey,
@@ -85,6 +86,7 @@ fn init(
         blockstore: &C::BlockstoreInterface,
         fetcher: &C::FetcherInterface,
         keystore: &C::KeystoreInterface,
+        handshake: &C::HandshakeInterface,
         fdi::Cloned(archive): fdi::Cloned<c!(C::ArchiveInterface)>,
         fdi::Cloned(query_runner): fdi::Cloned<c!(C::ApplicationInterface::SyncExecutor)>,
     ) -> anyhow::Result<Self> {
@@ -93,6 +95,7 @@ fn init(
             query_runner,
             mempool_socket: forwarder.mempool_socket(),
             fetcher_socket: fetcher.get_socket(),
+            handshake_socket: handshake.get_admin_socket(),
             _blockstore: blockstore.clone(),
             node_public_key: keystore.get_ed25519_pk(),
             consensus_public_key: keystore.get_bls_pk(),
@@ -2,7 +2,9 @@
 
 use jsonrpsee::core::RpcResult;
 use lightning_interfaces::prelude::*;
-use lightning_interfaces::types::{Blake3Hash, CompressionAlgorithm};
+use lightning_interfaces::schema::handshake::TerminationReason;
+use lightning_interfaces::types::{Blake3Hash, CompressionAlgorithm, HandshakeConnection};
+use lightning_interfaces::{HandshakeAdminRequest, HandshakeAdminResponse};
 
 use crate::api::AdminApiServer;
 use crate::error::RPCError;
@@ -36,4 +38,37 @@ async fn store(&self, path: String) -> RpcResult<Blake3Hash> {
 
         Ok(hash)
     }
+
+    async fn handshake_connections(&self) -> RpcResult<Vec<HandshakeConnection>> {
+        match self
+            .data
+            .handshake_socket
+            .run(HandshakeAdminRequest::Connections)
+            .await
+            .map_err(RPCError::from)?
+        {
+            HandshakeAdminResponse::Connections(connections) => Ok(connections),
+            _ => Err(RPCError::custom("unexpected handshake response".to_string()).into()),
+        }
+    }
+
+    async fn handshake_terminate(
+        &self,
+        connection_id: u64,
+        reason: TerminationReason,
+    ) -> RpcResult<bool> {
+        match self
+            .data
+            .handshake_socket
+            .run(HandshakeAdminRequest::Terminate {
+                connection_id,
+                reason,
+            })
+            .await
+            .map_err(RPCError::from)?
+        {
+            HandshakeAdminResponse::Terminated(terminated) => Ok(terminated),
+            _ => Err(RPCError::custom("unexpected handshake response".to_string()).into()),
+        }
+    }
 }
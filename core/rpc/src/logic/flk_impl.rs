@@ -12,6 +12,7 @@
     Epoch,
     EpochInfo,
     EventType,
+    FetchPriority,
     FetcherRequest,
     FetcherResponse,
     ImmutablePointer,
@@ -397,9 +398,15 @@ async fn put(&self, data: Vec<u8>) -> RpcResult<Blake3Hash> {
         let res = self
             .data
             .fetcher_socket
-            .run(FetcherRequest::Put { pointer })
+            .run(FetcherRequest::Put {
+                pointer,
+                priority: FetchPriority::Interactive,
+            })
+            .await
+            .map_err(RPCError::from)?
+            .response()
             .await
-            .map_err(RPCError::from)?;
+            .map_err(|e| RPCError::socket(e.to_string()))?;
 
         if let FetcherResponse::Put(res) = res {
             match res {
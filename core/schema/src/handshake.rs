@@ -4,6 +4,7 @@
 use arrayref::array_ref;
 use bytes::{BufMut, Bytes};
 use fleek_crypto::{ClientPublicKey, ClientSignature, NodePublicKey, NodeSignature};
+use serde::{Deserialize, Serialize};
 
 pub const NETWORK_PREFIX: &[u8; 5] = b"FLEEK";
 
@@ -15,11 +16,27 @@
 pub const REQ_ACCESS_TOKEN_TAG: u8 = 0x01;
 pub const REQ_EXTEND_ACCESS_TOKEN_TAG: u8 = 0x02;
 pub const REQ_DELIVERY_ACK_TAG: u8 = 0x03;
+pub const REQ_RECEIVED_TAG: u8 = 0x04;
+pub const REQ_SERVICE_PAYLOAD_CHUNK_TAG: u8 = 0x40;
 
 pub const RES_SERVICE_PAYLOAD_TAG: u8 = 0x00;
 pub const RES_SERVICE_PAYLOAD_CHUNK_TAG: u8 = 0x40;
 pub const RES_ACCESS_TOKEN_TAG: u8 = 0x01;
 
+/// The client key that anonymous clients use. They have no key to prove the possession of.
+pub const ANONYMOUS_CLIENT: ClientPublicKey = ClientPublicKey([0; 96]);
+
+/// Returns the message a client signs to prove the possession of its key when it starts a new
+/// connection. Signing its own key needs no round trip to the node, so it works for one-shot
+/// transports such as http as well.
+pub fn proof_of_possession_message(pk: &ClientPublicKey) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(NETWORK_PREFIX.len() + 4 + 96);
+    buf.put_slice(NETWORK_PREFIX);
+    buf.put_slice(b"-POP");
+    buf.put_slice(&pk.0);
+    buf
+}
+
 /// Challenge sent by the server for the client to sign in their handshake request.
 /// TODO: Determine if the extra round trip is ideal here, and identify other
 /// solutions for safely determining some bytes for the client proof of possession.
@@ -55,9 +72,14 @@ pub fn decode(bytes: &[u8]) -> Result<Self> {
 pub enum HandshakeRequestFrame {
     /// Primary connection handshake.
     Handshake {
+        /// The id of an existing connection to resume after its transport was lost.
         retry: Option<u64>,
         service: u32,
         pk: ClientPublicKey,
+        /// The proof of possession of the client key. For a new connection, it is the signature
+        /// of the [`proof_of_possession_message`] of the key, and it is ignored for the
+        /// [`ANONYMOUS_CLIENT`]. When resuming a connection, it is the signature of the access
+        /// token of the connection.
         pop: ClientSignature,
     },
     /// Secondary connection join request.
@@ -181,15 +203,24 @@ pub fn decode(bytes: &[u8]) -> Result<Self> {
 pub enum RequestFrame {
     /// Raw message to be sent to the service implementation. Available for any connection level.
     ServicePayload { bytes: bytes::Bytes },
-    /// Client request for an access token. Should only be used by the primary connection.
+    /// A chunk of a message that is combined with the following frames up to the next payload
+    /// frame, and delivered to the service as a single message.
+    ServicePayloadChunk { bytes: bytes::Bytes },
+    /// Client request for an access token valid for `ttl` seconds. Should only be used by the
+    /// primary connection.
     AccessToken { ttl: u64 },
-    /// Extend the access token associated with this primary connection.
+    /// Extend the access token associated with this primary connection, so it is valid for at
+    /// least `ttl` more seconds.
     ExtendAccessToken { ttl: u64 },
     /// Delivery acknowledgment, a client signature for some work the node and
     /// service committed to.
     DeliveryAcknowledgment {
         // TODO:
     },
+    /// The number of complete service payloads the client has received on this connection so
+    /// far. After resuming a connection with a retry handshake, the client must send this before
+    /// it receives any more payloads, and the node replays the payloads that were lost.
+    Received { count: u64 },
 }
 
 impl RequestFrame {
@@ -202,6 +233,12 @@ pub fn encode(&self) -> Bytes {
                 buf.put_slice(bytes);
                 buf.into()
             },
+            Self::ServicePayloadChunk { bytes } => {
+                let mut buf = Vec::with_capacity(1 + bytes.len());
+                buf.put_u8(REQ_SERVICE_PAYLOAD_CHUNK_TAG);
+                buf.put_slice(bytes);
+                buf.into()
+            },
             Self::AccessToken { ttl } => {
                 let mut buf = Vec::with_capacity(9);
                 buf.put_u8(REQ_ACCESS_TOKEN_TAG);
@@ -216,6 +253,12 @@ pub fn encode(&self) -> Bytes {
             },
             // TODO: encode signature bytes
             Self::DeliveryAcknowledgment { .. } => vec![REQ_DELIVERY_ACK_TAG].into(),
+            Self::Received { count } => {
+                let mut buf = Vec::with_capacity(9);
+                buf.put_u8(REQ_RECEIVED_TAG);
+                buf.put_u64(*count);
+                buf.into()
+            },
         }
     }
 
@@ -230,6 +273,10 @@ pub fn decode(bytes: &[u8]) -> Result<Self> {
                 let bytes = bytes[1..].to_vec().into();
                 Ok(Self::ServicePayload { bytes })
             },
+            REQ_SERVICE_PAYLOAD_CHUNK_TAG => {
+                let bytes = bytes[1..].to_vec().into();
+                Ok(Self::ServicePayloadChunk { bytes })
+            },
             REQ_ACCESS_TOKEN_TAG => {
                 if bytes.len() != 9 {
                     return Err(anyhow!("wrong number of bytes"));
@@ -248,6 +295,14 @@ pub fn decode(bytes: &[u8]) -> Result<Self> {
             },
             // TODO: decode signature bytes
             REQ_DELIVERY_ACK_TAG => Ok(Self::DeliveryAcknowledgment {}),
+            REQ_RECEIVED_TAG => {
+                if bytes.len() != 9 {
+                    return Err(anyhow!("wrong number of bytes"));
+                }
+
+                let count = u64::from_be_bytes(*array_ref!(bytes, 1, 8));
+                Ok(Self::Received { count })
+            },
             _ => Err(anyhow!("invalid frame tag")),
         }
     }
@@ -263,7 +318,7 @@ pub enum ResponseFrame {
     ///
     /// This frame is *never* used by stream based transports.
     ServicePayloadChunk { bytes: bytes::Bytes },
-    /// Access token granted for secondary connections.
+    /// Access token granted for secondary connections, valid for `ttl` more seconds.
     AccessToken {
         ttl: u64,
         access_token: Box<[u8; 48]>,
@@ -336,7 +391,7 @@ pub fn decode(bytes: &[u8]) -> Result<Self> {
 }
 
 /// Termination signals
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(u8)]
 #[non_exhaustive]
 pub enum TerminationReason {
@@ -366,6 +421,9 @@ pub fn from_u8(byte: u8) -> Self {
             0x85 => Self::ServiceTerminated,
             0x86 => Self::ConnectionInUse,
             0x87 => Self::WrongPermssion,
+            0x88 => Self::ResourcesUnavailable,
+            0x89 => Self::InternalError,
+            0x8A => Self::Shutdown,
             _ => Self::Unknown,
         }
     }
@@ -423,9 +481,13 @@ fn request_frames() {
             RequestFrame::ServicePayload {
                 bytes: vec![1; 64].into(),
             },
+            RequestFrame::ServicePayloadChunk {
+                bytes: vec![1; 64].into(),
+            },
             RequestFrame::AccessToken { ttl: 2 },
             RequestFrame::ExtendAccessToken { ttl: 12 },
-            RequestFrame::DeliveryAcknowledgment {}
+            RequestFrame::DeliveryAcknowledgment {},
+            RequestFrame::Received { count: 7 }
         );
     }
 
@@ -467,9 +529,33 @@ fn response_frames() {
             ResponseFrame::Termination {
                 reason: TerminationReason::ServiceTerminated
             },
+            ResponseFrame::Termination {
+                reason: TerminationReason::ResourcesUnavailable
+            },
+            ResponseFrame::Termination {
+                reason: TerminationReason::Shutdown
+            },
             ResponseFrame::Termination {
                 reason: TerminationReason::Unknown
             }
         );
     }
+
+    #[test]
+    fn termination_reasons() {
+        for byte in 0x80..=0xFF {
+            let reason = TerminationReason::from_u8(byte);
+            if reason != TerminationReason::Unknown {
+                assert_eq!(reason as u8, byte);
+            }
+        }
+        for reason in [
+            TerminationReason::ResourcesUnavailable,
+            TerminationReason::InternalError,
+            TerminationReason::Shutdown,
+        ] {
+            assert_eq!(TerminationReason::from_u8(reason as u8), reason);
+        }
+        assert_eq!(TerminationReason::from_u8(0x8B), TerminationReason::Unknown);
+    }
 }
@@ -0,0 +1,151 @@
+//! Installation of the service binaries published in the on-chain registry.
+//!
+//! Every artifact is installed in its own directory, named after its version and content hash:
+//!
+//! ```txt
+//! <root>/service-<id>/<version>-<hash>/fn-service-<id>
+//! ```
+//!
+//! so the previous versions of a service stay available to roll back to.
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use blake3_tree::blake3::tree::HashTreeBuilder;
+use blake3_tree::blake3::Hash;
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::{
+    FetchPriority,
+    FetcherRequest,
+    FetcherResponse,
+    ServiceArtifact,
+    ServiceId,
+};
+
+/// Fetches, verifies and installs the artifacts of the services.
+pub struct Installer<C: Collection> {
+    root: PathBuf,
+    blockstore: C::BlockstoreInterface,
+    fetcher_socket: FetcherSocket,
+}
+
+impl<C: Collection> Installer<C> {
+    pub fn new(
+        root: PathBuf,
+        blockstore: C::BlockstoreInterface,
+        fetcher_socket: FetcherSocket,
+    ) -> Self {
+        Self {
+            root,
+            blockstore,
+            fetcher_socket,
+        }
+    }
+
+    /// Returns the path of the binary of the artifact once it's installed.
+    pub fn program_path(&self, id: ServiceId, artifact: &ServiceArtifact) -> PathBuf {
+        self.root
+            .join(format!("service-{id}"))
+            .join(format!(
+                "{}-{}",
+                artifact.version,
+                Hash::from(artifact.hash).to_hex()
+            ))
+            .join(format!("fn-service-{id}"))
+    }
+
+    /// Returns the path of the binary of the artifact if it's already installed.
+    pub fn installed(&self, id: ServiceId, artifact: &ServiceArtifact) -> Option<PathBuf> {
+        let path = self.program_path(id, artifact);
+        path.is_file().then_some(path)
+    }
+
+    /// Install the artifact of a service, fetching it first if it's not in the blockstore.
+    /// Returns the path of the installed binary.
+    pub async fn install(&self, id: ServiceId, artifact: &ServiceArtifact) -> Result<PathBuf> {
+        if let Some(path) = self.installed(id, artifact) {
+            return Ok(path);
+        }
+
+        let hash = artifact.hash;
+        let request = FetcherRequest::Fetch {
+            hash,
+            priority: FetchPriority::Normal,
+        };
+        let response = self
+            .fetcher_socket
+            .run(request)
+            .await
+            .map_err(|_| anyhow!("fetcher socket closed"))?
+            .response()
+            .await
+            .map_err(|_| anyhow!("fetcher dropped the request"))?;
+        let FetcherResponse::Fetch(result) = response else {
+            unreachable!()
+        };
+        result.context("failed to fetch the artifact")?;
+
+        let content = self
+            .blockstore
+            .read_all_to_vec(&hash)
+            .await
+            .ok_or(anyhow!("artifact is missing from the blockstore"))?;
+        verify(&content, &hash)?;
+
+        let path = self.program_path(id, artifact);
+        write_program(&path, &content)
+            .await
+            .with_context(|| format!("failed to install the artifact to {path:?}"))?;
+        tracing::info!(
+            "Installed version {} of service {id} to {path:?}",
+            artifact.version
+        );
+        Ok(path)
+    }
+}
+
+/// Verify the content has the given blake3 root hash.
+pub fn verify(content: &[u8], hash: &[u8; 32]) -> Result<()> {
+    let mut builder = HashTreeBuilder::new();
+    builder.update(content);
+    let output = builder.finalize();
+    if output.hash.as_bytes() != hash {
+        bail!(
+            "artifact hash mismatch: expected {} got {}",
+            Hash::from(*hash).to_hex(),
+            output.hash.to_hex()
+        );
+    }
+    Ok(())
+}
+
+/// Write an executable file, through a temporary file so a partially written binary is never
+/// run.
+async fn write_program(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path.parent().expect("program path has a parent");
+    tokio::fs::create_dir_all(dir).await?;
+
+    let tmp = dir.join(".download");
+    tokio::fs::write(&tmp, content).await?;
+    tokio::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o755)).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let content = vec![7; 300 << 10];
+        let mut builder = HashTreeBuilder::new();
+        builder.update(&content);
+        let hash = *builder.finalize().hash.as_bytes();
+
+        assert!(verify(&content, &hash).is_ok());
+        assert!(verify(&content[1..], &hash).is_err());
+        assert!(verify(&content, &[0; 32]).is_err());
+    }
+}
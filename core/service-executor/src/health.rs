@@ -0,0 +1,181 @@
+//! Health checks and restart policy of the services.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    /// The interval between the health checks of a service. Must not be zero.
+    pub interval: Duration,
+    /// The process of a service is restarted after it missed this many health checks in a row.
+    pub max_missed: u32,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            max_missed: 3,
+        }
+    }
+}
+
+impl HealthCheckConfig {
+    /// Check the config when it's loaded, rather than failing once the services are running.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            !self.interval.is_zero(),
+            "the interval of the health checks must not be zero"
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    /// The delay before restarting a service after its first failure.
+    pub initial_backoff: Duration,
+    /// The delay is multiplied by this after every failure in a row. Must be at least 1.
+    pub backoff_multiplier: f64,
+    /// The maximum delay before restarting a service.
+    pub max_backoff: Duration,
+    /// A run lasting at least this long is considered healthy, and resets the backoff.
+    pub healthy_after: Duration,
+    /// A service failing this many times in a row is crash looping. It's then reported as
+    /// unavailable, and not restarted until the cooldown has passed.
+    pub crash_loop_threshold: u32,
+    /// How long a crash looping service is left stopped.
+    pub crash_loop_cooldown: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(100),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(30),
+            healthy_after: Duration::from_secs(10),
+            crash_loop_threshold: 5,
+            crash_loop_cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Check the policy when it's loaded, since [`RestartPolicy::backoff`] panics with a
+    /// negative or NaN multiplier.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.backoff_multiplier.is_finite() && self.backoff_multiplier >= 1.0,
+            "the backoff multiplier must be a finite number of at least 1, not {}",
+            self.backoff_multiplier
+        );
+        Ok(())
+    }
+
+    /// Returns the delay before restarting a service that failed `failures` times in a row.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.backoff_multiplier.powi(exponent);
+        Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()))
+    }
+}
+
+/// The health of a service, shared by its supervisor, its IPC and the provider.
+#[derive(Default)]
+pub struct ServiceHealth {
+    /// Whether the process answered the last health check as ready.
+    ready: AtomicBool,
+    /// Whether the service is crash looping.
+    crash_looping: AtomicBool,
+    /// Notified when the process failed its health checks.
+    unhealthy: Notify,
+}
+
+impl ServiceHealth {
+    /// Returns true if the service can accept connections.
+    pub fn is_available(&self) -> bool {
+        self.ready.load(Ordering::Relaxed) && !self.crash_looping.load(Ordering::Relaxed)
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Relaxed);
+    }
+
+    pub fn set_crash_looping(&self, crash_looping: bool) {
+        self.crash_looping.store(crash_looping, Ordering::Relaxed);
+    }
+
+    /// Report the process as unhealthy, to have it restarted.
+    pub fn mark_unhealthy(&self) {
+        self.set_ready(false);
+        self.unhealthy.notify_waiters();
+    }
+
+    /// Wait until the process is reported as unhealthy.
+    pub async fn unhealthy(&self) {
+        self.unhealthy.notified().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RestartPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(100), policy.max_backoff);
+        assert_eq!(policy.backoff(u32::MAX), policy.max_backoff);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(RestartPolicy::default().validate().is_ok());
+        for multiplier in [1.0, 1.5, 1e300] {
+            let policy = RestartPolicy {
+                backoff_multiplier: multiplier,
+                ..Default::default()
+            };
+            assert!(policy.validate().is_ok());
+            policy.backoff(u32::MAX);
+        }
+        for multiplier in [-2.0, 0.5, f64::NAN, f64::INFINITY] {
+            let policy = RestartPolicy {
+                backoff_multiplier: multiplier,
+                ..Default::default()
+            };
+            assert!(policy.validate().is_err(), "{multiplier}");
+        }
+
+        assert!(HealthCheckConfig::default().validate().is_ok());
+        let config = HealthCheckConfig {
+            interval: Duration::ZERO,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_availability() {
+        let health = ServiceHealth::default();
+        assert!(!health.is_available());
+        health.set_ready(true);
+        assert!(health.is_available());
+        health.set_crash_looping(true);
+        assert!(!health.is_available());
+        health.set_crash_looping(false);
+        health.mark_unhealthy();
+        assert!(!health.is_available());
+    }
+}
@@ -0,0 +1,272 @@
+//! The persistent key-value storage of the services.
+//!
+//! Every service has its own namespace, the keys of a service are prefixed with its id in the
+//! database. The total size of the keys and values of a service is tracked in a separate column
+//! family to enforce its quota.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{bail, Context as _, Result};
+use lightning_interfaces::types::ServiceId;
+use resolved_pathbuf::ResolvedPathBuf;
+use rocksdb::{Direction, IteratorMode, Options, WriteBatch, DB};
+use serde::{Deserialize, Serialize};
+use triomphe::Arc;
+
+const ENTRIES: &str = "entries";
+const USAGE: &str = "usage";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KvConfig {
+    /// Path to the database of the storage of the services.
+    pub store_path: ResolvedPathBuf,
+    /// The maximum total size in bytes of the keys and values of a service.
+    pub quota: u64,
+    /// The maximum size in bytes of a value.
+    pub max_value_size: u64,
+}
+
+impl Default for KvConfig {
+    fn default() -> Self {
+        Self {
+            store_path: "~/.lightning/data/service_kv"
+                .try_into()
+                .expect("Failed to resolve path"),
+            quota: 64 << 20,
+            max_value_size: 1 << 20,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct KvStore {
+    db: Arc<DB>,
+    quota: u64,
+    max_value_size: u64,
+    /// Serializes the writes, so the usage of a service is updated consistently.
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl KvStore {
+    pub fn open(config: &KvConfig) -> Result<Self> {
+        Self::open_with(&config.store_path, config.quota, config.max_value_size)
+    }
+
+    fn open_with(path: &Path, quota: u64, max_value_size: u64) -> Result<Self> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let db = DB::open_cf(&options, path, [ENTRIES, USAGE])
+            .context("failed to open the storage of the services")?;
+        Ok(Self {
+            db: Arc::new(db),
+            quota,
+            max_value_size,
+            write_lock: Default::default(),
+        })
+    }
+
+    pub fn max_value_size(&self) -> u64 {
+        self.max_value_size
+    }
+
+    /// Run calls to the database on the blocking threads of the runtime.
+    pub async fn blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&KvStore) -> Result<T> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .context("the storage task panicked")?
+    }
+
+    pub fn get(&self, id: ServiceId, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let entries = self.db.cf_handle(ENTRIES).expect("missing column family");
+        Ok(self.db.get_cf(&entries, namespaced(id, key))?)
+    }
+
+    pub fn put(&self, id: ServiceId, key: &[u8], value: &[u8]) -> Result<()> {
+        if value.len() as u64 > self.max_value_size {
+            bail!("value is too large");
+        }
+
+        let _guard = self.write_lock.lock().unwrap();
+        let entries = self.db.cf_handle(ENTRIES).expect("missing column family");
+        let key = namespaced(id, key);
+        let previous = self
+            .db
+            .get_cf(&entries, &key)?
+            .map(|value| entry_size(&key, &value))
+            .unwrap_or(0);
+        let usage = self.usage(id)?.saturating_sub(previous) + entry_size(&key, value);
+        if usage > self.quota {
+            bail!("storage quota of service {id} exceeded");
+        }
+
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&entries, &key, value);
+        self.set_usage(&mut batch, id, usage);
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    pub fn delete(&self, id: ServiceId, key: &[u8]) -> Result<()> {
+        let _guard = self.write_lock.lock().unwrap();
+        let entries = self.db.cf_handle(ENTRIES).expect("missing column family");
+        let key = namespaced(id, key);
+        let Some(value) = self.db.get_cf(&entries, &key)? else {
+            return Ok(());
+        };
+
+        let mut batch = WriteBatch::default();
+        batch.delete_cf(&entries, &key);
+        let usage = self.usage(id)?.saturating_sub(entry_size(&key, &value));
+        self.set_usage(&mut batch, id, usage);
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Returns at most `limit` entries whose keys start with the prefix, in order of their keys,
+    /// and starting after the key `after` if given.
+    pub fn scan(
+        &self,
+        id: ServiceId,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let entries = self.db.cf_handle(ENTRIES).expect("missing column family");
+        let prefix = namespaced(id, prefix);
+        let after = after.map(|after| namespaced(id, after));
+        let start = match &after {
+            Some(after) if *after > prefix => after.clone(),
+            _ => prefix.clone(),
+        };
+
+        let mut result = Vec::new();
+        let iter = self
+            .db
+            .iterator_cf(&entries, IteratorMode::From(&start, Direction::Forward));
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(&prefix) || result.len() >= limit {
+                break;
+            }
+            if after.as_deref() >= Some(&*key) {
+                continue;
+            }
+            result.push((key[4..].to_vec(), value.to_vec()));
+        }
+        Ok(result)
+    }
+
+    /// Returns the total size of the keys and values of the service.
+    pub fn usage(&self, id: ServiceId) -> Result<u64> {
+        let usage = self.db.cf_handle(USAGE).expect("missing column family");
+        Ok(self
+            .db
+            .get_cf(&usage, id.to_be_bytes())?
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_be_bytes)
+            .unwrap_or(0))
+    }
+
+    fn set_usage(&self, batch: &mut WriteBatch, id: ServiceId, usage: u64) {
+        let cf = self.db.cf_handle(USAGE).expect("missing column family");
+        batch.put_cf(&cf, id.to_be_bytes(), usage.to_be_bytes());
+    }
+}
+
+fn namespaced(id: ServiceId, key: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(4 + key.len());
+    result.extend_from_slice(&id.to_be_bytes());
+    result.extend_from_slice(key);
+    result
+}
+
+/// The size of an entry counted against the quota, without the namespace of the key.
+fn entry_size(namespaced_key: &[u8], value: &[u8]) -> u64 {
+    (namespaced_key.len() - 4 + value.len()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(name: &str, quota: u64) -> KvStore {
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&path);
+        KvStore::open_with(&path, quota, 16).unwrap()
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let store = open("lightning-service-ex-test-kv-1", 1024);
+        store.put(1, b"key", b"one").unwrap();
+        store.put(2, b"key", b"two").unwrap();
+        assert_eq!(store.get(1, b"key").unwrap(), Some(b"one".to_vec()));
+        assert_eq!(store.get(2, b"key").unwrap(), Some(b"two".to_vec()));
+        assert_eq!(store.get(3, b"key").unwrap(), None);
+
+        store.delete(1, b"key").unwrap();
+        assert_eq!(store.get(1, b"key").unwrap(), None);
+        assert_eq!(store.get(2, b"key").unwrap(), Some(b"two".to_vec()));
+    }
+
+    #[test]
+    fn test_quota() {
+        let store = open("lightning-service-ex-test-kv-2", 20);
+        store.put(1, b"a", b"0123456789").unwrap();
+        assert_eq!(store.usage(1).unwrap(), 11);
+        // Replacing a value only counts the difference.
+        store.put(1, b"a", b"01234").unwrap();
+        assert_eq!(store.usage(1).unwrap(), 6);
+        store.put(1, b"b", b"0123456789").unwrap();
+        assert_eq!(store.usage(1).unwrap(), 17);
+        assert!(store.put(1, b"c", b"0123").is_err());
+        // Other services have their own quota.
+        store.put(2, b"c", b"0123").unwrap();
+
+        store.delete(1, b"b").unwrap();
+        assert_eq!(store.usage(1).unwrap(), 6);
+        store.put(1, b"c", b"0123").unwrap();
+
+        // Values over the maximum size are rejected.
+        assert!(store.put(2, b"d", &[0; 17]).is_err());
+    }
+
+    #[test]
+    fn test_scan() {
+        let store = open("lightning-service-ex-test-kv-3", 1024);
+        for key in ["a/1", "a/2", "a/3", "b/1"] {
+            store.put(1, key.as_bytes(), key.as_bytes()).unwrap();
+        }
+        store.put(2, b"a/4", b"").unwrap();
+
+        let keys = |entries: Vec<(Vec<u8>, Vec<u8>)>| {
+            entries
+                .into_iter()
+                .map(|(key, _)| String::from_utf8(key).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(store.scan(1, b"a/", None, 10).unwrap()),
+            ["a/1", "a/2", "a/3"]
+        );
+        assert_eq!(keys(store.scan(1, b"a/", None, 2).unwrap()), ["a/1", "a/2"]);
+        assert_eq!(
+            keys(store.scan(1, b"a/", Some(b"a/2"), 10).unwrap()),
+            ["a/3"]
+        );
+        assert_eq!(
+            keys(store.scan(1, b"b/", Some(b"a/9"), 10).unwrap()),
+            ["b/1"]
+        );
+        assert_eq!(keys(store.scan(1, b"", None, 10).unwrap()).len(), 4);
+        assert_eq!(keys(store.scan(2, b"", None, 10).unwrap()), ["a/4"]);
+    }
+}
@@ -1,8 +1,14 @@
 // it's not dead, it's just not born yet.
 #![allow(dead_code)]
 
+pub mod artifact;
+pub mod health;
+pub mod kv;
+pub mod logs;
+pub mod sandbox;
 pub mod service;
 pub mod shim;
 pub mod test_services;
 #[cfg(test)]
 mod tests;
+pub mod transactions;
@@ -0,0 +1,193 @@
+//! Capture of the output of the services into rotating log files.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use resolved_pathbuf::ResolvedPathBuf;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+use triomphe::Arc;
+
+/// Longer lines of output are split, so a process writing without newlines can't exhaust the
+/// memory of the node.
+const MAX_LINE_LENGTH: usize = 16 << 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// The directory the output of every service is written to, as `service-<id>.log`. Set to
+    /// `None` to have the services write to the output of the node.
+    pub path: Option<ResolvedPathBuf>,
+    /// The size in bytes after which a log file is rotated.
+    pub max_size: u64,
+    /// The number of rotated log files kept for every service.
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            path: Some(
+                "~/.lightning/logs"
+                    .try_into()
+                    .expect("Failed to resolve path"),
+            ),
+            max_size: 10 << 20,
+            max_files: 5,
+        }
+    }
+}
+
+/// A log file rotated once it grows over the maximum size. The rotated files are kept as
+/// `<path>.1` (the most recent) up to `<path>.<max_files>`.
+pub struct RotatingLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingLog {
+    /// Open the log file, appending to it if it already exists.
+    pub async fn open(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let file = open(&path, false).await?;
+        let size = file.metadata().await?.len();
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    pub async fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate().await?;
+        }
+        self.file.write_all(buf).await?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush().await?;
+        for i in (1..self.max_files).rev() {
+            ignore_not_found(tokio::fs::rename(self.rotated(i), self.rotated(i + 1)).await)?;
+        }
+        if self.max_files > 0 {
+            tokio::fs::rename(&self.path, self.rotated(1)).await?;
+        }
+        self.file = open(&self.path, true).await?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+}
+
+async fn open(path: &Path, truncate: bool) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(!truncate)
+        .write(true)
+        .truncate(truncate)
+        .open(path)
+        .await
+}
+
+fn ignore_not_found(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Copy the output of a process to the log until it's closed. The output is copied line by line,
+/// so the lines of the stdout and stderr of a process are not mixed up. Lines longer than
+/// [`MAX_LINE_LENGTH`] are split.
+pub async fn capture(output: impl AsyncRead + Unpin, log: Arc<Mutex<RotatingLog>>) {
+    let mut reader = BufReader::new(output);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = (&mut reader)
+            .take(MAX_LINE_LENGTH as u64)
+            .read_until(b'\n', &mut line)
+            .await;
+        match read {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if !line.ends_with(b"\n") {
+                    line.push(b'\n');
+                }
+                if let Err(e) = log.lock().await.write(&line).await {
+                    tracing::warn!("Failed to write the output of a service to its log: {e}");
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rotation() {
+        let dir = std::env::temp_dir().join("lightning-service-ex-test-logs");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("service-0.log");
+
+        let mut log = RotatingLog::open(path.clone(), 10, 2).await.unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            log.write(line.as_bytes()).await.unwrap();
+        }
+        log.file.flush().await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(std::fs::read_to_string(log.rotated(1)).unwrap(), "third\n");
+        assert_eq!(std::fs::read_to_string(log.rotated(2)).unwrap(), "second\n");
+        assert!(!log.rotated(3).exists());
+
+        // Reopening appends to the existing file.
+        drop(log);
+        let mut log = RotatingLog::open(path.clone(), 100, 2).await.unwrap();
+        log.write(b"fifth\n").await.unwrap();
+        log.file.flush().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fourth\nfifth\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_capture_long_lines() {
+        let dir = std::env::temp_dir().join("lightning-service-ex-test-capture");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("service-0.log");
+
+        let log = RotatingLog::open(path.clone(), u64::MAX, 0).await.unwrap();
+        let log = Arc::new(Mutex::new(log));
+        let long = "a".repeat(MAX_LINE_LENGTH + 10);
+        let output = format!("{long}\nshort\nlast");
+        capture(output.as_bytes(), log.clone()).await;
+        log.lock().await.file.flush().await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines = contents.lines().map(str::len).collect::<Vec<_>>();
+        assert_eq!(lines, [MAX_LINE_LENGTH, 10, 5, 4]);
+        assert!(contents.ends_with("last\n"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
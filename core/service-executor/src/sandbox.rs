@@ -0,0 +1,631 @@
+//! Sandboxing of the service processes.
+//!
+//! On Linux a sandboxed service is started:
+//!
+//! 1. In its own cgroup, with cpu, memory and pids limits.
+//! 2. With resource limits on the number of open files and the size of the files it writes, and
+//!    without core dumps.
+//! 3. In a private mount namespace, where the blockstore is read-only and the private directories
+//!    of the node, like the keystore, are hidden. When the node is not running as root a user
+//!    namespace is created along with it, mapping the user of the node to itself.
+//! 4. Without any capabilities, and unable to gain new ones or new privileges through `exec`.
+//! 5. With a seccomp filter denying the syscalls services have no use for, like `ptrace`, `mount`
+//!    and `io_uring_setup`, and the creation of namespaces with `clone`.
+//!
+//! The sandbox is set up in the child process between `fork` and `exec`, so the code doing so
+//! must not allocate. Everything it needs is prepared beforehand in [`Sandbox::new`].
+
+use std::path::{Path, PathBuf};
+
+use lightning_interfaces::types::ServiceId;
+use resolved_pathbuf::ResolvedPathBuf;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    /// Run the services in a sandbox. Only supported on Linux.
+    pub enabled: bool,
+    /// The cgroup under which a cgroup is created for every service, e.g. a cgroup delegated to
+    /// the node by systemd. The node has to be able to create cgroups in it with the cpu, memory
+    /// and pids controllers enabled. Set to `None` to disable the cgroup limits.
+    pub cgroup: Option<PathBuf>,
+    /// Create a private mount namespace for the services, where the blockstore is read-only and
+    /// the hidden paths are not visible.
+    pub mount_namespace: bool,
+    /// Directories of the node hidden from the services. The ones that don't exist are ignored.
+    pub hidden_paths: Vec<ResolvedPathBuf>,
+    /// The limits of every service without its own limits.
+    pub limits: ServiceLimits,
+    /// The limits of specific services, replacing the ones above.
+    pub services: Vec<ServiceSandboxConfig>,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cgroup: Some("/sys/fs/cgroup/lightning".into()),
+            mount_namespace: true,
+            hidden_paths: vec![
+                "~/.lightning/keystore"
+                    .try_into()
+                    .expect("Failed to resolve path"),
+                "~/.lightning/data"
+                    .try_into()
+                    .expect("Failed to resolve path"),
+            ],
+            limits: Default::default(),
+            services: Vec::new(),
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// Returns the limits of the given service.
+    pub fn limits(&self, id: ServiceId) -> &ServiceLimits {
+        self.services
+            .iter()
+            .find(|service| service.service == id)
+            .map(|service| &service.limits)
+            .unwrap_or(&self.limits)
+    }
+}
+
+/// The limits of a service. A missing limit means the service is not limited by it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServiceLimits {
+    /// The number of cpus the service can use, e.g. `0.5` for half of a cpu.
+    pub cpus: Option<f64>,
+    /// The maximum memory of the service in bytes.
+    pub memory: Option<u64>,
+    /// The maximum number of processes and threads of the service.
+    pub pids: Option<u64>,
+    /// The maximum number of files the service can have open.
+    pub open_files: Option<u64>,
+    /// The maximum size of the files written by the service in bytes.
+    pub file_size: Option<u64>,
+    /// Deny the syscalls services have no use for.
+    pub seccomp: bool,
+}
+
+impl Default for ServiceLimits {
+    fn default() -> Self {
+        Self {
+            cpus: None,
+            memory: Some(2 << 30),
+            pids: Some(1024),
+            open_files: Some(4096),
+            file_size: None,
+            seccomp: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceSandboxConfig {
+    pub service: ServiceId,
+    #[serde(flatten)]
+    pub limits: ServiceLimits,
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::Sandbox;
+
+#[cfg(not(target_os = "linux"))]
+pub struct Sandbox;
+
+#[cfg(not(target_os = "linux"))]
+impl Sandbox {
+    pub fn new(
+        _id: ServiceId,
+        _config: &SandboxConfig,
+        _blockstore: &Path,
+    ) -> anyhow::Result<Self> {
+        anyhow::bail!("sandboxing services is only supported on Linux")
+    }
+
+    pub fn apply_to(self, _command: &mut tokio::process::Command) {}
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+
+    use anyhow::{Context, Result};
+    use libc::{c_int, c_ulong};
+
+    use super::*;
+
+    /// The period of the cpu limit of the cgroups, in microseconds.
+    const CPU_PERIOD: u64 = 100_000;
+
+    /// The syscalls denied to services.
+    const DENIED_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_open_tree,
+        libc::SYS_move_mount,
+        libc::SYS_fsopen,
+        libc::SYS_fsconfig,
+        libc::SYS_fsmount,
+        libc::SYS_fspick,
+        libc::SYS_mount_setattr,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_kexec_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_reboot,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_userfaultfd,
+        libc::SYS_acct,
+        libc::SYS_settimeofday,
+        libc::SYS_clock_settime,
+        libc::SYS_adjtimex,
+        libc::SYS_open_by_handle_at,
+        libc::SYS_name_to_handle_at,
+        libc::SYS_quotactl,
+        libc::SYS_sethostname,
+        libc::SYS_setdomainname,
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_io_uring_register,
+    ];
+
+    /// The flags of `clone` creating new namespaces, denied like `unshare`. `clone3` is denied
+    /// entirely since its flags can't be inspected by the filter, and with `ENOSYS` the libc
+    /// falls back to `clone`.
+    const CLONE_NEW_FLAGS: c_int = libc::CLONE_NEWNS
+        | libc::CLONE_NEWCGROUP
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUSER
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET;
+
+    /// The architecture checked by the seccomp filter, see `linux/audit.h`.
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    const AUDIT_ARCH: Option<u32> = None;
+
+    // The parts of `linux/filter.h` and `linux/seccomp.h` used by the filter.
+    /// `BPF_LD | BPF_W | BPF_ABS`
+    const BPF_LD_W_ABS: u16 = 0x20;
+    /// `BPF_JMP | BPF_JEQ | BPF_K`
+    const BPF_JMP_JEQ_K: u16 = 0x15;
+    /// `BPF_JMP | BPF_JGE | BPF_K`
+    const BPF_JMP_JGE_K: u16 = 0x35;
+    /// `BPF_JMP | BPF_JSET | BPF_K`
+    const BPF_JMP_JSET_K: u16 = 0x45;
+    /// `BPF_RET | BPF_K`
+    const BPF_RET_K: u16 = 0x06;
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+    const SECCOMP_DATA_NR: u32 = 0;
+    const SECCOMP_DATA_ARCH: u32 = 4;
+    /// The lower half of the first argument, on little endian architectures.
+    const SECCOMP_DATA_ARG0: u32 = 16;
+    /// Syscalls of the x32 ABI on x86_64 have this bit set.
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    // The flags of `statvfs` that have to be kept when remounting the blockstore read-only.
+    const ST_NOSUID: c_ulong = 2;
+    const ST_NODEV: c_ulong = 4;
+    const ST_NOEXEC: c_ulong = 8;
+    const ST_NOATIME: c_ulong = 1024;
+    const ST_NODIRATIME: c_ulong = 2048;
+    const ST_RELATIME: c_ulong = 4096;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct SockFilter {
+        code: u16,
+        jt: u8,
+        jf: u8,
+        k: u32,
+    }
+
+    #[repr(C)]
+    struct SockFprog {
+        len: u16,
+        filter: *const SockFilter,
+    }
+
+    // The parts of `linux/capability.h` used to drop the capabilities.
+    const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+    const CAP_SETPCAP: u32 = 8;
+
+    #[repr(C)]
+    struct CapHeader {
+        version: u32,
+        pid: c_int,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct CapData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+
+    /// The sandbox of a service, applied to the process of the service before it is executed.
+    pub struct Sandbox {
+        /// The `cgroup.procs` file of the cgroup of the service.
+        cgroup_procs: Option<CString>,
+        open_files: Option<u64>,
+        file_size: Option<u64>,
+        namespace: Option<Namespace>,
+        seccomp: Option<Vec<SockFilter>>,
+    }
+
+    struct Namespace {
+        /// The contents of the uid and gid maps when a user namespace is created.
+        id_maps: Option<(CString, CString)>,
+        blockstore: CString,
+        /// The flags of the mount of the blockstore.
+        blockstore_flags: c_ulong,
+        hidden_paths: Vec<CString>,
+    }
+
+    impl Sandbox {
+        /// Prepare the sandbox of a service, creating its cgroup.
+        pub fn new(id: ServiceId, config: &SandboxConfig, blockstore: &Path) -> Result<Self> {
+            let limits = config.limits(id);
+
+            let cgroup_procs = match &config.cgroup {
+                Some(root) => {
+                    let cgroup = create_cgroup(root, id, limits)
+                        .with_context(|| format!("failed to create the cgroup of service {id}"))?;
+                    Some(c_path(&cgroup.join("cgroup.procs"))?)
+                },
+                None => None,
+            };
+
+            let namespace = if config.mount_namespace {
+                Some(Namespace::new(config, blockstore)?)
+            } else {
+                None
+            };
+
+            let seccomp = match (limits.seccomp, AUDIT_ARCH) {
+                (true, Some(arch)) => Some(seccomp_filter(arch)),
+                (true, None) => {
+                    tracing::warn!("seccomp filters are not supported on this architecture");
+                    None
+                },
+                (false, _) => None,
+            };
+
+            Ok(Self {
+                cgroup_procs,
+                open_files: limits.open_files,
+                file_size: limits.file_size,
+                namespace,
+                seccomp,
+            })
+        }
+
+        /// Apply the sandbox to every process spawned by the command.
+        pub fn apply_to(self, command: &mut tokio::process::Command) {
+            // Safety: `apply` only makes syscalls and does not allocate, which is safe between
+            // `fork` and `exec`.
+            unsafe {
+                command.pre_exec(move || self.apply());
+            }
+        }
+
+        /// Sandbox the current process. Only called in the child process before `exec`.
+        fn apply(&self) -> io::Result<()> {
+            // The process is moved to the cgroup first, while it still has the permissions to do
+            // so outside of the user namespace.
+            if let Some(procs) = &self.cgroup_procs {
+                write_file(procs, b"0")?;
+            }
+
+            check(unsafe { libc::setrlimit(libc::RLIMIT_CORE, &rlimit(0)) })?;
+            if let Some(limit) = self.open_files {
+                check(unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &rlimit(limit)) })?;
+            }
+            if let Some(limit) = self.file_size {
+                check(unsafe { libc::setrlimit(libc::RLIMIT_FSIZE, &rlimit(limit)) })?;
+            }
+
+            if let Some(namespace) = &self.namespace {
+                namespace.enter()?;
+            }
+
+            // The capabilities are needed for the namespace and are only dropped after it.
+            drop_capabilities()?;
+
+            // The filter comes last, since it denies the syscalls used above.
+            if let Some(filter) = &self.seccomp {
+                let prog = SockFprog {
+                    len: filter.len() as u16,
+                    filter: filter.as_ptr(),
+                };
+                check(unsafe {
+                    libc::prctl(
+                        libc::PR_SET_SECCOMP,
+                        libc::SECCOMP_MODE_FILTER,
+                        &prog as *const SockFprog,
+                    )
+                })?;
+            }
+
+            Ok(())
+        }
+    }
+
+    impl Namespace {
+        fn new(config: &SandboxConfig, blockstore: &Path) -> Result<Self> {
+            let id_maps = match unsafe { libc::getuid() } {
+                0 => None,
+                uid => {
+                    let gid = unsafe { libc::getgid() };
+                    Some((
+                        CString::new(format!("{uid} {uid} 1"))?,
+                        CString::new(format!("{gid} {gid} 1"))?,
+                    ))
+                },
+            };
+
+            let blockstore_c = c_path(blockstore)?;
+            let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+            check(unsafe { libc::statvfs(blockstore_c.as_ptr(), &mut stat) })
+                .with_context(|| format!("failed to stat {}", blockstore.display()))?;
+            let mut blockstore_flags =
+                stat.f_flag & (ST_NOSUID | ST_NODEV | ST_NOEXEC | ST_NOATIME | ST_NODIRATIME);
+            if stat.f_flag & ST_RELATIME != 0 {
+                blockstore_flags |= libc::MS_RELATIME;
+            }
+
+            let hidden_paths = config
+                .hidden_paths
+                .iter()
+                .filter(|path| path.is_dir())
+                .map(|path| c_path(path))
+                .collect::<Result<_>>()?;
+
+            Ok(Self {
+                id_maps,
+                blockstore: blockstore_c,
+                blockstore_flags,
+                hidden_paths,
+            })
+        }
+
+        fn enter(&self) -> io::Result<()> {
+            match &self.id_maps {
+                Some((uid_map, gid_map)) => {
+                    check(unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) })?;
+                    write_file(c"/proc/self/setgroups", b"deny")?;
+                    write_file(c"/proc/self/uid_map", uid_map.as_bytes())?;
+                    write_file(c"/proc/self/gid_map", gid_map.as_bytes())?;
+                },
+                None => check(unsafe { libc::unshare(libc::CLONE_NEWNS) })?,
+            }
+
+            // Keep the mounts below from propagating back to the node.
+            mount(None, c"/", None, libc::MS_REC | libc::MS_PRIVATE)?;
+
+            mount(
+                Some(&self.blockstore),
+                &self.blockstore,
+                None,
+                libc::MS_BIND | libc::MS_REC,
+            )?;
+            mount(
+                None,
+                &self.blockstore,
+                None,
+                libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | self.blockstore_flags,
+            )?;
+
+            for path in &self.hidden_paths {
+                mount(
+                    Some(c"tmpfs"),
+                    path,
+                    Some(c"tmpfs"),
+                    libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                )?;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Drop every capability of the process, including the ones it would get from `exec` when
+    /// running as root, and keep it from gaining new privileges through setuid binaries.
+    fn drop_capabilities() -> io::Result<()> {
+        let mut header = CapHeader {
+            version: LINUX_CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+        let mut data = [CapData::default(); 2];
+        check(unsafe { libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) } as c_int)?;
+
+        // Changing the bounding set needs `CAP_SETPCAP`, without it the set is left as is and
+        // `PR_SET_NO_NEW_PRIVS` keeps `exec` from granting the capabilities in it.
+        if data[0].effective & (1 << CAP_SETPCAP) != 0 {
+            for cap in 0..64 {
+                if unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) } < 0 {
+                    let err = io::Error::last_os_error();
+                    // The capabilities past the last one supported by the kernel are invalid.
+                    if err.raw_os_error() == Some(libc::EINVAL) {
+                        break;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        check(unsafe {
+            libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_CLEAR_ALL,
+                0,
+                0,
+                0,
+            )
+        })?;
+        let data = [CapData::default(); 2];
+        check(unsafe { libc::syscall(libc::SYS_capset, &header, data.as_ptr()) } as c_int)?;
+        check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })
+    }
+
+    /// Create the cgroup of a service with its limits, returning its path.
+    fn create_cgroup(root: &Path, id: ServiceId, limits: &ServiceLimits) -> Result<PathBuf> {
+        std::fs::create_dir_all(root)?;
+        let subtree_control = std::fs::read_to_string(root.join("cgroup.subtree_control"))?;
+        let missing = ["cpu", "memory", "pids"]
+            .into_iter()
+            .filter(|controller| !subtree_control.split_whitespace().any(|c| c == *controller))
+            .map(|controller| format!("+{controller}"))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            std::fs::write(root.join("cgroup.subtree_control"), missing.join(" "))
+                .context("failed to enable the cpu, memory and pids controllers")?;
+        }
+
+        let cgroup = root.join(format!("service-{id}"));
+        if let Err(e) = std::fs::create_dir(&cgroup) {
+            if e.kind() != io::ErrorKind::AlreadyExists {
+                return Err(e.into());
+            }
+        }
+
+        let cpu = match limits.cpus {
+            Some(cpus) => format!("{} {CPU_PERIOD}", (cpus * CPU_PERIOD as f64) as u64),
+            None => format!("max {CPU_PERIOD}"),
+        };
+        std::fs::write(cgroup.join("cpu.max"), cpu)?;
+        std::fs::write(cgroup.join("memory.max"), limit_value(limits.memory))?;
+        std::fs::write(cgroup.join("pids.max"), limit_value(limits.pids))?;
+        if limits.memory.is_some() {
+            // Swap is not limited by `memory.max`. The file is missing without swap accounting.
+            let _ = std::fs::write(cgroup.join("memory.swap.max"), "0");
+        }
+
+        Ok(cgroup)
+    }
+
+    fn limit_value(limit: Option<u64>) -> String {
+        limit.map_or_else(|| "max".to_string(), |limit| limit.to_string())
+    }
+
+    /// Build a filter denying the [`DENIED_SYSCALLS`] and `clone` with the [`CLONE_NEW_FLAGS`]
+    /// with `EPERM`, denying `clone3` with `ENOSYS`, and killing the process if it uses another
+    /// architecture.
+    fn seccomp_filter(arch: u32) -> Vec<SockFilter> {
+        let stmt = |code, k| SockFilter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        };
+        let jump = |code, k, jt, jf| SockFilter { code, jt, jf, k };
+        let deny = stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32);
+
+        let mut filter = vec![
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+            jump(BPF_JMP_JEQ_K, arch, 1, 0),
+            stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+        ];
+        if cfg!(target_arch = "x86_64") {
+            filter.push(jump(BPF_JMP_JGE_K, X32_SYSCALL_BIT, 0, 1));
+            filter.push(deny);
+        }
+        for nr in DENIED_SYSCALLS {
+            filter.push(jump(BPF_JMP_JEQ_K, *nr as u32, 0, 1));
+            filter.push(deny);
+        }
+        filter.extend([
+            jump(BPF_JMP_JEQ_K, libc::SYS_clone3 as u32, 0, 1),
+            stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+            // Skip to the end unless it's `clone` with any of the flags.
+            jump(BPF_JMP_JEQ_K, libc::SYS_clone as u32, 0, 3),
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG0),
+            jump(BPF_JMP_JSET_K, CLONE_NEW_FLAGS as u32, 0, 1),
+            deny,
+            stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+        ]);
+        filter
+    }
+
+    fn c_path(path: &Path) -> Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .with_context(|| format!("invalid path {}", path.display()))
+    }
+
+    fn check(res: c_int) -> io::Result<()> {
+        if res < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn rlimit(limit: u64) -> libc::rlimit {
+        libc::rlimit {
+            rlim_cur: limit,
+            rlim_max: limit,
+        }
+    }
+
+    fn mount(
+        source: Option<&CStr>,
+        target: &CStr,
+        fstype: Option<&CStr>,
+        flags: c_ulong,
+    ) -> io::Result<()> {
+        let ptr = |s: Option<&CStr>| s.map_or(std::ptr::null(), CStr::as_ptr);
+        check(unsafe {
+            libc::mount(
+                ptr(source),
+                target.as_ptr(),
+                ptr(fstype),
+                flags,
+                std::ptr::null(),
+            )
+        })
+    }
+
+    fn write_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = unsafe { libc::write(fd, contents.as_ptr().cast(), contents.len()) };
+        let res = if written < 0 {
+            Err(io::Error::last_os_error())
+        } else if written as usize != contents.len() {
+            Err(io::ErrorKind::WriteZero.into())
+        } else {
+            Ok(())
+        };
+        unsafe { libc::close(fd) };
+        res
+    }
+}
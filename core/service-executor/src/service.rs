@@ -1,31 +1,81 @@
 use std::error::Error;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
 use dashmap::DashMap;
-use fleek_crypto::ClientPublicKey;
-use fn_sdk::ipc_types::{self, IpcMessage, IpcRequest, DELIMITER_SIZE};
+use fleek_crypto::{ClientPublicKey, NodePublicKey};
+use fn_sdk::ipc_types::{self, IpcMessage, IpcRequest, SubmitTxError, DELIMITER_SIZE};
 use lightning_interfaces::prelude::*;
-use tokio::io::{self, Interest};
+use lightning_interfaces::types::{
+    CompressionAlgorithm,
+    FetchProgress,
+    Metadata,
+    NodeInfo,
+    Participation,
+    Service,
+    ServiceArtifact,
+    ServiceId,
+    TransactionReceipt,
+    TransactionRequest,
+    Value,
+};
+use lightning_interfaces::BlockExecutedNotification;
+use lightning_metrics::{histogram, increment_counter, set_gauge};
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt, Interest};
 use tokio::net::{UnixListener, UnixStream};
 use tokio::process::Command;
-use tokio::sync::Notify;
+use tokio::sync::{mpsc, watch, Mutex, Notify};
 use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
 use tokio::{pin, select};
 use tracing::instrument;
 use triomphe::Arc;
 
+use crate::artifact::Installer;
+use crate::health::{HealthCheckConfig, RestartPolicy, ServiceHealth};
+use crate::kv::KvStore;
+use crate::logs::{capture, LogConfig, RotatingLog};
+use crate::sandbox::{Sandbox, SandboxConfig};
+use crate::transactions::{
+    is_signed_by,
+    wait_for_receipt,
+    ServiceTransaction,
+    TransactionsConfig,
+    MAX_TRANSACTION_SIZE,
+};
+
 /// The shared object with every service.
 pub struct Context<C: Collection> {
+    pub blockstore: C::BlockstoreInterface,
     pub blockstore_path: PathBuf,
     pub ipc_path: PathBuf,
     pub fetcher_socket: FetcherSocket,
     pub query_runner: c!(C::ApplicationInterface::SyncExecutor),
+    pub sandbox: SandboxConfig,
+    pub installer: Installer<C>,
+    /// The number of failed starts of a new version after which the previous one is restored.
+    pub rollback_after: u32,
+    pub health_check: HealthCheckConfig,
+    pub restart: RestartPolicy,
+    pub logs: LogConfig,
+    pub kv: KvStore,
+    pub node_public_key: NodePublicKey,
+    pub signer_socket: SubmitTxSocket,
+    pub mempool_socket: MempoolSocket,
+    pub notifier: C::NotifierInterface,
+    pub transactions: TransactionsConfig,
 }
 
 impl<C: Collection> Context<C> {
-    pub async fn run(&self, request: ipc_types::Request) -> ipc_types::Response {
+    pub async fn run(
+        &self,
+        session: &Session<C>,
+        request: ipc_types::Request,
+        progress: Option<ProgressReporter>,
+    ) -> ipc_types::Response {
         match request {
             ipc_types::Request::QueryClientBandwidth { pk } => {
                 let balance = self
@@ -51,42 +101,446 @@ pub async fn run(&self, request: ipc_types::Request) -> ipc_types::Response {
                     balance: balance.try_into().unwrap(),
                 }
             },
-            ipc_types::Request::FetchFromOrigin { origin, uri } => {
-                let hash = match self
-                    .fetcher_socket
-                    .run(lightning_interfaces::types::FetcherRequest::Put {
-                        pointer: lightning_interfaces::types::ImmutablePointer {
-                            origin: match origin {
-                                0 => lightning_interfaces::types::OriginProvider::IPFS,
-                                _ => unreachable!(),
-                            },
-                            uri: Vec::from(&uri),
-                        },
-                    })
-                    .await
-                    .unwrap()
-                {
-                    lightning_interfaces::types::FetcherResponse::Put(hash) => hash.ok(),
-                    lightning_interfaces::types::FetcherResponse::Fetch(_) => unreachable!(),
+            ipc_types::Request::FetchFromOrigin {
+                origin,
+                uri,
+                priority,
+                progress: report,
+            } => {
+                let origin = match origin {
+                    0 => lightning_interfaces::types::OriginProvider::IPFS,
+                    1 => lightning_interfaces::types::OriginProvider::HTTP,
+                    2 => lightning_interfaces::types::OriginProvider::S3,
+                    _ => return ipc_types::Response::FetchFromOrigin { hash: None },
+                };
+                let request = lightning_interfaces::types::FetcherRequest::Put {
+                    pointer: lightning_interfaces::types::ImmutablePointer {
+                        origin,
+                        uri: Vec::from(&uri),
+                    },
+                    priority: priority.into(),
+                };
+                let hash = match self.fetch(request, progress.filter(|_| report)).await {
+                    Some(lightning_interfaces::types::FetcherResponse::Put(hash)) => hash.ok(),
+                    Some(lightning_interfaces::types::FetcherResponse::Fetch(_)) => {
+                        unreachable!()
+                    },
+                    None => None,
                 };
 
                 ipc_types::Response::FetchFromOrigin { hash }
             },
-            ipc_types::Request::FetchBlake3 { hash } => {
-                let succeeded = match self
-                    .fetcher_socket
-                    .run(lightning_interfaces::types::FetcherRequest::Fetch { hash })
+            ipc_types::Request::FetchBlake3 {
+                hash,
+                priority,
+                progress: report,
+            } => {
+                let request = lightning_interfaces::types::FetcherRequest::Fetch {
+                    hash,
+                    priority: priority.into(),
+                };
+                let succeeded = match self.fetch(request, progress.filter(|_| report)).await {
+                    Some(lightning_interfaces::types::FetcherResponse::Put(_)) => unreachable!(),
+                    Some(lightning_interfaces::types::FetcherResponse::Fetch(v)) => v.is_ok(),
+                    None => false,
+                };
+                ipc_types::Response::FetchBlake3 { succeeded }
+            },
+            ipc_types::Request::PutStart {} => {
+                let handle = session.next_put.fetch_add(1, Ordering::Relaxed);
+                session
+                    .puts
+                    .insert(handle, std::sync::Mutex::new(self.blockstore.put(None)));
+                ipc_types::Response::PutStart { handle }
+            },
+            ipc_types::Request::PutWrite { handle } => {
+                let succeeded = match session.write(handle).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::error!("Failed to write the content of put {handle}: {e:?}");
+                        // The put can't be finalized after a failed write.
+                        session.puts.remove(&handle);
+                        false
+                    },
+                };
+                ipc_types::Response::PutWrite { succeeded }
+            },
+            ipc_types::Request::PutFinalize { handle } => {
+                let hash = match session.puts.remove(&handle) {
+                    Some((_, putter)) => match putter.into_inner().unwrap().finalize().await {
+                        Ok(hash) => Some(hash),
+                        Err(e) => {
+                            tracing::error!("Failed to finalize put {handle}: {e:?}");
+                            None
+                        },
+                    },
+                    None => None,
+                };
+                ipc_types::Response::PutFinalize { hash }
+            },
+            ipc_types::Request::QueryEpoch {} => {
+                let epoch = match self.query_runner.get_metadata(&Metadata::Epoch) {
+                    Some(Value::Epoch(epoch)) => epoch,
+                    _ => 0,
+                };
+                let epoch_end = self
+                    .query_runner
+                    .get_committe_info(&epoch, |c| c.epoch_end_timestamp)
+                    .unwrap_or(0);
+                ipc_types::Response::QueryEpoch { epoch, epoch_end }
+            },
+            ipc_types::Request::QueryNodeIndex { public_key } => {
+                let index = self
+                    .query_runner
+                    .pubkey_to_index(&NodePublicKey(public_key));
+                ipc_types::Response::QueryNodeIndex { index }
+            },
+            ipc_types::Request::QueryNodeInfo { index } => {
+                let info = self.query_runner.get_node_info(&index, node_info_to_ipc);
+                ipc_types::Response::QueryNodeInfo { info }
+            },
+            ipc_types::Request::QueryServiceInfo { id } => {
+                let info = self.query_runner.get_service_info(&id).map(|service| {
+                    service_info_to_ipc(service, self.query_runner.get_service_artifact(&id))
+                });
+                ipc_types::Response::QueryServiceInfo { info }
+            },
+            ipc_types::Request::QueryReputation { index } => {
+                let score = self.query_runner.get_reputation_score(&index);
+                ipc_types::Response::QueryReputation { score }
+            },
+            ipc_types::Request::QueryContentProviders { hash, slot } => {
+                let providers = self
+                    .query_runner
+                    .get_cid_providers(&hash)
+                    .unwrap_or_default();
+                let content = providers
+                    .iter()
+                    .flat_map(|index| index.to_le_bytes())
+                    .collect::<Vec<_>>();
+                let result = session
+                    .write_staged(&format!("query-{slot}"), &content)
+                    .await;
+                if let Err(e) = &result {
+                    tracing::error!("Failed to write the providers of a content: {e:?}");
+                }
+                ipc_types::Response::QueryContentProviders {
+                    count: result.ok().map(|_| providers.len() as u32),
+                }
+            },
+            ipc_types::Request::QueryClientNonce { pk } => {
+                let nonce = self
+                    .query_runner
+                    .client_key_to_account_key(&ClientPublicKey(pk.into()))
+                    .and_then(|address| self.query_runner.get_account_info(&address, |a| a.nonce));
+                ipc_types::Response::QueryClientNonce { nonce }
+            },
+            ipc_types::Request::SubmitTransaction {
+                slot,
+                signed_by_node,
+            } => {
+                let name = format!("tx-{slot}");
+                let tx = session
+                    .read_staged(&name, MAX_TRANSACTION_SIZE)
+                    .await
+                    .ok()
+                    .and_then(|content| ServiceTransaction::decode(&content, signed_by_node));
+                let result = match tx {
+                    Some(tx) => self.submit_transaction(session.id, tx).await,
+                    None => Err(SubmitTxError::Invalid),
+                };
+                let error = match result {
+                    Ok(receipt) => {
+                        let content = bincode::serialize(&receipt).expect("serialization failed");
+                        match session.write_staged(&name, &content).await {
+                            Ok(()) => None,
+                            Err(e) => {
+                                tracing::error!(
+                                    "Failed to write the receipt of a transaction: {e:?}"
+                                );
+                                Some(SubmitTxError::Failed)
+                            },
+                        }
+                    },
+                    Err(e) => Some(e),
+                };
+                ipc_types::Response::SubmitTransaction { error }
+            },
+            ipc_types::Request::KvGet { key, slot } => {
+                let id = session.id;
+                let result = match self.kv.blocking(move |kv| kv.get(id, &key)).await {
+                    Ok(Some(value)) => session.write_staged(&format!("kv-{slot}"), &value).await,
+                    Ok(None) => return ipc_types::Response::KvGet { found: Some(false) },
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = &result {
+                    tracing::error!("Failed to get a value of service {id}: {e:?}");
+                }
+                ipc_types::Response::KvGet {
+                    found: result.ok().map(|_| true),
+                }
+            },
+            ipc_types::Request::KvPut { key, slot } => {
+                let result = match session
+                    .read_staged(&format!("kv-{slot}"), self.kv.max_value_size())
                     .await
-                    .unwrap()
                 {
-                    lightning_interfaces::types::FetcherResponse::Put(_) => unreachable!(),
-                    lightning_interfaces::types::FetcherResponse::Fetch(v) => v.is_ok(),
+                    Ok(value) => {
+                        let id = session.id;
+                        self.kv.blocking(move |kv| kv.put(id, &key, &value)).await
+                    },
+                    Err(e) => Err(e),
                 };
-                ipc_types::Response::FetchBlake3 { succeeded }
+                if let Err(e) = &result {
+                    tracing::error!("Failed to put a value of service {}: {e:?}", session.id);
+                }
+                ipc_types::Response::KvPut {
+                    succeeded: result.is_ok(),
+                }
+            },
+            ipc_types::Request::KvDelete { key } => {
+                let id = session.id;
+                let result = self.kv.blocking(move |kv| kv.delete(id, &key)).await;
+                if let Err(e) = &result {
+                    tracing::error!("Failed to delete a value of service {}: {e:?}", session.id);
+                }
+                ipc_types::Response::KvDelete {
+                    succeeded: result.is_ok(),
+                }
+            },
+            ipc_types::Request::KvScan {
+                prefix,
+                after,
+                limit,
+                slot,
+            } => {
+                let id = session.id;
+                let scan = self
+                    .kv
+                    .blocking(move |kv| kv.scan(id, &prefix, after.as_deref(), limit as usize))
+                    .await;
+                let result = match scan {
+                    Ok(entries) => session
+                        .write_staged(&format!("kv-{slot}"), &encode_entries(&entries))
+                        .await
+                        .map(|_| entries.len() as u32),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = &result {
+                    tracing::error!("Failed to scan the values of service {}: {e:?}", session.id);
+                }
+                ipc_types::Response::KvScan { count: result.ok() }
             },
             _ => unreachable!(),
         }
     }
+
+    /// Submits the transaction of a service if it's allowed to, and waits for it to be executed.
+    async fn submit_transaction(
+        &self,
+        id: ServiceId,
+        tx: ServiceTransaction,
+    ) -> Result<TransactionReceipt, SubmitTxError> {
+        // Subscribe before submitting, so the block of the transaction is not missed.
+        let blocks = self.notifier.subscribe_block_executed();
+        let timeout = self.transactions.timeout;
+        match tx {
+            ServiceTransaction::Sign(method) => {
+                if !self.transactions.can_sign(id, &method) {
+                    return Err(SubmitTxError::NotAllowed);
+                }
+                let nonce = self
+                    .signer_socket
+                    .run(method.clone())
+                    .await
+                    .map_err(|_| SubmitTxError::Failed)?;
+                let node = self.node_public_key;
+                wait_for_receipt(blocks, timeout, |tx| is_signed_by(tx, node, nonce, &method)).await
+            },
+            ServiceTransaction::Forward(request) => {
+                if !self.transactions.can_forward(id) {
+                    return Err(SubmitTxError::NotAllowed);
+                }
+                let request = TransactionRequest::from(request);
+                let hash = request.hash();
+                self.mempool_socket
+                    .run(request)
+                    .await
+                    .map_err(|_| SubmitTxError::Failed)?;
+                wait_for_receipt(blocks, timeout, |tx| tx.hash() == hash).await
+            },
+        }
+    }
+
+    /// Submits the request to the fetcher and waits for the response, reporting the progress
+    /// along the way if a reporter is given. Returns `None` if the fetcher dropped the request.
+    async fn fetch(
+        &self,
+        request: lightning_interfaces::types::FetcherRequest,
+        progress: Option<ProgressReporter>,
+    ) -> Option<lightning_interfaces::types::FetcherResponse> {
+        let handle = self.fetcher_socket.run(request).await.unwrap();
+        // The request is cancelled if the service goes away before the response.
+        let _cancel = handle.cancel_on_drop();
+        let Some(progress) = progress else {
+            return handle.response().await.ok();
+        };
+
+        let mut progress_rx = handle.progress_receiver();
+        let response = handle.response();
+        pin!(response);
+        loop {
+            select! {
+                response = &mut response => return response.ok(),
+                Ok(()) = progress_rx.changed() => {
+                    progress.report(*progress_rx.borrow_and_update());
+                },
+            }
+        }
+    }
+}
+
+fn node_info_to_ipc(info: NodeInfo) -> ipc_types::NodeInfo {
+    let domain = match info.domain {
+        std::net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        std::net::IpAddr::V6(ip) => ip,
+    };
+    ipc_types::NodeInfo {
+        owner: info.owner.0,
+        public_key: info.public_key.0,
+        staked_since: info.staked_since,
+        stake: info.stake.staked.try_into().unwrap_or(u128::MAX),
+        domain: domain.octets(),
+        http_port: info.ports.handshake.http,
+        participation: match info.participation {
+            Participation::True => 0,
+            Participation::False => 1,
+            Participation::OptedIn => 2,
+            Participation::OptedOut => 3,
+        },
+        nonce: info.nonce,
+    }
+}
+
+fn service_info_to_ipc(
+    service: Service,
+    artifact: Option<ServiceArtifact>,
+) -> ipc_types::ServiceInfo {
+    ipc_types::ServiceInfo {
+        owner: service.owner.0,
+        commodity_type: service.commodity_type as u8,
+        artifact: artifact.map(|artifact| (artifact.hash, artifact.version)),
+    }
+}
+
+/// Encode the entries of a scan of the key-value storage, see [`ipc_types::Request::KvScan`].
+fn encode_entries(entries: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut content = Vec::new();
+    for (key, value) in entries {
+        content.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        content.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        content.extend_from_slice(key);
+        content.extend_from_slice(value);
+    }
+    content
+}
+
+/// The state of the control connection of a service process, dropped with the connection.
+pub struct Session<C: Collection> {
+    id: ServiceId,
+    /// The IPC directory of the service.
+    ipc_dir: PathBuf,
+    /// The puts started by the service, by their handle.
+    puts: DashMap<u64, std::sync::Mutex<c!(C::BlockstoreInterface::Put)>>,
+    next_put: AtomicU64,
+}
+
+impl<C: Collection> Session<C> {
+    fn new(id: ServiceId, ipc_dir: PathBuf) -> Self {
+        Self {
+            id,
+            ipc_dir,
+            puts: DashMap::new(),
+            next_put: AtomicU64::new(0),
+        }
+    }
+
+    /// Write the content staged by the service to the put.
+    async fn write(&self, handle: u64) -> anyhow::Result<()> {
+        if !self.puts.contains_key(&handle) {
+            anyhow::bail!("unknown put");
+        }
+
+        let content = self.read_staged(&format!("put-{handle}"), u64::MAX).await?;
+
+        let Some(putter) = self.puts.get(&handle) else {
+            anyhow::bail!("unknown put");
+        };
+        putter
+            .lock()
+            .unwrap()
+            .write(&content, CompressionAlgorithm::Uncompressed)?;
+        Ok(())
+    }
+
+    /// Read and remove a file staged by the service in its IPC directory.
+    async fn read_staged(&self, name: &str, max_size: u64) -> anyhow::Result<Vec<u8>> {
+        // The file is controlled by the service, so it's never followed if it's a link.
+        let path = self.ipc_dir.join(name);
+        let mut file = tokio::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&path)
+            .await?;
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            anyhow::bail!("not a regular file");
+        }
+        let mut content = Vec::new();
+        let result = if metadata.len() > max_size {
+            Err(anyhow::anyhow!("staged file is too large"))
+        } else {
+            file.read_to_end(&mut content).await.map_err(Into::into)
+        };
+        tokio::fs::remove_file(&path).await?;
+        result.map(|_| content)
+    }
+
+    /// Stage a file for the service to read in its IPC directory.
+    async fn write_staged(&self, name: &str, content: &[u8]) -> anyhow::Result<()> {
+        let path = self.ipc_dir.join(name);
+        // A file left by a previous run of the service is replaced. The new file is always
+        // created, so a link placed by the service is never written through.
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&path)
+            .await?;
+        file.write_all(content).await?;
+        Ok(())
+    }
+}
+
+/// Sends the progress of a request back to the service that made it.
+pub struct ProgressReporter {
+    request_ctx: ipc_types::RequestCtxU64,
+    tx: mpsc::UnboundedSender<IpcMessage>,
+}
+
+impl ProgressReporter {
+    fn report(&self, progress: FetchProgress) {
+        let _ = self.tx.send(IpcMessage::Progress {
+            request_ctx: self.request_ctx,
+            bytes: progress.bytes,
+            blocks: progress.blocks,
+        });
+    }
 }
 
 /// Collection of every service that we have.
@@ -98,22 +552,58 @@ pub struct ServiceCollection {
 impl ServiceCollection {
     #[inline]
     pub fn get(&self, id: u32) -> Option<ServiceHandle> {
-        self.services.get(&id).map(|v| *v)
+        self.services.get(&id).map(|v| v.clone())
     }
 
     #[inline]
     pub fn insert(&self, id: u32, handle: ServiceHandle) {
         self.services.insert(id, handle);
     }
+
+    #[inline]
+    pub fn remove(&self, id: u32) {
+        self.services.remove(&id);
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ServiceHandle {
+    pub health: Arc<ServiceHealth>,
+}
+
+/// The program run by the process of a service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Program {
+    path: PathBuf,
+    args: Vec<OsString>,
 }
 
-#[derive(Clone, Copy)]
-pub struct ServiceHandle {}
+impl Program {
+    /// The binary of the service, either a standalone `fn-service-<id>` binary or the current
+    /// binary for the statically linked services.
+    fn builtin(id: ServiceId) -> Self {
+        match which::which(format!("fn-service-{id}")) {
+            Ok(path) => Self {
+                path,
+                args: Vec::new(),
+            },
+            Err(_) => {
+                let mut args = std::env::args_os();
+                let path = args.next().unwrap().into();
+                Self {
+                    path,
+                    args: args.collect(),
+                }
+            },
+        }
+    }
+}
 
 #[allow(unused)]
 pub async fn spawn_service<C: Collection>(
     id: u32,
     cx: Arc<Context<C>>,
+    blocks: impl Subscriber<BlockExecutedNotification>,
     waiter: ShutdownWaiter,
 ) -> ServiceHandle {
     tracing::info!("Initializing service {id}");
@@ -127,56 +617,169 @@ pub async fn spawn_service<C: Collection>(
         .await
         .expect("Failed to create IPC directory for service.");
 
-    let mut cmd = match which::which(format!("fn-service-{id}")) {
-        // Use the standalone service binary
-        Ok(path) => Command::new(path),
-        Err(_) => {
-            // Otherwise, relaunch the current binary for running statically linked services
-            let mut args = std::env::args_os();
-            let program = args.next().unwrap();
-            let mut cmd = Command::new(program);
-            cmd.args(args);
-            cmd
-        },
-    };
+    // Start with the version published on-chain if it's already installed, otherwise it's
+    // installed in the background and swapped in once ready.
+    let builtin = Program::builtin(id);
+    let program = cx
+        .query_runner
+        .get_service_artifact(&id)
+        .and_then(|artifact| cx.installer.installed(id, &artifact))
+        .map(|path| Program {
+            path,
+            args: Vec::new(),
+        })
+        .unwrap_or(builtin);
 
-    cmd.env("SERVICE_ID", format!("{id}"))
-        .env("BLOCKSTORE_PATH", &cx.blockstore_path)
-        .env("IPC_PATH", &ipc_dir);
+    if let Err(e) = command(id, &cx, &ipc_dir, &program) {
+        // Never run a service without the sandbox it's configured with.
+        tracing::error!("Failed to set up the sandbox of service {id}: {e:?}");
+        return ServiceHandle::default();
+    }
 
-    panic_report::add_context(format!("service_{id}"), format!("{cmd:?}"));
+    let health = Arc::new(ServiceHealth::default());
+
+    let (program_tx, program_rx) = watch::channel(program);
+    {
+        let cx = cx.clone();
+        let waiter = waiter.clone();
+        tokio::spawn(async move {
+            waiter
+                .run_until_shutdown(watch_artifact(id, cx, blocks, program_tx))
+                .await;
+        });
+    }
 
     let cmd_permit = Arc::new(Notify::new());
     let permit = cmd_permit.clone();
-    let conn_path = ipc_dir.join("conn");
 
     #[cfg(not(test))]
     {
+        let cx = cx.clone();
+        let ipc_dir = ipc_dir.clone();
+        let health = health.clone();
         let waiter = waiter.clone();
         tokio::spawn(async move {
+            let log = open_log(id, &cx.logs).await;
             // Wait until we have the UDS listener listening.
             permit.notified().await;
             tracing::trace!("Starting the child process for service '{id}'.");
-            run_command(format!("service-{id}"), cmd, waiter, conn_path).await;
+            run_command(id, cx, ipc_dir, program_rx, health, log, waiter).await;
             tracing::trace!("Exiting service '{id}' execution loop.");
         });
     }
 
-    tokio::spawn(async move {
-        let waiter2 = waiter.clone();
-        waiter
-            .run_until_shutdown(async move {
-                run_ctrl_loop(&ipc_dir, cx, cmd_permit, waiter2).await;
-            })
-            .await;
-    });
+    {
+        let health = health.clone();
+        tokio::spawn(async move {
+            let waiter2 = waiter.clone();
+            waiter
+                .run_until_shutdown(async move {
+                    run_ctrl_loop(id, &ipc_dir, cx, health, cmd_permit, waiter2).await;
+                })
+                .await;
+        });
+    }
+
+    ServiceHandle { health }
+}
+
+/// Open the log file of a service, if the output of the services is captured.
+async fn open_log(id: ServiceId, config: &LogConfig) -> Option<RotatingLog> {
+    let path = config.path.as_ref()?.join(format!("service-{id}.log"));
+    match RotatingLog::open(path, config.max_size, config.max_files).await {
+        Ok(log) => Some(log),
+        Err(e) => {
+            tracing::warn!("Failed to open the log of service {id}: {e}");
+            None
+        },
+    }
+}
+
+/// Build the command running the program of a service.
+fn command<C: Collection>(
+    id: ServiceId,
+    cx: &Context<C>,
+    ipc_dir: &Path,
+    program: &Program,
+) -> anyhow::Result<Command> {
+    let mut cmd = Command::new(&program.path);
+    cmd.args(&program.args)
+        .env("SERVICE_ID", format!("{id}"))
+        .env("BLOCKSTORE_PATH", &cx.blockstore_path)
+        .env("IPC_PATH", ipc_dir);
 
-    ServiceHandle {}
+    if cx.sandbox.enabled {
+        Sandbox::new(id, &cx.sandbox, &cx.blockstore_path)?.apply_to(&mut cmd);
+    }
+
+    panic_report::add_context(format!("service_{id}"), format!("{cmd:?}"));
+    Ok(cmd)
+}
+
+/// Install the artifact of the service every time a new one is published on-chain, and swap the
+/// program of the service to it. A failed install is retried on the first block after the backoff
+/// of the restart policy.
+async fn watch_artifact<C: Collection>(
+    id: ServiceId,
+    cx: Arc<Context<C>>,
+    mut blocks: impl Subscriber<BlockExecutedNotification>,
+    program: watch::Sender<Program>,
+) {
+    let mut current = None;
+    // The artifact that failed to install, the number of failures in a row and when to retry.
+    let mut failed: Option<(ServiceArtifact, u32, Instant)> = None;
+    loop {
+        let artifact = cx
+            .query_runner
+            .get_service_artifact(&id)
+            .filter(|artifact| Some(artifact) != current.as_ref())
+            .filter(|artifact| match &failed {
+                Some((failed, _, retry_at)) if failed == artifact => Instant::now() >= *retry_at,
+                _ => true,
+            });
+
+        if let Some(artifact) = artifact {
+            match cx.installer.install(id, &artifact).await {
+                Ok(path) => {
+                    current = Some(artifact);
+                    failed = None;
+                    program.send_if_modified(|program| {
+                        let new = Program {
+                            path,
+                            args: Vec::new(),
+                        };
+                        let modified = *program != new;
+                        *program = new;
+                        modified
+                    });
+                },
+                Err(e) => {
+                    let failures = match &failed {
+                        Some((failed, failures, _)) if *failed == artifact => failures + 1,
+                        _ => 1,
+                    };
+                    let backoff = cx.restart.backoff(failures);
+                    tracing::error!(
+                        "Failed to install version {} of service {id}, retrying in {backoff:?}: \
+                         {e:?}",
+                        artifact.version
+                    );
+                    failed = Some((artifact, failures, Instant::now() + backoff));
+                },
+            }
+        }
+
+        if blocks.last().await.is_none() {
+            break;
+        }
+    }
 }
 
 async fn run_ctrl_loop<C: Collection>(
+    id: ServiceId,
     ipc_path: &Path,
     ctx: Arc<Context<C>>,
+    health: Arc<ServiceHealth>,
     cmd_permit: Arc<Notify>,
     waiter: ShutdownWaiter,
 ) {
@@ -193,23 +796,28 @@ async fn run_ctrl_loop<C: Collection>(
     while let Ok((stream, _)) = listener.accept().await {
         // spawn a new task to handle the stream
         let ctx = ctx.clone();
+        let health = health.clone();
         let waiter = waiter.clone();
         tokio::spawn(async move {
             waiter
                 .run_until_shutdown(async move {
-                    if let Err(e) = handle_stream(stream, ctx).await {
+                    if let Err(e) = handle_stream(id, stream, ctx, &health).await {
                         tracing::error!("Error while handling the unix stream: {e:?}");
                     }
+                    // The process is gone or unresponsive.
+                    health.set_ready(false);
                 })
                 .await
         });
     }
 }
 
-#[instrument(skip(stream, ctx))]
+#[instrument(skip(stream, ctx, health))]
 async fn handle_stream<C: Collection>(
+    id: ServiceId,
     stream: UnixStream,
     ctx: Arc<Context<C>>,
+    health: &ServiceHealth,
 ) -> Result<(), Box<dyn Error>> {
     // incoming IpcRequests
     // start with a buffer of 8 bytes to read the length delimiter
@@ -221,6 +829,22 @@ async fn handle_stream<C: Collection>(
     let mut write_buffer_pos = 0;
 
     let mut task_set = JoinSet::<IpcMessage>::new();
+    // Progress of running requests, which has to be written before their response.
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<IpcMessage>();
+
+    let ipc_dir = ctx.ipc_path.join(format!("service-{id}"));
+    let conn_path = ipc_dir.join("conn");
+    let session = Arc::new(Session::new(id, ipc_dir));
+
+    // The first health check is sent right away.
+    let mut health_checks = tokio::time::interval(ctx.health_check.interval);
+    health_checks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut ping_nonce = 0;
+    let mut missed_pings = 0;
+    // Services opt into the health checks with a first pong, the ones built with an older SDK
+    // would not understand the pings. Until then a service is ready once its connection socket
+    // is bound, and it's never restarted for not answering.
+    let mut health_checked = false;
 
     'outer: loop {
         // Theres no messages to write
@@ -229,6 +853,10 @@ async fn handle_stream<C: Collection>(
                 Some(msg) = task_set.join_next() => {
                     match msg {
                         Ok(msg) => {
+                            // The task is done, so all of its progress is in the channel by now.
+                            while let Ok(progress) = progress_rx.try_recv() {
+                                IpcMessage::encode_length_delimited(&progress, &mut write_buffer)?;
+                            }
                             IpcMessage::encode_length_delimited(&msg, &mut write_buffer)?;
                             continue 'outer;
                         },
@@ -238,6 +866,26 @@ async fn handle_stream<C: Collection>(
                         },
                     }
                 },
+                Some(progress) = progress_rx.recv() => {
+                    IpcMessage::encode_length_delimited(&progress, &mut write_buffer)?;
+                    continue 'outer;
+                },
+                _ = health_checks.tick() => {
+                    if !health_checked {
+                        health.set_ready(conn_path.exists());
+                        continue 'outer;
+                    }
+                    if missed_pings >= ctx.health_check.max_missed {
+                        tracing::error!("Service {id} missed {missed_pings} health checks");
+                        health.mark_unhealthy();
+                        break 'outer;
+                    }
+                    missed_pings += 1;
+                    ping_nonce += 1;
+                    let ping = IpcMessage::Ping { nonce: ping_nonce };
+                    IpcMessage::encode_length_delimited(&ping, &mut write_buffer)?;
+                    continue 'outer;
+                },
                 ready_result = stream.ready(Interest::READABLE) => {
                     ready_result?
                 },
@@ -325,10 +973,33 @@ async fn handle_stream<C: Collection>(
                 read_buffer_pos = 0;
                 reading_len = true;
 
+                if let ipc_types::Request::Pong {
+                    nonce,
+                    ready,
+                    connections,
+                } = request.request
+                {
+                    // Only the answer to the last ping counts, the others are late.
+                    if nonce == ping_nonce {
+                        missed_pings = 0;
+                    }
+                    health_checked = true;
+                    health.set_ready(ready);
+                    report_connections(id, connections);
+                    continue 'read;
+                }
+
                 if let Some(request_ctx) = request.request_ctx {
                     let ctx = ctx.clone();
+                    let session = session.clone();
+                    let progress = ProgressReporter {
+                        request_ctx,
+                        tx: progress_tx.clone(),
+                    };
                     task_set.spawn(async move {
-                        let response = ctx.run(request.request).await;
+                        let start = Instant::now();
+                        let response = ctx.run(&session, request.request, Some(progress)).await;
+                        report_ipc_request_duration(id, start.elapsed());
                         IpcMessage::Response {
                             request_ctx,
                             response,
@@ -337,8 +1008,9 @@ async fn handle_stream<C: Collection>(
                 } else {
                     // Only enqueue the request. We don't need to send the response back.
                     let ctx = ctx.clone();
+                    let session = session.clone();
                     tokio::spawn(async move {
-                        ctx.run(request.request).await;
+                        ctx.run(&session, request.request, None).await;
                     });
                 }
             }
@@ -348,57 +1020,168 @@ async fn handle_stream<C: Collection>(
     Ok(())
 }
 
-/// Run the given command until the kill signal has been received. Restarting the child on failure.
-async fn run_command(
-    name: String,
-    mut command: Command,
+fn report_connections(id: ServiceId, connections: u64) {
+    let service = id.to_string();
+    set_gauge!(
+        "service_active_connections",
+        Some("Number of open connections of a service"),
+        connections as i64,
+        "service" => service.as_str()
+    );
+}
+
+/// Report the time it took the node to handle a request of a service over the IPC. The latency
+/// of the requests made to the service by its clients is reported by the handshake.
+fn report_ipc_request_duration(id: ServiceId, duration: Duration) {
+    let service = id.to_string();
+    histogram!(
+        "service_ipc_request_duration",
+        Some("Time it took the node to handle the IPC requests of a service, in seconds"),
+        duration.as_secs_f64(),
+        "service" => service.as_str()
+    );
+}
+
+/// Run the program of a service until the kill signal has been received. Restarting the child
+/// when it fails or its health checks do, following the restart policy, and when the program is
+/// swapped for a new version. A new version failing `rollback_after` times in a row is rolled
+/// back to the previous one.
+async fn run_command<C: Collection>(
+    id: ServiceId,
+    cx: Arc<Context<C>>,
+    ipc_dir: PathBuf,
+    mut program: watch::Receiver<Program>,
+    health: Arc<ServiceHealth>,
+    log: Option<RotatingLog>,
     kill: ShutdownWaiter,
-    conn_uds_path: PathBuf,
 ) {
     pin! {
         let kill_fut = kill.wait_for_shutdown();
     };
 
-    command
-        .stdin(Stdio::null())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit());
+    let name = format!("service-{id}");
+    let service = id.to_string();
+    let conn_uds_path = ipc_dir.join("conn");
+    let policy = &cx.restart;
+    let log = log.map(|log| Arc::new(Mutex::new(log)));
+
+    let mut current = program.borrow_and_update().clone();
+    let mut previous: Option<Program> = None;
+    // The number of failed runs in a row of the service, and of the current program.
+    let mut failures = 0;
+    let mut version_failures = 0;
 
-    let mut wait_time = 1000;
     loop {
+        let mut command = match command(id, &cx, &ipc_dir, &current) {
+            Ok(command) => command,
+            Err(e) => {
+                tracing::error!("Failed to create the command of '{name}': {e:?}");
+                break;
+            },
+        };
+        let output = || {
+            if log.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            }
+        };
+        command
+            .stdin(Stdio::null())
+            .stdout(output())
+            .stderr(output());
+
         // Remove the `/ipc/conn` file before (re-)running
         let _ = tokio::fs::remove_file(&conn_uds_path).await;
 
         let last_start = Instant::now();
         tracing::debug!("Starting child process '{name}' with {command:?}");
 
-        let mut child = command
-            // Unique group id for the subprocess to isolate signals from the parent process
-            .process_group(0)
-            .spawn()
-            .expect("Command failed to start.");
+        // Unique group id for the subprocess to isolate signals from the parent process. A
+        // command that fails to start is a failed run like any other.
+        let started = match command.process_group(0).spawn() {
+            Ok(mut child) => {
+                if let Some(log) = &log {
+                    if let Some(stdout) = child.stdout.take() {
+                        tokio::spawn(capture(stdout, log.clone()));
+                    }
+                    if let Some(stderr) = child.stderr.take() {
+                        tokio::spawn(capture(stderr, log.clone()));
+                    }
+                }
 
-        select! {
-            biased;
-            _ = &mut kill_fut => {
-                tracing::trace!("Got the signal to kill. Killing '{name}'");
-                child.kill().await.expect("Failed to kill the child.");
-                tracing::trace!("Killed process '{name}'");
-                break;
-            },
-            _ = child.wait() => {
-                tracing::error!("Child process '{name}' failed.");
-                if Instant::now().duration_since(last_start) >= Duration::from_secs(2) {
-                    tracing::info!("Last run seemed to have been healthy. Waiting for 100ms.");
-                    // Restart the wait time.
-                    wait_time = 100;
-                } else if wait_time <= 100 {
-                    wait_time = 1000;
+                select! {
+                    biased;
+                    _ = &mut kill_fut => {
+                        tracing::trace!("Got the signal to kill. Killing '{name}'");
+                        child.kill().await.expect("Failed to kill the child.");
+                        tracing::trace!("Killed process '{name}'");
+                        break;
+                    },
+                    Ok(()) = program.changed() => {
+                        let new = program.borrow_and_update().clone();
+                        tracing::info!("Swapping '{name}' to {:?}", new.path);
+                        child.kill().await.expect("Failed to kill the child.");
+                        health.set_ready(false);
+                        previous = Some(std::mem::replace(&mut current, new));
+                        failures = 0;
+                        version_failures = 0;
+                        continue;
+                    },
+                    _ = health.unhealthy() => {
+                        tracing::error!("Child process '{name}' is unhealthy. Killing it.");
+                        child.kill().await.expect("Failed to kill the child.");
+                    },
+                    _ = child.wait() => {
+                        tracing::error!("Child process '{name}' failed.");
+                    }
                 }
+                true
+            },
+            Err(e) => {
+                tracing::error!("Failed to start child process '{name}': {e:?}");
+                false
+            },
+        };
+        health.set_ready(false);
+
+        if started && last_start.elapsed() >= policy.healthy_after {
+            tracing::info!("Last run of '{name}' seemed to have been healthy.");
+            failures = 0;
+            version_failures = 0;
+        } else {
+            failures += 1;
+            version_failures += 1;
+        }
+
+        if version_failures >= cx.rollback_after {
+            if let Some(previous) = previous.take() {
+                tracing::warn!(
+                    "'{name}' failed {version_failures} times in a row, rolling back to {:?}",
+                    previous.path
+                );
+                current = previous;
+                version_failures = 0;
             }
         }
 
-        let wait_dur = Duration::from_millis(wait_time);
+        let wait_dur = if failures >= policy.crash_loop_threshold {
+            tracing::error!(
+                "'{name}' is crash looping after failing {failures} times in a row, \
+                 suspending it for {:?}",
+                policy.crash_loop_cooldown
+            );
+            increment_counter!(
+                "service_crash_loops",
+                Some("Counter for services suspended after failing repeatedly"),
+                "service" => service.as_str()
+            );
+            health.set_crash_looping(true);
+            failures = 0;
+            policy.crash_loop_cooldown
+        } else {
+            policy.backoff(failures)
+        };
         tracing::info!("Waiting for {wait_dur:?} before restarting '{name}'");
 
         select! {
@@ -407,10 +1190,15 @@ async fn run_command(
                 tracing::trace!("Got the signal to stop '{name}'");
                 break;
             }
-            _ = tokio::time::sleep(wait_dur) => {
-                wait_time = wait_time * 12 / 10;
-            }
+            _ = tokio::time::sleep(wait_dur) => {}
         }
+
+        health.set_crash_looping(false);
+        increment_counter!(
+            "service_restarts",
+            Some("Counter for restarts of the service processes"),
+            "service" => service.as_str()
+        );
     }
 
     tracing::info!("Exiting service execution loop [sid={name}]")
@@ -1,16 +1,29 @@
 use std::marker::PhantomData;
 use std::path::PathBuf;
 
-use fxhash::FxHashSet;
+use fxhash::{FxHashMap, FxHashSet};
 use lightning_interfaces::prelude::*;
-use lightning_interfaces::types::ServiceId;
+use lightning_interfaces::types::{
+    ServiceId,
+    TransactionDestination,
+    TransactionResponse,
+    UpdateMethod,
+};
+use lightning_interfaces::{BlockExecutedNotification, ShutdownController};
 use resolved_pathbuf::ResolvedPathBuf;
 use serde::{Deserialize, Serialize};
 use tokio::net::UnixStream;
-use tracing::{error, trace};
+use tokio::pin;
+use tracing::{error, info, trace};
 use triomphe::Arc;
 
+use crate::artifact::Installer;
+use crate::health::{HealthCheckConfig, RestartPolicy};
+use crate::kv::{KvConfig, KvStore};
+use crate::logs::LogConfig;
+use crate::sandbox::SandboxConfig;
 use crate::service::{spawn_service, Context, ServiceCollection};
+use crate::transactions::TransactionsConfig;
 
 #[derive(Clone)]
 pub struct ServiceExecutor<C: Collection> {
@@ -23,10 +36,29 @@ pub struct ServiceExecutor<C: Collection> {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ServiceExecutorConfig {
+    /// The services started with the node. The services with an artifact published on-chain are
+    /// started as well, and every service is stopped once it's removed on-chain.
     pub services: FxHashSet<ServiceId>,
     /// The IPC directory is used to contain the Unix domain sockets that we use to communicate
     /// with the different services.
     pub ipc_path: ResolvedPathBuf,
+    /// The sandbox the services are run in.
+    pub sandbox: SandboxConfig,
+    /// The directory the artifacts of the services published on-chain are installed to.
+    pub artifacts_path: ResolvedPathBuf,
+    /// Roll a service back to its previous version after the new version failed to start this
+    /// many times in a row.
+    pub rollback_after: u32,
+    /// The health checks of the services.
+    pub health_check: HealthCheckConfig,
+    /// The policy for restarting the services that failed.
+    pub restart: RestartPolicy,
+    /// Where the output of the services is written to.
+    pub logs: LogConfig,
+    /// The persistent key-value storage of the services.
+    pub kv: KvConfig,
+    /// The transactions the services are allowed to submit.
+    pub transactions: TransactionsConfig,
 }
 
 impl Default for ServiceExecutorConfig {
@@ -36,6 +68,16 @@ fn default() -> Self {
             ipc_path: "~/.lightning/ipc"
                 .try_into()
                 .expect("Failed to resolve path"),
+            sandbox: Default::default(),
+            artifacts_path: "~/.lightning/services"
+                .try_into()
+                .expect("Failed to resolve path"),
+            rollback_after: 3,
+            health_check: Default::default(),
+            restart: Default::default(),
+            logs: Default::default(),
+            kv: Default::default(),
+            transactions: Default::default(),
         }
     }
 }
@@ -47,6 +89,24 @@ pub fn test_default() -> Self {
             ipc_path: "~/.lightning-test/ipc"
                 .try_into()
                 .expect("Failed to resolve path"),
+            sandbox: Default::default(),
+            artifacts_path: "~/.lightning-test/services"
+                .try_into()
+                .expect("Failed to resolve path"),
+            rollback_after: 3,
+            health_check: Default::default(),
+            restart: Default::default(),
+            logs: LogConfig {
+                path: None,
+                ..Default::default()
+            },
+            kv: KvConfig {
+                store_path: "~/.lightning-test/data/service_kv"
+                    .try_into()
+                    .expect("Failed to resolve path"),
+                ..Default::default()
+            },
+            transactions: Default::default(),
         }
     }
 }
@@ -59,19 +119,43 @@ pub struct Provider {
 
 impl<C: Collection> ServiceExecutor<C> {
     /// Initialize the service executor.
+    #[allow(clippy::too_many_arguments)]
     fn init(
         config: &C::ConfigProviderInterface,
         blockstore: &C::BlockstoreInterface,
         fetcher: &C::FetcherInterface,
+        keystore: &C::KeystoreInterface,
+        signer: &C::SignerInterface,
+        forwarder: &C::ForwarderInterface,
+        notifier: &C::NotifierInterface,
         fdi::Cloned(query_runner): fdi::Cloned<c!(C::ApplicationInterface::SyncExecutor)>,
     ) -> anyhow::Result<Self> {
         let config = Arc::new(config.get::<Self>());
+        config.health_check.validate()?;
+        config.restart.validate()?;
 
         let ctx = Arc::new(Context {
+            blockstore: blockstore.clone(),
             blockstore_path: blockstore.get_root_dir(),
             ipc_path: config.ipc_path.to_path_buf(),
             fetcher_socket: fetcher.get_socket(),
             query_runner,
+            sandbox: config.sandbox.clone(),
+            installer: Installer::new(
+                config.artifacts_path.to_path_buf(),
+                blockstore.clone(),
+                fetcher.get_socket(),
+            ),
+            rollback_after: config.rollback_after,
+            health_check: config.health_check.clone(),
+            restart: config.restart.clone(),
+            logs: config.logs.clone(),
+            kv: KvStore::open(&config.kv)?,
+            node_public_key: keystore.get_ed25519_pk(),
+            signer_socket: signer.get_socket(),
+            mempool_socket: forwarder.mempool_socket(),
+            notifier: notifier.clone(),
+            transactions: config.transactions.clone(),
         });
 
         Ok(ServiceExecutor {
@@ -86,9 +170,94 @@ async fn start(
         fdi::Cloned(this): fdi::Cloned<Self>,
         fdi::Cloned(waiter): fdi::Cloned<ShutdownWaiter>,
     ) {
-        for &id in this.config.services.iter() {
-            let handle = spawn_service(id, this.ctx.clone(), waiter.clone()).await;
-            this.collection.insert(id, handle);
+        // Subscribe before starting the services to not miss the blocks executed meanwhile.
+        let blocks = this.ctx.notifier.subscribe_block_executed();
+
+        let published = this
+            .ctx
+            .query_runner
+            .get_service_artifact_table_iter(|iter| iter.collect::<Vec<_>>());
+        let mut running = FxHashMap::default();
+        for id in this.config.services.iter().copied().chain(published) {
+            if !running.contains_key(&id) {
+                let stop = this.start_service(id).await;
+                running.insert(id, stop);
+            }
+        }
+
+        tokio::spawn(this.watch_registry(running, blocks, waiter));
+    }
+
+    /// Start a service, returning the controller stopping it.
+    async fn start_service(&self, id: ServiceId) -> ShutdownController {
+        let stop = ShutdownController::default();
+        let handle = spawn_service(
+            id,
+            self.ctx.clone(),
+            self.ctx.notifier.subscribe_block_executed(),
+            stop.waiter(),
+        )
+        .await;
+        self.collection.insert(id, handle);
+        stop
+    }
+
+    /// Start the services once an artifact is published for them on-chain, and stop the ones
+    /// removed on-chain. The installation of the new versions of the running services is left
+    /// to the services themselves.
+    async fn watch_registry(
+        self,
+        mut running: FxHashMap<ServiceId, ShutdownController>,
+        mut blocks: impl Subscriber<BlockExecutedNotification>,
+        waiter: ShutdownWaiter,
+    ) {
+        // Held until the services are stopped, to keep the node waiting for them.
+        let shutdown = waiter.wait_for_shutdown();
+        pin!(shutdown);
+
+        loop {
+            let notification = tokio::select! {
+                biased;
+                _ = &mut shutdown => break,
+                notification = blocks.recv() => match notification {
+                    Some(notification) => notification,
+                    None => break,
+                },
+            };
+
+            for receipt in &notification.response.txn_receipts {
+                if !matches!(receipt.response, TransactionResponse::Success(_)) {
+                    continue;
+                }
+                match &receipt.to {
+                    TransactionDestination::Fleek(
+                        UpdateMethod::AddService { service_id, .. }
+                        | UpdateMethod::UpgradeService { service_id, .. },
+                    ) => {
+                        let id = *service_id;
+                        if !running.contains_key(&id)
+                            && self.ctx.query_runner.get_service_artifact(&id).is_some()
+                        {
+                            info!("Starting service {id} published on-chain");
+                            let stop = self.start_service(id).await;
+                            running.insert(id, stop);
+                        }
+                    },
+                    TransactionDestination::Fleek(UpdateMethod::RemoveService { service_id }) => {
+                        let id = *service_id;
+                        if let Some(mut stop) = running.remove(&id) {
+                            info!("Stopping service {id} removed on-chain");
+                            self.collection.remove(id);
+                            stop.shutdown().await;
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        for (_, mut stop) in running {
+            stop.shutdown().await;
         }
     }
 }
@@ -126,6 +295,10 @@ fn run_service(id: u32) {
             1001 => {
                 crate::test_services::io_stress::main();
             },
+            #[cfg(target_os = "linux")]
+            1002 => {
+                crate::test_services::sandbox::main();
+            },
             _ => eprintln!("Service {id} not found."),
         }
     }
@@ -137,6 +310,13 @@ impl<C: Collection> ConfigConsumer for ServiceExecutor<C> {
 }
 
 impl ExecutorProviderInterface for Provider {
+    /// Returns true if the service is running and ready, and not crash looping.
+    fn is_available(&self, service_id: ServiceId) -> bool {
+        self.collection
+            .get(service_id)
+            .is_some_and(|handle| handle.health.is_available())
+    }
+
     /// Make a connection to the provided service.
     async fn connect(&self, service_id: ServiceId) -> Option<UnixStream> {
         let _ = self.collection.get(service_id)?;
@@ -1 +1,3 @@
 pub mod io_stress;
+#[cfg(target_os = "linux")]
+pub mod sandbox;
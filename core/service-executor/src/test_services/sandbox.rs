@@ -0,0 +1,221 @@
+//! A service probing the sandbox it runs in, replying to every connection with a [`Report`] of
+//! the limits it ran into.
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+
+use crate::sandbox::SandboxConfig;
+
+/// Stop probing the number of open files and threads after this many.
+const MAX_PROBE: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    /// The syscalls that should be denied but succeeded.
+    pub escapes: Vec<String>,
+    /// Whether a file could be created in the blockstore, after trying to make it writable.
+    pub blockstore_writable: bool,
+    /// The hidden paths whose contents are visible.
+    pub visible_hidden_paths: Vec<PathBuf>,
+    /// The number of files that could be opened.
+    pub open_files: usize,
+    /// The number of threads that could be spawned.
+    pub threads: usize,
+    /// The permitted and effective capabilities, from `/proc/self/status`.
+    pub capabilities: u64,
+    /// The cgroup of the service, from `/proc/self/cgroup`.
+    pub cgroup: String,
+}
+
+/// Probe the limits of the current process.
+pub fn probe(blockstore: &Path, hidden_paths: &[PathBuf]) -> Report {
+    // Read before probing the syscalls, which may enter a new user namespace.
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let capabilities = status
+        .lines()
+        .filter_map(|line| {
+            let value = line
+                .strip_prefix("CapPrm:")
+                .or_else(|| line.strip_prefix("CapEff:"))?;
+            u64::from_str_radix(value.trim(), 16).ok()
+        })
+        .fold(0, |caps, value| caps | value);
+
+    let escapes = probe_syscalls(blockstore)
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    let test_file = blockstore.join(".sandbox-probe");
+    let blockstore_writable = std::fs::write(&test_file, b"probe").is_ok();
+    let _ = std::fs::remove_file(&test_file);
+
+    let visible_hidden_paths = hidden_paths
+        .iter()
+        .filter(|path| {
+            std::fs::read_dir(path)
+                .map(|mut entries| entries.next().is_some())
+                .unwrap_or(false)
+        })
+        .cloned()
+        .collect();
+
+    let mut files = Vec::new();
+    while files.len() < MAX_PROBE {
+        match std::fs::File::open("/dev/null") {
+            Ok(file) => files.push(file),
+            Err(_) => break,
+        }
+    }
+    let open_files = files.len();
+    drop(files);
+
+    // The threads are kept alive until the lock is released.
+    let release = Arc::new(RwLock::new(()));
+    let guard = release.write().unwrap();
+    let mut handles = Vec::new();
+    while handles.len() < MAX_PROBE {
+        let release = release.clone();
+        let spawned = std::thread::Builder::new().spawn(move || {
+            drop(release.read());
+        });
+        match spawned {
+            Ok(handle) => handles.push(handle),
+            Err(_) => break,
+        }
+    }
+    let threads = handles.len();
+    drop(guard);
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    let cgroup = std::fs::read_to_string("/proc/self/cgroup").unwrap_or_default();
+
+    Report {
+        escapes,
+        blockstore_writable,
+        visible_hidden_paths,
+        open_files,
+        threads,
+        capabilities,
+        cgroup,
+    }
+}
+
+/// Try the syscalls a service could use to leave its sandbox, or to make the blockstore
+/// writable, returning the ones that succeeded.
+fn probe_syscalls(blockstore: &Path) -> Vec<&'static str> {
+    // The parts of `linux/mount.h` used below.
+    const OPEN_TREE_CLONE: libc::c_long = 1;
+    const MOUNT_ATTR_RDONLY: u64 = 1;
+    #[repr(C)]
+    struct MountAttr {
+        attr_set: u64,
+        attr_clr: u64,
+        propagation: u64,
+        userns_fd: u64,
+    }
+
+    let blockstore = CString::new(blockstore.as_os_str().as_bytes()).unwrap();
+    let mut escapes = Vec::new();
+
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_open_tree,
+            libc::AT_FDCWD,
+            blockstore.as_ptr(),
+            OPEN_TREE_CLONE,
+        )
+    };
+    if fd >= 0 {
+        unsafe { libc::close(fd as libc::c_int) };
+        escapes.push("open_tree");
+    }
+
+    let attr = MountAttr {
+        attr_set: 0,
+        attr_clr: MOUNT_ATTR_RDONLY,
+        propagation: 0,
+        userns_fd: 0,
+    };
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            libc::AT_FDCWD,
+            blockstore.as_ptr(),
+            0,
+            &attr as *const MountAttr,
+            std::mem::size_of::<MountAttr>(),
+        )
+    };
+    if res == 0 {
+        escapes.push("mount_setattr");
+    }
+
+    let fd = unsafe { libc::syscall(libc::SYS_fsopen, c"tmpfs".as_ptr(), 0) };
+    if fd >= 0 {
+        unsafe { libc::close(fd as libc::c_int) };
+        escapes.push("fsopen");
+    }
+
+    if unsafe { libc::unshare(libc::CLONE_NEWUSER) } == 0 {
+        escapes.push("unshare");
+    }
+
+    let pid = unsafe {
+        libc::syscall(
+            libc::SYS_clone,
+            libc::CLONE_NEWUSER | libc::SIGCHLD,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    if pid == 0 {
+        unsafe { libc::_exit(0) };
+    } else if pid > 0 {
+        unsafe { libc::waitpid(pid as libc::pid_t, std::ptr::null_mut(), 0) };
+        escapes.push("clone");
+    }
+
+    let mut params = [0u8; 120];
+    let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, 1, params.as_mut_ptr()) };
+    if fd >= 0 {
+        unsafe { libc::close(fd as libc::c_int) };
+        escapes.push("io_uring_setup");
+    }
+
+    escapes
+}
+
+#[tokio::main]
+pub async fn main() {
+    fn_sdk::ipc::init_from_env();
+    info!("Running sandbox probe service!");
+
+    let blockstore: PathBuf = std::env::var("BLOCKSTORE_PATH")
+        .expect("Expected BLOCKSTORE_PATH env")
+        .into();
+    let hidden_paths = SandboxConfig::default()
+        .hidden_paths
+        .into_iter()
+        .map(|path| path.to_path_buf())
+        .collect::<Vec<_>>();
+
+    let mut listener = fn_sdk::ipc::conn_bind().await;
+    while let Ok(mut conn) = listener.accept().await {
+        let report = probe(&blockstore, &hidden_paths);
+        let bytes = serde_json::to_vec(&report).expect("failed to serialize report");
+        if conn.start_write(bytes.len()).await.is_ok() {
+            let _ = conn.write_all(&bytes).await;
+        }
+    }
+}
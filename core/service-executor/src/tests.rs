@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -6,21 +7,35 @@
     ClientPublicKey,
     ConsensusSecretKey,
     EthAddress,
+    NodePublicKey,
     SecretKey,
+    TransactionSender,
 };
+use fn_sdk::ipc_types::SubmitTxError;
 use lightning_application::app::Application;
 use lightning_application::config::{Config as AppConfig, Mode, StorageConfig};
-use lightning_application::genesis::{Genesis, GenesisAccount};
+use lightning_application::genesis::{Genesis, GenesisAccount, GenesisNode};
 use lightning_blockstore::blockstore::Blockstore;
 use lightning_blockstore::config::Config as BlockstoreConfig;
 use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::{
+    ExecutionData,
+    NodePorts,
+    TransactionDestination,
+    TransactionResponse,
+    UpdateMethod,
+};
 use lightning_notifier::Notifier;
 use lightning_signer::Signer;
+use lightning_test_utils::consensus::{Config as ConsensusConfig, MockConsensus, MockForwarder};
 use lightning_test_utils::json_config::JsonConfigProvider;
 use lightning_test_utils::keys::EphemeralKeystore;
 use serial_test::serial;
 
+use crate::health::HealthCheckConfig;
+use crate::kv::KvConfig;
 use crate::shim::{ServiceExecutor, ServiceExecutorConfig};
+use crate::transactions::{TransactionPermissions, TransactionsConfig};
 
 partial!(TestBinding {
     ConfigProviderInterface = JsonConfigProvider;
@@ -30,6 +45,8 @@
     BlockstoreInterface = Blockstore<Self>;
     SignerInterface = Signer<Self>;
     ApplicationInterface = Application<Self>;
+    ForwarderInterface = MockForwarder<Self>;
+    ConsensusInterface = MockConsensus<Self>;
     //FetcherInterface = Fetcher<Self>;
     //OriginProviderInterface = OriginDemuxer<Self>;
     //BroadcastInterface = Broadcast<Self>;
@@ -46,26 +63,65 @@ async fn init_service_executor(
     genesis: Genesis,
     path: PathBuf,
     service_id: u32,
+) -> Node<TestBinding> {
+    init_service_executor_with(
+        genesis,
+        path,
+        service_id,
+        TransactionsConfig::default(),
+        EphemeralKeystore::default(),
+    )
+    .await
+}
+
+/// Same as [`init_service_executor`], with the transactions the service is allowed to submit and
+/// the keys of the node.
+async fn init_service_executor_with(
+    genesis: Genesis,
+    path: PathBuf,
+    service_id: u32,
+    transactions: TransactionsConfig,
+    keystore: EphemeralKeystore<TestBinding>,
 ) -> Node<TestBinding> {
     let node = Node::<TestBinding>::init_with_provider(
-        fdi::Provider::default().with(
-            JsonConfigProvider::default()
-                .with::<Blockstore<TestBinding>>(BlockstoreConfig {
-                    root: path.join("dummy_blockstore").try_into().unwrap(),
-                })
-                .with::<Application<TestBinding>>(AppConfig {
-                    genesis: Some(genesis),
-                    mode: Mode::Test,
-                    testnet: false,
-                    storage: StorageConfig::InMemory,
-                    db_path: None,
-                    db_options: None,
-                })
-                .with::<ServiceExecutor<TestBinding>>(ServiceExecutorConfig {
-                    services: [service_id].into_iter().collect(),
-                    ipc_path: path.join("ipc").try_into().unwrap(),
-                }),
-        ),
+        fdi::Provider::default()
+            .with(
+                JsonConfigProvider::default()
+                    .with::<Blockstore<TestBinding>>(BlockstoreConfig {
+                        root: path.join("dummy_blockstore").try_into().unwrap(),
+                    })
+                    .with::<Application<TestBinding>>(AppConfig {
+                        genesis: Some(genesis),
+                        mode: Mode::Test,
+                        testnet: false,
+                        storage: StorageConfig::InMemory,
+                        db_path: None,
+                        db_options: None,
+                    })
+                    .with::<ServiceExecutor<TestBinding>>(ServiceExecutorConfig {
+                        services: [service_id].into_iter().collect(),
+                        ipc_path: path.join("ipc").try_into().unwrap(),
+                        artifacts_path: path.join("services").try_into().unwrap(),
+                        health_check: HealthCheckConfig {
+                            interval: Duration::from_millis(200),
+                            ..Default::default()
+                        },
+                        kv: KvConfig {
+                            store_path: path.join("kv").try_into().unwrap(),
+                            ..Default::default()
+                        },
+                        transactions,
+                        ..ServiceExecutorConfig::test_default()
+                    })
+                    .with::<MockConsensus<TestBinding>>(ConsensusConfig {
+                        min_ordering_time: 0,
+                        max_ordering_time: 1,
+                        probability_txn_lost: 0.0,
+                        transactions_to_lose: HashSet::new(),
+                        new_block_interval: Duration::from_secs(5),
+                    }),
+            )
+            .with(keystore),
     )
     .expect("failed to initialize node");
 
@@ -159,3 +215,433 @@ async fn test_query_missing_client_info() {
 
     node.shutdown().await
 }
+
+#[tokio::test]
+#[serial]
+async fn test_health_check_readiness() {
+    let path = std::env::temp_dir().join("lightning-service-ex-test-3");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+
+    let mut genesis = Genesis::load().unwrap();
+    genesis.node_info.clear();
+
+    let mut node = init_service_executor(genesis, path.clone(), 1071).await;
+    let provider = node
+        .provider
+        .get::<ServiceExecutor<TestBinding>>()
+        .get_provider();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(!provider.is_available(1071));
+
+    // Start the service, which is ready once it accepts connections.
+    fn_sdk::ipc::init_from_env();
+    let _listener = fn_sdk::ipc::conn_bind().await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(provider.is_available(1071));
+
+    // Other services are never available.
+    assert!(!provider.is_available(1072));
+
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    node.shutdown().await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_health_check_legacy_service() {
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = std::env::temp_dir().join("lightning-service-ex-test-8");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+
+    let mut genesis = Genesis::load().unwrap();
+    genesis.node_info.clear();
+
+    let mut node = init_service_executor(genesis, path.clone(), 1073).await;
+    let provider = node
+        .provider
+        .get::<ServiceExecutor<TestBinding>>()
+        .get_provider();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // A service of an older SDK connects without ever answering the health checks.
+    let ipc_dir = path.join("ipc").join("service-1073");
+    let mut ctrl = UnixStream::connect(ipc_dir.join("ctrl")).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(!provider.is_available(1073));
+
+    // It's ready once it accepts connections, and is never pinged.
+    let _listener = UnixListener::bind(ipc_dir.join("conn")).unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(provider.is_available(1073));
+    let mut buf = [0; 8];
+    let read = tokio::time::timeout(Duration::from_secs(1), ctrl.read(&mut buf)).await;
+    assert!(read.is_err(), "the service was sent {read:?}");
+    assert!(provider.is_available(1073));
+
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    node.shutdown().await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_blockstore_put() {
+    let path = std::env::temp_dir().join("lightning-service-ex-test-4");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+
+    let mut genesis = Genesis::load().unwrap();
+    genesis.node_info.clear();
+
+    let mut node = init_service_executor(genesis, path.clone(), 1073).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // Start the service
+    fn_sdk::ipc::init_from_env();
+
+    let content = (0..300 << 10).map(|i| i as u8).collect::<Vec<_>>();
+    let hash = fn_sdk::blockstore::put(&content).await.unwrap();
+    let blockstore = node.provider.get::<Blockstore<TestBinding>>();
+    assert_eq!(
+        blockstore.read_all_to_vec(&hash).await,
+        Some(content.clone())
+    );
+
+    // Writing the content in parts results in the same content.
+    let mut putter = fn_sdk::blockstore::ContentPutter::new().await.unwrap();
+    for part in content.chunks(100 << 10) {
+        putter.write(part).await.unwrap();
+    }
+    assert_eq!(putter.finalize().await.unwrap(), hash);
+
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    node.shutdown().await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_kv() {
+    let path = std::env::temp_dir().join("lightning-service-ex-test-5");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+
+    let mut genesis = Genesis::load().unwrap();
+    genesis.node_info.clear();
+
+    let mut node = init_service_executor(genesis, path.clone(), 1074).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // Start the service
+    fn_sdk::ipc::init_from_env();
+
+    assert_eq!(fn_sdk::api::kv_get("a/1").await.unwrap(), None);
+    for key in ["a/1", "a/2", "b/1"] {
+        fn_sdk::api::kv_put(key, key.repeat(2)).await.unwrap();
+    }
+    assert_eq!(
+        fn_sdk::api::kv_get("a/1").await.unwrap(),
+        Some(b"a/1a/1".to_vec())
+    );
+
+    let entries = fn_sdk::api::kv_scan("a/", None, 10).await.unwrap();
+    assert_eq!(
+        entries,
+        [
+            (b"a/1".to_vec(), b"a/1a/1".to_vec()),
+            (b"a/2".to_vec(), b"a/2a/2".to_vec())
+        ]
+    );
+    let entries = fn_sdk::api::kv_scan("", Some(b"a/2"), 10).await.unwrap();
+    assert_eq!(entries, [(b"b/1".to_vec(), b"b/1b/1".to_vec())]);
+
+    fn_sdk::api::kv_delete("a/1").await.unwrap();
+    assert_eq!(fn_sdk::api::kv_get("a/1").await.unwrap(), None);
+
+    // Values over the maximum size are rejected.
+    let value = vec![0; KvConfig::default().max_value_size as usize + 1];
+    assert!(fn_sdk::api::kv_put("c", value).await.is_err());
+
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    node.shutdown().await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_query_node_state() {
+    let path = std::env::temp_dir().join("lightning-service-ex-test-6");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+
+    let mut genesis = Genesis::load().unwrap();
+    genesis.node_info.clear();
+    let owner = genesis.service[0].owner;
+
+    let mut node = init_service_executor(genesis, path.clone(), 1075).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // Start the service
+    fn_sdk::ipc::init_from_env();
+
+    assert_eq!(fn_sdk::api::query_epoch().await.epoch, 0);
+
+    let service = fn_sdk::api::query_service_info(0).await.unwrap();
+    assert_eq!(service.owner, owner);
+    assert_eq!(
+        service.commodity_type,
+        Some(fn_sdk::api::CommodityType::Bandwidth)
+    );
+    assert_eq!(fn_sdk::api::query_service_info(u32::MAX).await, None);
+
+    let public_key = NodePublicKey([7; 32]);
+    assert_eq!(fn_sdk::api::query_node_index(public_key).await, None);
+    assert_eq!(fn_sdk::api::query_node_info(0).await, None);
+    assert_eq!(fn_sdk::api::query_reputation(0).await, None);
+    assert_eq!(
+        fn_sdk::api::query_content_providers([0; 32]).await.unwrap(),
+        Vec::<u32>::new()
+    );
+
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    node.shutdown().await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_submit_transaction_not_allowed() {
+    let path = std::env::temp_dir().join("lightning-service-ex-test-7");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+
+    let mut genesis = Genesis::load().unwrap();
+    genesis.node_info.clear();
+
+    let mut node = init_service_executor(genesis, path.clone(), 1076).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // Start the service
+    fn_sdk::ipc::init_from_env();
+
+    // The services are not allowed to submit any transaction by default.
+    assert_eq!(
+        fn_sdk::api::submit_transaction(UpdateMethod::OptIn {}).await,
+        Err(SubmitTxError::NotAllowed)
+    );
+
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    node.shutdown().await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_submit_transaction_signed() {
+    let path = std::env::temp_dir().join("lightning-service-ex-test-9");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+
+    let keystore = EphemeralKeystore::<TestBinding>::default();
+    let node_public_key = keystore.get_ed25519_pk();
+    let owner_public_key = AccountOwnerSecretKey::generate().to_pk();
+
+    let mut genesis = Genesis::load().unwrap();
+    genesis.node_info = vec![GenesisNode::new(
+        owner_public_key.into(),
+        node_public_key,
+        "127.0.0.1".parse().unwrap(),
+        keystore.get_bls_pk(),
+        "127.0.0.1".parse().unwrap(),
+        node_public_key,
+        NodePorts {
+            primary: 48000,
+            worker: 48101,
+            mempool: 48102,
+            rpc: 48103,
+            pool: 48104,
+            pinger: 48106,
+            handshake: Default::default(),
+        },
+        None,
+        true,
+    )];
+
+    let transactions = TransactionsConfig {
+        allowed: [(
+            1077,
+            TransactionPermissions {
+                methods: vec!["OptIn".into()],
+                forward: false,
+            },
+        )]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+    let mut node =
+        init_service_executor_with(genesis, path.clone(), 1077, transactions, keystore).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // Start the service
+    fn_sdk::ipc::init_from_env();
+
+    // The node signs the allowed method, and the receipt of its transaction is returned.
+    let receipt = fn_sdk::api::submit_transaction(UpdateMethod::OptIn {})
+        .await
+        .unwrap();
+    assert_eq!(receipt.from, TransactionSender::NodeMain(node_public_key));
+    assert_eq!(
+        receipt.to,
+        TransactionDestination::Fleek(UpdateMethod::OptIn {})
+    );
+    assert_eq!(
+        receipt.response,
+        TransactionResponse::Success(ExecutionData::None)
+    );
+
+    // Other methods are still not signed.
+    assert_eq!(
+        fn_sdk::api::submit_transaction(UpdateMethod::OptOut {}).await,
+        Err(SubmitTxError::NotAllowed)
+    );
+
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    node.shutdown().await
+}
+
+/// Printed before the report of [`sandbox_probe_child`].
+#[cfg(target_os = "linux")]
+const SANDBOX_REPORT_PREFIX: &str = "sandbox report: ";
+
+/// Runs as the sandboxed child process of [`test_sandbox_limits`], and does nothing otherwise.
+#[cfg(target_os = "linux")]
+#[test]
+fn sandbox_probe_child() {
+    let Ok(blockstore) = std::env::var("SANDBOX_PROBE_BLOCKSTORE") else {
+        return;
+    };
+    let hidden = std::env::var("SANDBOX_PROBE_HIDDEN").unwrap();
+    let report = crate::test_services::sandbox::probe(blockstore.as_ref(), &[hidden.into()]);
+    println!(
+        "{SANDBOX_REPORT_PREFIX}{}",
+        serde_json::to_string(&report).unwrap()
+    );
+}
+
+/// Whether user and mount namespaces can be created, they are usually not allowed in containers.
+#[cfg(target_os = "linux")]
+fn namespaces_supported() -> bool {
+    use std::os::unix::process::CommandExt;
+
+    let mut command = std::process::Command::new("true");
+    unsafe {
+        command.pre_exec(|| {
+            if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+    command.status().map(|s| s.success()).unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_sandbox_limits() {
+    use crate::sandbox::{Sandbox, SandboxConfig, ServiceLimits};
+
+    let path = std::env::temp_dir().join("lightning-service-ex-test-sandbox");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    let blockstore = path.join("blockstore");
+    let hidden = path.join("keystore");
+    std::fs::create_dir_all(&blockstore).unwrap();
+    std::fs::create_dir_all(&hidden).unwrap();
+    std::fs::write(hidden.join("node.pem"), b"secret").unwrap();
+
+    // Creating cgroups needs a cgroup delegated to the user running the tests.
+    let cgroup = std::env::var("LIGHTNING_TEST_CGROUP")
+        .ok()
+        .map(PathBuf::from);
+    let namespaces = namespaces_supported();
+    let config = SandboxConfig {
+        enabled: true,
+        cgroup: cgroup.clone(),
+        mount_namespace: namespaces,
+        hidden_paths: vec![hidden.clone().try_into().unwrap()],
+        limits: ServiceLimits {
+            memory: Some(64 << 20),
+            pids: Some(32),
+            open_files: Some(64),
+            ..Default::default()
+        },
+        services: Vec::new(),
+    };
+
+    let mut command = tokio::process::Command::new(std::env::current_exe().unwrap());
+    command
+        .args(["--exact", "tests::sandbox_probe_child", "--nocapture"])
+        .env("SANDBOX_PROBE_BLOCKSTORE", &blockstore)
+        .env("SANDBOX_PROBE_HIDDEN", &hidden);
+    Sandbox::new(1002, &config, &blockstore)
+        .unwrap()
+        .apply_to(&mut command);
+    let output = command.output().await.unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let report: crate::test_services::sandbox::Report = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix(SANDBOX_REPORT_PREFIX))
+        .map(|report| serde_json::from_str(report).unwrap())
+        .expect("missing sandbox report");
+
+    // A few files are already open by the test harness.
+    assert!(report.open_files < 64);
+    // Threads are still spawned with `clone3` denied.
+    assert!(report.threads > 0);
+    assert!(report.escapes.is_empty(), "{:?}", report.escapes);
+    assert_eq!(report.capabilities, 0);
+    if namespaces {
+        assert!(!report.blockstore_writable);
+        assert!(report.visible_hidden_paths.is_empty());
+    }
+    if let Some(cgroup) = cgroup {
+        assert!(report.cgroup.trim_end().ends_with("/service-1002"));
+        assert!(report.threads < 32);
+        let memory = std::fs::read_to_string(cgroup.join("service-1002/memory.max")).unwrap();
+        assert_eq!(memory.trim(), (64 << 20).to_string());
+    }
+
+    std::fs::remove_dir_all(&path).unwrap();
+}
@@ -0,0 +1,234 @@
+//! Submission of transactions by the services.
+//!
+//! A service can only submit transactions if it's allowed to in the config: either update methods
+//! signed by the node, restricted to the methods listed for the service, or update requests
+//! already signed by a client, which are forwarded to the mempool as they are.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use fleek_crypto::{NodePublicKey, TransactionSender};
+use fn_sdk::ipc_types::SubmitTxError;
+use lightning_interfaces::prelude::*;
+use lightning_interfaces::types::{
+    BlockExecutedNotification,
+    ServiceId,
+    TransactionReceipt,
+    TransactionRequest,
+    UpdateMethod,
+    UpdateRequest,
+};
+use serde::{Deserialize, Serialize};
+
+/// The maximum size in bytes of a transaction staged by a service.
+pub const MAX_TRANSACTION_SIZE: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionsConfig {
+    /// The services allowed to submit transactions. Services not listed can't submit any.
+    pub allowed: HashMap<ServiceId, TransactionPermissions>,
+    /// How long to wait for a submitted transaction to be executed.
+    pub timeout: Duration,
+}
+
+impl Default for TransactionsConfig {
+    fn default() -> Self {
+        Self {
+            allowed: HashMap::new(),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionPermissions {
+    /// The update methods the node signs for the service, by their name, e.g. `"OptIn"`.
+    pub methods: Vec<String>,
+    /// Whether the service can forward the update requests signed by clients.
+    pub forward: bool,
+}
+
+impl TransactionsConfig {
+    /// Returns true if the service can have the method signed by the node.
+    pub fn can_sign(&self, id: ServiceId, method: &UpdateMethod) -> bool {
+        let Some(permissions) = self.allowed.get(&id) else {
+            return false;
+        };
+        let name = method_name(method);
+        permissions.methods.iter().any(|allowed| *allowed == name)
+    }
+
+    /// Returns true if the service can forward the requests signed by clients.
+    pub fn can_forward(&self, id: ServiceId) -> bool {
+        self.allowed
+            .get(&id)
+            .map(|permissions| permissions.forward)
+            .unwrap_or(false)
+    }
+}
+
+/// Returns the name of the variant of the update method.
+pub fn method_name(method: &UpdateMethod) -> &'static str {
+    match method {
+        UpdateMethod::SubmitDeliveryAcknowledgmentAggregation { .. } => {
+            "SubmitDeliveryAcknowledgmentAggregation"
+        },
+        UpdateMethod::Withdraw { .. } => "Withdraw",
+        UpdateMethod::Deposit { .. } => "Deposit",
+        UpdateMethod::Transfer { .. } => "Transfer",
+        UpdateMethod::Stake { .. } => "Stake",
+        UpdateMethod::StakeLock { .. } => "StakeLock",
+        UpdateMethod::Unstake { .. } => "Unstake",
+        UpdateMethod::WithdrawUnstaked { .. } => "WithdrawUnstaked",
+        UpdateMethod::ChangeEpoch { .. } => "ChangeEpoch",
+        UpdateMethod::AddService { .. } => "AddService",
+        UpdateMethod::RemoveService { .. } => "RemoveService",
+        UpdateMethod::Slash { .. } => "Slash",
+        UpdateMethod::SubmitReputationMeasurements { .. } => "SubmitReputationMeasurements",
+        UpdateMethod::ChangeProtocolParam { .. } => "ChangeProtocolParam",
+        UpdateMethod::OptOut { .. } => "OptOut",
+        UpdateMethod::OptIn { .. } => "OptIn",
+        UpdateMethod::UpdateContentRegistry { .. } => "UpdateContentRegistry",
+        UpdateMethod::UpgradeService { .. } => "UpgradeService",
+    }
+}
+
+/// A transaction submitted by a service, as staged by the SDK.
+pub enum ServiceTransaction {
+    /// An update method to sign by the node.
+    Sign(UpdateMethod),
+    /// An update request signed by a client.
+    Forward(UpdateRequest),
+}
+
+impl ServiceTransaction {
+    pub fn decode(content: &[u8], signed_by_node: bool) -> Option<Self> {
+        if signed_by_node {
+            bincode::deserialize(content).ok().map(Self::Sign)
+        } else {
+            bincode::deserialize(content).ok().map(Self::Forward)
+        }
+    }
+}
+
+/// Wait until a transaction matching the predicate is executed, and return its receipt.
+///
+/// The subscriber must be created before the transaction is submitted, so its block is not
+/// missed.
+pub async fn wait_for_receipt(
+    mut blocks: impl Subscriber<BlockExecutedNotification>,
+    timeout: Duration,
+    mut matches: impl FnMut(&TransactionRequest) -> bool,
+) -> Result<TransactionReceipt, SubmitTxError> {
+    let wait = async {
+        while let Some(notification) = blocks.recv().await {
+            let Some(tx) = notification
+                .block
+                .transactions
+                .iter()
+                .find(|tx| matches(tx))
+            else {
+                continue;
+            };
+            let hash = tx.hash();
+            return notification
+                .response
+                .txn_receipts
+                .into_iter()
+                .find(|receipt| receipt.transaction_hash == hash)
+                .ok_or(SubmitTxError::Failed);
+        }
+        Err(SubmitTxError::Failed)
+    };
+    tokio::time::timeout(timeout, wait)
+        .await
+        .unwrap_or(Err(SubmitTxError::Timeout))
+}
+
+/// Returns true if the transaction is the method signed by the node with the nonce assigned by the
+/// signer. The signer re-sequences the nonces of transactions that were not ordered in time, so
+/// another transaction of the node may end up with the nonce.
+pub fn is_signed_by(
+    tx: &TransactionRequest,
+    node: NodePublicKey,
+    nonce: u64,
+    method: &UpdateMethod,
+) -> bool {
+    match tx {
+        TransactionRequest::UpdateRequest(request) => {
+            request.payload.sender == TransactionSender::NodeMain(node)
+                && request.payload.nonce == nonce
+                && request.payload.method == *method
+        },
+        TransactionRequest::EthereumRequest(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fleek_crypto::{NodeSignature, TransactionSignature};
+    use lightning_interfaces::types::UpdatePayload;
+
+    use super::*;
+
+    #[test]
+    fn test_permissions() {
+        let config = TransactionsConfig {
+            allowed: [(
+                7,
+                TransactionPermissions {
+                    methods: vec!["OptIn".into()],
+                    forward: false,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        assert!(config.can_sign(7, &UpdateMethod::OptIn {}));
+        assert!(!config.can_sign(7, &UpdateMethod::OptOut {}));
+        assert!(!config.can_sign(8, &UpdateMethod::OptIn {}));
+        assert!(!config.can_forward(7));
+        assert!(!config.can_forward(8));
+    }
+
+    #[test]
+    fn test_is_signed_by() {
+        let node = NodePublicKey([1; 32]);
+        let other = NodePublicKey([2; 32]);
+        let tx = TransactionRequest::UpdateRequest(UpdateRequest {
+            signature: TransactionSignature::NodeMain(NodeSignature([0; 64])),
+            payload: UpdatePayload {
+                sender: TransactionSender::NodeMain(node),
+                nonce: 3,
+                secondary_nonce: 0,
+                method: UpdateMethod::OptIn {},
+                chain_id: 0,
+            },
+        });
+
+        assert!(is_signed_by(&tx, node, 3, &UpdateMethod::OptIn {}));
+        assert!(!is_signed_by(&tx, node, 4, &UpdateMethod::OptIn {}));
+        assert!(!is_signed_by(&tx, other, 3, &UpdateMethod::OptIn {}));
+        // Another transaction of the node that was given the nonce after a re-sequence.
+        assert!(!is_signed_by(&tx, node, 3, &UpdateMethod::OptOut {}));
+    }
+
+    #[test]
+    fn test_method_name() {
+        assert_eq!(method_name(&UpdateMethod::OptIn {}), "OptIn");
+        let method = UpdateMethod::SubmitDeliveryAcknowledgmentAggregation {
+            commodity: 0,
+            service_id: 0,
+            proofs: vec![],
+            metadata: None,
+        };
+        assert_eq!(
+            method_name(&method),
+            "SubmitDeliveryAcknowledgmentAggregation"
+        );
+    }
+}
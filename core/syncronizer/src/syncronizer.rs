@@ -7,6 +7,7 @@
     Blake3Hash,
     Epoch,
     EpochInfo,
+    FetchPriority,
     NodeIndex,
     NodeInfo,
     Participation,
@@ -308,6 +309,7 @@ async fn ask_bootstrap_nodes<T: DeserializeOwned>(&self, req: String) -> Result<
                 .run(ServerRequest {
                     hash: checkpoint_hash,
                     peer: *node_index,
+                    priority: FetchPriority::Interactive,
                 })
                 .await
                 .expect("Failed to send blockstore server request");
@@ -1,9 +1,10 @@
-use crate::{Blake3Hash, NodeIndex, RejectReason};
+use crate::{Blake3Hash, FetchPriority, NodeIndex, RejectReason};
 
 #[derive(Clone, Debug)]
 pub struct ServerRequest {
     pub hash: Blake3Hash,
     pub peer: NodeIndex,
+    pub priority: FetchPriority,
 }
 
 #[derive(Debug, Clone, thiserror::Error)]
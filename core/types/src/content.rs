@@ -6,6 +6,7 @@
 
 const HTTP_ORIGIN: &str = "http";
 const IPFS_ORIGIN: &str = "ipfs";
+const S3_ORIGIN: &str = "s3";
 
 /// An immutable pointer is used as a general address to a content living off Fleek Network.
 ///
@@ -36,6 +37,7 @@ fn from_str(s: &str) -> Result<Self, Self::Err> {
                     .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
                 (OriginProvider::IPFS, cid.to_bytes())
             },
+            S3_ORIGIN => (OriginProvider::S3, uri.to_string().into_bytes()),
             ty => {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
@@ -56,6 +58,7 @@ fn from_str(s: &str) -> Result<Self, Self::Err> {
 pub enum OriginProvider {
     IPFS,
     HTTP,
+    S3,
 }
 
 impl ToString for OriginProvider {
@@ -63,6 +66,7 @@ fn to_string(&self) -> String {
         match self {
             OriginProvider::IPFS => String::from("ipfs"),
             OriginProvider::HTTP => String::from("http"),
+            OriginProvider::S3 => String::from("s3"),
         }
     }
 }
@@ -94,6 +98,14 @@ fn test_parse_immutable_pointer() {
                 .unwrap();
         assert_eq!(expected_pointer_ipfs, parsed_pointer_ipfs);
 
+        let expected_pointer_s3 = ImmutablePointer {
+            origin: OriginProvider::S3,
+            uri: b"s3://bucket/path/to/key?versionId=1".to_vec(),
+        };
+        let parsed_pointer_s3: ImmutablePointer =
+            "s3=s3://bucket/path/to/key?versionId=1".parse().unwrap();
+        assert_eq!(expected_pointer_s3, parsed_pointer_s3);
+
         assert_eq!(
             "https://lightning.com"
                 .parse::<ImmutablePointer>()
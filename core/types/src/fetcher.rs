@@ -1,11 +1,18 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
 use crate::{Blake3Hash, ImmutablePointer};
 
 #[derive(Clone, Debug)]
 pub enum FetcherRequest {
-    Put { pointer: ImmutablePointer },
-    Fetch { hash: Blake3Hash },
+    Put {
+        pointer: ImmutablePointer,
+        priority: FetchPriority,
+    },
+    Fetch {
+        hash: Blake3Hash,
+        priority: FetchPriority,
+    },
 }
 
 #[derive(Debug)]
@@ -13,3 +20,38 @@ pub enum FetcherResponse {
     Put(Result<Blake3Hash>),
     Fetch(Result<()>),
 }
+
+/// The priority of a fetch. When the fetcher or the blockstore server are at capacity, queued
+/// requests with a higher priority are started first, and requests with the same priority are
+/// started in the order they were submitted.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum FetchPriority {
+    /// Prefetching and replication, nobody is waiting on the result.
+    Background,
+    #[default]
+    Normal,
+    /// A client is waiting on the result.
+    Interactive,
+}
+
+impl From<u8> for FetchPriority {
+    /// Unknown values are mapped to [`FetchPriority::Normal`].
+    fn from(value: u8) -> Self {
+        match value {
+            0 => FetchPriority::Background,
+            2 => FetchPriority::Interactive,
+            _ => FetchPriority::Normal,
+        }
+    }
+}
+
+/// The progress of a fetch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FetchProgress {
+    /// The number of bytes received so far.
+    pub bytes: u64,
+    /// The number of blocks received so far.
+    pub blocks: u64,
+}
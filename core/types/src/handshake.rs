@@ -0,0 +1,23 @@
+use std::net::IpAddr;
+
+use fleek_crypto::ClientPublicKey;
+use serde::{Deserialize, Serialize};
+
+/// A connection of a client to a service through the handshake.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandshakeConnection {
+    pub id: u64,
+    pub client: ClientPublicKey,
+    /// The address the connection was made from, if the transport knows it.
+    pub ip: Option<IpAddr>,
+    /// The transport of the most recent primary or secondary connection.
+    pub transport: String,
+    pub service: u32,
+    /// The number of bytes of service payloads received from the client.
+    pub bytes_in: u64,
+    /// The number of bytes of service payloads sent by the service.
+    pub bytes_out: u64,
+    /// When the access token of the connection expires, in milliseconds since the unix epoch.
+    /// `None` if no token was requested.
+    pub token_expiry: Option<u64>,
+}
@@ -11,6 +11,7 @@
 mod content_registry;
 mod dack_aggregator;
 mod fetcher;
+mod handshake;
 mod misbehavior;
 mod pool;
 mod reputation;
@@ -30,6 +31,7 @@
 pub use content_registry::*;
 pub use dack_aggregator::*;
 pub use fetcher::*;
+pub use handshake::*;
 pub use misbehavior::*;
 pub use pool::*;
 pub use reputation::*;
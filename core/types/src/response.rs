@@ -147,4 +147,6 @@ pub enum ExecutionError {
     TooManyMeasurements,
     TooManyUpdates,
     TooManyUpdatesForContent,
+    OnlyServiceOwner,
+    InvalidServiceVersion,
 }
@@ -345,6 +345,16 @@ pub struct Service {
     pub slashing: (),
 }
 
+/// The binary of a service, fetched by its hash and run by the nodes. It is stored in its own
+/// table, keyed by the id of the service, rather than in [`Service`].
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Hash, Eq, PartialEq, schemars::JsonSchema)]
+pub struct ServiceArtifact {
+    /// The blake3 hash of the binary.
+    pub hash: [u8; 32],
+    /// The version of the service, increased with every upgrade.
+    pub version: u32,
+}
+
 #[derive(Debug, Hash, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize, Clone, Default)]
 pub struct Committee {
     pub members: Vec<NodeIndex>,
@@ -362,6 +372,16 @@ fn to_transcript_builder_input(&self) -> Vec<u8> {
     }
 }
 
+impl TranscriptBuilderInput for ServiceArtifact {
+    const TYPE: &'static str = "service_artifact";
+
+    fn to_transcript_builder_input(&self) -> Vec<u8> {
+        let mut input = self.hash.to_vec();
+        input.extend_from_slice(&self.version.to_be_bytes());
+        input
+    }
+}
+
 impl TranscriptBuilderInput for Tokens {
     const TYPE: &'static str = "Tokens";
 
@@ -23,6 +23,7 @@
     ProtocolParams,
     ReputationMeasurements,
     Service,
+    ServiceArtifact,
     ServiceId,
     Tokens,
 };
@@ -410,6 +411,11 @@ pub enum UpdateMethod {
     /// provided by the network and the corresponding nodes that
     /// are providing that content.
     UpdateContentRegistry { updates: Vec<ContentUpdate> },
+    /// Upgrading the binary of a service to a new version
+    UpgradeService {
+        service_id: ServiceId,
+        artifact: ServiceArtifact,
+    },
 }
 
 impl ToDigest for UpdatePayload {
@@ -628,6 +634,16 @@ fn transcript(&self) -> TranscriptBuilder {
                         .with("remove", &(update.remove as u8));
                 }
             },
+            UpdateMethod::UpgradeService {
+                service_id,
+                artifact,
+            } => {
+                transcript_builder = transcript_builder
+                    .with("transaction_name", &"upgrade_service")
+                    .with_prefix("input".to_owned())
+                    .with("service_id", service_id)
+                    .with("artifact", artifact);
+            },
         }
 
         transcript_builder
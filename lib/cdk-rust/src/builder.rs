@@ -1,9 +1,10 @@
 use anyhow::Result;
-use fleek_crypto::ClientPublicKey;
+use fleek_crypto::{ClientPublicKey, ClientSecretKey, SecretKey};
 
 use crate::connection::{Connector, PrimaryConnection, SecondaryConnection};
 use crate::context::Context;
 use crate::mode::{Mode, ModeSetting, PrimaryMode, SecondaryMode};
+use crate::schema::ANONYMOUS_CLIENT;
 use crate::transport::Transport;
 
 pub struct AttachedTransport<T>(T);
@@ -20,8 +21,7 @@ impl<M, T> Builder<M, T>
     M: Mode,
     T: Transport,
 {
-    // Todo: Remove this method.
-    // Get public key from the secret.
+    // Todo: Remove this method, the public key is derived from the secret key.
     pub fn pk(self, pk: ClientPublicKey) -> Builder<M, T> {
         Builder {
             mode: self.mode,
@@ -45,7 +45,7 @@ impl<T: Transport> Builder<PrimaryMode, T> {
     pub fn primary(client_secret_key: [u8; 32], service_id: u32) -> Builder<PrimaryMode, T> {
         Self {
             mode: PrimaryMode {
-                _client_secret_key: client_secret_key,
+                client_secret_key,
                 service_id,
             },
             transport: None,
@@ -59,11 +59,14 @@ pub fn build(self) -> Result<Connector<PrimaryConnection<T>, T>> {
         // This unwrap is safe because `transport()` because this method is only available
         // after attaching a transport.
         let transport = self.transport.unwrap().0;
-        let ctx = Context::new(
-            ModeSetting::Primary(self.mode),
-            // Todo: better default?
-            self.pk.unwrap_or(ClientPublicKey([1; 96])),
-        );
+        // Without a valid secret key the client can't prove the possession of any key, so it
+        // connects anonymously.
+        let pk = self.pk.unwrap_or_else(|| {
+            ClientSecretKey::from_bytes(&self.mode.client_secret_key)
+                .map(|sk| sk.to_pk())
+                .unwrap_or(ANONYMOUS_CLIENT)
+        });
+        let ctx = Context::new(ModeSetting::Primary(self.mode), pk);
         Ok(Connector::new(transport, ctx))
     }
 }
@@ -1,12 +1,22 @@
+use std::collections::VecDeque;
 use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use anyhow::Result;
-use bytes::Bytes;
-use fleek_crypto::{ClientPublicKey, ClientSignature};
+use anyhow::{anyhow, bail, Result};
+use bytes::{Bytes, BytesMut};
+use fleek_crypto::{ClientPublicKey, ClientSecretKey, ClientSignature, SecretKey};
+use tokio::time::Instant;
 
 use crate::context::Context;
 use crate::mode::{ModeSetting, PrimaryMode, SecondaryMode};
-use crate::schema::{HandshakeRequestFrame, RequestFrame, ResponseFrame};
+use crate::schema::{
+    proof_of_possession_message,
+    HandshakeRequestFrame,
+    RequestFrame,
+    ResponseFrame,
+    TerminationReason,
+};
 use crate::transport::{Transport, TransportReceiver, TransportSender};
 
 pub async fn connect<T: Transport>(
@@ -17,9 +27,23 @@ pub async fn connect<T: Transport>(
 
     match ctx.mode() {
         ModeSetting::Primary(setting) => {
-            start_handshake::<T>(&mut sender, setting, *ctx.pk()).await?
+            // Clients without a valid secret key connect anonymously, and the node ignores
+            // their proof of possession.
+            let pk = *ctx.pk();
+            let pop = ClientSecretKey::from_bytes(&setting.client_secret_key)
+                .map(|sk| sk.sign(&proof_of_possession_message(&pk)))
+                .unwrap_or(ClientSignature([0; 48]));
+            start_handshake::<T>(&mut sender, setting, pk, None, pop).await?
+        },
+        ModeSetting::Secondary(setting) => {
+            // Connectors created from a primary connection always use its latest token.
+            let access_token = match ctx.token().and_then(|token| *token.lock().unwrap()) {
+                Some(token) if token.is_expired() => bail!("the access token has expired"),
+                Some(token) => token.token,
+                None => setting.access_token,
+            };
+            join_connection::<T>(&mut sender, access_token).await?
         },
-        ModeSetting::Secondary(setting) => join_connection::<T>(&mut sender, setting).await?,
     }
 
     Ok((sender, receiver))
@@ -29,13 +53,14 @@ async fn start_handshake<T: Transport>(
     stream: &mut T::Sender,
     setting: &PrimaryMode,
     pk: ClientPublicKey,
+    retry: Option<u64>,
+    pop: ClientSignature,
 ) -> Result<()> {
     let frame = HandshakeRequestFrame::Handshake {
-        retry: None,
+        retry,
         service: setting.service_id,
         pk,
-        // Todo: Create signature.
-        pop: ClientSignature([0; 48]),
+        pop,
     }
     .encode();
     stream.send(frame.as_ref()).await?;
@@ -47,12 +72,9 @@ async fn start_handshake<T: Transport>(
 
 async fn join_connection<T: Transport>(
     stream: &mut T::Sender,
-    setting: &SecondaryMode,
+    access_token: [u8; 48],
 ) -> Result<()> {
-    let frame = HandshakeRequestFrame::JoinRequest {
-        access_token: setting.access_token,
-    }
-    .encode();
+    let frame = HandshakeRequestFrame::JoinRequest { access_token }.encode();
     stream.send(frame.as_ref()).await?;
 
     // Todo: Complete JOIN.
@@ -79,11 +101,64 @@ pub(crate) fn new(transport: T, ctx: Context) -> Self {
 impl<T: Transport> Connector<PrimaryConnection<T>, T> {
     pub async fn connect(&self) -> Result<PrimaryConnection<T>> {
         let (sender, receiver) = connect(&self.transport, &self.ctx).await?;
+        let token = SharedToken::default();
         let inner = InnerConnection {
-            sender: Sender { inner: sender },
-            receiver: Receiver { inner: receiver },
+            sender: Sender::new(sender, Some(token.clone())),
+            receiver: Receiver::new(receiver),
+        };
+        Ok(PrimaryConnection {
+            inner,
+            token,
+            pk: *self.ctx.pk(),
+            node_pk: [0; 32],
+        })
+    }
+
+    /// Connect in primary mode, resuming the connection automatically whenever the transport
+    /// is lost. Responses that were lost along with the transport are replayed by the node.
+    ///
+    /// The client secret key must be valid, and match the public key of the connector if one
+    /// was set, since the node only lets the client sign its way back into the connection.
+    pub async fn connect_resumable(self) -> Result<ResumableConnection<T>> {
+        let ModeSetting::Primary(setting) = self.ctx.mode() else {
+            unreachable!("primary connectors always have a primary mode");
+        };
+        let sk = ClientSecretKey::from_bytes(&setting.client_secret_key).ok_or(anyhow!(
+            "resuming a connection requires a valid client secret key"
+        ))?;
+
+        let (mut sender, mut receiver) = connect(&self.transport, &self.ctx).await?;
+
+        // The connection id is the start of the access token, and the proof needed to resume
+        // the connection is the signature of the token.
+        sender
+            .send(RequestFrame::AccessToken { ttl: 0 }.encode().as_ref())
+            .await?;
+        let bytes = receiver
+            .recv()
+            .await
+            .ok_or(anyhow!("transport connection closed during the handshake"))?;
+        let (connection_id, pop) = match ResponseFrame::decode(&bytes)? {
+            ResponseFrame::AccessToken { access_token, .. } => (
+                u64::from_be_bytes(*arrayref::array_ref![access_token, 0, 8]),
+                sk.sign(access_token.as_ref()),
+            ),
+            ResponseFrame::Termination { reason } => {
+                return Err(anyhow!("connection terminated: {reason:?}"));
+            },
+            _ => return Err(anyhow!("received an invalid frame during the handshake")),
         };
-        Ok(PrimaryConnection { inner })
+
+        Ok(ResumableConnection {
+            connector: self,
+            connection_id,
+            pop,
+            sender,
+            receiver,
+            received: 0,
+            chunks: BytesMut::new(),
+            terminated: false,
+        })
     }
 }
 
@@ -91,10 +166,13 @@ impl<T: Transport> Connector<SecondaryConnection<T>, T> {
     pub async fn connect(&self) -> Result<SecondaryConnection<T>> {
         let (sender, receiver) = connect(&self.transport, &self.ctx).await?;
         let inner = InnerConnection {
-            sender: Sender { inner: sender },
-            receiver: Receiver { inner: receiver },
+            sender: Sender::new(sender, None),
+            receiver: Receiver::new(receiver),
         };
-        Ok(SecondaryConnection { inner })
+        Ok(SecondaryConnection {
+            inner,
+            token: self.ctx.token().cloned(),
+        })
     }
 }
 
@@ -103,38 +181,169 @@ struct InnerConnection<T: Transport> {
     receiver: Receiver<T>,
 }
 
+/// An access token of a primary connection, which secondary connections use to join it.
+#[derive(Clone, Copy, Debug)]
+pub struct AccessToken {
+    token: [u8; 48],
+    expires_at: Instant,
+}
+
+impl AccessToken {
+    fn new(token: [u8; 48], ttl: u64) -> Self {
+        Self {
+            token,
+            expires_at: Instant::now() + Duration::from_secs(ttl),
+        }
+    }
+
+    pub fn token(&self) -> [u8; 48] {
+        self.token
+    }
+
+    /// Returns the id of the connection the token belongs to.
+    pub fn connection_id(&self) -> u64 {
+        u64::from_be_bytes(*arrayref::array_ref![self.token, 0, 8])
+    }
+
+    pub fn expires_at(&self) -> Instant {
+        self.expires_at
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Instant::now()
+    }
+}
+
+/// The access token of a primary connection, shared with its sender and with the secondary
+/// connections created from it.
+pub(crate) type SharedToken = Arc<Mutex<Option<AccessToken>>>;
+
+/// Extend the access token for `ttl` seconds whenever it expires in less than `margin`.
+#[derive(Clone, Copy)]
+struct TokenRefresh {
+    ttl: u64,
+    margin: Duration,
+}
+
 // These primary and secondary connection objects
 // allow us to restrict some operations that
 // are only allowed for specific type of connection.
 pub struct PrimaryConnection<T: Transport> {
     inner: InnerConnection<T>,
+    token: SharedToken,
+    /// The public key of the client, shared with the secondary connectors.
+    pk: ClientPublicKey,
+    node_pk: [u8; 32],
 }
 
 impl<T: Transport> PrimaryConnection<T> {
+    /// Request an access token valid for `ttl` seconds, returning the ttl granted by the node.
+    /// Payloads received while waiting for the token are kept for [`PrimaryConnection::recv`].
     pub async fn request_access_token(&mut self, ttl: u64) -> Result<(u64, Box<[u8; 48]>)> {
         self.inner
             .sender
-            .send(RequestFrame::AccessToken { ttl }.encode())
+            .inner
+            .send(RequestFrame::AccessToken { ttl }.encode().as_ref())
             .await?;
-        match self.inner.receiver.recv().await.ok_or(anyhow::anyhow!(
-            "failed to request an access token: transport connection closed"
-        ))?? {
-            ResponseFrame::AccessToken { ttl, access_token } => Ok((ttl, access_token)),
-            ResponseFrame::Termination { reason } => {
-                Err(anyhow::anyhow!("failed to get token: {reason:?}"))
-            },
-            ResponseFrame::ServicePayload { .. } => {
-                // This assumes that the server will not send any frame until we do.
-                // This assumption may not be true in the future.
-                panic!("received an invalid frame: received a service payload frame");
-            },
-            _ => unimplemented!(),
+
+        loop {
+            let bytes = self.inner.receiver.inner.recv().await.ok_or(anyhow!(
+                "failed to request an access token: transport connection closed"
+            ))?;
+            match ResponseFrame::decode(&bytes)? {
+                ResponseFrame::AccessToken { ttl, access_token } => {
+                    *self.token.lock().unwrap() = Some(AccessToken::new(*access_token, ttl));
+                    return Ok((ttl, access_token));
+                },
+                ResponseFrame::Termination { reason } => {
+                    self.inner
+                        .receiver
+                        .pending
+                        .push_back(ResponseFrame::Termination { reason });
+                    return Err(anyhow!("failed to get token: {reason:?}"));
+                },
+                frame => self.inner.receiver.pending.push_back(frame),
+            }
+        }
+    }
+
+    /// Extend the access token so it is valid for at least `ttl` more seconds.
+    pub async fn extend_access_token(&mut self, ttl: u64) -> Result<()> {
+        self.inner.sender.extend_access_token(ttl).await
+    }
+
+    /// Returns the access token of the connection, if one was requested.
+    pub fn access_token(&self) -> Option<AccessToken> {
+        *self.token.lock().unwrap()
+    }
+
+    /// Extend the access token for `ttl` seconds whenever it expires in less than `margin`. The
+    /// token is refreshed while the connection is used to send or receive payloads, including
+    /// by the sender after the connection is split.
+    ///
+    /// The `ttl` must be longer than the `margin`, otherwise the token would be due for another
+    /// refresh as soon as it is extended.
+    pub fn refresh_access_token(&mut self, ttl: u64, margin: Duration) -> Result<()> {
+        if Duration::from_secs(ttl) <= margin {
+            bail!("the ttl of the access token must be longer than the refresh margin");
         }
+        self.inner.sender.refresh = Some(TokenRefresh { ttl, margin });
+        Ok(())
+    }
+
+    /// Set the public key of the node, used by the secondary connectors created from this
+    /// connection.
+    pub fn set_node_pk(&mut self, node_pk: [u8; 32]) {
+        self.node_pk = node_pk;
+    }
+
+    /// Create a connector for secondary connections that join this connection. Every
+    /// secondary connection uses the current access token, so they keep working as long as the
+    /// token is extended. The node only keeps the most recent secondary connection, the
+    /// previous one is terminated with [`TerminationReason::ConnectionInUse`].
+    pub fn secondary_connector<U: Transport>(
+        &self,
+        transport: U,
+    ) -> Result<Connector<SecondaryConnection<U>, U>> {
+        let token = self
+            .access_token()
+            .ok_or(anyhow!("an access token must be requested first"))?;
+        let ctx = Context::with_token(
+            ModeSetting::Secondary(SecondaryMode {
+                access_token: token.token,
+                _node_pk: self.node_pk,
+            }),
+            self.pk,
+            self.token.clone(),
+        );
+        Ok(Connector::new(transport, ctx))
+    }
+
+    /// Send a payload to the service.
+    ///
+    /// Cancel safety: This method is not cancel-safe.
+    pub async fn send(&mut self, data: Bytes) -> Result<()> {
+        self.inner.sender.send(data).await
     }
 
-    pub async fn extend_access_token(&mut self, _: usize) -> Result<()> {
-        // Todo: discuss what is the response given this request.
-        todo!()
+    /// Receive the next complete payload from the service, see [`Receiver::recv_payload`]. The
+    /// access token is refreshed while waiting, if enabled.
+    ///
+    /// Cancel safety: This method is cancel-safe.
+    pub async fn recv(&mut self) -> Option<Result<Bytes>> {
+        loop {
+            let Some(deadline) = self.inner.sender.refresh_deadline() else {
+                return self.inner.receiver.recv_payload().await;
+            };
+            tokio::select! {
+                res = self.inner.receiver.recv_payload() => return res,
+                _ = tokio::time::sleep_until(deadline) => {
+                    if let Err(e) = self.inner.sender.refresh_token().await {
+                        return Some(Err(e));
+                    }
+                },
+            }
+        }
     }
 
     pub fn split(self) -> (Sender<T>, Receiver<T>) {
@@ -144,9 +353,30 @@ pub fn split(self) -> (Sender<T>, Receiver<T>) {
 
 pub struct SecondaryConnection<T: Transport> {
     inner: InnerConnection<T>,
+    token: Option<SharedToken>,
 }
 
 impl<T: Transport> SecondaryConnection<T> {
+    /// Returns the current access token of the primary connection, if the connection was
+    /// created with [`PrimaryConnection::secondary_connector`].
+    pub fn access_token(&self) -> Option<AccessToken> {
+        self.token.as_ref().and_then(|token| *token.lock().unwrap())
+    }
+
+    /// Send a payload to the service.
+    ///
+    /// Cancel safety: This method is not cancel-safe.
+    pub async fn send(&mut self, data: Bytes) -> Result<()> {
+        self.inner.sender.send(data).await
+    }
+
+    /// Receive the next complete payload from the service, see [`Receiver::recv_payload`].
+    ///
+    /// Cancel safety: This method is cancel-safe.
+    pub async fn recv(&mut self) -> Option<Result<Bytes>> {
+        self.inner.receiver.recv_payload().await
+    }
+
     pub fn split(self) -> (Sender<T>, Receiver<T>) {
         (self.inner.sender, self.inner.receiver)
     }
@@ -154,24 +384,264 @@ pub fn split(self) -> (Sender<T>, Receiver<T>) {
 
 pub struct Sender<T: Transport> {
     inner: T::Sender,
+    /// The access token, only set for primary connections.
+    token: Option<SharedToken>,
+    refresh: Option<TokenRefresh>,
 }
 
 impl<T: Transport> Sender<T> {
+    fn new(inner: T::Sender, token: Option<SharedToken>) -> Self {
+        Self {
+            inner,
+            token,
+            refresh: None,
+        }
+    }
+
     // Todo: should this be cancel-safe?
+    /// Send a payload to the service, refreshing the access token first if it is about to
+    /// expire.
+    ///
     /// Cancel safety: This method is not cancel-safe.
     pub async fn send(&mut self, data: Bytes) -> Result<()> {
+        if self
+            .refresh_deadline()
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            self.refresh_token().await?;
+        }
+
         let serialized_frame = RequestFrame::ServicePayload { bytes: data }.encode();
         self.inner.send(serialized_frame.as_ref()).await
     }
+
+    /// Extend the access token so it is valid for at least `ttl` more seconds. Only allowed on
+    /// primary connections, after an access token was requested.
+    pub async fn extend_access_token(&mut self, ttl: u64) -> Result<()> {
+        let Some(token) = &self.token else {
+            bail!("only primary connections can extend the access token");
+        };
+        if token.lock().unwrap().is_none() {
+            bail!("an access token must be requested first");
+        }
+
+        // The node doesn't respond, the token is extended to the later of the two expirations.
+        let frame = RequestFrame::ExtendAccessToken { ttl }.encode();
+        self.inner.send(frame.as_ref()).await?;
+        if let Some(token) = token.lock().unwrap().as_mut() {
+            token.expires_at = token
+                .expires_at
+                .max(Instant::now() + Duration::from_secs(ttl));
+        }
+        Ok(())
+    }
+
+    /// Returns when the access token should be refreshed, if refreshing is enabled.
+    fn refresh_deadline(&self) -> Option<Instant> {
+        let refresh = self.refresh?;
+        let token = (*self.token.as_ref()?.lock().unwrap())?;
+        Some(
+            token
+                .expires_at
+                .checked_sub(refresh.margin)
+                .unwrap_or_else(Instant::now),
+        )
+    }
+
+    async fn refresh_token(&mut self) -> Result<()> {
+        match self.refresh {
+            Some(refresh) => self.extend_access_token(refresh.ttl).await,
+            None => Ok(()),
+        }
+    }
 }
 
 pub struct Receiver<T: Transport> {
     inner: T::Receiver,
+    /// Frames received while waiting for a response to a request.
+    pending: VecDeque<ResponseFrame>,
+    /// The chunks of the payload currently being received.
+    chunks: BytesMut,
 }
 
 impl<T: Transport> Receiver<T> {
+    fn new(inner: T::Receiver) -> Self {
+        Self {
+            inner,
+            pending: VecDeque::new(),
+            chunks: BytesMut::new(),
+        }
+    }
+
+    /// Receive the next frame from the node.
+    ///
     /// Cancel safety: This method is cancel-safe.
     pub async fn recv(&mut self) -> Option<Result<ResponseFrame>> {
+        if let Some(frame) = self.pending.pop_front() {
+            return Some(Ok(frame));
+        }
         Some(ResponseFrame::decode(self.inner.recv().await?.as_ref()))
     }
+
+    /// Receive the next complete payload from the service, assembling the payloads sent in
+    /// chunks. Returns `None` once the connection is closed, or terminated by the service. Any
+    /// other termination is returned as an error.
+    ///
+    /// Cancel safety: This method is cancel-safe.
+    pub async fn recv_payload(&mut self) -> Option<Result<Bytes>> {
+        loop {
+            match self.recv().await? {
+                Ok(ResponseFrame::ServicePayloadChunk { bytes }) => {
+                    self.chunks.extend_from_slice(&bytes);
+                },
+                Ok(ResponseFrame::ServicePayload { bytes }) => {
+                    if self.chunks.is_empty() {
+                        return Some(Ok(bytes));
+                    }
+                    self.chunks.extend_from_slice(&bytes);
+                    return Some(Ok(self.chunks.split().freeze()));
+                },
+                Ok(ResponseFrame::Termination { reason }) => {
+                    return match reason {
+                        TerminationReason::ServiceTerminated => None,
+                        reason => Some(Err(anyhow!("connection terminated: {reason:?}"))),
+                    };
+                },
+                // Access tokens are only sent in response to a request, and are of no use here.
+                Ok(_) => {},
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Number of attempts made to resume a connection before giving up.
+const RESUME_ATTEMPTS: u32 = 5;
+/// Delay before the first attempt to resume a connection, doubled on every attempt.
+const RESUME_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A primary connection that is resumed automatically when its transport is lost.
+pub struct ResumableConnection<T: Transport> {
+    connector: Connector<PrimaryConnection<T>, T>,
+    connection_id: u64,
+    /// The signature of the access token, sent to resume the connection.
+    pop: ClientSignature,
+    sender: T::Sender,
+    receiver: T::Receiver,
+    /// The number of complete payloads received so far.
+    received: u64,
+    /// The chunks of the payload currently being received.
+    chunks: BytesMut,
+    terminated: bool,
+}
+
+impl<T: Transport> ResumableConnection<T> {
+    /// Send a payload to the service, resuming the connection first if needed. Payloads are not
+    /// replayed, so one that was sent right before the transport was lost may not reach the
+    /// service.
+    ///
+    /// Cancel safety: This method is not cancel-safe.
+    pub async fn send(&mut self, data: Bytes) -> Result<()> {
+        let frame = RequestFrame::ServicePayload { bytes: data }.encode();
+        if self.sender.send(frame.as_ref()).await.is_ok() {
+            return Ok(());
+        }
+
+        self.resume().await?;
+        self.sender.send(frame.as_ref()).await
+    }
+
+    /// Receive the next complete payload from the service. Returns `None` once the connection
+    /// is terminated by the node.
+    ///
+    /// Cancel safety: This method is cancel-safe, as long as the connection doesn't have to be
+    /// resumed.
+    pub async fn recv(&mut self) -> Option<Result<Bytes>> {
+        if self.terminated {
+            return None;
+        }
+
+        loop {
+            let Some(bytes) = self.receiver.recv().await else {
+                if let Err(e) = self.resume().await {
+                    self.terminated = true;
+                    return Some(Err(e));
+                }
+                continue;
+            };
+
+            match ResponseFrame::decode(&bytes) {
+                Ok(ResponseFrame::ServicePayloadChunk { bytes }) => {
+                    self.chunks.extend_from_slice(&bytes);
+                },
+                Ok(ResponseFrame::ServicePayload { bytes }) => {
+                    self.received += 1;
+                    if self.chunks.is_empty() {
+                        return Some(Ok(bytes));
+                    }
+                    self.chunks.extend_from_slice(&bytes);
+                    return Some(Ok(self.chunks.split().freeze()));
+                },
+                Ok(ResponseFrame::Termination { reason }) => {
+                    self.terminated = true;
+                    return match reason {
+                        TerminationReason::ServiceTerminated => None,
+                        reason => Some(Err(anyhow!("connection terminated: {reason:?}"))),
+                    };
+                },
+                // Access tokens are only requested when connecting.
+                Ok(_) => {},
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Reconnect with a retry handshake and tell the node how many payloads we received, so it
+    /// replays the ones we missed.
+    async fn resume(&mut self) -> Result<()> {
+        let ModeSetting::Primary(setting) = self.connector.ctx.mode() else {
+            unreachable!("resumable connections are always primary connections");
+        };
+
+        // A payload that was cut off is replayed from the start.
+        self.chunks.clear();
+
+        let mut delay = RESUME_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            let res = async {
+                let (mut sender, receiver) = self.connector.transport.connect().await?;
+                start_handshake::<T>(
+                    &mut sender,
+                    setting,
+                    *self.connector.ctx.pk(),
+                    Some(self.connection_id),
+                    self.pop,
+                )
+                .await?;
+                let frame = RequestFrame::Received {
+                    count: self.received,
+                };
+                sender.send(frame.encode().as_ref()).await?;
+                anyhow::Ok((sender, receiver))
+            }
+            .await;
+
+            match res {
+                Ok((sender, receiver)) => {
+                    self.sender = sender;
+                    self.receiver = receiver;
+                    return Ok(());
+                },
+                Err(e) if attempt + 1 >= RESUME_ATTEMPTS => {
+                    return Err(e.context("failed to resume the connection"));
+                },
+                Err(_) => {
+                    attempt += 1;
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                },
+            }
+        }
+    }
 }
@@ -1,16 +1,31 @@
 use fleek_crypto::ClientPublicKey;
 
+use crate::connection::SharedToken;
 use crate::mode::ModeSetting;
 
 pub struct Context {
     mode: ModeSetting,
     // The provider's public key.
     pk: ClientPublicKey,
+    // The access token of the primary connection, for secondary connectors created from it.
+    token: Option<SharedToken>,
 }
 
 impl Context {
     pub fn new(mode: ModeSetting, pk: ClientPublicKey) -> Self {
-        Self { mode, pk }
+        Self {
+            mode,
+            pk,
+            token: None,
+        }
+    }
+
+    pub(crate) fn with_token(mode: ModeSetting, pk: ClientPublicKey, token: SharedToken) -> Self {
+        Self {
+            mode,
+            pk,
+            token: Some(token),
+        }
     }
 
     /// Returns the mode setting of the connection.
@@ -21,4 +36,8 @@ pub fn mode(&self) -> &ModeSetting {
     pub fn pk(&self) -> &ClientPublicKey {
         &self.pk
     }
+
+    pub(crate) fn token(&self) -> Option<&SharedToken> {
+        self.token.as_ref()
+    }
 }
@@ -0,0 +1,340 @@
+//! Verified downloads from the fetcher service.
+//!
+//! The content is requested along with the proofs of its blocks, and every block is verified
+//! against the blake3 root of the content before it is handed out.
+//!
+//! ```ignore
+//! use std::net::SocketAddr;
+//!
+//! use cdk_rust::fetch::Fetch;
+//! use cdk_rust::transport::tcp::TcpTransport;
+//! use cdk_rust::Builder;
+//! use tokio::io::AsyncReadExt;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let target: SocketAddr = "0.0.0.0:0".parse().unwrap();
+//!     let connector = Builder::primary([0u8; 32], cdk_rust::fetch::FETCHER_SERVICE)
+//!         .transport(TcpTransport::new(target))
+//!         .build()
+//!         .unwrap();
+//!
+//!     let mut reader = Fetch::blake3([0u8; 32])
+//!         .range(1024..4096)
+//!         .start(connector)
+//!         .await
+//!         .unwrap();
+//!
+//!     let mut content = Vec::new();
+//!     reader.read_to_end(&mut content).await.unwrap();
+//! }
+//! ```
+use std::io;
+use std::ops::{Bound, RangeBounds};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use arrayref::array_ref;
+use blake3_tree::blake3::tree::BlockHasher;
+use blake3_tree::IncrementalVerifier;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::StreamExt;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio_util::io::StreamReader;
+
+use crate::connection::{Connector, PrimaryConnection, Receiver, Sender};
+use crate::transport::Transport;
+
+/// The id of the fetcher service.
+pub const FETCHER_SERVICE: u32 = 0;
+/// The size of the blocks of the content.
+pub const BLOCK_SIZE: u64 = 256 * 1024;
+
+/// Flag set on the origin of requests for verified content.
+const VERIFIED_FLAG: u8 = 0x80;
+/// Number of attempts made to resume a download before giving up.
+const RESUME_ATTEMPTS: u32 = 5;
+/// Delay before the first attempt to resume a download, doubled on every attempt.
+const RESUME_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The origin of the content.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+    /// Content already on the network, addressed by its blake3 hash.
+    Blake3([u8; 32]),
+    /// Content from IPFS, addressed by its binary CID.
+    Ipfs(Vec<u8>),
+}
+
+impl Origin {
+    fn tag(&self) -> u8 {
+        match self {
+            Origin::Blake3(_) => 0x00,
+            Origin::Ipfs(_) => 0x01,
+        }
+    }
+
+    fn uid(&self) -> &[u8] {
+        match self {
+            Origin::Blake3(hash) => hash,
+            Origin::Ipfs(cid) => cid,
+        }
+    }
+}
+
+/// A request for verified content from the fetcher service.
+#[derive(Clone, Debug)]
+pub struct Fetch {
+    origin: Origin,
+    start: u64,
+    end: Option<u64>,
+    resume_attempts: u32,
+}
+
+impl Fetch {
+    pub fn new(origin: Origin) -> Self {
+        Self {
+            origin,
+            start: 0,
+            end: None,
+            resume_attempts: RESUME_ATTEMPTS,
+        }
+    }
+
+    /// Fetch the content with the given blake3 hash.
+    pub fn blake3(hash: [u8; 32]) -> Self {
+        Self::new(Origin::Blake3(hash))
+    }
+
+    /// Fetch the content with the given binary CID from IPFS.
+    pub fn ipfs(cid: impl Into<Vec<u8>>) -> Self {
+        Self::new(Origin::Ipfs(cid.into()))
+    }
+
+    /// Only fetch the given range of bytes of the content. Only the blocks overlapping the range
+    /// are downloaded.
+    pub fn range(mut self, range: impl RangeBounds<u64>) -> Self {
+        self.start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        self.end = match range.end_bound() {
+            Bound::Included(end) => Some(end + 1),
+            Bound::Excluded(end) => Some(*end),
+            Bound::Unbounded => None,
+        };
+        self
+    }
+
+    /// Set the number of times the download is resumed from the last verified block when the
+    /// connection is lost.
+    pub fn resume_attempts(mut self, attempts: u32) -> Self {
+        self.resume_attempts = attempts;
+        self
+    }
+
+    /// Start the download, returning once the header of the content is received. The returned
+    /// reader fails with [`io::ErrorKind::InvalidData`] if a block can't be verified.
+    pub async fn start<T>(
+        self,
+        connector: Connector<PrimaryConnection<T>, T>,
+    ) -> Result<FetchReader>
+    where
+        T: Transport,
+        T::Sender: Send,
+        T::Receiver: Send,
+    {
+        if matches!(self.end, Some(end) if end <= self.start) {
+            bail!("the range is empty");
+        }
+
+        let mut download = Download {
+            next_block: self.start / BLOCK_SIZE,
+            fetch: self,
+            connector,
+            connection: None,
+            root: None,
+            end_block: 0,
+        };
+        download.connect().await?;
+
+        let root = download.root.expect("received the header");
+        let stream = futures::stream::unfold(download, |mut download| async move {
+            match download.next().await {
+                Ok(Some(bytes)) => Some((Ok(bytes), download)),
+                Ok(None) => None,
+                Err(e) => {
+                    // Nothing is read after an error.
+                    download.next_block = download.end_block;
+                    Some((Err(io::Error::new(io::ErrorKind::InvalidData, e)), download))
+                },
+            }
+        });
+
+        Ok(FetchReader {
+            root,
+            inner: StreamReader::new(stream.boxed()),
+        })
+    }
+}
+
+/// Reads verified content from the fetcher service.
+pub struct FetchReader {
+    root: [u8; 32],
+    inner: StreamReader<futures::stream::BoxStream<'static, io::Result<Bytes>>, Bytes>,
+}
+
+impl FetchReader {
+    /// The blake3 root hash of the content, every block read was verified against it.
+    pub fn root(&self) -> &[u8; 32] {
+        &self.root
+    }
+}
+
+impl AsyncRead for FetchReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+/// The state of a download.
+struct Download<T: Transport> {
+    fetch: Fetch,
+    connector: Connector<PrimaryConnection<T>, T>,
+    connection: Option<(Sender<T>, Receiver<T>, IncrementalVerifier)>,
+    /// The root of the content, known once the first header is received.
+    root: Option<[u8; 32]>,
+    /// The next block to download.
+    next_block: u64,
+    /// The block the download stops at.
+    end_block: u64,
+}
+
+impl<T: Transport> Download<T> {
+    /// Request the rest of the content on a new connection.
+    async fn connect(&mut self) -> Result<()> {
+        let (mut sender, mut receiver) = self.connector.connect().await?.split();
+
+        let end_block = match self.fetch.end {
+            Some(end) => u32::try_from(end.div_ceil(BLOCK_SIZE)).unwrap_or(u32::MAX),
+            None => u32::MAX,
+        };
+        let uid = self.fetch.origin.uid();
+        let mut request = BytesMut::with_capacity(9 + uid.len());
+        request.put_u8(self.fetch.origin.tag() | VERIFIED_FLAG);
+        request.put_u32(u32::try_from(self.next_block).context("the range is too large")?);
+        request.put_u32(end_block);
+        request.put_slice(uid);
+        sender.send(request.freeze()).await?;
+
+        let header = receiver
+            .recv_payload()
+            .await
+            .ok_or(anyhow!("connection closed before the header was received"))??;
+        if header.len() != 36 {
+            // The service responds with an error message instead of the header.
+            bail!("fetch failed: {}", String::from_utf8_lossy(&header));
+        }
+        let num_blocks = u32::from_be_bytes(*array_ref![header, 0, 4]) as u64;
+        let root = *array_ref![header, 4, 32];
+
+        match (&self.root, &self.fetch.origin) {
+            (Some(expected), _) | (None, Origin::Blake3(expected)) if *expected != root => {
+                bail!("the root of the content doesn't match");
+            },
+            _ => {},
+        }
+        self.root = Some(root);
+        self.end_block = num_blocks.min(end_block as u64);
+
+        let verifier = IncrementalVerifier::new(root, self.next_block as usize);
+        self.connection = Some((sender, receiver, verifier));
+        Ok(())
+    }
+
+    /// Download and verify the next block, resuming the download if the connection is lost.
+    async fn next(&mut self) -> Result<Option<Bytes>> {
+        if self.next_block >= self.end_block {
+            return Ok(None);
+        }
+
+        let mut attempt = 0;
+        loop {
+            let result = if self.connection.is_some() {
+                self.next_block().await
+            } else {
+                match self.connect().await {
+                    Ok(()) => self.next_block().await,
+                    Err(e) => Err(Error::Connection(e)),
+                }
+            };
+
+            match result {
+                Ok(block) => return Ok(Some(self.trim(block))),
+                Err(Error::Invalid(e)) => return Err(e),
+                Err(Error::Connection(e)) => {
+                    if attempt >= self.fetch.resume_attempts {
+                        return Err(e);
+                    }
+                    self.connection = None;
+                    tokio::time::sleep(RESUME_BACKOFF * 2u32.pow(attempt)).await;
+                    attempt += 1;
+                },
+            }
+        }
+    }
+
+    async fn next_block(&mut self) -> Result<Bytes, Error> {
+        let (_, receiver, verifier) = self.connection.as_mut().expect("connected");
+
+        let proof = recv(receiver).await?;
+        if !proof.is_empty() {
+            verifier
+                .feed_proof(&proof)
+                .map_err(|e| Error::Invalid(anyhow!("invalid proof: {e:?}")))?;
+        }
+
+        let block = recv(receiver).await?;
+        let mut hasher = BlockHasher::new();
+        hasher.set_block(self.next_block as usize);
+        hasher.update(&block);
+        verifier
+            .verify(hasher)
+            .map_err(|e| Error::Invalid(anyhow!("invalid block {}: {e:?}", self.next_block)))?;
+
+        self.next_block += 1;
+        Ok(block)
+    }
+
+    /// Remove the bytes of the block that are out of the requested range.
+    fn trim(&self, mut block: Bytes) -> Bytes {
+        let offset = (self.next_block - 1) * BLOCK_SIZE;
+        if let Some(end) = self.fetch.end {
+            block.truncate(end.saturating_sub(offset) as usize);
+        }
+        let skip = (self.fetch.start.saturating_sub(offset) as usize).min(block.len());
+        block.split_off(skip)
+    }
+}
+
+enum Error {
+    /// The connection was lost, the download can be resumed.
+    Connection(anyhow::Error),
+    /// The content could not be verified.
+    Invalid(anyhow::Error),
+}
+
+async fn recv<T: Transport>(receiver: &mut Receiver<T>) -> Result<Bytes, Error> {
+    match receiver.recv_payload().await {
+        Some(Ok(bytes)) => Ok(bytes),
+        Some(Err(e)) => Err(Error::Connection(e)),
+        None => Err(Error::Connection(anyhow!("connection closed"))),
+    }
+}
@@ -26,6 +26,102 @@
 //! }
 //! ```
 //!
+//! ## Resumable Connection
+//!
+//! ```ignore
+//! use std::net::SocketAddr;
+//!
+//! use cdk_rust::transport::tcp::TcpTransport;
+//! use cdk_rust::Builder;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let target: SocketAddr = "0.0.0.0:0".parse().unwrap();
+//!     let transport = TcpTransport::new(target);
+//!     // Resuming requires a valid BLS12-381 client secret key.
+//!     let secret = read_client_secret_key();
+//!     let service_id = 1;
+//!
+//!     let connector = Builder::primary(secret, service_id)
+//!         .transport(transport)
+//!         .build()
+//!         .unwrap();
+//!
+//!     // The connection is resumed automatically if the transport is lost.
+//!     let mut connection = connector.connect_resumable().await.unwrap();
+//!
+//!     connection.send(b"hello".to_vec().into()).await.unwrap();
+//!     let response = connection.recv().await.unwrap().unwrap();
+//! }
+//! ```
+//!
+//! ## Access Tokens
+//!
+//! ```ignore
+//! use std::net::SocketAddr;
+//! use std::time::Duration;
+//!
+//! use cdk_rust::transport::tcp::TcpTransport;
+//! use cdk_rust::Builder;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let target: SocketAddr = "0.0.0.0:0".parse().unwrap();
+//!     let secret = [0u8; 32];
+//!     let service_id = 1;
+//!
+//!     let connector = Builder::primary(secret, service_id)
+//!         .transport(TcpTransport::new(target))
+//!         .build()
+//!         .unwrap();
+//!
+//!     let mut connection = connector.connect().await.unwrap();
+//!
+//!     // Request a token valid for a minute, and keep extending it while the connection is used.
+//!     connection.request_access_token(60).await.unwrap();
+//!     connection
+//!         .refresh_access_token(60, Duration::from_secs(10))
+//!         .unwrap();
+//!
+//!     // Secondary connections join with the current token of the primary connection.
+//!     let secondary = connection
+//!         .secondary_connector(TcpTransport::new(target))
+//!         .unwrap();
+//!     let mut first = secondary.connect().await.unwrap();
+//!
+//!     first.send(b"hello".to_vec().into()).await.unwrap();
+//!     let response = first.recv().await.unwrap().unwrap();
+//! }
+//! ```
+//!
+//! ## Transport Selection
+//!
+//! ```ignore
+//! use cdk_rust::transport::http::HttpTransport;
+//! use cdk_rust::transport::webrtc::WebRtcTransport;
+//! use cdk_rust::transport::websocket::WebSocketTransport;
+//! use cdk_rust::transport::{select, AnyTransport};
+//! use cdk_rust::Builder;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     // The best transport the node accepts connections on is used.
+//!     let transport = select([
+//!         AnyTransport::WebRtc(WebRtcTransport::new("http://127.0.0.1:4220/sdp".into())),
+//!         AnyTransport::WebSocket(WebSocketTransport::new("ws://127.0.0.1:4220/ws".into())),
+//!         AnyTransport::Http(HttpTransport::new("http://127.0.0.1:4220".into())),
+//!     ])
+//!     .await
+//!     .unwrap();
+//!
+//!     let connector = Builder::primary([0u8; 32], 1)
+//!         .transport(transport)
+//!         .build()
+//!         .unwrap();
+//!     let mut connection = connector.connect().await.unwrap();
+//! }
+//! ```
+//!
 //! ## Secondary Connection
 //!
 //! ```ignore
@@ -58,7 +154,17 @@
 #[cfg(not(feature = "cloudflare"))]
 mod tls;
 
+pub mod fetch;
 pub mod transport;
 
 pub use builder::Builder;
+pub use connection::{
+    AccessToken,
+    Connector,
+    PrimaryConnection,
+    Receiver,
+    ResumableConnection,
+    SecondaryConnection,
+    Sender,
+};
 pub use lightning_schema::handshake as schema;
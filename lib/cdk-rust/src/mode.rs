@@ -1,5 +1,5 @@
 pub struct PrimaryMode {
-    pub(crate) _client_secret_key: [u8; 32],
+    pub(crate) client_secret_key: [u8; 32],
     pub(crate) service_id: u32,
 }
 
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+use crate::transport::http::{HttpReceiver, HttpSender, HttpTransport};
+use crate::transport::tcp::{TcpReceiver, TcpSender, TcpTransport};
+use crate::transport::webrtc::{WebRtcReceiver, WebRtcSender, WebRtcTransport};
+use crate::transport::websocket::{WebSocketReceiver, WebSocketSender, WebSocketTransport};
+use crate::transport::webtransport::{WebTransport, WebTransportReceiver, WebTransportSender};
+use crate::transport::{Transport, TransportReceiver, TransportSender};
+
+/// How long a transport is given to connect when selecting one.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Any of the transports supported by the CDK, so the transport can be chosen at runtime.
+pub enum AnyTransport {
+    WebTransport(WebTransport),
+    WebRtc(WebRtcTransport),
+    WebSocket(WebSocketTransport),
+    Tcp(TcpTransport),
+    Http(HttpTransport),
+}
+
+impl AnyTransport {
+    /// The preference of the transport, lower is better. Transports on top of udp come first,
+    /// and http comes last since it only carries a single request per connection.
+    fn rank(&self) -> u8 {
+        match self {
+            AnyTransport::WebTransport(_) => 0,
+            AnyTransport::WebRtc(_) => 1,
+            AnyTransport::WebSocket(_) => 2,
+            AnyTransport::Tcp(_) => 3,
+            AnyTransport::Http(_) => 4,
+        }
+    }
+
+    /// The name of the transport.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnyTransport::WebTransport(_) => "webtransport",
+            AnyTransport::WebRtc(_) => "webrtc",
+            AnyTransport::WebSocket(_) => "websocket",
+            AnyTransport::Tcp(_) => "tcp",
+            AnyTransport::Http(_) => "http",
+        }
+    }
+}
+
+/// Select the best transport available among the candidates, by connecting with each of them in
+/// order of preference until one succeeds.
+pub async fn select(candidates: impl IntoIterator<Item = AnyTransport>) -> Result<AnyTransport> {
+    let mut candidates = candidates.into_iter().collect::<Vec<_>>();
+    candidates.sort_by_key(AnyTransport::rank);
+
+    for transport in candidates {
+        match tokio::time::timeout(PROBE_TIMEOUT, transport.connect()).await {
+            Ok(Ok(_)) => return Ok(transport),
+            Ok(Err(e)) => log::debug!("failed to connect over {}: {e}", transport.name()),
+            Err(_) => log::debug!("timed out connecting over {}", transport.name()),
+        }
+    }
+
+    Err(anyhow!("no transport available"))
+}
+
+#[async_trait]
+impl Transport for AnyTransport {
+    type Sender = AnySender;
+    type Receiver = AnyReceiver;
+
+    async fn connect(&self) -> Result<(Self::Sender, Self::Receiver)> {
+        Ok(match self {
+            AnyTransport::WebTransport(transport) => {
+                let (sender, receiver) = transport.connect().await?;
+                (
+                    AnySender::WebTransport(sender),
+                    AnyReceiver::WebTransport(receiver),
+                )
+            },
+            AnyTransport::WebRtc(transport) => {
+                let (sender, receiver) = transport.connect().await?;
+                (AnySender::WebRtc(sender), AnyReceiver::WebRtc(receiver))
+            },
+            AnyTransport::WebSocket(transport) => {
+                let (sender, receiver) = transport.connect().await?;
+                (
+                    AnySender::WebSocket(sender),
+                    AnyReceiver::WebSocket(receiver),
+                )
+            },
+            AnyTransport::Tcp(transport) => {
+                let (sender, receiver) = transport.connect().await?;
+                (AnySender::Tcp(sender), AnyReceiver::Tcp(receiver))
+            },
+            AnyTransport::Http(transport) => {
+                let (sender, receiver) = transport.connect().await?;
+                (AnySender::Http(sender), AnyReceiver::Http(receiver))
+            },
+        })
+    }
+}
+
+pub enum AnySender {
+    WebTransport(WebTransportSender),
+    WebRtc(WebRtcSender),
+    WebSocket(WebSocketSender),
+    Tcp(TcpSender<OwnedWriteHalf>),
+    Http(HttpSender),
+}
+
+#[async_trait]
+impl TransportSender for AnySender {
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            AnySender::WebTransport(sender) => sender.send(data).await,
+            AnySender::WebRtc(sender) => sender.send(data).await,
+            AnySender::WebSocket(sender) => sender.send(data).await,
+            AnySender::Tcp(sender) => sender.send(data).await,
+            AnySender::Http(sender) => sender.send(data).await,
+        }
+    }
+}
+
+pub enum AnyReceiver {
+    WebTransport(WebTransportReceiver),
+    WebRtc(WebRtcReceiver),
+    WebSocket(WebSocketReceiver),
+    Tcp(TcpReceiver<OwnedReadHalf>),
+    Http(HttpReceiver),
+}
+
+#[async_trait]
+impl TransportReceiver for AnyReceiver {
+    async fn recv(&mut self) -> Option<Bytes> {
+        match self {
+            AnyReceiver::WebTransport(receiver) => receiver.recv().await,
+            AnyReceiver::WebRtc(receiver) => receiver.recv().await,
+            AnyReceiver::WebSocket(receiver) => receiver.recv().await,
+            AnyReceiver::Tcp(receiver) => receiver.recv().await,
+            AnyReceiver::Http(receiver) => receiver.recv().await,
+        }
+    }
+}
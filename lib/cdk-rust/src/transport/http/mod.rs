@@ -0,0 +1,223 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use reqwest::{Method, StatusCode};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::schema::{HandshakeRequestFrame, RequestFrame, ResponseFrame, TerminationReason};
+use crate::transport::{Transport, TransportReceiver, TransportSender};
+
+/// Transport over the `/services/<id>` http routes of a node.
+///
+/// Http connections carry a single request. For methods with a body, the first payload sent is
+/// the body of the request, otherwise the request is made as soon as the handshake is sent. The
+/// response body is received as a single payload, streamed in chunks, and the connection is
+/// terminated by the service afterwards. A response with an error status terminates the
+/// connection instead, with the reason closest to the status.
+///
+/// Http connections are anonymous, and can't request access tokens.
+pub struct HttpTransport {
+    /// The url of the node, e.g. `http://127.0.0.1:4220`.
+    base_url: String,
+    /// The path given to the service.
+    path: String,
+    method: Method,
+    client: reqwest::Client,
+    /// Set once a request reached the node, see [`HttpTransport::connect`].
+    reachable: AtomicBool,
+}
+
+impl HttpTransport {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            path: String::new(),
+            method: Method::POST,
+            client: reqwest::Client::new(),
+            reachable: AtomicBool::new(false),
+        }
+    }
+
+    /// Set the path of the requests after `/services/<id>`, including the query.
+    pub fn with_path(self, path: String) -> Self {
+        Self { path, ..self }
+    }
+
+    /// Set the method of the requests, `POST` by default.
+    pub fn with_method(self, method: Method) -> Self {
+        Self { method, ..self }
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    type Sender = HttpSender;
+    type Receiver = HttpReceiver;
+
+    /// The request of a connection is only made once the handshake, and the body if any, are
+    /// sent. Until a request reached the node, connecting makes a request to it first, so an
+    /// unreachable node fails to connect like with the other transports. Any response will do.
+    async fn connect(&self) -> Result<(Self::Sender, Self::Receiver)> {
+        if !self.reachable.load(Ordering::Relaxed) {
+            self.client
+                .head(&self.base_url)
+                .send()
+                .await
+                .with_context(|| format!("failed to reach {}", self.base_url))?;
+            self.reachable.store(true, Ordering::Relaxed);
+        }
+
+        let (tx, rx) = mpsc::channel(32);
+        Ok((
+            HttpSender {
+                base_url: self.base_url.clone(),
+                path: self.path.clone(),
+                method: self.method.clone(),
+                client: self.client.clone(),
+                state: State::AwaitingHandshake,
+                frames: Some(tx),
+            },
+            HttpReceiver { inner: rx },
+        ))
+    }
+}
+
+enum State {
+    AwaitingHandshake,
+    /// Waiting for the body of the request to the service.
+    AwaitingBody(String),
+    /// The request was made.
+    Sent,
+}
+
+pub struct HttpSender {
+    base_url: String,
+    path: String,
+    method: Method,
+    client: reqwest::Client,
+    state: State,
+    /// Response frames given to the receiver, moved to the task making the request so the
+    /// receiver is closed once it's done.
+    frames: Option<Sender<Bytes>>,
+}
+
+impl HttpSender {
+    fn request(&mut self, url: String, body: Bytes) {
+        let request = self.client.request(self.method.clone(), url).body(body);
+        if let Some(frames) = self.frames.take() {
+            tokio::spawn(respond(request, frames));
+        }
+        self.state = State::Sent;
+    }
+}
+
+#[async_trait]
+impl TransportSender for HttpSender {
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        match &mut self.state {
+            State::AwaitingHandshake => {
+                let HandshakeRequestFrame::Handshake { service, .. } =
+                    HandshakeRequestFrame::decode(data)?
+                else {
+                    bail!("http connections can only be primary connections");
+                };
+                let url = format!(
+                    "{}/services/{service}{}",
+                    self.base_url.trim_end_matches('/'),
+                    self.path
+                );
+                if matches!(self.method, Method::GET | Method::HEAD | Method::DELETE) {
+                    self.request(url, Bytes::new());
+                } else {
+                    self.state = State::AwaitingBody(url);
+                }
+            },
+            State::AwaitingBody(url) => match RequestFrame::decode(data)? {
+                RequestFrame::ServicePayload { bytes } => {
+                    let url = std::mem::take(url);
+                    self.request(url, bytes);
+                },
+                _ => bail!("only service payloads are supported over http"),
+            },
+            State::Sent => bail!("http connections carry a single request"),
+        }
+        Ok(())
+    }
+}
+
+/// Make the request, and give the response to the receiver as frames.
+async fn respond(request: reqwest::RequestBuilder, frames: Sender<Bytes>) {
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            log::warn!("http request failed: {e}");
+            return;
+        },
+    };
+
+    if !response.status().is_success() {
+        log::debug!("http request failed with status {}", response.status());
+        let reason = termination_reason(response.status());
+        let _ = frames
+            .send(ResponseFrame::Termination { reason }.encode())
+            .await;
+        return;
+    }
+
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let bytes = match chunk {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::warn!("failed to read the http response: {e}");
+                return;
+            },
+        };
+        let frame = ResponseFrame::ServicePayloadChunk { bytes }.encode();
+        if frames.send(frame).await.is_err() {
+            return;
+        }
+    }
+
+    let _ = frames
+        .send(
+            ResponseFrame::ServicePayload {
+                bytes: Bytes::new(),
+            }
+            .encode(),
+        )
+        .await;
+    let _ = frames
+        .send(
+            ResponseFrame::Termination {
+                reason: TerminationReason::ServiceTerminated,
+            }
+            .encode(),
+        )
+        .await;
+}
+
+/// The termination reason of a response with an error status, which the node uses when the
+/// connection was refused, and the service for its own errors.
+fn termination_reason(status: StatusCode) -> TerminationReason {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => TerminationReason::ResourcesUnavailable,
+        StatusCode::NOT_FOUND => TerminationReason::InvalidService,
+        status if status.is_server_error() => TerminationReason::InternalError,
+        _ => TerminationReason::InvalidHandshake,
+    }
+}
+
+pub struct HttpReceiver {
+    inner: Receiver<Bytes>,
+}
+
+#[async_trait]
+impl TransportReceiver for HttpReceiver {
+    async fn recv(&mut self) -> Option<Bytes> {
+        self.inner.recv().await
+    }
+}
@@ -1,7 +1,17 @@
+#[cfg(not(feature = "cloudflare"))]
+mod any;
+#[cfg(not(feature = "cloudflare"))]
+pub mod http;
 pub mod tcp;
 #[cfg(not(feature = "cloudflare"))]
+pub mod webrtc;
+#[cfg(not(feature = "cloudflare"))]
+pub mod websocket;
+#[cfg(not(feature = "cloudflare"))]
 pub mod webtransport;
 
+#[cfg(not(feature = "cloudflare"))]
+pub use any::{select, AnyReceiver, AnySender, AnyTransport};
 use anyhow::Result;
 use async_trait::async_trait;
 use bytes::{BufMut, Bytes, BytesMut};
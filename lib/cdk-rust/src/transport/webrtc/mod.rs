@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use str0m::change::{SdpAnswer, SdpOffer};
+use str0m::channel::ChannelId;
+use str0m::net::{Protocol, Receive};
+use str0m::{Candidate, Event, IceConnectionState, Input, Output, Rtc};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+
+use crate::schema::RequestFrame;
+use crate::transport::{Transport, TransportReceiver, TransportSender};
+
+/// The maximum size of a message on the data channel, payloads over this size are sent in
+/// chunks. Same as the limit used by the node.
+pub const MAX_PAYLOAD_SIZE: usize = 48 << 10;
+
+/// Transport over a WebRTC data channel. The session is negotiated by posting an SDP offer to the
+/// `/sdp` route of the node, after which frames are sent as binary messages on a single data
+/// channel.
+///
+/// The node keeps a single WebRTC session per IP address, so connecting again replaces the
+/// previous connection of the client.
+pub struct WebRtcTransport {
+    /// The url of the signalling route, e.g. `http://127.0.0.1:4220/sdp`.
+    signal_url: String,
+    client: reqwest::Client,
+}
+
+impl WebRtcTransport {
+    pub fn new(signal_url: String) -> Self {
+        Self {
+            signal_url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for WebRtcTransport {
+    type Sender = WebRtcSender;
+    type Receiver = WebRtcReceiver;
+
+    async fn connect(&self) -> Result<(Self::Sender, Self::Receiver)> {
+        let socket = bind_socket(&self.signal_url).await?;
+
+        let mut rtc = Rtc::new();
+        rtc.add_local_candidate(Candidate::host(socket.local_addr()?, "udp")?);
+        let mut change = rtc.sdp_api();
+        let channel = change.add_channel("fleek".to_string());
+        let (offer, pending) = change
+            .apply()
+            .ok_or(anyhow!("failed to create the sdp offer"))?;
+
+        let answer: SdpAnswer = self
+            .client
+            .post(&self.signal_url)
+            .json::<SdpOffer>(&offer)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        rtc.sdp_api().accept_answer(pending, answer)?;
+
+        let (out_tx, out_rx) = mpsc::unbounded_channel();
+        let (in_tx, in_rx) = mpsc::unbounded_channel();
+        let (open_tx, open_rx) = oneshot::channel();
+        tokio::spawn(
+            Driver {
+                rtc,
+                socket,
+                channel,
+                open: false,
+                opened: Some(open_tx),
+                queue: VecDeque::new(),
+                outgoing: out_rx,
+                incoming: in_tx,
+            }
+            .run(),
+        );
+
+        // The connection only works once the data channel is open.
+        open_rx
+            .await
+            .map_err(|_| anyhow!("webrtc connection closed before the data channel was open"))?;
+
+        Ok((
+            WebRtcSender { inner: out_tx },
+            WebRtcReceiver { inner: in_rx },
+        ))
+    }
+}
+
+/// Bind a udp socket on the interface used to reach the node, so its address can be used as a
+/// host candidate.
+async fn bind_socket(signal_url: &str) -> Result<UdpSocket> {
+    let url = reqwest::Url::parse(signal_url)?;
+    let host = url
+        .host_str()
+        .ok_or(anyhow!("missing host in {signal_url}"))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let target = tokio::net::lookup_host((host, port))
+        .await?
+        .next()
+        .ok_or(anyhow!("failed to resolve {host}"))?;
+
+    let probe = UdpSocket::bind(unspecified(target.ip())).await?;
+    probe.connect(target).await?;
+    let ip = probe.local_addr()?.ip();
+
+    Ok(UdpSocket::bind(SocketAddr::new(ip, 0)).await?)
+}
+
+fn unspecified(ip: IpAddr) -> SocketAddr {
+    match ip {
+        IpAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        IpAddr::V6(_) => ([0u16; 8], 0).into(),
+    }
+}
+
+/// Drives the rtc state of a connection, moving data between the socket and the channels of the
+/// sender and receiver.
+struct Driver {
+    rtc: Rtc,
+    socket: UdpSocket,
+    channel: ChannelId,
+    /// Whether the data channel is open.
+    open: bool,
+    /// Notifies the connecting side once the data channel is open.
+    opened: Option<oneshot::Sender<()>>,
+    /// Messages written before the data channel was open.
+    queue: VecDeque<Bytes>,
+    outgoing: UnboundedReceiver<Bytes>,
+    incoming: UnboundedSender<Bytes>,
+}
+
+impl Driver {
+    async fn run(mut self) {
+        if let Err(e) = self.run_inner().await {
+            log::warn!("webrtc connection failed: {e}");
+        }
+        self.rtc.disconnect();
+    }
+
+    async fn run_inner(&mut self) -> Result<()> {
+        // UDP datagrams should be ~2KB max
+        let mut buf = vec![0; 2 << 10];
+
+        while self.rtc.is_alive() {
+            let timeout = match self.poll_until_idle().await? {
+                Some(timeout) => timeout,
+                None => return Ok(()),
+            };
+            let sleep = timeout.saturating_duration_since(Instant::now());
+
+            tokio::select! {
+                res = self.socket.recv_from(&mut buf) => {
+                    let (n, source) = res?;
+                    self.rtc.handle_input(Input::Receive(
+                        Instant::now(),
+                        Receive {
+                            proto: Protocol::Udp,
+                            source,
+                            destination: self.socket.local_addr()?,
+                            contents: buf[..n].try_into()?,
+                        },
+                    ))?;
+                },
+                data = self.outgoing.recv() => match data {
+                    Some(data) => self.write(data)?,
+                    // The sender was dropped.
+                    None => return Ok(()),
+                },
+                _ = tokio::time::sleep(sleep) => {},
+            }
+
+            self.rtc.handle_input(Input::Timeout(Instant::now()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Poll and handle output until the rtc state is idle and returns a timeout. Returns `None`
+    /// once the connection is closed.
+    async fn poll_until_idle(&mut self) -> Result<Option<Instant>> {
+        loop {
+            match self.rtc.poll_output()? {
+                Output::Timeout(timeout) => return Ok(Some(timeout)),
+                Output::Transmit(transmit) => {
+                    self.socket
+                        .send_to(transmit.contents.as_ref(), transmit.destination)
+                        .await?;
+                },
+                Output::Event(Event::ChannelOpen(id, _)) if id == self.channel => {
+                    self.open = true;
+                    if let Some(opened) = self.opened.take() {
+                        let _ = opened.send(());
+                    }
+                    while let Some(data) = self.queue.pop_front() {
+                        self.write(data)?;
+                    }
+                },
+                Output::Event(Event::ChannelData(msg)) if msg.id == self.channel => {
+                    if self.incoming.send(msg.data.into()).is_err() {
+                        // The receiver was dropped.
+                        return Ok(None);
+                    }
+                },
+                Output::Event(Event::ChannelClose(id)) if id == self.channel => return Ok(None),
+                Output::Event(Event::IceConnectionStateChange(
+                    IceConnectionState::Disconnected,
+                )) => return Ok(None),
+                Output::Event(_) => {},
+            }
+        }
+    }
+
+    fn write(&mut self, data: Bytes) -> Result<()> {
+        if !self.open {
+            self.queue.push_back(data);
+            return Ok(());
+        }
+
+        let mut channel = self
+            .rtc
+            .channel(self.channel)
+            .ok_or(anyhow!("failed to get channel writer"))?;
+        channel.write(true, &data)?;
+        Ok(())
+    }
+}
+
+pub struct WebRtcSender {
+    inner: UnboundedSender<Bytes>,
+}
+
+impl WebRtcSender {
+    fn send_message(&self, data: Bytes) -> Result<()> {
+        self.inner
+            .send(data)
+            .map_err(|_| anyhow!("webrtc connection closed"))
+    }
+}
+
+#[async_trait]
+impl TransportSender for WebRtcSender {
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() <= MAX_PAYLOAD_SIZE {
+            return self.send_message(Bytes::copy_from_slice(data));
+        }
+
+        // Only service payloads can be larger than a message, they are split into chunks.
+        let RequestFrame::ServicePayload { mut bytes } = RequestFrame::decode(data)? else {
+            bail!("frame is too large");
+        };
+        // Leave room for the tag of the frame.
+        let max = MAX_PAYLOAD_SIZE - 1;
+        while bytes.len() > max {
+            let chunk = bytes.split_to(max);
+            self.send_message(RequestFrame::ServicePayloadChunk { bytes: chunk }.encode())?;
+        }
+        self.send_message(RequestFrame::ServicePayload { bytes }.encode())
+    }
+}
+
+pub struct WebRtcReceiver {
+    inner: UnboundedReceiver<Bytes>,
+}
+
+#[async_trait]
+impl TransportReceiver for WebRtcReceiver {
+    async fn recv(&mut self) -> Option<Bytes> {
+        self.inner.recv().await
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::transport::{Transport, TransportReceiver, TransportSender};
+
+type Stream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Transport over a WebSocket connection to the `/ws` route of a node. Every frame is sent as a
+/// single binary message, so unlike the stream based transports no length delimiter is used.
+pub struct WebSocketTransport {
+    url: String,
+}
+
+impl WebSocketTransport {
+    /// Create a new transport connecting to the given url, e.g. `ws://127.0.0.1:4220/ws`.
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    type Sender = WebSocketSender;
+    type Receiver = WebSocketReceiver;
+
+    async fn connect(&self) -> Result<(Self::Sender, Self::Receiver)> {
+        let (stream, _) = tokio_tungstenite::connect_async(self.url.as_str()).await?;
+        let (sink, stream) = stream.split();
+
+        Ok((
+            WebSocketSender { inner: sink },
+            WebSocketReceiver { inner: stream },
+        ))
+    }
+}
+
+pub struct WebSocketSender {
+    inner: SplitSink<Stream, Message>,
+}
+
+#[async_trait]
+impl TransportSender for WebSocketSender {
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        self.inner
+            .send(Message::Binary(data.to_vec()))
+            .await
+            .map_err(Into::into)
+    }
+}
+
+pub struct WebSocketReceiver {
+    inner: SplitStream<Stream>,
+}
+
+#[async_trait]
+impl TransportReceiver for WebSocketReceiver {
+    async fn recv(&mut self) -> Option<Bytes> {
+        loop {
+            // Todo: log error.
+            match self.inner.next().await?.ok()? {
+                Message::Binary(bytes) => return Some(bytes.into()),
+                Message::Close(_) => return None,
+                // Pings are answered by tungstenite, and text messages are not part of the
+                // protocol.
+                _ => continue,
+            }
+        }
+    }
+}
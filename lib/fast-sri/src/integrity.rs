@@ -4,7 +4,7 @@
 use fastcrypto::hash::{Digest, HashFunction};
 
 use crate::verify::Verifier;
-use crate::BufferedVerifier;
+use crate::{BufferedVerifier, StreamingVerifier};
 
 const SHA256_ALGO: &str = "sha256";
 const SHA512_ALGO: &str = "sha512";
@@ -22,6 +22,11 @@ pub fn into_verifier(self) -> BufferedVerifier {
         BufferedVerifier::new(self)
     }
 
+    /// Returns a verifier that hashes the data as it is fed, without keeping it in memory.
+    pub fn into_streaming_verifier(self) -> StreamingVerifier {
+        StreamingVerifier::new(self)
+    }
+
     /// Verifies data against this integrity metadata.
     pub fn verify(self, data: Vec<u8>) -> (bool, Vec<u8>) {
         BufferedVerifier::new_with_data(self, data).verify()
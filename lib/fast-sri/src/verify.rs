@@ -22,6 +22,40 @@ pub fn verify(self) -> bool {
     }
 }
 
+/// A verifier for any of the supported hash functions that consumes the data as it is fed,
+/// without buffering it. Useful for verifying content that is streamed to its destination.
+pub enum StreamingVerifier {
+    Sha256(Verifier<fastcrypto::hash::Sha256, 32>),
+    Sha512(Verifier<fastcrypto::hash::Sha512, 64>),
+    Blake3(Verifier<fastcrypto::hash::Blake3, 32>),
+}
+
+impl StreamingVerifier {
+    pub(crate) fn new(integrity_metadata: IntegrityMetadata) -> Self {
+        match integrity_metadata {
+            IntegrityMetadata::Sha256(integrity) => Self::Sha256(integrity.verifier()),
+            IntegrityMetadata::Sha512(integrity) => Self::Sha512(integrity.verifier()),
+            IntegrityMetadata::Blake3(integrity) => Self::Blake3(integrity.verifier()),
+        }
+    }
+
+    pub fn update<T: AsRef<[u8]>>(&mut self, data: T) {
+        match self {
+            Self::Sha256(verifier) => verifier.update(data),
+            Self::Sha512(verifier) => verifier.update(data),
+            Self::Blake3(verifier) => verifier.update(data),
+        }
+    }
+
+    pub fn verify(self) -> bool {
+        match self {
+            Self::Sha256(verifier) => verifier.verify(),
+            Self::Sha512(verifier) => verifier.verify(),
+            Self::Blake3(verifier) => verifier.verify(),
+        }
+    }
+}
+
 pub struct BufferedVerifier {
     buff: Vec<u8>,
     integrity_metadata: IntegrityMetadata,
@@ -72,6 +106,37 @@ pub fn verify(self) -> (bool, Vec<u8>) {
 mod tests {
     use crate::IntegrityMetadata;
 
+    #[test]
+    fn test_streaming_verifier() {
+        for metadata in [
+            "sha256-MV9b23bQeMQ7isAGTkoBZGErH853yGk0W/yUx1iU7dM=",
+            "sha512-wVJ82JPBJHc9gRkRlwyP5uhX1t9dySJr2KFgYUwM2WOk3eorlLt9NgIe+dhl1c6ilKgt1JoLsmn1H256V/eUIQ==",
+            "blake3-7eXAsQ8uxJecabUvYeQv9bQTUZzgm+DxTQmNz+X2+Y0=",
+        ] {
+            // Given: an integrity metadata.
+            let integrity_metadata: IntegrityMetadata = metadata.parse().unwrap();
+
+            // When: we stream the matching data into the verifier.
+            let mut verifier = integrity_metadata.into_streaming_verifier();
+            verifier.update("Hello,");
+            verifier.update(" world!");
+
+            // Then: verifies that digest is valid for the data.
+            assert!(verifier.verify());
+
+            // Given: an integrity metadata.
+            let integrity_metadata: IntegrityMetadata = metadata.parse().unwrap();
+
+            // When: we stream data that doesn't match the digest.
+            let mut verifier = integrity_metadata.into_streaming_verifier();
+            verifier.update("foo");
+            verifier.update("bar");
+
+            // Then: verifies that digest is invalid for the data.
+            assert!(!verifier.verify());
+        }
+    }
+
     #[test]
     fn test_verify_sha256() {
         // Given: an integrity metadata.
@@ -2,4 +2,4 @@
 mod sk;
 
 pub use pk::*;
-pub use sk::{AccountOwnerSecretKey, ConsensusSecretKey, NodeSecretKey};
+pub use sk::{AccountOwnerSecretKey, ClientSecretKey, ConsensusSecretKey, NodeSecretKey};
@@ -43,8 +43,13 @@ impl PublicKey for $pk_name {
             type Signature = $sig_name;
 
             fn verify(&self, signature: &Self::Signature, digest: &[u8]) -> bool {
-                let pubkey: $pk_fc = self.into();
-                let signature: $sig_fc = signature.into();
+                // Keys and signatures sent by peers may not decode, and they verify nothing.
+                let (Ok(pubkey), Ok(signature)) = (
+                    $pk_fc::from_bytes(&self.0),
+                    $sig_fc::from_bytes(&signature.0),
+                ) else {
+                    return false;
+                };
                 pubkey.$verify(digest, &signature.into()).is_ok()
             }
 
@@ -1,7 +1,9 @@
 mod account;
+mod client;
 mod consensus;
 mod node;
 
 pub use account::AccountOwnerSecretKey;
+pub use client::ClientSecretKey;
 pub use consensus::ConsensusSecretKey;
 pub use node::NodeSecretKey;
@@ -1,4 +1,12 @@
-use crate::{AccountOwnerSecretKey, EthAddress, SecretKey};
+use crate::{
+    AccountOwnerSecretKey,
+    ClientPublicKey,
+    ClientSecretKey,
+    ClientSignature,
+    EthAddress,
+    PublicKey,
+    SecretKey,
+};
 
 #[test]
 fn account_owner_to_eth_address() {
@@ -38,8 +46,30 @@ fn test_verify_false_eth_address() {
     assert!(!eth_address.verify(&signature, &digest));
 }
 
+#[test]
+fn test_verify_invalid_client_key_and_signature() {
+    let secret_key = ClientSecretKey::generate();
+    let digest = [0; 32];
+    let signature = secret_key.sign(&digest);
+    assert!(secret_key.to_pk().verify(&signature, &digest));
+
+    // Bytes that are not a key or a signature fail the verification instead of panicking.
+    assert!(!ClientPublicKey([1; 96]).verify(&signature, &digest));
+    assert!(
+        !secret_key
+            .to_pk()
+            .verify(&ClientSignature([1; 48]), &digest)
+    );
+}
+
 mod pem {
-    use crate::{AccountOwnerSecretKey, ConsensusSecretKey, NodeSecretKey, SecretKey};
+    use crate::{
+        AccountOwnerSecretKey,
+        ClientSecretKey,
+        ConsensusSecretKey,
+        NodeSecretKey,
+        SecretKey,
+    };
 
     #[test]
     fn node_key_encode_decode() {
@@ -65,6 +95,14 @@ fn account_owner_key_encode_decode() {
             AccountOwnerSecretKey::decode_pem(&pem).expect("failed to decode secp256k1 pem");
         assert_eq!(key, decoded);
     }
+
+    #[test]
+    fn client_key_encode_decode() {
+        let key = ClientSecretKey::generate();
+        let pem = key.encode_pem();
+        let decoded = ClientSecretKey::decode_pem(&pem).expect("failed to decode client pem");
+        assert_eq!(key, decoded);
+    }
 }
 
 mod from_display {
@@ -74,6 +112,7 @@ mod from_display {
 
     use crate::{
         AccountOwnerSecretKey,
+        ClientSecretKey,
         ConsensusSecretKey,
         EthAddress,
         NodeSecretKey,
@@ -127,6 +166,11 @@ fn node() {
     fn consensus() {
         run_test::<ConsensusSecretKey>();
     }
+
+    #[test]
+    fn client() {
+        run_test::<ClientSecretKey>();
+    }
 }
 
 mod test_serde {
@@ -138,6 +182,7 @@ mod test_serde {
 
     use crate::{
         AccountOwnerSecretKey,
+        ClientSecretKey,
         ConsensusSecretKey,
         EthAddress,
         NodeSecretKey,
@@ -228,4 +273,9 @@ fn node() {
     fn consensus() {
         run_test::<ConsensusSecretKey>();
     }
+
+    #[test]
+    fn client() {
+        run_test::<ClientSecretKey>();
+    }
 }
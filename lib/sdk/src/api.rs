@@ -1,11 +1,102 @@
-use fleek_crypto::ClientPublicKey;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::ipc::send_and_await_response;
-use crate::ipc_types::{Request, StaticVec};
+use fleek_crypto::{ClientPublicKey, EthAddress, NodePublicKey};
+use lightning_types::{TransactionReceipt, UpdateMethod, UpdateRequest};
+use tokio::sync::watch;
+
+use crate::ipc::{send_and_await_response, send_and_await_response_with_progress, staging_path};
+use crate::ipc_types::{Request, Response, StaticVec, SubmitTxError};
+
+/// The maximum length of a key of the key-value storage.
+pub const KV_MAX_KEY_LEN: usize = 256;
+
+/// The counter of the files used to stage the data exchanged with the node.
+static STAGING_SLOT: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Origin {
     IPFS,
+    HTTP,
+    S3,
+}
+
+/// The priority of a fetch. When the node is busy, fetches with a higher priority are started
+/// first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FetchPriority {
+    /// Prefetching, nobody is waiting on the result.
+    Background,
+    #[default]
+    Normal,
+    /// A client is waiting on the result.
+    Interactive,
+}
+
+/// The progress of a fetch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FetchProgress {
+    /// The number of bytes received so far.
+    pub bytes: u64,
+    /// The number of blocks received so far.
+    pub blocks: u64,
+}
+
+/// The current epoch of the network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EpochInfo {
+    pub epoch: u64,
+    /// The timestamp in milliseconds at which the epoch ends.
+    pub epoch_end: u64,
+}
+
+/// The participation status of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Participation {
+    /// The node is participating in the network.
+    True,
+    /// The node is not participating in the network.
+    False,
+    /// The node will start participating in the next epoch.
+    OptedIn,
+    /// The node will stop participating in the next epoch.
+    OptedOut,
+}
+
+/// The information of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub owner: EthAddress,
+    pub public_key: NodePublicKey,
+    /// The epoch the node has been staked since.
+    pub staked_since: u64,
+    /// The amount of FLK staked by the node.
+    pub stake: u128,
+    /// The primary domain of the node.
+    pub domain: IpAddr,
+    /// The port of the HTTP handshake of the node.
+    pub http_port: u16,
+    pub participation: Participation,
+    pub nonce: u64,
+}
+
+/// The commodity served by a service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommodityType {
+    Bandwidth,
+    Compute,
+    Gpu,
+}
+
+/// The information of a service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServiceInfo {
+    pub owner: EthAddress,
+    /// The commodity served by the service, `None` if it's unknown to this SDK.
+    pub commodity_type: Option<CommodityType>,
+    /// The hash and version of the binary of the service, if it's installed from the registry.
+    pub artifact: Option<([u8; 32], u32)>,
 }
 
 /// Returns the balance of a client with the following public key.
@@ -31,6 +122,8 @@ pub async fn query_client_flk_balance(pk: ClientPublicKey) -> u128 {
     let req = Request::FetchFromOrigin {
         origin: origin as u8,
         uri: StaticVec::new(uri.as_ref()),
+        priority: FetchPriority::Normal as u8,
+        progress: false,
     };
     let res = send_and_await_response(req).await;
     match res {
@@ -39,11 +132,301 @@ pub async fn query_client_flk_balance(pk: ClientPublicKey) -> u128 {
     }
 }
 
+/// Like [`fetch_from_origin`], with the given priority. The progress of the fetch is sent to
+/// `progress` until the fetch completes.
+pub async fn fetch_from_origin_with_progress(
+    origin: Origin,
+    uri: impl AsRef<[u8]>,
+    priority: FetchPriority,
+    progress: watch::Sender<FetchProgress>,
+) -> Option<[u8; 32]> {
+    let req = Request::FetchFromOrigin {
+        origin: origin as u8,
+        uri: StaticVec::new(uri.as_ref()),
+        priority: priority as u8,
+        progress: true,
+    };
+    let res = send_and_await_response_with_progress(req, progress).await;
+    match res {
+        crate::ipc_types::Response::FetchFromOrigin { hash } => hash,
+        _ => unreachable!(),
+    }
+}
+
 pub async fn fetch_blake3(hash: [u8; 32]) -> bool {
-    let req = Request::FetchBlake3 { hash };
+    let req = Request::FetchBlake3 {
+        hash,
+        priority: FetchPriority::Normal as u8,
+        progress: false,
+    };
     let res = send_and_await_response(req).await;
     match res {
         crate::ipc_types::Response::FetchBlake3 { succeeded } => succeeded,
         _ => unreachable!(),
     }
 }
+
+/// Like [`fetch_blake3`], with the given priority. The progress of the fetch is sent to
+/// `progress` until the fetch completes.
+pub async fn fetch_blake3_with_progress(
+    hash: [u8; 32],
+    priority: FetchPriority,
+    progress: watch::Sender<FetchProgress>,
+) -> bool {
+    let req = Request::FetchBlake3 {
+        hash,
+        priority: priority as u8,
+        progress: true,
+    };
+    let res = send_and_await_response_with_progress(req, progress).await;
+    match res {
+        crate::ipc_types::Response::FetchBlake3 { succeeded } => succeeded,
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the current epoch.
+pub async fn query_epoch() -> EpochInfo {
+    match send_and_await_response(Request::QueryEpoch {}).await {
+        Response::QueryEpoch { epoch, epoch_end } => EpochInfo { epoch, epoch_end },
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the index of the node with the given public key.
+pub async fn query_node_index(public_key: NodePublicKey) -> Option<u32> {
+    let req = Request::QueryNodeIndex {
+        public_key: public_key.0,
+    };
+    match send_and_await_response(req).await {
+        Response::QueryNodeIndex { index } => index,
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the information of the node with the given index.
+pub async fn query_node_info(index: u32) -> Option<NodeInfo> {
+    let info = match send_and_await_response(Request::QueryNodeInfo { index }).await {
+        Response::QueryNodeInfo { info } => info?,
+        _ => unreachable!(),
+    };
+    let domain = Ipv6Addr::from(info.domain);
+    Some(NodeInfo {
+        owner: EthAddress(info.owner),
+        public_key: NodePublicKey(info.public_key),
+        staked_since: info.staked_since,
+        stake: info.stake,
+        domain: domain
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(domain)),
+        http_port: info.http_port,
+        participation: match info.participation {
+            0 => Participation::True,
+            1 => Participation::False,
+            2 => Participation::OptedIn,
+            _ => Participation::OptedOut,
+        },
+        nonce: info.nonce,
+    })
+}
+
+/// Returns the information of the service with the given id.
+pub async fn query_service_info(id: u32) -> Option<ServiceInfo> {
+    let info = match send_and_await_response(Request::QueryServiceInfo { id }).await {
+        Response::QueryServiceInfo { info } => info?,
+        _ => unreachable!(),
+    };
+    Some(ServiceInfo {
+        owner: EthAddress(info.owner),
+        commodity_type: match info.commodity_type {
+            0 => Some(CommodityType::Bandwidth),
+            1 => Some(CommodityType::Compute),
+            2 => Some(CommodityType::Gpu),
+            _ => None,
+        },
+        artifact: info.artifact,
+    })
+}
+
+/// Returns the reputation score of the node with the given index, from 0 to 100.
+pub async fn query_reputation(index: u32) -> Option<u8> {
+    match send_and_await_response(Request::QueryReputation { index }).await {
+        Response::QueryReputation { score } => score,
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the indices of the nodes providing the content with the given blake3 hash.
+pub async fn query_content_providers(hash: [u8; 32]) -> io::Result<Vec<u32>> {
+    let slot = STAGING_SLOT.fetch_add(1, Ordering::Relaxed);
+    let count = match send_and_await_response(Request::QueryContentProviders { hash, slot }).await {
+        Response::QueryContentProviders { count: Some(count) } => count,
+        Response::QueryContentProviders { count: None } => return Err(ErrorKind::Other.into()),
+        _ => unreachable!(),
+    };
+
+    let content = read_staged(&format!("query-{slot}")).await?;
+    if content.len() != count as usize * 4 {
+        return Err(ErrorKind::InvalidData.into());
+    }
+    Ok(content
+        .chunks_exact(4)
+        .map(|index| u32::from_le_bytes(index.try_into().unwrap()))
+        .collect())
+}
+
+/// Returns the nonce of the account of the client, if it has one.
+pub async fn query_client_nonce(pk: ClientPublicKey) -> Option<u64> {
+    let req = Request::QueryClientNonce { pk: pk.0.into() };
+    match send_and_await_response(req).await {
+        Response::QueryClientNonce { nonce } => nonce,
+        _ => unreachable!(),
+    }
+}
+
+/// Submit a transaction signed by the node, and wait for it to be executed. The node only signs
+/// the update methods the service is allowed to submit in its config.
+pub async fn submit_transaction(method: UpdateMethod) -> Result<TransactionReceipt, SubmitTxError> {
+    let content = bincode::serialize(&method).map_err(|_| SubmitTxError::Invalid)?;
+    send_transaction(&content, true).await
+}
+
+/// Forward a transaction signed by a client to the mempool, and wait for it to be executed. The
+/// service must be allowed to forward transactions in the config of the node.
+pub async fn forward_transaction(
+    request: UpdateRequest,
+) -> Result<TransactionReceipt, SubmitTxError> {
+    let content = bincode::serialize(&request).map_err(|_| SubmitTxError::Invalid)?;
+    send_transaction(&content, false).await
+}
+
+async fn send_transaction(
+    content: &[u8],
+    signed_by_node: bool,
+) -> Result<TransactionReceipt, SubmitTxError> {
+    let slot = STAGING_SLOT.fetch_add(1, Ordering::Relaxed);
+    let name = format!("tx-{slot}");
+    tokio::fs::write(staging_path(&name), content)
+        .await
+        .map_err(|_| SubmitTxError::Failed)?;
+
+    let req = Request::SubmitTransaction {
+        slot,
+        signed_by_node,
+    };
+    match send_and_await_response(req).await {
+        Response::SubmitTransaction { error: Some(error) } => Err(error),
+        Response::SubmitTransaction { error: None } => {
+            let receipt = read_staged(&name)
+                .await
+                .map_err(|_| SubmitTxError::Failed)?;
+            bincode::deserialize(&receipt).map_err(|_| SubmitTxError::Failed)
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the value of the key in the key-value storage of the service.
+///
+/// Every service has its own persistent key-value storage, kept by the node across restarts and
+/// upgrades of the service, and limited by a quota.
+pub async fn kv_get(key: impl AsRef<[u8]>) -> io::Result<Option<Vec<u8>>> {
+    let key = kv_key(key.as_ref())?;
+    let slot = STAGING_SLOT.fetch_add(1, Ordering::Relaxed);
+    match send_and_await_response(Request::KvGet { key, slot }).await {
+        Response::KvGet { found: Some(true) } => read_staged(&format!("kv-{slot}")).await.map(Some),
+        Response::KvGet { found: Some(false) } => Ok(None),
+        Response::KvGet { found: None } => Err(ErrorKind::Other.into()),
+        _ => unreachable!(),
+    }
+}
+
+/// Set the value of the key in the key-value storage of the service. Fails if the value is too
+/// large or the quota of the service is exceeded.
+pub async fn kv_put(key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> io::Result<()> {
+    let key = kv_key(key.as_ref())?;
+    let slot = STAGING_SLOT.fetch_add(1, Ordering::Relaxed);
+    let path = staging_path(&format!("kv-{slot}"));
+    tokio::fs::write(&path, value).await?;
+    match send_and_await_response(Request::KvPut { key, slot }).await {
+        Response::KvPut { succeeded: true } => Ok(()),
+        Response::KvPut { succeeded: false } => {
+            let _ = tokio::fs::remove_file(&path).await;
+            Err(ErrorKind::Other.into())
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Delete the key from the key-value storage of the service.
+pub async fn kv_delete(key: impl AsRef<[u8]>) -> io::Result<()> {
+    let key = kv_key(key.as_ref())?;
+    match send_and_await_response(Request::KvDelete { key }).await {
+        Response::KvDelete { succeeded: true } => Ok(()),
+        Response::KvDelete { succeeded: false } => Err(ErrorKind::Other.into()),
+        _ => unreachable!(),
+    }
+}
+
+/// Returns at most `limit` entries of the key-value storage of the service whose keys start with
+/// the prefix, in order of their keys. Pass the last key returned as `after` to get the next
+/// entries.
+pub async fn kv_scan(
+    prefix: impl AsRef<[u8]>,
+    after: Option<&[u8]>,
+    limit: u32,
+) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let prefix = kv_key(prefix.as_ref())?;
+    let after = after.map(kv_key).transpose()?;
+    let slot = STAGING_SLOT.fetch_add(1, Ordering::Relaxed);
+    let req = Request::KvScan {
+        prefix,
+        after,
+        limit,
+        slot,
+    };
+    let count = match send_and_await_response(req).await {
+        Response::KvScan { count: Some(count) } => count,
+        Response::KvScan { count: None } => return Err(ErrorKind::Other.into()),
+        _ => unreachable!(),
+    };
+
+    let content = read_staged(&format!("kv-{slot}")).await?;
+    let mut entries = Vec::with_capacity(count as usize);
+    let mut rest = content.as_slice();
+    for _ in 0..count {
+        let (Some(key_len), Some(value_len)) = (read_u32(&mut rest), read_u32(&mut rest)) else {
+            return Err(ErrorKind::InvalidData.into());
+        };
+        if rest.len() < key_len + value_len {
+            return Err(ErrorKind::InvalidData.into());
+        }
+        let (key, tail) = rest.split_at(key_len);
+        let (value, tail) = tail.split_at(value_len);
+        entries.push((key.to_vec(), value.to_vec()));
+        rest = tail;
+    }
+    Ok(entries)
+}
+
+fn kv_key(key: &[u8]) -> io::Result<StaticVec<256>> {
+    if key.len() > KV_MAX_KEY_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "key is too long"));
+    }
+    Ok(StaticVec::new(key))
+}
+
+/// Read and remove the file the node staged the result of a request in.
+async fn read_staged(name: &str) -> io::Result<Vec<u8>> {
+    let path = staging_path(name);
+    let content = tokio::fs::read(&path).await?;
+    tokio::fs::remove_file(&path).await?;
+    Ok(content)
+}
+
+fn read_u32(buf: &mut &[u8]) -> Option<usize> {
+    let bytes = buf.get(..4)?.try_into().ok()?;
+    *buf = &buf[4..];
+    Some(u32::from_le_bytes(bytes) as usize)
+}
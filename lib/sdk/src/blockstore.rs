@@ -4,7 +4,8 @@
 use arrayvec::ArrayString;
 use blake3_tree::utils::HashTree;
 
-use crate::ipc::BLOCKSTORE;
+use crate::ipc::{send_and_await_response, staging_path, BLOCKSTORE};
+use crate::ipc_types::{Request, Response};
 
 /// Returns the root blockstore.
 ///
@@ -26,7 +27,7 @@ pub fn blockstore_root() -> &'static PathBuf {
 }
 
 #[inline]
-fn to_hex(slice: &[u8; 32]) -> ArrayString<64> {
+pub(crate) fn to_hex(slice: &[u8; 32]) -> ArrayString<64> {
     let mut s = ArrayString::new();
     let table = b"0123456789abcdef";
     for &b in slice {
@@ -80,3 +81,67 @@ pub async fn read_to_end(&self) -> std::io::Result<Vec<u8>> {
         Ok(buf)
     }
 }
+
+/// Writes a content to the blockstore through the core, since services only have read access to
+/// the blockstore. Like any content put in the blockstore, the content is registered through the
+/// indexer once the put is finalized.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut putter = fn_sdk::blockstore::ContentPutter::new().await?;
+/// putter.write(b"hello ").await?;
+/// putter.write(b"world").await?;
+/// let hash = putter.finalize().await?;
+/// ```
+pub struct ContentPutter {
+    handle: u64,
+}
+
+impl ContentPutter {
+    /// Start putting a new content.
+    pub async fn new() -> std::io::Result<Self> {
+        match send_and_await_response(Request::PutStart {}).await {
+            Response::PutStart { handle } => Ok(Self { handle }),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write the next part of the content.
+    pub async fn write(&mut self, content: &[u8]) -> std::io::Result<()> {
+        // The content is staged in a file for the core to read it, since the IPC messages have
+        // a fixed size.
+        let path = staging_path(&format!("put-{}", self.handle));
+        tokio::fs::write(&path, content).await?;
+
+        let req = Request::PutWrite {
+            handle: self.handle,
+        };
+        match send_and_await_response(req).await {
+            Response::PutWrite { succeeded: true } => Ok(()),
+            Response::PutWrite { succeeded: false } => {
+                let _ = tokio::fs::remove_file(&path).await;
+                Err(ErrorKind::Other.into())
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Finish the put, returning the hash of the content.
+    pub async fn finalize(self) -> std::io::Result<[u8; 32]> {
+        let req = Request::PutFinalize {
+            handle: self.handle,
+        };
+        match send_and_await_response(req).await {
+            Response::PutFinalize { hash } => hash.ok_or(ErrorKind::Other.into()),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Put the content to the blockstore, returning its hash. See [`ContentPutter`].
+pub async fn put(content: &[u8]) -> std::io::Result<[u8; 32]> {
+    let mut putter = ContentPutter::new().await?;
+    putter.write(content).await?;
+    putter.finalize().await
+}
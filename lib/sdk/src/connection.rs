@@ -1,4 +1,5 @@
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use bytes::BytesMut;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
@@ -8,6 +9,9 @@
 use crate::header::{read_header, ConnectionHeader, TransportDetail};
 use crate::io_util::read_length_delimited;
 
+/// The number of open connections, reported to the core in the health checks.
+pub(crate) static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+
 /// Listener for incoming connections
 pub struct ConnectionListener {
     rx: mpsc::Receiver<std::io::Result<Connection>>,
@@ -62,6 +66,23 @@ pub struct Connection {
     pub header: ConnectionHeader,
     // TODO: Should the wrapper have a debug assertion to ensure the correct
     //       number of bytes are written to the stream?
+    _open: OpenConnection,
+}
+
+/// Counts a connection as open until it's dropped.
+struct OpenConnection;
+
+impl OpenConnection {
+    fn new() -> Self {
+        CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 impl Connection {
@@ -69,7 +90,11 @@ async fn new_with_info(mut stream: UnixStream) -> std::io::Result<Self> {
         let header = read_header(&mut stream)
             .await
             .ok_or(std::io::ErrorKind::Other)?;
-        Ok(Self { stream, header })
+        Ok(Self {
+            stream,
+            header,
+            _open: OpenConnection::new(),
+        })
     }
 
     /// Start writing a payload to the handshake server for a new payload to the client.
@@ -1,10 +1,14 @@
 use std::cell::UnsafeCell;
+use std::collections::BTreeMap;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Mutex;
 use std::task::{Poll, Waker};
 
+use tokio::sync::watch;
 use triomphe::Arc;
 
+use crate::api::FetchProgress;
 use crate::ipc_types::Response;
 
 #[derive(Default)]
@@ -14,6 +18,10 @@ struct RequestFutureState {
     waker: Option<Waker>,
 }
 
+/// The progress senders of the pending requests, by their context. The context is only used as a
+/// key here and never dereferenced, since progress could arrive after its state is freed.
+static PROGRESS: Mutex<BTreeMap<u64, watch::Sender<FetchProgress>>> = Mutex::new(BTreeMap::new());
+
 #[derive(Clone, Default)]
 struct StateContainer(Arc<UnsafeCell<RequestFutureState>>);
 
@@ -64,8 +72,30 @@ pub(crate) fn create_future() -> (RequestCtx, RequestFuture) {
     (raw, RequestFuture { state })
 }
 
+/// Like [`create_future`], but progress reported for the request is sent to `progress`.
+#[inline(always)]
+pub(crate) fn create_future_with_progress(
+    progress: watch::Sender<FetchProgress>,
+) -> (RequestCtx, RequestFuture) {
+    let (raw, future) = create_future();
+    PROGRESS.lock().unwrap().insert(raw.into(), progress);
+    (raw, future)
+}
+
+#[inline(always)]
+pub(crate) fn progress_callback(ctx: RequestCtx, progress: FetchProgress) {
+    match PROGRESS.lock().unwrap().get(&u64::from(ctx)) {
+        Some(tx) => {
+            tx.send_replace(progress);
+        },
+        None => tracing::warn!("ignoring progress received after the response"),
+    }
+}
+
 #[inline(always)]
 pub(crate) fn future_callback(ctx: RequestCtx, response: Response) {
+    // The context can be reused once the state is freed, stop sending progress to this request.
+    PROGRESS.lock().unwrap().remove(&u64::from(ctx));
     let state = StateContainer::from_raw(ctx);
     let state_mut = unsafe { &mut *state.as_mut() };
     assert!(!state_mut.responded_to, "already responded to future.");
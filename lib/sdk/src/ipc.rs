@@ -10,19 +10,33 @@
 
 use std::error::Error;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use lightning_schema::LightningMessage;
 use tokio::io::{self, Interest};
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 
-use crate::connection::ConnectionListener;
-use crate::futures::future_callback;
+use crate::api::FetchProgress;
+use crate::connection::{ConnectionListener, CONNECTIONS};
+use crate::futures::{future_callback, progress_callback};
 use crate::ipc_types::{IpcMessage, IpcRequest, Request, Response, DELIMITER_SIZE};
 
 static mut SENDER: Option<tokio::sync::mpsc::Sender<IpcRequest>> = None;
 pub(crate) static mut IPC_PATH: Option<PathBuf> = None;
 pub(crate) static mut BLOCKSTORE: Option<PathBuf> = None;
+/// Whether the connection socket is bound.
+static BOUND: AtomicBool = AtomicBool::new(false);
+/// Whether the service considers itself ready, see [`set_ready`].
+static READY: AtomicBool = AtomicBool::new(true);
+
+/// Returns the path of a file in the IPC directory of the service, used to stage the data too
+/// large to fit in the IPC messages.
+pub(crate) fn staging_path(name: &str) -> PathBuf {
+    unsafe { IPC_PATH.as_ref() }
+        .expect("setup not completed")
+        .join(name)
+}
 
 /// Bind to the connection stream.
 pub async fn conn_bind() -> ConnectionListener {
@@ -31,9 +45,17 @@ pub async fn conn_bind() -> ConnectionListener {
         .join("conn");
 
     let listener = UnixListener::bind(path).expect("IPC bind failed.");
+    BOUND.store(true, Ordering::Relaxed);
     ConnectionListener::new(listener)
 }
 
+/// Set whether the service is ready to accept connections, e.g. to report it's still warming up
+/// after binding. The service is reported as ready once the connection socket is bound, unless
+/// this is set to false.
+pub fn set_ready(ready: bool) {
+    READY.store(ready, Ordering::Relaxed);
+}
+
 /// Init the service event loop using environment variables. This method *MUST* only be called
 /// once.
 pub fn init_from_env() {
@@ -45,14 +67,33 @@ pub fn init_from_env() {
         .expect("Expected IPC_PATH env")
         .into();
 
+    init(blockstore_path, ipc_path);
+}
+
+/// Set up the IPC with the core and spawn the service event loop.
+pub(crate) fn init(blockstore_path: PathBuf, ipc_path: PathBuf) {
     let (tx, rx) = mpsc::channel::<IpcRequest>(1024);
 
-    // SAFETY: `init_from_env` is the entry function of the entire service process.
+    // An unprompted pong opts into the health checks, the core doesn't ping the services of the
+    // older SDKs which would not understand it.
+    let _ = tx.try_send(IpcRequest {
+        request_ctx: None,
+        request: Request::Pong {
+            nonce: 0,
+            ready: false,
+            connections: 0,
+        },
+    });
+
+    // SAFETY: `init_from_env` is the entry function of the entire service process, and the
+    // testing harness only sets up one fake core at a time.
     unsafe {
         SENDER = Some(tx);
         BLOCKSTORE = Some(blockstore_path);
         IPC_PATH = Some(ipc_path.clone());
     }
+    BOUND.store(false, Ordering::Relaxed);
+    READY.store(true, Ordering::Relaxed);
 
     tokio::spawn(async {
         let _ = spawn_service_loop(ipc_path, rx).await;
@@ -203,6 +244,26 @@ fn handle_message(message: IpcMessage) {
             // Wake up the future that is awaiting for the response.
             future_callback(request_ctx.into(), response);
         },
+        IpcMessage::Progress {
+            request_ctx,
+            bytes,
+            blocks,
+        } => {
+            progress_callback(request_ctx.into(), FetchProgress { bytes, blocks });
+        },
+        IpcMessage::Ping { nonce } => {
+            let request = Request::Pong {
+                nonce,
+                ready: BOUND.load(Ordering::Relaxed) && READY.load(Ordering::Relaxed),
+                connections: CONNECTIONS.load(Ordering::Relaxed),
+            };
+            // A full queue means the service is too busy to answer, which fails the check.
+            let sender = unsafe { SENDER.as_ref() }.expect("setup not completed");
+            let _ = sender.try_send(IpcRequest {
+                request_ctx: None,
+                request,
+            });
+        },
     }
 }
 
@@ -245,12 +306,37 @@ pub async fn send_and_await_response(request: Request) -> Response {
     future.await
 }
 
+/// Like [`send_and_await_response`], but the progress the core reports for the request is sent
+/// to `progress` while awaiting the response.
+///
+/// # Panics
+///
+/// You should only call this method from within a service handlers.
+pub async fn send_and_await_response_with_progress(
+    request: Request,
+    progress: watch::Sender<FetchProgress>,
+) -> Response {
+    let (request_ctx, future) = crate::futures::create_future_with_progress(progress);
+    unsafe {
+        let sender = SENDER.as_ref().expect("setup not completed");
+        sender
+            .send(IpcRequest {
+                request_ctx: Some(request_ctx.into()),
+                request,
+            })
+            .await
+            .expect("Failed to send the IPC message.");
+    }
+    future.await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[tokio::test]
     async fn test_message_flow() {
+        let _guard = crate::testing::exclusive().await;
         let (tx, rx) = mpsc::channel::<IpcRequest>(1024);
         unsafe {
             SENDER = Some(tx);
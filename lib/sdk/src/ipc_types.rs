@@ -26,6 +26,54 @@ fn from(value: ClientPublicKeyBytes) -> Self {
 
 pub type RequestCtxU64 = u64;
 
+/// The reason a [`Request::SubmitTransaction`] failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+pub enum SubmitTxError {
+    /// The service is not allowed to submit the transaction.
+    NotAllowed,
+    /// The staged transaction could not be read or decoded.
+    Invalid,
+    /// The transaction was not executed in time.
+    Timeout,
+    /// The transaction could not be submitted.
+    Failed,
+}
+
+/// The information of a node, as returned by [`Request::QueryNodeInfo`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+pub struct NodeInfo {
+    /// The address of the owner of the node.
+    pub owner: [u8; 20],
+    /// The public key of the node.
+    pub public_key: [u8; 32],
+    /// The epoch the node has been staked since.
+    pub staked_since: u64,
+    /// The amount of FLK staked by the node.
+    pub stake: u128,
+    /// The primary domain of the node, as an IPv6 address. IPv4 addresses are mapped.
+    pub domain: [u8; 16],
+    /// The port of the HTTP handshake of the node.
+    pub http_port: u16,
+    /// The participation status of the node, see [`crate::api::Participation`].
+    pub participation: u8,
+    /// The nonce of the node.
+    pub nonce: u64,
+}
+
+/// The information of a service, as returned by [`Request::QueryServiceInfo`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+pub struct ServiceInfo {
+    /// The address of the owner of the service.
+    pub owner: [u8; 20],
+    /// The commodity served by the service.
+    pub commodity_type: u8,
+    /// The hash and version of the binary of the service, if it's installed from the registry.
+    pub artifact: Option<([u8; 32], u32)>,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Archive, Serialize, Deserialize)]
 #[archive(check_bytes)]
 pub struct StaticVec<const CAP: usize> {
@@ -75,6 +123,14 @@ pub enum IpcMessage {
         request_ctx: RequestCtxU64,
         response: Response,
     },
+    /// The progress of a request that is still running. Always sent before the response.
+    Progress {
+        request_ctx: RequestCtxU64,
+        bytes: u64,
+        blocks: u64,
+    },
+    /// A health check of the service, answered with a [`Request::Pong`].
+    Ping { nonce: u64 },
 }
 /// The size of the length delimiter in bytes.
 ///
@@ -179,6 +235,10 @@ fn encode_length_delimited<W: std::io::prelude::Write>(
         origin: u8,
         /// The encoded URI.
         uri: StaticVec<256>,
+        /// The priority of the fetch, see [`crate::api::FetchPriority`].
+        priority: u8,
+        /// Whether the progress of the fetch should be reported.
+        progress: bool,
         =>
         /// Returns the hash of the content on successful fetch.
         hash: Option<[u8; 32]>,
@@ -187,8 +247,144 @@ fn encode_length_delimited<W: std::io::prelude::Write>(
     FetchBlake3 {
         /// Hash of the content we are interested to fetch.
         hash: [u8; 32],
+        /// The priority of the fetch, see [`crate::api::FetchPriority`].
+        priority: u8,
+        /// Whether the progress of the fetch should be reported.
+        progress: bool,
         =>
         /// Returns true if the fetch succeeded.
         succeeded: bool
     },
+    /// Start putting a content to the blockstore.
+    PutStart {
+        =>
+        /// The handle of the put, used in the following requests.
+        handle: u64,
+    },
+    /// Write the content staged for the put, in the `put-<handle>` file of the IPC directory of
+    /// the service. The file is removed once it's read.
+    PutWrite {
+        handle: u64,
+        =>
+        /// Returns true if the content was written.
+        succeeded: bool,
+    },
+    /// Finish the put.
+    PutFinalize {
+        handle: u64,
+        =>
+        /// Returns the hash of the content if the put succeeded.
+        hash: Option<[u8; 32]>,
+    },
+    /// Query the current epoch.
+    QueryEpoch {
+        =>
+        epoch: u64,
+        /// The timestamp in milliseconds at which the epoch ends.
+        epoch_end: u64,
+    },
+    /// Query the index of a node by its public key.
+    QueryNodeIndex {
+        public_key: [u8; 32],
+        =>
+        index: Option<u32>,
+    },
+    /// Query the information of a node.
+    QueryNodeInfo {
+        index: u32,
+        =>
+        info: Option<NodeInfo>,
+    },
+    /// Query the information of a service.
+    QueryServiceInfo {
+        id: u32,
+        =>
+        info: Option<ServiceInfo>,
+    },
+    /// Query the reputation score of a node.
+    QueryReputation {
+        index: u32,
+        =>
+        /// The score from 0 to 100, if the node has one.
+        score: Option<u8>,
+    },
+    /// Query the nodes providing a content. Their indices are written to the `query-<slot>` file
+    /// of the IPC directory of the service as little-endian `u32`, which must be removed once
+    /// read.
+    QueryContentProviders {
+        hash: [u8; 32],
+        slot: u64,
+        =>
+        /// Returns the number of providers written, or `None` if the query failed.
+        count: Option<u32>,
+    },
+    /// Query the nonce of the account of a client.
+    QueryClientNonce {
+        pk: ClientPublicKeyBytes,
+        =>
+        /// The nonce, if the client has an account.
+        nonce: Option<u64>,
+    },
+    /// Submit the transaction staged in the `tx-<slot>` file of the IPC directory of the service,
+    /// encoded with bincode: an `UpdateMethod` to be signed by the node, or an `UpdateRequest`
+    /// signed by a client to forward to the mempool. The file is removed once it's read. Once
+    /// the transaction is executed, its `TransactionReceipt` is written to the same file, which
+    /// must be removed once read.
+    SubmitTransaction {
+        slot: u64,
+        /// Whether the transaction is an update method to sign by the node.
+        signed_by_node: bool,
+        =>
+        /// Returns an error if the transaction was not executed, the receipt is not staged then.
+        error: Option<SubmitTxError>,
+    },
+    /// Get a value from the key-value storage of the service. The value is written to the
+    /// `kv-<slot>` file of the IPC directory of the service, which must be removed once read.
+    KvGet {
+        key: StaticVec<256>,
+        slot: u64,
+        =>
+        /// Returns whether the key was found, or `None` if its value could not be read.
+        found: Option<bool>,
+    },
+    /// Put the value staged in the `kv-<slot>` file of the IPC directory of the service to the
+    /// key-value storage of the service. The file is removed once it's read.
+    KvPut {
+        key: StaticVec<256>,
+        slot: u64,
+        =>
+        /// Returns false if the value could not be read or the quota of the service is exceeded.
+        succeeded: bool,
+    },
+    /// Delete a key from the key-value storage of the service.
+    KvDelete {
+        key: StaticVec<256>,
+        =>
+        succeeded: bool,
+    },
+    /// List the entries of the key-value storage of the service whose keys start with the
+    /// prefix, in order of their keys. The entries are written to the `kv-<slot>` file of the IPC
+    /// directory of the service, each as the little-endian `u32` lengths of its key and value
+    /// followed by the key and the value.
+    KvScan {
+        prefix: StaticVec<256>,
+        /// Only list the keys after this one.
+        after: Option<StaticVec<256>>,
+        /// The maximum number of entries.
+        limit: u32,
+        slot: u64,
+        =>
+        /// Returns the number of entries written, or `None` if the scan failed.
+        count: Option<u32>,
+    },
+    /// The answer of the service to a [`IpcMessage::Ping`], sent without awaiting a response.
+    Pong {
+        /// The nonce of the ping.
+        nonce: u64,
+        /// Whether the service is ready to accept connections.
+        ready: bool,
+        /// The number of open connections of the service.
+        connections: u64,
+        =>
+    },
 }
@@ -9,3 +9,5 @@
 pub mod header;
 pub mod io_util;
 mod reqres;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
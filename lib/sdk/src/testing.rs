@@ -0,0 +1,632 @@
+//! A fake core to test services without running a node.
+//!
+//! The [`TestCore`] answers the requests of the SDK from an in-memory [`TestState`], keeps a
+//! temporary blockstore, and opens connections to the service like the handshake would, so the
+//! services can be tested with a plain `cargo test`. It's available with the `testing` feature.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use fn_sdk::testing::{TestCore, TestState};
+//!
+//! #[tokio::test]
+//! async fn test_fetch() {
+//!     let core = TestCore::start(TestState::default()).await.unwrap();
+//!     let hash = core.put(b"hello world").await.unwrap();
+//!     core.serve(my_service::handle_connection).await;
+//!
+//!     let mut conn = core.connect_anonymous().await.unwrap();
+//!     conn.write_payload(&hash).await.unwrap();
+//!     let response = conn.read_payload().await.unwrap();
+//! }
+//! ```
+//!
+//! Only one core is active at a time in a process, since the SDK has a single connection to the
+//! core. [`TestCore::start`] waits for the previous core to be dropped, so the tests using it
+//! are run one after the other.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use blake3_tree::blake3::tree::HashTreeBuilder;
+use blake3_tree::utils::tree_index;
+use bytes::BytesMut;
+use fleek_crypto::{ClientPublicKey, NodePublicKey};
+use lightning_schema::LightningMessage;
+use lightning_types::{TransactionReceipt, UpdateMethod, UpdateRequest};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, OwnedMutexGuard};
+use url::Url;
+
+use crate::api::{CommodityType, EpochInfo, NodeInfo, Participation, ServiceInfo};
+use crate::blockstore::to_hex;
+use crate::connection::Connection;
+use crate::header::{write_header, ConnectionHeader, HttpMethod, TransportDetail};
+use crate::io_util::read_length_delimited;
+use crate::ipc_types::{self, IpcMessage, IpcRequest, Request, Response, SubmitTxError};
+
+/// The size of the blocks of the blockstore.
+const BLOCK_SIZE: usize = 256 << 10;
+
+/// Handles the transactions submitted by the service.
+pub type TransactionHandler =
+    Arc<dyn Fn(&Transaction) -> Result<TransactionReceipt, SubmitTxError> + Send + Sync>;
+
+/// The state of the network the [`TestCore`] answers the requests of the service from.
+#[derive(Default)]
+pub struct TestState {
+    pub client_bandwidth: HashMap<ClientPublicKey, u128>,
+    pub client_flk: HashMap<ClientPublicKey, u128>,
+    pub client_nonce: HashMap<ClientPublicKey, u64>,
+    pub epoch: Option<EpochInfo>,
+    pub nodes: HashMap<u32, NodeInfo>,
+    pub services: HashMap<u32, ServiceInfo>,
+    pub reputation: HashMap<u32, u8>,
+    pub content_providers: HashMap<[u8; 32], Vec<u32>>,
+    /// The content fetched from the origins, by their URI. It's put to the blockstore once
+    /// fetched.
+    pub origins: HashMap<Vec<u8>, Vec<u8>>,
+    /// The key-value storage of the service.
+    pub kv: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// The transactions submitted by the service so far.
+    pub transactions: Vec<Transaction>,
+    /// Handles the transactions submitted by the service. They are rejected as not allowed if
+    /// it's not set.
+    pub on_transaction: Option<TransactionHandler>,
+}
+
+/// A transaction submitted by the service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transaction {
+    /// An update method to be signed by the node.
+    Signed(UpdateMethod),
+    /// An update request signed by a client.
+    Forwarded(UpdateRequest),
+}
+
+/// A fake core, answering the requests of the service.
+pub struct TestCore {
+    shared: Arc<Shared>,
+    dir: PathBuf,
+    _active: OwnedMutexGuard<()>,
+}
+
+struct Shared {
+    state: Mutex<TestState>,
+    blockstore: PathBuf,
+    ipc_dir: PathBuf,
+    puts: Mutex<HashMap<u64, Vec<u8>>>,
+    next_put: AtomicU64,
+}
+
+impl TestCore {
+    /// Start the core, and set up the SDK to use it. Must be called from a tokio runtime.
+    pub async fn start(state: TestState) -> io::Result<Self> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let active = exclusive().await;
+
+        let dir = std::env::temp_dir().join(format!(
+            "fn-sdk-test-core-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let blockstore = dir.join("blockstore");
+        let ipc_dir = dir.join("ipc");
+        for path in ["internal", "block"] {
+            std::fs::create_dir_all(blockstore.join(path))?;
+        }
+        std::fs::create_dir_all(&ipc_dir)?;
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(state),
+            blockstore: blockstore.clone(),
+            ipc_dir: ipc_dir.clone(),
+            puts: Default::default(),
+            next_put: AtomicU64::new(0),
+        });
+        let listener = UnixListener::bind(ipc_dir.join("ctrl"))?;
+        tokio::spawn(run(listener, shared.clone()));
+        crate::ipc::init(blockstore, ipc_dir);
+
+        Ok(Self {
+            shared,
+            dir,
+            _active: active,
+        })
+    }
+
+    /// Returns the state of the core, e.g. to check the transactions submitted by the service.
+    pub fn state(&self) -> MutexGuard<'_, TestState> {
+        self.shared.state.lock().unwrap()
+    }
+
+    /// Returns the root of the temporary blockstore.
+    pub fn blockstore_path(&self) -> &Path {
+        &self.shared.blockstore
+    }
+
+    /// Put a content to the blockstore, returning its hash.
+    pub async fn put(&self, content: &[u8]) -> io::Result<[u8; 32]> {
+        put_content(&self.shared.blockstore, content).await
+    }
+
+    /// Put the content of a file to the blockstore, returning its hash.
+    pub async fn put_file(&self, path: impl AsRef<Path>) -> io::Result<[u8; 32]> {
+        let content = tokio::fs::read(path).await?;
+        self.put(&content).await
+    }
+
+    /// Bind the connection socket of the service, and handle every connection with the handler.
+    pub async fn serve<F, Fut>(&self, handler: F)
+    where
+        F: Fn(Connection) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut listener = crate::ipc::conn_bind().await;
+        tokio::spawn(async move {
+            while let Ok(conn) = listener.accept().await {
+                tokio::spawn(handler(conn));
+            }
+        });
+    }
+
+    /// Open a connection to the service with the given header, like the handshake does. The
+    /// service must be listening, see [`TestCore::serve`].
+    pub async fn connect(&self, header: ConnectionHeader) -> io::Result<TestConnection> {
+        let mut stream = UnixStream::connect(self.shared.ipc_dir.join("conn")).await?;
+        write_header(&header, &mut stream)
+            .await
+            .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+        Ok(TestConnection { stream })
+    }
+
+    /// Open a connection to the service without a client public key.
+    pub async fn connect_anonymous(&self) -> io::Result<TestConnection> {
+        self.connect(ConnectionHeader {
+            pk: None,
+            transport_detail: TransportDetail::Other,
+        })
+        .await
+    }
+
+    /// Open a connection to the service for an HTTP request.
+    pub async fn connect_http(
+        &self,
+        method: HttpMethod,
+        url: Url,
+        header: HashMap<String, String>,
+    ) -> io::Result<TestConnection> {
+        self.connect(ConnectionHeader {
+            pk: None,
+            transport_detail: TransportDetail::HttpRequest {
+                method,
+                url,
+                header,
+            },
+        })
+        .await
+    }
+}
+
+impl Drop for TestCore {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The client end of a connection to the service.
+pub struct TestConnection {
+    pub stream: UnixStream,
+}
+
+impl TestConnection {
+    /// Send a payload to the service.
+    pub async fn write_payload(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.stream.write_u32(payload.len() as u32).await?;
+        self.stream.write_all(payload).await
+    }
+
+    /// Read a payload from the service. Returns `None` once the connection is closed.
+    pub async fn read_payload(&mut self) -> Option<BytesMut> {
+        read_length_delimited(&mut self.stream).await
+    }
+}
+
+/// Wait until no other test is using the IPC of the SDK, which is global to the process.
+pub(crate) async fn exclusive() -> OwnedMutexGuard<()> {
+    static ACTIVE: OnceLock<Arc<tokio::sync::Mutex<()>>> = OnceLock::new();
+    ACTIVE
+        .get_or_init(Default::default)
+        .clone()
+        .lock_owned()
+        .await
+}
+
+/// Accept the control connection of the service and answer its requests.
+async fn run(listener: UnixListener, shared: Arc<Shared>) -> io::Result<()> {
+    let (stream, _) = listener.accept().await?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<IpcMessage>();
+    tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let mut buffer = Vec::new();
+            if message.encode_length_delimited(&mut buffer).is_err()
+                || writer.write_all(&buffer).await.is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let len = reader.read_u64_le().await? as usize;
+        let mut buffer = vec![0; len];
+        reader.read_exact(&mut buffer).await?;
+        let request = IpcRequest::decode(&buffer)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+
+        let Some(request_ctx) = request.request_ctx else {
+            // Only the answers to the health checks are sent without awaiting a response, and
+            // the fake core doesn't check the health of the service.
+            continue;
+        };
+        let response = shared.handle(request.request).await;
+        let _ = tx.send(IpcMessage::Response {
+            request_ctx,
+            response,
+        });
+    }
+}
+
+impl Shared {
+    async fn handle(&self, request: Request) -> Response {
+        match request {
+            Request::QueryClientBandwidth { pk } => Response::QueryClientBandwidth {
+                balance: self.query(|s| s.client_bandwidth.get(&client(pk)).copied().unwrap_or(0)),
+            },
+            Request::QueryClientFLK { pk } => Response::QueryClientFLK {
+                balance: self.query(|s| s.client_flk.get(&client(pk)).copied().unwrap_or(0)),
+            },
+            Request::FetchFromOrigin { uri, .. } => {
+                let content = self.query(|s| s.origins.get(&*uri).cloned());
+                let hash = match content {
+                    Some(content) => put_content(&self.blockstore, &content).await.ok(),
+                    None => None,
+                };
+                Response::FetchFromOrigin { hash }
+            },
+            Request::FetchBlake3 { hash, .. } => Response::FetchBlake3 {
+                succeeded: self
+                    .blockstore
+                    .join("internal")
+                    .join(to_hex(&hash).as_str())
+                    .is_file(),
+            },
+            Request::PutStart {} => {
+                let handle = self.next_put.fetch_add(1, Ordering::Relaxed);
+                self.puts.lock().unwrap().insert(handle, Vec::new());
+                Response::PutStart { handle }
+            },
+            Request::PutWrite { handle } => {
+                let content = self.read_staged(&format!("put-{handle}")).await;
+                let mut puts = self.puts.lock().unwrap();
+                let succeeded = match (puts.get_mut(&handle), content) {
+                    (Some(buffer), Ok(content)) => {
+                        buffer.extend_from_slice(&content);
+                        true
+                    },
+                    _ => {
+                        puts.remove(&handle);
+                        false
+                    },
+                };
+                Response::PutWrite { succeeded }
+            },
+            Request::PutFinalize { handle } => {
+                let content = self.puts.lock().unwrap().remove(&handle);
+                let hash = match content {
+                    Some(content) => put_content(&self.blockstore, &content).await.ok(),
+                    None => None,
+                };
+                Response::PutFinalize { hash }
+            },
+            Request::QueryEpoch {} => {
+                let info = self.query(|s| s.epoch).unwrap_or(EpochInfo {
+                    epoch: 0,
+                    epoch_end: 0,
+                });
+                Response::QueryEpoch {
+                    epoch: info.epoch,
+                    epoch_end: info.epoch_end,
+                }
+            },
+            Request::QueryNodeIndex { public_key } => Response::QueryNodeIndex {
+                index: self.query(|s| {
+                    s.nodes
+                        .iter()
+                        .find(|(_, info)| info.public_key == NodePublicKey(public_key))
+                        .map(|(index, _)| *index)
+                }),
+            },
+            Request::QueryNodeInfo { index } => Response::QueryNodeInfo {
+                info: self.query(|s| s.nodes.get(&index).map(node_info_to_ipc)),
+            },
+            Request::QueryServiceInfo { id } => Response::QueryServiceInfo {
+                info: self.query(|s| s.services.get(&id).map(service_info_to_ipc)),
+            },
+            Request::QueryReputation { index } => Response::QueryReputation {
+                score: self.query(|s| s.reputation.get(&index).copied()),
+            },
+            Request::QueryContentProviders { hash, slot } => {
+                let providers = self.query(|s| s.content_providers.get(&hash).cloned());
+                let providers = providers.unwrap_or_default();
+                let content = providers
+                    .iter()
+                    .flat_map(|index| index.to_le_bytes())
+                    .collect::<Vec<_>>();
+                let written = self.write_staged(&format!("query-{slot}"), &content).await;
+                Response::QueryContentProviders {
+                    count: written.ok().map(|_| providers.len() as u32),
+                }
+            },
+            Request::QueryClientNonce { pk } => Response::QueryClientNonce {
+                nonce: self.query(|s| s.client_nonce.get(&client(pk)).copied()),
+            },
+            Request::SubmitTransaction {
+                slot,
+                signed_by_node,
+            } => {
+                let name = format!("tx-{slot}");
+                let error = match self.submit(&name, signed_by_node).await {
+                    Ok(receipt) => {
+                        let content = bincode::serialize(&receipt).expect("serialization failed");
+                        self.write_staged(&name, &content)
+                            .await
+                            .err()
+                            .map(|_| SubmitTxError::Failed)
+                    },
+                    Err(e) => Some(e),
+                };
+                Response::SubmitTransaction { error }
+            },
+            Request::KvGet { key, slot } => {
+                let value = self.query(|s| s.kv.get(&*key).cloned());
+                let found = match value {
+                    Some(value) => self
+                        .write_staged(&format!("kv-{slot}"), &value)
+                        .await
+                        .ok()
+                        .map(|_| true),
+                    None => Some(false),
+                };
+                Response::KvGet { found }
+            },
+            Request::KvPut { key, slot } => {
+                let value = self.read_staged(&format!("kv-{slot}")).await;
+                let succeeded = value.is_ok();
+                if let Ok(value) = value {
+                    self.state().kv.insert(key.to_vec(), value);
+                }
+                Response::KvPut { succeeded }
+            },
+            Request::KvDelete { key } => {
+                self.state().kv.remove(&*key);
+                Response::KvDelete { succeeded: true }
+            },
+            Request::KvScan {
+                prefix,
+                after,
+                limit,
+                slot,
+            } => {
+                let entries = self.query(|s| {
+                    s.kv.range(prefix.to_vec()..)
+                        .take_while(|(key, _)| key.starts_with(&prefix))
+                        .filter(|(key, _)| after.as_deref() < Some(key.as_slice()))
+                        .take(limit as usize)
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect::<Vec<_>>()
+                });
+                let mut content = Vec::new();
+                for (key, value) in &entries {
+                    content.extend_from_slice(&(key.len() as u32).to_le_bytes());
+                    content.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    content.extend_from_slice(key);
+                    content.extend_from_slice(value);
+                }
+                let written = self.write_staged(&format!("kv-{slot}"), &content).await;
+                Response::KvScan {
+                    count: written.ok().map(|_| entries.len() as u32),
+                }
+            },
+            Request::Pong { .. } => Response::Pong {},
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, TestState> {
+        self.state.lock().unwrap()
+    }
+
+    fn query<T>(&self, f: impl FnOnce(&TestState) -> T) -> T {
+        f(&self.state())
+    }
+
+    async fn submit(
+        &self,
+        name: &str,
+        signed_by_node: bool,
+    ) -> Result<TransactionReceipt, SubmitTxError> {
+        let content = self
+            .read_staged(name)
+            .await
+            .map_err(|_| SubmitTxError::Invalid)?;
+        let tx = if signed_by_node {
+            bincode::deserialize(&content).map(Transaction::Signed)
+        } else {
+            bincode::deserialize(&content).map(Transaction::Forwarded)
+        }
+        .map_err(|_| SubmitTxError::Invalid)?;
+
+        let mut state = self.state();
+        let result = match &state.on_transaction {
+            Some(handler) => handler(&tx),
+            None => Err(SubmitTxError::NotAllowed),
+        };
+        state.transactions.push(tx);
+        result
+    }
+
+    async fn read_staged(&self, name: &str) -> io::Result<Vec<u8>> {
+        let path = self.ipc_dir.join(name);
+        let content = tokio::fs::read(&path).await?;
+        tokio::fs::remove_file(&path).await?;
+        Ok(content)
+    }
+
+    async fn write_staged(&self, name: &str, content: &[u8]) -> io::Result<()> {
+        tokio::fs::write(self.ipc_dir.join(name), content).await
+    }
+}
+
+fn client(pk: ipc_types::ClientPublicKeyBytes) -> ClientPublicKey {
+    ClientPublicKey(pk.into())
+}
+
+fn node_info_to_ipc(info: &NodeInfo) -> ipc_types::NodeInfo {
+    let domain = match info.domain {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    };
+    ipc_types::NodeInfo {
+        owner: info.owner.0,
+        public_key: info.public_key.0,
+        staked_since: info.staked_since,
+        stake: info.stake,
+        domain: domain.octets(),
+        http_port: info.http_port,
+        participation: match info.participation {
+            Participation::True => 0,
+            Participation::False => 1,
+            Participation::OptedIn => 2,
+            Participation::OptedOut => 3,
+        },
+        nonce: info.nonce,
+    }
+}
+
+fn service_info_to_ipc(info: &ServiceInfo) -> ipc_types::ServiceInfo {
+    ipc_types::ServiceInfo {
+        owner: info.owner.0,
+        commodity_type: match info.commodity_type {
+            Some(CommodityType::Bandwidth) => 0,
+            Some(CommodityType::Compute) => 1,
+            Some(CommodityType::Gpu) => 2,
+            None => u8::MAX,
+        },
+        artifact: info.artifact,
+    }
+}
+
+/// Write a content to the blockstore with the layout of the node, returning its hash.
+async fn put_content(root: &Path, content: &[u8]) -> io::Result<[u8; 32]> {
+    let mut builder = HashTreeBuilder::new();
+    builder.update(content);
+    let output = builder.finalize();
+    let hash = *output.hash.as_bytes();
+
+    let num_blocks = content.len().div_ceil(BLOCK_SIZE).max(1);
+    for counter in 0..num_blocks {
+        let start = (counter * BLOCK_SIZE).min(content.len());
+        let end = ((counter + 1) * BLOCK_SIZE).min(content.len());
+        let block_hash = output.tree[tree_index(counter)];
+        let path = root
+            .join("block")
+            .join(format!("{counter}-{}", to_hex(&block_hash)));
+        tokio::fs::write(path, &content[start..end]).await?;
+    }
+
+    let tree = output.tree.concat();
+    tokio::fs::write(root.join("internal").join(to_hex(&hash).as_str()), tree).await?;
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockstore::ContentHandle;
+
+    async fn echo(mut conn: Connection) {
+        let url = match &conn.header.transport_detail {
+            TransportDetail::HttpRequest { url, .. } => Some(url.to_string()),
+            TransportDetail::Other => None,
+        };
+        if let Some(url) = url {
+            let _ = conn.write_payload(url.as_bytes()).await;
+            return;
+        }
+        while let Some(payload) = conn.read_payload().await {
+            let _ = conn.write_payload(&payload).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_core() {
+        let pk = ClientPublicKey([1; 96]);
+        let mut state = TestState::default();
+        state.client_bandwidth.insert(pk, 42);
+        state
+            .origins
+            .insert(b"ipfs://content".to_vec(), vec![7; 300 << 10]);
+        let core = TestCore::start(state).await.unwrap();
+
+        assert_eq!(crate::api::query_client_bandwidth_balance(pk).await, 42);
+        assert_eq!(crate::api::query_client_flk_balance(pk).await, 0);
+
+        // Fetched content is readable from the blockstore.
+        let hash = crate::api::fetch_from_origin(crate::api::Origin::IPFS, "ipfs://content")
+            .await
+            .unwrap();
+        let handle = ContentHandle::load(&hash).await.unwrap();
+        assert_eq!(handle.len(), 2);
+        assert_eq!(handle.read_to_end().await.unwrap(), vec![7; 300 << 10]);
+        assert!(crate::api::fetch_blake3(hash).await);
+        assert!(!crate::api::fetch_blake3([0; 32]).await);
+
+        // Content put by the service matches the content put by the test.
+        let hash = crate::blockstore::put(b"hello").await.unwrap();
+        assert_eq!(hash, core.put(b"hello").await.unwrap());
+
+        crate::api::kv_put("key", "value").await.unwrap();
+        assert_eq!(core.state().kv.get(b"key".as_slice()).unwrap(), b"value");
+
+        let result = crate::api::submit_transaction(UpdateMethod::OptIn {}).await;
+        assert_eq!(result.err(), Some(SubmitTxError::NotAllowed));
+        assert_eq!(
+            core.state().transactions,
+            [Transaction::Signed(UpdateMethod::OptIn {})]
+        );
+
+        core.serve(echo).await;
+        let mut conn = core.connect_anonymous().await.unwrap();
+        conn.write_payload(b"ping").await.unwrap();
+        assert_eq!(&conn.read_payload().await.unwrap()[..], b"ping");
+
+        let url = Url::parse("http://fleek/path").unwrap();
+        let mut conn = core
+            .connect_http(HttpMethod::GET, url.clone(), HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            &conn.read_payload().await.unwrap()[..],
+            url.as_str().as_bytes()
+        );
+    }
+}
@@ -10,9 +10,30 @@
 //!
 //! Service will send a single u32 counter with the number of blocks for the content.
 //! The content will then be streamed in 256KiB payloads.
+//!
+//! ## Verified request layout:
+//!
+//! Setting the high bit of the origin requests a range of blocks along with the proofs needed to
+//! verify them. The range ends at `end_block` (exclusive), or at the end of the content if it is
+//! `u32::MAX`.
+//!
+//! ```text
+//! Payload [ origin | 0x80 (u8) . start_block (u32) . end_block (u32) . uid (<1024 bytes) ]
+//! ```
+//!
+//! ## Verified response:
+//!
+//! Service will send a header with the number of blocks for the content and its blake3 root
+//! hash. Then, for every block of the range, a payload with the proof for the block (which may
+//! be empty) followed by a payload with the block itself.
+//!
+//! ```text
+//! Header [ num_blocks (u32) . root (32 bytes) ]
+//! ```
 
 use anyhow::bail;
 use arrayref::array_ref;
+use blake3_tree::ProofBuf;
 use bytes::{Buf, Bytes};
 use cid::Cid;
 use fn_sdk::api::Origin as ApiOrigin;
@@ -30,6 +51,16 @@ pub enum Origin {
     Unknown = 0xFF,
 }
 
+/// Flag set on the origin of requests for verified content.
+pub const VERIFIED_FLAG: u8 = 0x80;
+
+/// A range of blocks to send along with their proofs.
+#[derive(Debug, Clone, Copy)]
+struct BlockRange {
+    start: u32,
+    end: u32,
+}
+
 impl From<u8> for Origin {
     #[inline(always)]
     fn from(val: u8) -> Self {
@@ -77,9 +108,27 @@ pub async fn handle_connection(mut conn: Connection) {
         }
     } else {
         while let Some(mut payload) = conn.read_payload().await {
-            let origin = Origin::from(payload[0]);
+            if payload.is_empty() {
+                let _ = conn.write_payload(b"empty request").await;
+                return;
+            }
+            let tag = payload[0];
             payload.advance(1);
-            if let Err(e) = handle_request(&mut conn, origin, payload.into()).await {
+
+            let res = if tag & VERIFIED_FLAG == 0 {
+                handle_request(&mut conn, Origin::from(tag), payload.into()).await
+            } else if payload.len() < 8 {
+                let _ = conn.write_payload(b"invalid block range").await;
+                return;
+            } else {
+                let range = BlockRange {
+                    start: payload.get_u32(),
+                    end: payload.get_u32(),
+                };
+                let origin = Origin::from(tag & !VERIFIED_FLAG);
+                handle_verified_request(&mut conn, origin, payload.into(), range).await
+            };
+            if let Err(e) = res {
                 error!("{e}");
             }
         }
@@ -106,6 +155,89 @@ fn parse_http_url(url: &Url) -> Option<(Origin, Bytes)> {
 async fn handle_request(conn: &mut Connection, origin: Origin, uri: Bytes) -> anyhow::Result<()> {
     debug!("got request for cid");
 
+    let content_handle = load_content(conn, origin, uri).await?;
+
+    if !conn.is_http_request() {
+        // Only write block count for non-HTTP transports.
+        let bytes = (content_handle.len() as u32).to_be_bytes();
+        if let Err(e) = conn.write_payload(bytes.as_slice()).await {
+            bail!("failed to send number of blocks: {e}");
+        }
+        debug!("sent block count {}", content_handle.len());
+    } else {
+        // Respond with header before streaming the body (if connection is http)
+        respond_only_default_headers(conn).await?;
+    }
+
+    for block in 0..content_handle.len() {
+        let Ok(bytes) = content_handle.read(block).await else {
+            bail!("failed to read content from the blockstore :(");
+        };
+
+        debug!("sending block {block}");
+
+        if let Err(e) = conn.write_payload(&bytes).await {
+            bail!("failed to send block: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_verified_request(
+    conn: &mut Connection,
+    origin: Origin,
+    uri: Bytes,
+    range: BlockRange,
+) -> anyhow::Result<()> {
+    debug!("got verified request for {range:?}");
+
+    let content_handle = load_content(conn, origin, uri).await?;
+    let num_blocks = content_handle.len();
+    let start = range.start as usize;
+    let end = (range.end as usize).min(num_blocks);
+    if start >= end {
+        respond_with_error(conn, b"Invalid block range", 400).await?;
+        bail!("invalid block range {range:?} for {num_blocks} blocks");
+    }
+
+    let mut header = Vec::with_capacity(36);
+    header.extend_from_slice(&(num_blocks as u32).to_be_bytes());
+    header.extend_from_slice(content_handle.tree.get_root());
+    if let Err(e) = conn.write_payload(&header).await {
+        bail!("failed to send header: {e}");
+    }
+
+    let tree: &[[u8; 32]] = content_handle.tree.as_ref();
+    for block in start..end {
+        // The first proof starts from the root, the next ones only cover what's new.
+        let proof = if block == start {
+            ProofBuf::new(tree, block)
+        } else {
+            ProofBuf::resume(tree, block)
+        };
+        if let Err(e) = conn.write_payload(proof.as_slice()).await {
+            bail!("failed to send proof: {e}");
+        }
+
+        let Ok(bytes) = content_handle.read(block).await else {
+            bail!("failed to read content from the blockstore");
+        };
+        if let Err(e) = conn.write_payload(&bytes).await {
+            bail!("failed to send block: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetch the content from the origin and load it from the blockstore, responding with an error
+/// if that fails.
+async fn load_content(
+    conn: &mut Connection,
+    origin: Origin,
+    uri: Bytes,
+) -> anyhow::Result<fn_sdk::blockstore::ContentHandle> {
     // Fetch the content from the origin
     let hash = match origin {
         Origin::Unknown => {
@@ -147,29 +279,5 @@ async fn handle_request(conn: &mut Connection, origin: Origin, uri: Bytes) -> an
 
     debug!("got content handle");
 
-    if !conn.is_http_request() {
-        // Only write block count for non-HTTP transports.
-        let bytes = (content_handle.len() as u32).to_be_bytes();
-        if let Err(e) = conn.write_payload(bytes.as_slice()).await {
-            bail!("failed to send number of blocks: {e}");
-        }
-        debug!("sent block count {}", content_handle.len());
-    } else {
-        // Respond with header before streaming the body (if connection is http)
-        respond_only_default_headers(conn).await?;
-    }
-
-    for block in 0..content_handle.len() {
-        let Ok(bytes) = content_handle.read(block).await else {
-            bail!("failed to read content from the blockstore :(");
-        };
-
-        debug!("sending block {block}");
-
-        if let Err(e) = conn.write_payload(&bytes).await {
-            bail!("failed to send block: {e}");
-        }
-    }
-
-    Ok(())
+    Ok(content_handle)
 }
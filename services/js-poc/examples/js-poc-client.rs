@@ -33,6 +33,7 @@ async fn main() -> anyhow::Result<()> {
                 origin,
                 uri,
                 path: None,
+                import_map: None,
                 param,
             })
             .expect("failed to encode request")
@@ -6,6 +6,9 @@
 
 use crate::stream::{Origin, Request};
 
+/// The header giving the import map of the request.
+const IMPORT_MAP_HEADER: &str = "x-fleek-import-map";
+
 pub fn extract(
     url: &Url,
     headers: &HashMap<String, String>,
@@ -61,6 +64,12 @@ pub fn extract(
         },
     );
     let query = (!query.is_empty()).then_some(query);
+
+    // The import map is given by a header, since the path and the query belong to the script.
+    let import_map = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(IMPORT_MAP_HEADER))
+        .map(|(_, value)| value.clone());
     let headers = (!headers.is_empty()).then_some(headers);
 
     let param = Some(json!({
@@ -74,6 +83,7 @@ pub fn extract(
         origin,
         uri,
         path: Some(path),
+        import_map,
         param,
     })
 }
@@ -96,6 +106,7 @@ async fn test_extract_request() {
                 origin: Origin::Blake3,
                 uri: "content-hash".to_string(),
                 path: Some("/".to_string()),
+                import_map: None,
                 param: Some(json!({
                     "method": "GET",
                     "headers": null,
@@ -118,6 +129,7 @@ async fn test_extract_request() {
                 origin: Origin::Blake3,
                 uri: "content-hash".to_string(),
                 path: Some("/".to_string()),
+                import_map: None,
                 param: Some(json!({
                     "method": "GET",
                     "headers": null,
@@ -140,6 +152,7 @@ async fn test_extract_request() {
                 origin: Origin::Blake3,
                 uri: "content-hash".to_string(),
                 path: Some("/".to_string()),
+                import_map: None,
                 param: Some(json!({
                     "method": "GET",
                     "headers": null,
@@ -162,6 +175,7 @@ async fn test_extract_request() {
                 origin: Origin::Blake3,
                 uri: "content-hash".to_string(),
                 path: Some("/a".to_string()),
+                import_map: None,
                 param: Some(json!({
                     "method": "GET",
                     "headers": null,
@@ -184,6 +198,7 @@ async fn test_extract_request() {
                 origin: Origin::Blake3,
                 uri: "content-hash".to_string(),
                 path: Some("/a/b".to_string()),
+                import_map: None,
                 param: Some(json!({
                     "method": "POST",
                     "headers": null,
@@ -206,6 +221,7 @@ async fn test_extract_request() {
                 origin: Origin::Blake3,
                 uri: "content-hash".to_string(),
                 path: Some("/a/b".to_string()),
+                import_map: None,
                 param: Some(json!({
                     "method": "GET",
                     "headers": null,
@@ -215,5 +231,32 @@ async fn test_extract_request() {
                 })),
             })
         );
+
+        // Request with an import map
+        let headers = HashMap::from([(
+            "X-Fleek-Import-Map".to_string(),
+            "blake3://import-map-hash".to_string(),
+        )]);
+        assert_eq!(
+            extract(
+                &Url::parse("http://fleek/blake3/content-hash/").unwrap(),
+                &headers,
+                HttpMethod::GET,
+                vec![],
+            ),
+            Some(Request {
+                origin: Origin::Blake3,
+                uri: "content-hash".to_string(),
+                path: Some("/".to_string()),
+                import_map: Some("blake3://import-map-hash".to_string()),
+                param: Some(json!({
+                    "method": "GET",
+                    "headers": { "X-Fleek-Import-Map": "blake3://import-map-hash" },
+                    "path": "/",
+                    "query": null,
+                    "body": null,
+                })),
+            })
+        );
     }
 }
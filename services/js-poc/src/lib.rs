@@ -10,10 +10,12 @@
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::{debug, error, info};
 
-use crate::runtime::Runtime;
+use crate::pool::{Pool, Task};
+use crate::runtime::{ImportMap, Runtime};
 use crate::stream::{Origin, Request};
 
 mod http;
+mod pool;
 mod runtime;
 pub mod stream;
 
@@ -23,6 +25,12 @@ pub(crate) mod params {
     pub const HEAP_INIT: usize = 1 << 10;
     pub const HEAP_LIMIT: usize = 50 << 20;
     pub const REQ_TIMEOUT: Duration = Duration::from_secs(15);
+    /// The maximum number of requests waiting for a worker.
+    pub const QUEUE_SIZE: usize = 256;
+    /// The maximum time a request waits for a worker.
+    pub const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+    /// The maximum number of compiled scripts cached.
+    pub const MODULE_CACHE_SIZE: usize = 256;
     pub const FETCH_BLACKLIST: &[&str] = &["localhost", "127.0.0.1", "::1"];
 }
 
@@ -48,30 +56,23 @@ pub async fn main() {
         }
     });
 
+    // Execute the scripts on a worker per core.
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let pool = Pool::new(workers, params::QUEUE_SIZE, tx);
+
     while let Ok(conn) = listener.accept().await {
-        let tx = tx.clone();
-
-        // spawn a new thread and tokio runtime to handle the connection
-        // TODO: This is very hacky and not very scalable
-        // Research using deno's JsRealms to provide the script sandboxing in a single or a
-        // few shared multithreaded runtimes, or use a custom work scheduler.
-        std::thread::spawn(move || {
-            if let Err(e) = tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .expect("failed to create connection async runtime")
-                .block_on(handle_connection(tx, conn))
-            {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(&pool, conn).await {
                 error!("session failed: {e:?}");
             }
         });
     }
 }
 
-async fn handle_connection(
-    tx: UnboundedSender<IsolateHandle>,
-    mut connection: Connection,
-) -> anyhow::Result<()> {
+async fn handle_connection(pool: &Pool, mut connection: Connection) -> anyhow::Result<()> {
     if connection.is_http_request() {
         let body = connection
             .read_payload()
@@ -87,26 +88,29 @@ async fn handle_connection(
         };
         let request = http::request::extract(url, header, method, body.to_vec())
             .context("failed to parse request")?;
-        handle_request(&mut connection, &tx, request).await?;
+        handle_request(pool, connection, request).await?;
     } else {
         while let Some(payload) = connection.read_payload().await {
             let request: Request = serde_json::from_slice(&payload)?;
-            handle_request(&mut connection, &tx, request).await?;
+            connection = handle_request(pool, connection, request).await?;
         }
     }
 
     Ok(())
 }
 
+/// Fetch the script of the request, and execute it on the pool. Returns the connection once the
+/// response is sent.
 async fn handle_request(
-    connection: &mut Connection,
-    tx: &UnboundedSender<IsolateHandle>,
+    pool: &Pool,
+    mut connection: Connection,
     request: Request,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<Connection> {
     let Request {
         origin,
         uri,
         path,
+        import_map,
         param,
     } = request;
 
@@ -116,7 +120,7 @@ async fn handle_request(
             let hash = hex::decode(uri).context("failed to decode blake3 hash")?;
 
             if hash.len() != 32 {
-                respond_with_error(connection, b"Invalid blake3 hash length", 400).await?;
+                respond_with_error(&mut connection, b"Invalid blake3 hash length", 400).await?;
                 return Err(anyhow!("invalid blake3 hash length"));
             }
 
@@ -125,7 +129,7 @@ async fn handle_request(
             if fn_sdk::api::fetch_blake3(hash).await {
                 hash
             } else {
-                respond_with_error(connection, b"Failed to fetch blake3 content", 400).await?;
+                respond_with_error(&mut connection, b"Failed to fetch blake3 content", 400).await?;
                 return Err(anyhow!("failed to fetch file"));
             }
         },
@@ -138,14 +142,15 @@ async fn handle_request(
             {
                 Some(hash) => hash,
                 None => {
-                    respond_with_error(connection, b"Failed to fetch from origin", 400).await?;
+                    respond_with_error(&mut connection, b"Failed to fetch from origin", 400)
+                        .await?;
                     return Err(anyhow!("failed to fetch from origin"));
                 },
             }
         },
         o => {
             let err = anyhow!("unknown origin: {o:?}");
-            respond_with_error(connection, err.to_string().as_bytes(), 400).await?;
+            respond_with_error(&mut connection, err.to_string().as_bytes(), 400).await?;
             return Err(err);
         },
     };
@@ -165,28 +170,67 @@ async fn handle_request(
         .context("failed to read source from blockstore")?;
     let source = String::from_utf8(source_bytes).context("failed to parse source as utf8")?;
 
-    // Create runtime and execute the source
-    let mut runtime = match Runtime::new(location.clone()) {
-        Ok(runtime) => runtime,
-        Err(e) => {
-            respond_with_error(connection, e.to_string().as_bytes(), 400).await?;
-            return Err(e).context("failed to initialize runtime");
+    // Fetch the import map resolving the imports of the source
+    let import_map = match import_map {
+        Some(specifier) => match ImportMap::fetch(&specifier, &location).await {
+            Ok(import_map) => Some(import_map),
+            Err(e) => {
+                respond_with_error(&mut connection, e.to_string().as_bytes(), 400).await?;
+                return Err(e).context("failed to load import map");
+            },
         },
+        None => None,
+    };
+
+    let task = Task {
+        location,
+        hash,
+        source,
+        import_map,
+        param,
     };
+    pool.execute(connection, task).await
+}
+
+/// Execute the script of a request on the runtime, and respond with its output.
+pub(crate) async fn run_script(
+    connection: &mut Connection,
+    mut runtime: Runtime,
+    task: Task,
+    tx: &UnboundedSender<IsolateHandle>,
+) -> anyhow::Result<()> {
+    let Task {
+        location,
+        hash,
+        source,
+        import_map,
+        param,
+    } = task;
+
+    if let Err(e) = runtime.bootstrap(location.clone()) {
+        respond_with_error(connection, e.to_string().as_bytes(), 400).await?;
+        return Err(e).context("failed to initialize runtime");
+    }
 
     tx.send(runtime.deno.v8_isolate().thread_safe_handle())
         .context("Failed to send the IsolateHandle to main thread.")?;
 
-    let res = match runtime.exec(location, source, param).await {
-        Ok(Some(res)) => res,
-        Ok(None) => {
+    // The imports of the source are fetched while it's loaded
+    let exec = runtime.exec(location, hash, source, import_map, param);
+    let res = match tokio::time::timeout(params::REQ_TIMEOUT, exec).await {
+        Ok(Ok(Some(res))) => res,
+        Ok(Ok(None)) => {
             respond_with_error(connection, b"no response available", 400).await?;
             bail!("no response available");
         },
-        Err(e) => {
+        Ok(Err(e)) => {
             respond_with_error(connection, e.to_string().as_bytes(), 400).await?;
             return Err(e).context("failed to run javascript");
         },
+        Err(e) => {
+            respond_with_error(connection, b"Request timeout", 504).await?;
+            return Err(e).context("execution timeout");
+        },
     };
 
     // Resolve async if applicable
@@ -200,7 +244,7 @@ async fn handle_request(
             return Err(e).context("failed to resolve output");
         },
         Err(e) => {
-            respond_with_error(connection, b"Request timeout", 400).await?;
+            respond_with_error(connection, b"Request timeout", 504).await?;
             return Err(e).context("execution timeout");
         },
     };
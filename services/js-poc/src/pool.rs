@@ -0,0 +1,151 @@
+//! The pool of workers executing the scripts.
+//!
+//! Every worker owns a thread and a V8 isolate created ahead of the request it serves, from the
+//! startup snapshot. A runtime only serves a single request, and the worker prepares the runtime
+//! of its next request once it has responded, so the requests don't wait on its creation. The
+//! requests are queued until a worker is available, and responded to with an error if none was
+//! for too long.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use deno_core::url::Url;
+use deno_core::v8::IsolateHandle;
+use fn_sdk::connection::Connection;
+use fn_sdk::http_util::respond_with_error;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::{oneshot, Mutex};
+use tokio::time::timeout;
+use tracing::error;
+
+use crate::params;
+use crate::runtime::{ImportMap, ModuleCache, Runtime};
+
+/// A script to execute for a request.
+pub struct Task {
+    pub location: Url,
+    /// The blake3 hash of the source.
+    pub hash: [u8; 32],
+    pub source: String,
+    pub import_map: Option<ImportMap>,
+    pub param: Option<serde_json::Value>,
+}
+
+struct Job {
+    /// Taken by the worker that serves the job, or by the requester once the job has waited too
+    /// long in the queue, whichever comes first.
+    connection: Arc<Mutex<Option<Connection>>>,
+    task: Task,
+    /// Hands the connection back once the worker has responded.
+    done: oneshot::Sender<(Connection, anyhow::Result<()>)>,
+}
+
+#[derive(Clone)]
+pub struct Pool {
+    queue: mpsc::Sender<Job>,
+}
+
+impl Pool {
+    /// Spawn the workers. The terminate handles of the isolates are sent to the watchdog when
+    /// they start executing a request.
+    pub fn new(
+        workers: usize,
+        queue_size: usize,
+        watchdog: UnboundedSender<IsolateHandle>,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(queue_size);
+        let rx = Arc::new(Mutex::new(rx));
+        let cache = Arc::new(ModuleCache::new(params::MODULE_CACHE_SIZE));
+
+        for i in 0..workers.max(1) {
+            let rx = rx.clone();
+            let cache = cache.clone();
+            let watchdog = watchdog.clone();
+            std::thread::Builder::new()
+                .name(format!("js-worker-{i}"))
+                .spawn(move || {
+                    // A panic only loses the request the worker was serving. The worker starts
+                    // over with a new isolate, until the queue is closed.
+                    while catch_unwind(AssertUnwindSafe(|| run_worker(&rx, &cache, &watchdog)))
+                        .is_err()
+                    {
+                        error!("js worker {i} panicked, restarting it");
+                    }
+                })
+                .expect("failed to spawn worker thread");
+        }
+
+        Self { queue: tx }
+    }
+
+    /// Execute the task on a worker, which responds to the request on the connection. The
+    /// connection is handed back once the response is sent.
+    pub async fn execute(&self, connection: Connection, task: Task) -> anyhow::Result<Connection> {
+        let (done, mut result) = oneshot::channel();
+        let slot = Arc::new(Mutex::new(Some(connection)));
+        let job = Job {
+            connection: slot.clone(),
+            task,
+            done,
+        };
+
+        if self.queue.try_send(job).is_err() {
+            let connection = slot.lock().await.take();
+            if let Some(mut connection) = connection {
+                respond_with_error(&mut connection, b"Service is busy", 503).await?;
+            }
+            bail!("the request queue is full");
+        }
+
+        // The connection is still in the slot if no worker started on the request in time.
+        if timeout(params::QUEUE_TIMEOUT, &mut result).await.is_err() {
+            let connection = slot.lock().await.take();
+            if let Some(mut connection) = connection {
+                respond_with_error(&mut connection, b"Request timeout", 503).await?;
+                bail!("request timed out in the queue");
+            }
+        }
+
+        let (connection, result) = result.await.context("worker stopped")?;
+        result.map(|_| connection)
+    }
+}
+
+fn run_worker(
+    queue: &Mutex<mpsc::Receiver<Job>>,
+    cache: &Arc<ModuleCache>,
+    watchdog: &UnboundedSender<IsolateHandle>,
+) {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to create worker async runtime")
+        .block_on(async move {
+            // The runtime of the next request.
+            let mut next = Some(Runtime::new(cache.clone()));
+
+            loop {
+                let Some(job) = queue.lock().await.recv().await else {
+                    break;
+                };
+                let Job {
+                    connection,
+                    task,
+                    done,
+                } = job;
+                // The requester responded already if the job waited too long in the queue.
+                let Some(mut connection) = connection.lock().await.take() else {
+                    continue;
+                };
+
+                let runtime = next.take().unwrap_or_else(|| Runtime::new(cache.clone()));
+                let result = crate::run_script(&mut connection, runtime, task, watchdog).await;
+                let _ = done.send((connection, result));
+
+                if next.is_none() {
+                    next = Some(Runtime::new(cache.clone()));
+                }
+            }
+        });
+}
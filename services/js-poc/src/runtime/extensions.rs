@@ -3,9 +3,11 @@
 use anyhow::{anyhow, Result};
 use arrayref::array_ref;
 use blake3_tree::utils::{tree_index, HashVec};
-use deno_core::{extension, op2};
-use fleek_crypto::ClientPublicKey;
+use deno_core::{extension, op2, JsBuffer, ToJsBuffer};
+use fleek_crypto::{ClientPublicKey, NodePublicKey};
+use fn_sdk::api::{CommodityType, Participation};
 use fn_sdk::blockstore::get_internal_path;
+use serde::Serialize;
 use tracing::info;
 
 use crate::runtime::Permissions;
@@ -29,7 +31,18 @@
         load_content,
         read_block,
         query_client_flk_balance,
-        query_client_bandwidth_balance
+        query_client_bandwidth_balance,
+        query_epoch,
+        query_node_index,
+        query_node_info,
+        query_service_info,
+        query_reputation,
+        query_content_providers,
+        query_client_nonce,
+        kv_get,
+        kv_put,
+        kv_delete,
+        kv_scan
     ],
     state = |state| {
         // initialize permissions
@@ -106,3 +119,157 @@ pub async fn query_client_bandwidth_balance(#[buffer(copy)] address: Vec<u8>) ->
             .to_string(),
     )
 }
+
+#[derive(Serialize)]
+pub struct EpochInfo {
+    epoch: u64,
+    epoch_end: u64,
+}
+
+#[op2(async)]
+#[serde]
+pub async fn query_epoch() -> Result<EpochInfo> {
+    let info = fn_sdk::api::query_epoch().await;
+    Ok(EpochInfo {
+        epoch: info.epoch,
+        epoch_end: info.epoch_end,
+    })
+}
+
+#[op2(async)]
+#[serde]
+pub async fn query_node_index(#[buffer(copy)] public_key: Vec<u8>) -> Result<Option<u32>> {
+    if public_key.len() != 32 {
+        return Err(anyhow!("public key must be 32 bytes"));
+    }
+    let bytes = *array_ref![public_key, 0, 32];
+    Ok(fn_sdk::api::query_node_index(NodePublicKey(bytes)).await)
+}
+
+#[derive(Serialize)]
+pub struct NodeInfo {
+    owner: ToJsBuffer,
+    public_key: ToJsBuffer,
+    staked_since: u64,
+    /// The stake as a decimal string, since it doesn't fit in a javascript number.
+    stake: String,
+    domain: String,
+    http_port: u16,
+    participation: &'static str,
+    nonce: u64,
+}
+
+#[op2(async)]
+#[serde]
+pub async fn query_node_info(index: u32) -> Result<Option<NodeInfo>> {
+    let Some(info) = fn_sdk::api::query_node_info(index).await else {
+        return Ok(None);
+    };
+    Ok(Some(NodeInfo {
+        owner: info.owner.0.to_vec().into(),
+        public_key: info.public_key.0.to_vec().into(),
+        staked_since: info.staked_since,
+        stake: info.stake.to_string(),
+        domain: info.domain.to_string(),
+        http_port: info.http_port,
+        participation: match info.participation {
+            Participation::True => "true",
+            Participation::False => "false",
+            Participation::OptedIn => "opted_in",
+            Participation::OptedOut => "opted_out",
+        },
+        nonce: info.nonce,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct ServiceArtifact {
+    hash: ToJsBuffer,
+    version: u32,
+}
+
+#[derive(Serialize)]
+pub struct ServiceInfo {
+    owner: ToJsBuffer,
+    commodity_type: Option<&'static str>,
+    artifact: Option<ServiceArtifact>,
+}
+
+#[op2(async)]
+#[serde]
+pub async fn query_service_info(id: u32) -> Result<Option<ServiceInfo>> {
+    let Some(info) = fn_sdk::api::query_service_info(id).await else {
+        return Ok(None);
+    };
+    Ok(Some(ServiceInfo {
+        owner: info.owner.0.to_vec().into(),
+        commodity_type: info.commodity_type.map(|commodity| match commodity {
+            CommodityType::Bandwidth => "bandwidth",
+            CommodityType::Compute => "compute",
+            CommodityType::Gpu => "gpu",
+        }),
+        artifact: info.artifact.map(|(hash, version)| ServiceArtifact {
+            hash: hash.to_vec().into(),
+            version,
+        }),
+    }))
+}
+
+#[op2(async)]
+#[serde]
+pub async fn query_reputation(index: u32) -> Result<Option<u8>> {
+    Ok(fn_sdk::api::query_reputation(index).await)
+}
+
+#[op2(async)]
+#[serde]
+pub async fn query_content_providers(#[buffer(copy)] hash: Vec<u8>) -> Result<Vec<u32>> {
+    if hash.len() != 32 {
+        return Err(anyhow!("blake3 hash must be 32 bytes"));
+    }
+    Ok(fn_sdk::api::query_content_providers(*array_ref![hash, 0, 32]).await?)
+}
+
+#[op2(async)]
+#[serde]
+pub async fn query_client_nonce(#[buffer(copy)] address: Vec<u8>) -> Result<Option<u64>> {
+    if address.len() != 96 {
+        return Err(anyhow!("address must be 96 bytes"));
+    }
+    let bytes = *array_ref![address, 0, 96];
+    Ok(fn_sdk::api::query_client_nonce(ClientPublicKey(bytes)).await)
+}
+
+#[op2(async)]
+#[serde]
+pub async fn kv_get(#[buffer(copy)] key: Vec<u8>) -> Result<Option<ToJsBuffer>> {
+    Ok(fn_sdk::api::kv_get(key).await?.map(Into::into))
+}
+
+#[op2(async)]
+pub async fn kv_put(#[buffer(copy)] key: Vec<u8>, #[buffer(copy)] value: Vec<u8>) -> Result<()> {
+    fn_sdk::api::kv_put(key, value)
+        .await
+        .map_err(|e| anyhow!("failed to put the value: {e}"))
+}
+
+#[op2(async)]
+pub async fn kv_delete(#[buffer(copy)] key: Vec<u8>) -> Result<()> {
+    fn_sdk::api::kv_delete(key)
+        .await
+        .map_err(|e| anyhow!("failed to delete the value: {e}"))
+}
+
+#[op2(async)]
+#[serde]
+pub async fn kv_scan(
+    #[buffer(copy)] prefix: Vec<u8>,
+    #[serde] after: Option<JsBuffer>,
+    limit: u32,
+) -> Result<Vec<(ToJsBuffer, ToJsBuffer)>> {
+    let entries = fn_sdk::api::kv_scan(prefix, after.as_deref(), limit).await?;
+    Ok(entries
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect())
+}
@@ -0,0 +1,202 @@
+//! Import maps, stored as content, mapping the specifiers imported by the scripts to modules.
+//!
+//! Only the `imports` and the `integrity` of an import map are supported. Its relative keys and
+//! addresses are resolved against the location of the main module, so `"./util.js"` maps the
+//! relative imports of the main module. Keys ending with a slash map every specifier they are a
+//! prefix of. The `integrity` entries pin the https modules imported without an
+//! `#integrity=blake3-<hash>` fragment, such as the relative imports of a pinned module.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use deno_core::url::Url;
+use deno_core::{resolve_import, ModuleSpecifier};
+use serde::Deserialize;
+
+use super::module_loader::{fetch_module, parse_hash};
+
+#[derive(Deserialize)]
+struct ImportMapJson {
+    #[serde(default)]
+    imports: BTreeMap<String, String>,
+    #[serde(default)]
+    integrity: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportMap {
+    imports: BTreeMap<String, ModuleSpecifier>,
+    /// The `blake3-<hash>` integrity of the https modules, by URL.
+    integrity: BTreeMap<String, String>,
+}
+
+impl ImportMap {
+    /// Fetch and parse the import map at the specifier.
+    pub async fn fetch(specifier: &str, base: &Url) -> Result<Self> {
+        let specifier = Url::parse(specifier).context("invalid import map specifier")?;
+        let (_, source) = fetch_module(&specifier)
+            .await
+            .context("failed to fetch import map")?;
+        Self::parse(&source, base)
+    }
+
+    pub fn parse(source: &str, base: &Url) -> Result<Self> {
+        let json: ImportMapJson = serde_json::from_str(source).context("invalid import map")?;
+        let mut imports = BTreeMap::new();
+        for (key, address) in json.imports {
+            let address = resolve_import(&address, base.as_str())
+                .with_context(|| format!("invalid address for {key} in the import map"))?;
+            if key.ends_with('/') && !address.as_str().ends_with('/') {
+                bail!("the address of {key} in the import map must end with a slash");
+            }
+            imports.insert(normalize(&key, base), address);
+        }
+        let mut integrity = BTreeMap::new();
+        for (key, value) in json.integrity {
+            let url = resolve_import(&key, base.as_str())
+                .with_context(|| format!("invalid integrity key {key} in the import map"))?;
+            value
+                .strip_prefix("blake3-")
+                .context("unsupported integrity, expected blake3-<hash>")
+                .and_then(parse_hash)
+                .with_context(|| format!("invalid integrity for {key} in the import map"))?;
+            integrity.insert(url.into(), value);
+        }
+        Ok(Self { imports, integrity })
+    }
+
+    /// Returns the module mapped to the specifier imported by the referrer, if any.
+    pub fn resolve(&self, specifier: &str, referrer: &Url) -> Option<ModuleSpecifier> {
+        let key = normalize(specifier, referrer);
+        if let Some(address) = self.imports.get(&key) {
+            return Some(address.clone());
+        }
+
+        // The keys a specifier starts with are sorted before it, the longest one last.
+        let (prefix, address) = self
+            .imports
+            .range(..key.clone())
+            .rev()
+            .find(|(prefix, _)| prefix.ends_with('/') && key.starts_with(prefix.as_str()))?;
+        address.join(&key[prefix.len()..]).ok()
+    }
+
+    /// Pins an https module imported without an integrity to the integrity of its URL, if any.
+    pub fn pin(&self, mut specifier: ModuleSpecifier) -> ModuleSpecifier {
+        if specifier.scheme() == "https" && specifier.fragment().is_none() {
+            if let Some(integrity) = self.integrity.get(specifier.as_str()) {
+                specifier.set_fragment(Some(&format!("integrity={integrity}")));
+            }
+        }
+        specifier
+    }
+}
+
+/// Returns the key of a specifier in the import map. Relative specifiers are resolved against the
+/// base, URLs are normalized, and bare specifiers are kept as they are.
+fn normalize(specifier: &str, base: &Url) -> String {
+    if ["/", "./", "../"]
+        .iter()
+        .any(|prefix| specifier.starts_with(prefix))
+    {
+        if let Ok(url) = base.join(specifier) {
+            return url.into();
+        }
+    }
+    Url::parse(specifier)
+        .map(Into::into)
+        .unwrap_or_else(|_| specifier.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let base = Url::parse("blake3://aaaa/index").unwrap();
+        let map = ImportMap::parse(
+            r#"{
+                "imports": {
+                    "lodash": "ipfs://bafkreib",
+                    "./util.js": "blake3://bbbb",
+                    "std/": "https://example.com/std/",
+                    "https://example.com/lib.js": "blake3://cccc"
+                }
+            }"#,
+            &base,
+        )
+        .unwrap();
+
+        let resolve = |specifier: &str, referrer: &Url| {
+            map.resolve(specifier, referrer).map(|url| url.to_string())
+        };
+        assert_eq!(resolve("lodash", &base).as_deref(), Some("ipfs://bafkreib"));
+        assert_eq!(
+            resolve("./util.js", &base).as_deref(),
+            Some("blake3://bbbb")
+        );
+        assert_eq!(resolve("/util.js", &base).as_deref(), Some("blake3://bbbb"));
+        assert_eq!(
+            resolve("std/fs/mod.js", &base).as_deref(),
+            Some("https://example.com/std/fs/mod.js")
+        );
+        assert_eq!(
+            resolve("https://example.com/lib.js", &base).as_deref(),
+            Some("blake3://cccc")
+        );
+        // Relative imports are mapped by the module they resolve to.
+        let nested = Url::parse("blake3://aaaa/nested/index").unwrap();
+        assert_eq!(
+            resolve("../util.js", &nested).as_deref(),
+            Some("blake3://bbbb")
+        );
+        assert_eq!(resolve("./util.js", &nested), None);
+        assert_eq!(resolve("react", &base), None);
+    }
+
+    #[test]
+    fn test_pin() {
+        let base = Url::parse("blake3://aaaa/index").unwrap();
+        let hash = "aa".repeat(32);
+        let map = ImportMap::parse(
+            &format!(r#"{{"integrity": {{"https://example.com/b.js": "blake3-{hash}"}}}}"#),
+            &base,
+        )
+        .unwrap();
+
+        // The relative imports of a pinned module are pinned by their integrity.
+        let referrer = format!("https://example.com/a.js#integrity=blake3-{hash}");
+        let child = resolve_import("./b.js", &referrer).unwrap();
+        assert_eq!(
+            map.pin(child).as_str(),
+            format!("https://example.com/b.js#integrity=blake3-{hash}")
+        );
+        let other = resolve_import("./c.js", &referrer).unwrap();
+        assert_eq!(map.pin(other).as_str(), "https://example.com/c.js");
+
+        assert!(
+            ImportMap::parse(
+                r#"{"integrity": {"https://example.com/b.js": "sha256-aaaa"}}"#,
+                &base
+            )
+            .is_err()
+        );
+        assert!(
+            ImportMap::parse(
+                r#"{"integrity": {"https://example.com/b.js": "blake3-aaaa"}}"#,
+                &base
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let base = Url::parse("blake3://aaaa/").unwrap();
+        assert!(ImportMap::parse("1", &base).is_err());
+        assert!(ImportMap::parse(r#"{"imports": {"a": "bare"}}"#, &base).is_err());
+        assert!(ImportMap::parse(r#"{"imports": {"a/": "https://a.com/a"}}"#, &base).is_err());
+        assert!(ImportMap::parse("{}", &base).is_ok());
+    }
+}
@@ -34,6 +34,107 @@ const query_client_bandwidth_balance = async (account) => {
   return BigInt(balance, 10);
 };
 
+/** Fetch the current epoch.
+ * @returns {Promise<{epoch: number, epoch_end: number}>} The epoch, and the timestamp in
+ * milliseconds at which it ends
+ */
+const query_epoch = async () => await ops.query_epoch();
+
+/** Fetch the index of a node.
+ * @param {Uint8Array} public_key - The public key of the node
+ * @returns {Promise<number | null>} The index of the node, or null if it's unknown
+ */
+const query_node_index = async (public_key) =>
+  await ops.query_node_index(public_key);
+
+/** Fetch the information of a node.
+ * @param {number} index - The index of the node
+ * @returns {Promise<NodeInfo | null>} The information of the node, or null if it's unknown
+ */
+const query_node_info = async (index) => {
+  const info = await ops.query_node_info(index);
+  return info === null ? null : { ...info, stake: BigInt(info.stake, 10) };
+};
+
+/** Fetch the information of a service.
+ * @param {number} id - The id of the service
+ * @returns {Promise<ServiceInfo | null>} The information of the service, or null if it's unknown
+ */
+const query_service_info = async (id) => await ops.query_service_info(id);
+
+/** Fetch the reputation score of a node.
+ * @param {number} index - The index of the node
+ * @returns {Promise<number | null>} The score from 0 to 100, or null if the node has none
+ */
+const query_reputation = async (index) => await ops.query_reputation(index);
+
+/** Fetch the nodes providing some blake3 content.
+ * @param {Uint8Array} hash - Blake3 hash of the content
+ * @returns {Promise<number[]>} The indices of the nodes
+ */
+const query_content_providers = async (hash) =>
+  await ops.query_content_providers(hash);
+
+/** Fetch the nonce of a client.
+ * @param {Uint8Array} account - The public key of the client
+ * @returns {Promise<number | null>} The nonce, or null if the client has no account
+ */
+const query_client_nonce = async (account) =>
+  await ops.query_client_nonce(account);
+
+/** Information of a node.
+ * @typedef {Object} NodeInfo
+ * @property {Uint8Array} owner - The address of the owner of the node
+ * @property {Uint8Array} public_key - The public key of the node
+ * @property {number} staked_since - The epoch the node has been staked since
+ * @property {BigInt} stake - The amount of FLK staked by the node
+ * @property {string} domain - The primary domain of the node
+ * @property {number} http_port - The port of the HTTP handshake of the node
+ * @property {"true" | "false" | "opted_in" | "opted_out"} participation - The participation status
+ * @property {number} nonce - The nonce of the node
+ */
+
+/** Information of a service.
+ * @typedef {Object} ServiceInfo
+ * @property {Uint8Array} owner - The address of the owner of the service
+ * @property {"bandwidth" | "compute" | "gpu" | null} commodity_type - The commodity served
+ * @property {{hash: Uint8Array, version: number} | null} artifact - The binary of the service,
+ * if it's installed from the registry
+ */
+
+const encoder = new TextEncoder();
+const toBytes = (data) => typeof data === "string" ? encoder.encode(data) : data;
+
+/** Get a value from the persistent key-value storage of the service
+ * @param {Uint8Array | string} key - The key, at most 256 bytes
+ * @returns {Promise<Uint8Array | null>} The value, or null if the key is not set
+ */
+const kv_get = async (key) => await ops.kv_get(toBytes(key));
+
+/** Set a value in the persistent key-value storage of the service
+ * @param {Uint8Array | string} key - The key, at most 256 bytes
+ * @param {Uint8Array | string} value - The value
+ * @returns {Promise<void>} Rejects if the storage quota of the service is exceeded
+ */
+const kv_put = async (key, value) =>
+  await ops.kv_put(toBytes(key), toBytes(value));
+
+/** Delete a value from the persistent key-value storage of the service
+ * @param {Uint8Array | string} key - The key, at most 256 bytes
+ * @returns {Promise<void>}
+ */
+const kv_delete = async (key) => await ops.kv_delete(toBytes(key));
+
+/** List the entries of the persistent key-value storage of the service, in order of their keys
+ * @param {Uint8Array | string} prefix - Only list the keys starting with this prefix
+ * @param {Uint8Array | string | null} after - Only list the keys after this one, e.g. the last
+ * key of the previous page
+ * @param {number} limit - The maximum number of entries
+ * @returns {Promise<Array<[Uint8Array, Uint8Array]>>} The keys and values
+ */
+const kv_scan = async (prefix, after = null, limit = 100) =>
+  await ops.kv_scan(toBytes(prefix), after === null ? null : toBytes(after), limit);
+
 /** Handle to blockstore content.
  * Utility for traversing the proof and reading blocks from the blockstore.
  * @property {Uint8Array} proof - Blake3 proof of the content
@@ -64,4 +165,15 @@ export const Fleek = {
   load_content,
   query_client_flk_balance,
   query_client_bandwidth_balance,
+  query_epoch,
+  query_node_index,
+  query_node_info,
+  query_service_info,
+  query_reputation,
+  query_content_providers,
+  query_client_nonce,
+  kv_get,
+  kv_put,
+  kv_delete,
+  kv_scan,
 };
@@ -17,11 +17,15 @@
 use deno_webgpu::deno_webgpu;
 use deno_webidl::deno_webidl;
 use extensions::fleek;
+pub use import_map::ImportMap;
+pub use module_loader::ModuleCache;
 
+use self::module_loader::FleekModuleLoader;
 use self::tape::{Punch, Tape};
 use crate::params::{FETCH_BLACKLIST, HEAP_INIT, HEAP_LIMIT};
 
 mod extensions;
+mod import_map;
 mod module_loader;
 mod tape;
 
@@ -31,6 +35,7 @@
 pub struct Runtime {
     pub deno: JsRuntime,
     tape: Tape,
+    loader: Rc<FleekModuleLoader>,
 }
 
 struct Permissions {}
@@ -92,10 +97,11 @@ fn check_write(
 }
 
 impl Runtime {
-    /// Create a new runtime
-    pub fn new(mut location: Url) -> Result<Self> {
-        let tape = Tape::new(location.clone());
-        let mut deno = JsRuntime::new(RuntimeOptions {
+    /// Create a new runtime from the snapshot. It must be bootstrapped before executing a script.
+    pub fn new(cache: Arc<ModuleCache>) -> Self {
+        let tape = Tape::default();
+        let loader = Rc::new(FleekModuleLoader::new(cache));
+        let deno = JsRuntime::new(RuntimeOptions {
             extensions: vec![
                 // WebApi subset
                 deno_webidl::init_ops(),
@@ -114,14 +120,20 @@ pub fn new(mut location: Url) -> Result<Self> {
             op_metrics_factory_fn: Some(tape.op_metrics_factory_fn()),
             // Heap initializes with 1KiB, maxes out at 10MiB
             create_params: Some(CreateParams::default().heap_limits(HEAP_INIT, HEAP_LIMIT)),
-            module_loader: Some(Rc::new(module_loader::node_crypto())),
+            module_loader: Some(loader.clone()),
             ..Default::default()
         });
 
+        Self { deno, tape, loader }
+    }
+
+    /// Bootstrap the runtime for a request to the location.
+    pub fn bootstrap(&mut self, mut location: Url) -> Result<()> {
+        self.tape.start(location.clone());
         {
             // Get global scope
-            let context = deno.main_context();
-            let scope = &mut deno.handle_scope();
+            let context = self.deno.main_context();
+            let scope = &mut self.deno.handle_scope();
             let context_local = v8::Local::new(scope, context);
             let global_obj = context_local.global(scope);
 
@@ -146,20 +158,21 @@ pub fn new(mut location: Url) -> Result<Self> {
                 .expect("Failed to execute bootstrap");
         }
 
-        Ok(Self { deno, tape })
+        Ok(())
     }
 
-    /// Execute javascript source on the runtime
+    /// Execute javascript source on the runtime. The source is identified by its blake3 hash, to
+    /// reuse its compiled code across requests, and its imports are resolved with the import map.
     pub async fn exec(
         &mut self,
         url: Url,
+        hash: [u8; 32],
         source: String,
+        import_map: Option<ImportMap>,
         param: Option<serde_json::Value>,
     ) -> anyhow::Result<Option<Global<Value>>> {
-        let id = self
-            .deno
-            .load_main_es_module_from_code(&url, source)
-            .await?;
+        self.loader.set_main(url.clone(), hash, source, import_map);
+        let id = self.deno.load_main_es_module(&url).await?;
 
         self.deno
             .run_event_loop(PollEventLoopOptions::default())
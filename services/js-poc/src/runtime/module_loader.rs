@@ -1,7 +1,397 @@
-use deno_core::{ModuleSpecifier, StaticModuleLoader};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, ensure, Context, Result};
+use cid::Cid;
+use deno_core::error::AnyError;
+use deno_core::url::Url;
+use deno_core::{
+    ModuleLoadResponse,
+    ModuleLoader,
+    ModuleSource,
+    ModuleSourceCode,
+    ModuleSpecifier,
+    ModuleType,
+    RequestedModuleType,
+    ResolutionKind,
+    StaticModuleLoader,
+};
+use fn_sdk::api::Origin;
+use fn_sdk::blockstore::ContentHandle;
+
+use super::import_map::ImportMap;
 
 pub fn node_crypto() -> StaticModuleLoader {
     let source = include_str!("js/node_crypto.js");
     let modules = vec![(ModuleSpecifier::parse("node:crypto").unwrap(), source)];
     StaticModuleLoader::new(modules)
 }
+
+/// The V8 code caches of the compiled modules, shared by all the workers and keyed by the blake3
+/// hash of the source. Once full, the oldest entries are evicted first.
+pub struct ModuleCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    caches: HashMap<[u8; 32], Arc<[u8]>>,
+    order: VecDeque<[u8; 32]>,
+}
+
+impl ModuleCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    pub fn get(&self, hash: &[u8; 32]) -> Option<Arc<[u8]>> {
+        self.entries.lock().unwrap().caches.get(hash).cloned()
+    }
+
+    pub fn insert(&self, hash: [u8; 32], code_cache: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.caches.insert(hash, code_cache.into()).is_some() {
+            return;
+        }
+        entries.order.push_back(hash);
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.caches.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// A module imported by a script. Only content addressed modules, and remote modules pinned to
+/// the blake3 hash of their content with `#integrity=blake3-<hash>`, can be imported. The path
+/// of an ipfs module is resolved inside the directory of the cid, so the relative imports of a
+/// module in a directory are pinned by the directory.
+enum Remote {
+    Blake3([u8; 32]),
+    Ipfs { cid: Cid, path: String },
+    Https { url: Url, integrity: [u8; 32] },
+}
+
+impl Remote {
+    fn parse(specifier: &ModuleSpecifier) -> Result<Self> {
+        match specifier.scheme() {
+            "blake3" => {
+                ensure_content_root(specifier)?;
+                let hash = parse_hash(specifier.host_str().unwrap_or_default())?;
+                Ok(Self::Blake3(hash))
+            },
+            "ipfs" => {
+                if specifier.query().is_some() {
+                    bail!("no module for {specifier}, ipfs imports can't have a query");
+                }
+                let cid = Cid::try_from(specifier.host_str().unwrap_or_default())
+                    .context("invalid ipfs cid")?;
+                let path = specifier.path().trim_start_matches('/').to_string();
+                Ok(Self::Ipfs { cid, path })
+            },
+            "https" => {
+                let integrity = specifier
+                    .fragment()
+                    .and_then(|fragment| fragment.strip_prefix("integrity="))
+                    .with_context(|| {
+                        format!("unpinned remote import {specifier}, add #integrity=blake3-<hash>")
+                    })?;
+                let hash = integrity
+                    .strip_prefix("blake3-")
+                    .context("unsupported integrity, expected blake3-<hash>")?;
+                let mut url = specifier.clone();
+                url.set_fragment(None);
+                Ok(Self::Https {
+                    url,
+                    integrity: parse_hash(hash)?,
+                })
+            },
+            scheme => bail!("unsupported import scheme {scheme}"),
+        }
+    }
+
+    /// Fetch the module to the blockstore, returning its hash.
+    async fn fetch(&self) -> Result<[u8; 32]> {
+        match self {
+            Self::Blake3(hash) => {
+                ensure!(
+                    fn_sdk::api::fetch_blake3(*hash).await,
+                    "failed to fetch blake3 content"
+                );
+                Ok(*hash)
+            },
+            Self::Ipfs { cid, path } => {
+                let uri = if path.is_empty() {
+                    cid.to_bytes()
+                } else {
+                    format!("{cid}/{path}").into_bytes()
+                };
+                fn_sdk::api::fetch_from_origin(Origin::IPFS, uri)
+                    .await
+                    .context("failed to fetch from ipfs")
+            },
+            Self::Https { url, integrity } => {
+                // The module is cached in the blockstore once fetched.
+                if ContentHandle::load(integrity).await.is_ok() {
+                    return Ok(*integrity);
+                }
+                let hash = fn_sdk::api::fetch_from_origin(Origin::HTTP, url.as_str())
+                    .await
+                    .with_context(|| format!("failed to fetch {url}"))?;
+                ensure!(hash == *integrity, "integrity mismatch for {url}");
+                Ok(hash)
+            },
+        }
+    }
+}
+
+/// Blake3 modules have no path. Their relative imports must be mapped by the import map of the
+/// request.
+fn ensure_content_root(specifier: &ModuleSpecifier) -> Result<()> {
+    if !matches!(specifier.path(), "" | "/") || specifier.query().is_some() {
+        bail!("no module for {specifier}, map it in the import map");
+    }
+    Ok(())
+}
+
+pub(super) fn parse_hash(hex: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(hex).context("failed to decode blake3 hash")?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("invalid blake3 hash length"))
+}
+
+/// Fetch a module, returning the hash and the source of its content.
+pub async fn fetch_module(specifier: &ModuleSpecifier) -> Result<([u8; 32], String)> {
+    let hash = Remote::parse(specifier)?.fetch().await?;
+    let source = ContentHandle::load(&hash)
+        .await
+        .context("failed to get handle for module from blockstore")?
+        .read_to_end()
+        .await
+        .context("failed to read module from blockstore")?;
+    let source = String::from_utf8(source).context("failed to parse module as utf8")?;
+    Ok((hash, source))
+}
+
+/// The module being executed by a runtime.
+struct MainModule {
+    specifier: ModuleSpecifier,
+    source: Option<String>,
+}
+
+/// Loads the modules of a request: its main module, the modules it imports through the fetcher,
+/// and the static modules provided to the scripts. The compiled code of the modules is cached
+/// across the requests by their hash.
+pub struct FleekModuleLoader {
+    main: RefCell<Option<MainModule>>,
+    import_map: RefCell<Option<ImportMap>>,
+    /// The hashes of the loaded modules.
+    hashes: Rc<RefCell<HashMap<ModuleSpecifier, [u8; 32]>>>,
+    statics: StaticModuleLoader,
+    cache: Arc<ModuleCache>,
+}
+
+impl FleekModuleLoader {
+    pub fn new(cache: Arc<ModuleCache>) -> Self {
+        Self {
+            main: RefCell::new(None),
+            import_map: RefCell::new(None),
+            hashes: Default::default(),
+            statics: node_crypto(),
+            cache,
+        }
+    }
+
+    /// Set the source of the main module, to be loaded from the specifier, and the import map
+    /// resolving its imports.
+    pub fn set_main(
+        &self,
+        specifier: ModuleSpecifier,
+        hash: [u8; 32],
+        source: String,
+        import_map: Option<ImportMap>,
+    ) {
+        self.hashes.borrow_mut().insert(specifier.clone(), hash);
+        *self.import_map.borrow_mut() = import_map;
+        *self.main.borrow_mut() = Some(MainModule {
+            specifier,
+            source: Some(source),
+        });
+    }
+}
+
+impl ModuleLoader for FleekModuleLoader {
+    fn resolve(
+        &self,
+        specifier: &str,
+        referrer: &str,
+        kind: ResolutionKind,
+    ) -> Result<ModuleSpecifier, AnyError> {
+        let import_map = self.import_map.borrow();
+        let Some(import_map) = &*import_map else {
+            return self.statics.resolve(specifier, referrer, kind);
+        };
+        let mapped = ModuleSpecifier::parse(referrer)
+            .ok()
+            .and_then(|referrer| import_map.resolve(specifier, &referrer));
+        let specifier = match mapped {
+            Some(specifier) => specifier,
+            None => self.statics.resolve(specifier, referrer, kind)?,
+        };
+        Ok(import_map.pin(specifier))
+    }
+
+    fn load(
+        &self,
+        module_specifier: &ModuleSpecifier,
+        maybe_referrer: Option<&ModuleSpecifier>,
+        is_dyn_import: bool,
+        requested_module_type: RequestedModuleType,
+    ) -> ModuleLoadResponse {
+        if module_specifier.scheme() == "node" {
+            return self.statics.load(
+                module_specifier,
+                maybe_referrer,
+                is_dyn_import,
+                requested_module_type,
+            );
+        }
+        if !matches!(requested_module_type, RequestedModuleType::None) {
+            return ModuleLoadResponse::Sync(Err(anyhow::anyhow!(
+                "unsupported module type for {module_specifier}"
+            )));
+        }
+
+        let mut main = self.main.borrow_mut();
+        if let Some(main) = main
+            .as_mut()
+            .filter(|main| main.specifier == *module_specifier)
+        {
+            let Some(source) = main.source.take() else {
+                return ModuleLoadResponse::Sync(Err(anyhow::anyhow!(
+                    "main module already loaded"
+                )));
+            };
+            let hash = self.hashes.borrow()[module_specifier];
+            return ModuleLoadResponse::Sync(Ok(module_source(
+                module_specifier,
+                source,
+                &self.cache,
+                &hash,
+            )));
+        }
+
+        let specifier = module_specifier.clone();
+        let hashes = self.hashes.clone();
+        let cache = self.cache.clone();
+        ModuleLoadResponse::Async(Box::pin(async move {
+            let (hash, source) = fetch_module(&specifier)
+                .await
+                .with_context(|| format!("failed to load module {specifier}"))?;
+            hashes.borrow_mut().insert(specifier.clone(), hash);
+            Ok(module_source(&specifier, source, &cache, &hash))
+        }))
+    }
+
+    fn code_cache_ready(
+        &self,
+        module_specifier: &ModuleSpecifier,
+        code_cache: &[u8],
+    ) -> Pin<Box<dyn Future<Output = ()>>> {
+        if let Some(hash) = self.hashes.borrow().get(module_specifier) {
+            self.cache.insert(*hash, code_cache);
+        }
+        Box::pin(async {})
+    }
+}
+
+fn module_source(
+    specifier: &ModuleSpecifier,
+    source: String,
+    cache: &ModuleCache,
+    hash: &[u8; 32],
+) -> ModuleSource {
+    let code_cache = cache.get(hash).map(|cache| Cow::Owned(cache.to_vec()));
+    ModuleSource::new(
+        ModuleType::JavaScript,
+        ModuleSourceCode::String(source.into()),
+        specifier,
+        code_cache,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_cache_eviction() {
+        let cache = ModuleCache::new(2);
+        cache.insert([1; 32], b"one");
+        cache.insert([2; 32], b"two");
+        // Replacing an entry doesn't evict anything.
+        cache.insert([1; 32], b"uno");
+        assert_eq!(cache.get(&[1; 32]).as_deref(), Some(b"uno".as_slice()));
+
+        cache.insert([3; 32], b"three");
+        assert!(cache.get(&[1; 32]).is_none());
+        assert!(cache.get(&[2; 32]).is_some());
+        assert!(cache.get(&[3; 32]).is_some());
+    }
+
+    #[test]
+    fn test_parse_remote() {
+        let parse = |specifier: &str| Remote::parse(&ModuleSpecifier::parse(specifier).unwrap());
+        let hash = "aa".repeat(32);
+
+        let pinned = format!("https://example.com/a.js#integrity=blake3-{hash}");
+        assert!(matches!(
+            parse(&pinned),
+            Ok(Remote::Https { url, integrity })
+                if url.as_str() == "https://example.com/a.js" && integrity == [0xaa; 32]
+        ));
+        assert!(matches!(
+            parse(&format!("blake3://{hash}")),
+            Ok(Remote::Blake3(_))
+        ));
+
+        // The path of an ipfs module is inside the directory of the cid.
+        let cid = "bafybeidsn76pyalucpd7zbn6fpzlmdnq2fjcw5wddcimdg3hamwi3ldfym";
+        assert!(matches!(
+            parse(&format!("ipfs://{cid}")),
+            Ok(Remote::Ipfs { path, .. }) if path.is_empty()
+        ));
+        assert!(matches!(
+            parse(&format!("ipfs://{cid}/lib/b.js")),
+            Ok(Remote::Ipfs { path, .. }) if path == "lib/b.js"
+        ));
+        assert!(parse(&format!("ipfs://{cid}/b.js?v=1")).is_err());
+        assert!(parse("ipfs://bafy/b.js").is_err());
+
+        // Remote imports must be pinned to a valid blake3 hash.
+        assert!(parse("https://example.com/a.js").is_err());
+        assert!(parse("https://example.com/a.js#integrity=").is_err());
+        assert!(parse(&format!("https://example.com/a.js#integrity=sha256-{hash}")).is_err());
+        assert!(parse("https://example.com/a.js#integrity=blake3-aaaa").is_err());
+        assert!(parse("https://example.com/a.js#integrity=blake3-zz").is_err());
+
+        // Blake3 modules have no path, and other schemes are not supported.
+        assert!(parse(&format!("blake3://{hash}/b.js")).is_err());
+        assert!(parse("file:///etc/passwd").is_err());
+        assert!(parse("http://example.com/a.js").is_err());
+        assert!(parse("data:text/javascript,export default 1").is_err());
+    }
+}
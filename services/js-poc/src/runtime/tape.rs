@@ -4,16 +4,15 @@
 use deno_core::url::Url;
 use deno_core::{OpMetricsEvent, OpMetricsFactoryFn, OpMetricsFn};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Tape {
     pub feed: Rc<RefCell<Vec<Punch>>>,
 }
 
 impl Tape {
-    pub fn new(hash: Url) -> Self {
-        Self {
-            feed: Rc::new(RefCell::new(vec![Punch::Start(hash)])),
-        }
+    /// Start the feed for a request to the location.
+    pub fn start(&self, location: Url) {
+        self.feed.borrow_mut().push(Punch::Start(location));
     }
 
     /// Returns a [`OpMetricsFn`] for this tracker.
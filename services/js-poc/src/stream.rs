@@ -13,6 +13,10 @@ pub struct Request {
     /// Optional path to provide as the window location,
     /// including query parameters and the fragment.
     pub path: Option<String>,
+    /// Optional import map resolving the imports of the script, as the `blake3://<hash>` or
+    /// `ipfs://<cid>` specifier of its content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub import_map: Option<String>,
     /// Parameter to pass to the script's main function.
     /// For http oriented functions, an object can be passed
     /// here to simulate the http object with the fields for